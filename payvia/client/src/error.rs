@@ -0,0 +1,121 @@
+use payvia::Error as PayviaError;
+
+// Why a contract call failed
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    // The contract rejected the call
+    Contract(ContractError),
+    // The host aborted the invocation, e.g. a failed auth check or an exhausted budget
+    Host(std::string::String),
+    // The result could not be decoded into the expected type
+    Conversion,
+}
+
+// A contract rejection, classified by the contract's error code
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ContractError {
+    // Retrying later can succeed: rate limits, pauses, maintenance windows, stale rates and
+    // cooling-off holds. The `simulate_*` previews report the earliest time to retry at.
+    TryAgain(PayviaError),
+    InsufficientBalance,
+    Unauthorized,
+    // A user, payment or other record does not exist
+    NotFound(PayviaError),
+    // Any other validation failure, including ones that need someone else to act first such as
+    // council approval or a contract wind-down
+    Rejected(PayviaError),
+}
+
+impl ContractError {
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, ContractError::TryAgain(_))
+    }
+}
+
+impl From<PayviaError> for ContractError {
+    fn from(error: PayviaError) -> Self {
+        match error {
+            // Rejections that clear up on their own
+            PayviaError::RateLimited
+            | PayviaError::ModulePaused
+            | PayviaError::RailUnderMaintenance
+            | PayviaError::RateStale
+            | PayviaError::WithdrawalsOnHold => ContractError::TryAgain(error),
+            PayviaError::InsufficientBalance => ContractError::InsufficientBalance,
+            PayviaError::Unauthorized => ContractError::Unauthorized,
+            PayviaError::UserNotFound
+            | PayviaError::SenderNotFound
+            | PayviaError::RecipientNotFound
+            | PayviaError::AllowanceNotFound
+            | PayviaError::RateNotFound
+            | PayviaError::GoalNotFound
+            | PayviaError::GroupNotFound
+            | PayviaError::ScheduledPaymentNotFound
+            | PayviaError::StreamNotFound
+            | PayviaError::ReferrerNotFound
+            | PayviaError::AgentNotFound
+            | PayviaError::CashOutNotFound
+            | PayviaError::MerchantNotFound
+            | PayviaError::FeeStatementNotFound
+            | PayviaError::ProposalNotFound
+            | PayviaError::BillProviderNotFound
+            | PayviaError::TelcoNotFound
+            | PayviaError::DataBundleNotFound
+            | PayviaError::WithdrawalRailNotFound
+            | PayviaError::PaymentNotFound
+            | PayviaError::WithdrawalHoldNotFound
+            | PayviaError::WithdrawalNotFound
+            | PayviaError::RecoveryNotFound
+            | PayviaError::ParticipantNotFound
+            | PayviaError::SplitNotFound
+            | PayviaError::VoucherNotFound
+            | PayviaError::LoanNotFound
+            | PayviaError::BeneficiaryNotFound
+            | PayviaError::RemittanceNotFound
+            | PayviaError::TaskNotFound
+            | PayviaError::TransactionNotFound
+            | PayviaError::DisputeNotFound
+            | PayviaError::ContactNotFound
+            | PayviaError::RuleNotFound
+            | PayviaError::PromoNotFound
+            | PayviaError::HoldNotFound
+            | PayviaError::ProcessorAccountNotFound
+            | PayviaError::DependentNotFound
+            | PayviaError::PayrollNotFound
+            | PayviaError::NotificationNotFound
+            | PayviaError::ClaimNotFound
+            | PayviaError::MaintenanceWindowNotFound
+            | PayviaError::RailNotFound
+            | PayviaError::QuarantinedTransferNotFound
+            | PayviaError::SubAccountNotFound
+            | PayviaError::CharityNotFound => ContractError::NotFound(error),
+            _ => ContractError::Rejected(error),
+        }
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Contract(ContractError::TryAgain(error))
+            | Error::Contract(ContractError::NotFound(error))
+            | Error::Contract(ContractError::Rejected(error)) => write!(f, "{error:?}"),
+            Error::Contract(ContractError::InsufficientBalance) => f.write_str("Insufficient balance"),
+            Error::Contract(ContractError::Unauthorized) => f.write_str("Unauthorized"),
+            Error::Host(error) => write!(f, "invocation failed: {error}"),
+            Error::Conversion => f.write_str("could not decode the contract result"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// Flatten the nested result of a generated `try_` call
+pub(crate) fn flatten<T, C, H: core::fmt::Debug>(result: Result<Result<T, C>, Result<PayviaError, H>>) -> Result<T, Error> {
+    match result {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(_)) => Err(Error::Conversion),
+        Err(Ok(error)) => Err(Error::Contract(error.into())),
+        Err(Err(error)) => Err(Error::Host(format!("{error:?}"))),
+    }
+}
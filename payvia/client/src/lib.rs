@@ -0,0 +1,161 @@
+// Typed Rust SDK for the Payvia contract. Wraps the generated `PayviaClient` so services get
+// typed errors, retries on transient rejections and iterators over paged list endpoints
+// instead of hand-rolling calls.
+
+use soroban_sdk::{Address, BytesN, Env, String, Symbol, Vec};
+
+mod error;
+mod pages;
+mod test;
+
+pub use error::{ContractError, Error};
+pub use pages::{Pages, PAGE_SIZE};
+pub use payvia::{
+    BillPayment, FailureRecord, FeeBearer, Notification, PaymentStatus, PayviaClient, Transfer, User, Withdrawal,
+};
+
+// How often to retry a call the contract rejected with a transient error
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    // Total attempts, including the first
+    pub max_attempts: u32,
+    // Called before each retry with the attempt number about to run, e.g. to sleep
+    pub backoff: fn(attempt: u32),
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            backoff: |_| {},
+        }
+    }
+}
+
+pub struct Client<'a> {
+    inner: PayviaClient<'a>,
+    retry: RetryPolicy,
+}
+
+impl<'a> Client<'a> {
+    pub fn new(env: &'a Env, contract_id: &'a Address) -> Self {
+        Client {
+            inner: PayviaClient::new(env, contract_id),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    // The generated client, for entrypoints without a wrapper
+    pub fn inner(&self) -> &PayviaClient<'a> {
+        &self.inner
+    }
+
+    // Run `invoke`, retrying while the contract answers with a transient rejection. A rejected
+    // call changes no state, so retrying is safe even for payments.
+    fn call<T>(&self, mut invoke: impl FnMut(&PayviaClient<'a>) -> Result<T, Error>) -> Result<T, Error> {
+        let mut attempt = 1;
+        loop {
+            match invoke(&self.inner) {
+                Err(Error::Contract(error)) if error.is_retryable() && attempt < self.retry.max_attempts => {
+                    attempt += 1;
+                    (self.retry.backoff)(attempt);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    pub fn get_user(&self, user: &Address) -> Result<User, Error> {
+        self.call(|client| error::flatten(client.try_get_user(user)))
+    }
+
+    pub fn get_balance(&self, user: &Address) -> Result<i128, Error> {
+        self.call(|client| error::flatten(client.try_get_balance(user)))
+    }
+
+    pub fn deposit(&self, user: &Address, amount: i128) -> Result<(), Error> {
+        self.call(|client| error::flatten(client.try_deposit(user, &amount)))
+    }
+
+    // Send USDC to another user; returns the transfer id
+    pub fn send(
+        &self,
+        from: &Address,
+        to: &Address,
+        amount: i128,
+        fee_bearer: FeeBearer,
+        memo: Option<String>,
+        idempotency_key: Option<BytesN<32>>,
+    ) -> Result<u64, Error> {
+        self.call(|client| error::flatten(client.try_send_usdc(from, to, &amount, &fee_bearer, &memo, &None, &idempotency_key)))
+    }
+
+    // Pay a bill from the user's balance; returns the payment id
+    pub fn pay_bill(
+        &self,
+        user: &Address,
+        bill_type: &String,
+        account_number: &String,
+        amount: i128,
+        memo: Option<String>,
+        idempotency_key: Option<BytesN<32>>,
+    ) -> Result<String, Error> {
+        self.call(|client| error::flatten(client.try_pay_bill(user, bill_type, account_number, &amount, &memo, &idempotency_key)))
+    }
+
+    // Withdraw to local currency; returns the withdrawal id
+    #[allow(clippy::too_many_arguments)]
+    pub fn withdraw(
+        &self,
+        user: &Address,
+        method: &String,
+        account_number: &String,
+        usdc_amount: i128,
+        currency: &Symbol,
+        memo: Option<String>,
+        idempotency_key: Option<BytesN<32>>,
+    ) -> Result<String, Error> {
+        self.call(|client| {
+            error::flatten(client.try_withdraw(user, method, account_number, &usdc_amount, currency, &memo, &None, &idempotency_key))
+        })
+    }
+
+    pub fn set_bill_status(&self, caller: &Address, payment_id: &String, status: PaymentStatus) -> Result<(), Error> {
+        self.call(|client| error::flatten(client.try_set_bill_status(caller, payment_id, &status)))
+    }
+
+    pub fn set_withdrawal_status(
+        &self,
+        caller: &Address,
+        withdrawal_id: &String,
+        status: PaymentStatus,
+        payout_reference: Option<String>,
+    ) -> Result<(), Error> {
+        self.call(|client| error::flatten(client.try_set_withdrawal_status(caller, withdrawal_id, &status, &payout_reference)))
+    }
+
+    // All of a user's bill payments, oldest first
+    pub fn bill_payments(&self, user: &'a Address) -> Pages<BillPayment, impl FnMut(u32, u32) -> (Vec<BillPayment>, Option<u32>) + '_> {
+        Pages::new(move |cursor, limit| self.inner.get_bill_payments(user, &cursor, &limit))
+    }
+
+    // All of a user's withdrawals, oldest first
+    pub fn withdrawals(&self, user: &'a Address) -> Pages<Withdrawal, impl FnMut(u32, u32) -> (Vec<Withdrawal>, Option<u32>) + '_> {
+        Pages::new(move |cursor, limit| self.inner.get_withdrawals(user, &cursor, &limit))
+    }
+
+    // All P2P transfers a user sent or received
+    pub fn transfers(&self, user: &'a Address) -> Pages<Transfer, impl FnMut(u32, u32) -> (Vec<Transfer>, Option<u32>) + '_> {
+        Pages::new(move |cursor, limit| self.inner.get_transfers(user, &cursor, &limit))
+    }
+
+    // A user's notification inbox, newest first
+    pub fn notifications(&self, user: &'a Address) -> Pages<Notification, impl FnMut(u32, u32) -> (Vec<Notification>, Option<u32>) + '_> {
+        Pages::new(move |cursor, limit| self.inner.get_notifications(user, &cursor, &limit))
+    }
+}
@@ -0,0 +1,54 @@
+use soroban_sdk::{Env, IntoVal, TryFromVal, Val, Vec};
+
+// Largest page the contract returns; asking for more is capped by the contract anyway
+pub const PAGE_SIZE: u32 = payvia::MAX_PAGE_SIZE;
+
+// Iterator over every item of a paged list endpoint, fetching the next page as needed
+pub struct Pages<T, F> {
+    fetch: F,
+    page: Option<Vec<T>>,
+    index: u32,
+    next_cursor: Option<u32>,
+}
+
+impl<T, F> Pages<T, F>
+where
+    T: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+    F: FnMut(u32, u32) -> (Vec<T>, Option<u32>),
+{
+    // `fetch` is called with a cursor and a limit and returns the page and the next cursor
+    pub fn new(fetch: F) -> Self {
+        Pages {
+            fetch,
+            page: None,
+            index: 0,
+            next_cursor: Some(0),
+        }
+    }
+}
+
+impl<T, F> Iterator for Pages<T, F>
+where
+    T: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+    F: FnMut(u32, u32) -> (Vec<T>, Option<u32>),
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        loop {
+            if let Some(page) = &self.page {
+                if self.index < page.len() {
+                    self.index += 1;
+                    return page.get(self.index - 1);
+                }
+            }
+
+            let cursor = self.next_cursor?;
+            let (page, next_cursor) = (self.fetch)(cursor, PAGE_SIZE);
+            // An empty page with a cursor would loop forever; treat it as the end
+            self.next_cursor = if page.is_empty() { None } else { next_cursor };
+            self.page = Some(page);
+            self.index = 0;
+        }
+    }
+}
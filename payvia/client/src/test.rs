@@ -0,0 +1,87 @@
+#![cfg(test)]
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use payvia::testutils::Fixture;
+use payvia::{Error as PayviaError, Role};
+use soroban_sdk::testutils::Address as _;
+use soroban_sdk::{symbol_short, vec, Address};
+
+use crate::{Client, ContractError, Error, FeeBearer, Pages, RetryPolicy};
+
+#[test]
+fn rejections_are_classified_by_error_code() {
+    let fixture = Fixture::new();
+    let client = Client::new(&fixture.env, &fixture.contract_id);
+
+    let stranger = Address::generate(&fixture.env);
+    assert_eq!(client.get_balance(&stranger), Err(Error::Contract(ContractError::NotFound(PayviaError::UserNotFound))));
+
+    let from = fixture.funded_user(100);
+    let to = fixture.user();
+    let overdrawn = client.send(&from, &to, 200, FeeBearer::Payer, None, None);
+    assert_eq!(overdrawn, Err(Error::Contract(ContractError::InsufficientBalance)));
+
+    assert_eq!(client.send(&from, &to, 50, FeeBearer::Payer, None, None), Ok(1));
+    assert_eq!(client.get_balance(&to), Ok(50));
+}
+
+#[test]
+fn failed_auth_is_a_host_error() {
+    let fixture = Fixture::new();
+    let client = Client::new(&fixture.env, &fixture.contract_id);
+
+    let from = fixture.funded_user(100);
+    let to = fixture.user();
+    fixture.revoke_auths();
+    assert!(matches!(client.send(&from, &to, 50, FeeBearer::Payer, None, None), Err(Error::Host(_))));
+}
+
+static RETRIES: AtomicU32 = AtomicU32::new(0);
+
+#[test]
+fn transient_rejections_are_retried_up_to_the_policy() {
+    let fixture = Fixture::new();
+    let pauser = Address::generate(&fixture.env);
+    fixture.client().grant_role(&pauser, &Role::Pauser);
+    fixture.client().pause_module(&pauser, &symbol_short!("transfers"), &0);
+
+    let client = Client::new(&fixture.env, &fixture.contract_id).with_retry(RetryPolicy {
+        max_attempts: 3,
+        backoff: |_| {
+            RETRIES.fetch_add(1, Ordering::SeqCst);
+        },
+    });
+    let from = fixture.funded_user(100);
+    let to = fixture.user();
+    let paused = client.send(&from, &to, 50, FeeBearer::Payer, None, None);
+
+    assert_eq!(paused, Err(Error::Contract(ContractError::TryAgain(PayviaError::ModulePaused))));
+    assert!(paused.unwrap_err().to_string().contains("ModulePaused"));
+    assert_eq!(RETRIES.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn pages_follow_the_cursor_to_the_end() {
+    let fixture = Fixture::new();
+    let items = vec![&fixture.env, 1u32, 2, 3, 4, 5];
+
+    let mut fetches = 0;
+    let pages = Pages::new(|cursor: u32, _limit: u32| {
+        fetches += 1;
+        let end = (cursor + 2).min(items.len());
+        let next_cursor = if end < items.len() { Some(end) } else { None };
+        (items.slice(cursor..end), next_cursor)
+    });
+
+    assert_eq!(pages.collect::<std::vec::Vec<u32>>(), [1, 2, 3, 4, 5]);
+    assert_eq!(fetches, 3);
+}
+
+#[test]
+fn empty_page_ends_the_iteration() {
+    let fixture = Fixture::new();
+
+    let mut pages = Pages::new(|cursor: u32, _limit: u32| (vec![&fixture.env], Some(cursor + 1)));
+    assert_eq!(pages.next(), None::<u32>);
+}
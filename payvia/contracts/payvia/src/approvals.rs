@@ -0,0 +1,158 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, xdr::ToXdr, Address, Bytes, BytesN, Env, Map, String};
+
+use crate::{AuditTarget, ComplianceKey, ConfigKey, DataKey, Error, PaymentStatus, Payvia, Role, Users};
+
+// What an operator service pre-signed for a user to submit themselves
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ApprovalKind {
+    // Mark the user verified, as `verify_user` would
+    KycUpgrade,
+    // Per-transaction limit for the user until the approval expires
+    LimitOverride(i128),
+    // Withdrawal id and off-ramp payout reference, completing the withdrawal
+    PayoutConfirmation(String, String),
+}
+
+// An approval signed with the ed25519 key registered for the role that would otherwise send it
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Approval {
+    pub kind: ApprovalKind,
+    pub subject: Address,
+    pub expires_at: u64,
+    // Unique per signing role; an approval can be submitted once
+    pub nonce: u64,
+    pub signature: BytesN<64>,
+}
+
+// A per-transaction limit granted to one user by a `LimitOverride` approval
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LimitOverride {
+    pub max_transaction: i128,
+    pub expires_at: u64,
+}
+
+#[contractimpl]
+impl Payvia {
+    // Register the ed25519 key a role's operator service signs approvals with (admin only)
+    pub fn set_approval_key(env: Env, role: Role, public_key: BytesN<32>) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("appr_key"), AuditTarget::Global, 0);
+
+        let mut keys: Map<Role, BytesN<32>> = env.storage().instance().get(&ConfigKey::ApprovalKeys).unwrap_or(Map::new(&env));
+        keys.set(role, public_key);
+        env.storage().instance().set(&ConfigKey::ApprovalKeys, &keys);
+
+        Ok(())
+    }
+
+    pub fn get_approval_key(env: Env, role: Role) -> Option<BytesN<32>> {
+        let keys: Map<Role, BytesN<32>> = env.storage().instance().get(&ConfigKey::ApprovalKeys).unwrap_or(Map::new(&env));
+
+        keys.get(role)
+    }
+
+    // Apply an approval an operator service signed off-chain; submitted by the user it is for
+    pub fn submit_approval(env: Env, approval: Approval) -> Result<(), Error> {
+        approval.subject.require_auth();
+
+        let role = Self::approval_role(&approval.kind);
+        Self::verify_approval(&env, role, &approval)?;
+        let signer = env.current_contract_address();
+
+        match approval.kind {
+            ApprovalKind::KycUpgrade => {
+                Self::audit(&env, &signer, symbol_short!("verify"), AuditTarget::Address(approval.subject.clone()), 0);
+
+                let mut users = Users::load(&env);
+                let mut user = users.get(approval.subject.clone()).ok_or(Error::UserNotFound)?;
+                user.is_verified = true;
+                users.set(approval.subject.clone(), user);
+                users.save();
+            }
+            ApprovalKind::LimitOverride(max_transaction) => {
+                if max_transaction <= 0 {
+                    return Err(Error::InvalidAmount);
+                }
+                Self::audit(&env, &signer, symbol_short!("limit_ovr"), AuditTarget::Address(approval.subject.clone()), max_transaction);
+
+                let mut overrides: Map<Address, LimitOverride> = env.storage().instance().get(&ComplianceKey::LimitOverrides).unwrap_or(Map::new(&env));
+                overrides.set(approval.subject.clone(), LimitOverride {
+                    max_transaction,
+                    expires_at: approval.expires_at,
+                });
+                env.storage().instance().set(&ComplianceKey::LimitOverrides, &overrides);
+            }
+            ApprovalKind::PayoutConfirmation(withdrawal_id, payout_reference) => {
+                let withdrawal = Self::load_withdrawal(&env, &withdrawal_id).ok_or(Error::WithdrawalNotFound)?;
+                if withdrawal.user_address != approval.subject {
+                    return Err(Error::Unauthorized);
+                }
+
+                // The status updater's key signed this payout, which is the approval it needs
+                Self::update_withdrawal_status(&env, &signer, withdrawal_id, PaymentStatus::Completed, Some(payout_reference))?;
+            }
+        }
+
+        env.events().publish((symbol_short!("approval"), approval.subject), (role, approval.nonce));
+
+        Ok(())
+    }
+}
+
+impl Payvia {
+    // Traps unless `signature` is `public_key`'s ed25519 signature over `message`
+    pub(crate) fn verify_signature(env: &Env, public_key: &BytesN<32>, message: &Bytes, signature: &BytesN<64>) {
+        env.crypto().ed25519_verify(public_key, message, signature);
+    }
+
+    // Check an approval is signed by `role`'s key, unexpired and not used before, and mark it used
+    fn verify_approval(env: &Env, role: Role, approval: &Approval) -> Result<(), Error> {
+        let public_key = Self::get_approval_key(env.clone(), role).ok_or(Error::ApprovalKeyNotSet)?;
+        if approval.expires_at < env.ledger().timestamp() {
+            return Err(Error::ApprovalHasExpired);
+        }
+
+        let mut used: Map<(Role, u64), u64> = env.storage().instance().get(&ComplianceKey::UsedApprovals).unwrap_or(Map::new(env));
+        if used.contains_key((role, approval.nonce)) {
+            return Err(Error::ApprovalAlreadyUsed);
+        }
+
+        let message = (
+            symbol_short!("approval"),
+            env.current_contract_address(),
+            approval.kind.clone(),
+            approval.subject.clone(),
+            approval.expires_at,
+            approval.nonce,
+        )
+            .to_xdr(env);
+        Self::verify_signature(env, &public_key, &message, &approval.signature);
+
+        used.set((role, approval.nonce), approval.expires_at);
+        env.storage().instance().set(&ComplianceKey::UsedApprovals, &used);
+
+        Ok(())
+    }
+
+    // The role that would make the change on-chain, whose key must sign the approval
+    fn approval_role(kind: &ApprovalKind) -> Role {
+        match kind {
+            ApprovalKind::KycUpgrade => Role::KycOfficer,
+            ApprovalKind::LimitOverride(_) => Role::RiskOfficer,
+            ApprovalKind::PayoutConfirmation(_, _) => Role::StatusUpdater,
+        }
+    }
+
+    // The per-transaction limit granted to the user by an unexpired override
+    pub(crate) fn limit_override(env: &Env, user_address: &Address) -> Option<i128> {
+        let overrides: Map<Address, LimitOverride> = env.storage().instance().get(&ComplianceKey::LimitOverrides).unwrap_or(Map::new(env));
+
+        overrides
+            .get(user_address.clone())
+            .filter(|limit| limit.expires_at >= env.ledger().timestamp())
+            .map(|limit| limit.max_transaction)
+    }
+}
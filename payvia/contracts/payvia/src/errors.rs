@@ -0,0 +1,291 @@
+use soroban_sdk::contracterror;
+
+// Why the contract rejected a call. The codes are part of the interface: add new variants at
+// the end and never renumber.
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum Error {
+    UserAlreadyExists = 1,
+    UserNotFound = 2,
+    InvalidDisplayName = 3,
+    UnsupportedCurrency = 4,
+    CountryChangeRequiresAKycOfficer = 5,
+    InvalidAmount = 6,
+    UsdcTokenNotConfigured = 7,
+    AccountClosed = 8,
+    ContractWindingDown = 9,
+    IdempotencyKeyAlreadyUsed = 10,
+    CannotSendToYourself = 11,
+    SenderNotFound = 12,
+    RecipientNotFound = 13,
+    InsufficientBalance = 14,
+    AmountBelowMinimumSend = 15,
+    SendWouldLeaveADustBalance = 16,
+    ExpiryMustBeInTheFuture = 17,
+    AllowanceNotFound = 18,
+    AllowanceExpired = 19,
+    InsufficientAllowance = 20,
+    Unauthorized = 21,
+    InvalidRate = 22,
+    RateNotFound = 23,
+    RateTimestampInTheFuture = 24,
+    RateOlderThanTheCurrentOne = 25,
+    RateStale = 26,
+    InvalidSpread = 27,
+    GoalNotFound = 28,
+    SavingsInYield = 29,
+    InsufficientSavings = 30,
+    GoalLocked = 31,
+    InvalidPenalty = 32,
+    InvalidGroupParameters = 33,
+    InvalidGroupMembers = 34,
+    DuplicateGroupMember = 35,
+    GroupNotFound = 36,
+    GroupNotActive = 37,
+    NotAGroupMember = 38,
+    AlreadyContributedThisRound = 39,
+    RoundStillOpen = 40,
+    ExecutionTimeMustBeInTheFuture = 41,
+    ScheduledPaymentNotFound = 42,
+    ScheduledPaymentNotPending = 43,
+    ScheduledPaymentNotDueYet = 44,
+    SandboxAfterRegistration = 45,
+    FaucetRequiresSandbox = 46,
+    InvalidStreamPeriod = 47,
+    CannotStreamToYourself = 48,
+    NothingToWithdraw = 49,
+    StreamNotFound = 50,
+    StreamNotActive = 51,
+    DemoRequiresSandbox = 52,
+    UnknownDemoProfile = 53,
+    CannotReferYourself = 54,
+    ReferrerNotFound = 55,
+    NoRewardsToRedeem = 56,
+    RewardsPoolUnderfunded = 57,
+    AgentAlreadyExists = 58,
+    AgentNotFound = 59,
+    AgentNotActive = 60,
+    InsufficientAgentFloat = 61,
+    CashOutNotFound = 62,
+    CashOutNotPending = 63,
+    CashOutHasExpired = 64,
+    InvalidConfirmationCode = 65,
+    CashOutHasNotExpired = 66,
+    InvalidWindow = 67,
+    NoCommissionToClaim = 68,
+    InsufficientFeesToPayCommission = 69,
+    MerchantAlreadyExists = 70,
+    MerchantNotFound = 71,
+    MerchantNotActive = 72,
+    FeePeriodStillOpen = 73,
+    FeeStatementNotFound = 74,
+    FeeStatementAlreadySettled = 75,
+    AlreadyMigrated = 76,
+    InvalidThreshold = 77,
+    ProposalNotFound = 78,
+    ProposalAlreadyExecuted = 79,
+    AlreadyApproved = 80,
+    InvalidAmountLimits = 81,
+    InvalidAccountNumberRules = 82,
+    InvalidAmountPolicy = 83,
+    BillProviderNotFound = 84,
+    TelcoNotFound = 85,
+    TelcoNotActive = 86,
+    InvalidAirtimeAmount = 87,
+    DataBundleNotFound = 88,
+    DataBundleNotAvailable = 89,
+    InvalidFee = 90,
+    WithdrawalRailNotFound = 91,
+    PaymentNotFound = 92,
+    WithdrawalHoldNotFound = 93,
+    WithdrawalHoldHasNotExpired = 94,
+    WithdrawalNotFound = 95,
+    RoleNotGranted = 96,
+    PinVerificationRequired = 97,
+    PinNotSet = 98,
+    PinLocked = 99,
+    UserCannotBeTheirOwnGuardian = 100,
+    NoGuardiansSet = 101,
+    NotAGuardian = 102,
+    NewAddressAlreadyRegistered = 103,
+    RecoveryAlreadyRequested = 104,
+    RecoveryNotFound = 105,
+    NotEnoughGuardianApprovals = 106,
+    RecoveryDelayHasNotPassed = 107,
+    InvalidRetention = 108,
+    AccountHasPendingWithdrawals = 109,
+    AccountHasPendingCardHolds = 110,
+    AccountHasAPendingPayroll = 111,
+    AccountHasFundedSavingsGoals = 112,
+    AccountHasAnOpenCreditLine = 113,
+    AccountHasAnOpenLoan = 114,
+    BalanceMustBeZeroOrSwept = 115,
+    InvalidSweepAddress = 116,
+    DuplicateParticipant = 117,
+    ParticipantNotFound = 118,
+    SharesMustAddUpToTheTotal = 119,
+    SplitNotFound = 120,
+    SplitNotOpen = 121,
+    SplitHasExpired = 122,
+    NotAParticipant = 123,
+    ShareAlreadyFunded = 124,
+    SplitHasNotExpired = 125,
+    InvalidExpiry = 126,
+    VoucherAlreadyExists = 127,
+    VoucherNotFound = 128,
+    VoucherNoLongerValid = 129,
+    VoucherHasExpired = 130,
+    VoucherHasNotExpired = 131,
+    UserAlreadyHasAnOpenLoan = 132,
+    AmountAboveLoanLimit = 133,
+    LoanNotFound = 134,
+    LoanNotAwaitingApproval = 135,
+    InsufficientLoanPool = 136,
+    LoanNotActive = 137,
+    InvalidLtv = 138,
+    InsufficientCollateral = 139,
+    CollateralBacksDebt = 140,
+    BorrowExceedsCreditLimit = 141,
+    PositionHealthy = 142,
+    UnsupportedCountry = 143,
+    BeneficiaryNotFound = 144,
+    CorridorUnavailable = 145,
+    RemittanceNotFound = 146,
+    TaskNotFound = 147,
+    PaymentNotSettled = 148,
+    WithdrawalNotCompleted = 149,
+    TransactionNotFound = 150,
+    TransactionAlreadyDisputed = 151,
+    DisputeNotFound = 152,
+    DisputeAlreadyResolved = 153,
+    UnknownDepositReference = 154,
+    BalanceNotDust = 155,
+    CannotSweepToTheSameAccount = 156,
+    CannotAddYourselfAsAContact = 157,
+    InvalidNickname = 158,
+    ContactNotFound = 159,
+    ContactLimitReached = 160,
+    InvalidShare = 161,
+    RuleLimitReached = 162,
+    RuleNotFound = 163,
+    InvalidBatchSize = 164,
+    AccountNotFlagged = 165,
+    InvalidDiscount = 166,
+    PromoAlreadyExists = 167,
+    PromoNotFound = 168,
+    HoldNotFound = 169,
+    HoldNotActive = 170,
+    HoldHasExpired = 171,
+    ProcessorAccountNotFound = 172,
+    InvalidDependent = 173,
+    ParentMustBeVerified = 174,
+    DependentNotFound = 175,
+    AccountAlreadyASubAccount = 176,
+    InvalidPayrollSize = 177,
+    PaydayMustBeInTheFuture = 178,
+    InvalidEmployee = 179,
+    PayrollNotFound = 180,
+    PayrollNotPending = 181,
+    PaydayHasNotCome = 182,
+    PaydayHasPassed = 183,
+    NotificationNotFound = 184,
+    InvalidSla = 185,
+    StatusFinal = 186,
+    WithdrawalWithinItsSla = 187,
+    ClaimNotFound = 188,
+    ClaimNoLongerValid = 189,
+    ClaimHasNotExpired = 190,
+    TipFeeCannotExceedTransferFee = 191,
+    InvalidMaintenanceWindow = 192,
+    MaintenanceWindowNotFound = 193,
+    ReceiptAlreadyAttached = 194,
+    InvalidRiskScore = 195,
+    RetryLimitReached = 196,
+    RetryBackoffHasNotElapsed = 197,
+    StepUpNotConfigured = 198,
+    AttestationHasExpired = 199,
+    AttestationDoesNotCoverTheAmount = 200,
+    AttestationAlreadyUsed = 201,
+    RailNotFound = 202,
+    ArithmeticOverflow = 203,
+    MemoTooLong = 204,
+    AmountBelowRecipientMinimum = 205,
+    AccountFrozen = 206,
+    AddressBlacklisted = 207,
+    RejectedByScreening = 208,
+    QuarantinedTransferNotFound = 209,
+    RequiresCouncilApproval = 210,
+    UnknownBillProvider = 211,
+    BillProviderNotActive = 212,
+    AmountBelowBillerMinimum = 213,
+    AmountAboveBillerMaximum = 214,
+    AmountNotAcceptedByBiller = 215,
+    AmountNotBillerMultiple = 216,
+    InvalidAccountNumber = 217,
+    ModulePaused = 218,
+    UnknownWithdrawalMethod = 219,
+    WithdrawalMethodUnavailable = 220,
+    AmountBelowWithdrawalMinimum = 221,
+    AmountAboveWithdrawalMaximum = 222,
+    PayoutReferenceRequired = 223,
+    MissingRole = 224,
+    StepUpVerificationRequired = 225,
+    CreditNotConfigured = 226,
+    RateLimited = 227,
+    AmountAboveTransactionLimit = 228,
+    AccountUnderReview = 229,
+    PromoHasExpired = 230,
+    PromoUsedUp = 231,
+    PromoAlreadyUsed = 232,
+    SubAccountNotFound = 233,
+    BlockedByParentalControls = 234,
+    AmountAboveSubAccountLimit = 235,
+    RailUnderMaintenance = 236,
+    WithdrawalsOnHold = 237,
+    AmountAboveCoolingOffWithdrawalCap = 238,
+    VerificationRequiredInThisCountry = 239,
+    AmountAboveCountryLimit = 240,
+    RailNotAvailableInThisCountry = 241,
+    ApprovalKeyNotSet = 242,
+    ApprovalHasExpired = 243,
+    ApprovalAlreadyUsed = 244,
+    TaskLeasedToAnotherKeeper = 245,
+    UnknownStatus = 246,
+    PoolHasOpenPositions = 247,
+    YieldPoolNotConfigured = 248,
+    YieldSuspended = 249,
+    NoIdleSavings = 250,
+    YieldNotEnabled = 251,
+    VerificationRequired = 252,
+    OverdraftNotRepaid = 253,
+    CharityNotFound = 254,
+    DonateAccruedRoundUpsFirst = 255,
+    RoundUpNotEnabled = 256,
+    AmountExceedsSettlementDue = 257,
+    SettlementAccountNotSet = 258,
+    SunsetAlreadyInitiated = 259,
+    SunsetNotInitiated = 260,
+    SunsetNoticePeriodHasNotPassed = 261,
+    NothingToReturn = 262,
+    NotAvailableInSandboxMode = 263,
+    TokenNotSupported = 264,
+    UseDepositForUsdc = 265,
+    TokensMustDiffer = 266,
+    SwapPoolNotConfigured = 267,
+    SwapOutputBelowMinimum = 268,
+    DuplicateRecipient = 269,
+    AccountHasPendingScheduledPayments = 270,
+    AccountHasActiveStreams = 271,
+    AccountIsInAnActiveSavingsGroup = 272,
+    AccountHasOpenAllowances = 273,
+    AccountHasPendingCashOuts = 274,
+    AccountHasActiveVouchers = 275,
+    AccountHasLinkedSubAccounts = 276,
+    TrustedTierRequired = 277,
+    VoucherNotCommitted = 278,
+    VoucherCommitmentTooRecent = 279,
+    DemoDataAlreadySeeded = 280,
+    AlreadyInitialized = 281,
+    NotInvitedToGroup = 282,
+}
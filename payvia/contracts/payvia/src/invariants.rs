@@ -0,0 +1,175 @@
+#![cfg(all(test, feature = "fuzz"))]
+
+use proptest::prelude::*;
+use soroban_sdk::{testutils::Address as _, Address, Map, String, Vec};
+
+use crate::testutils::{Fixture, TEST_BILLER, TEST_CURRENCY, TEST_RAIL};
+use crate::{Bucket, FeeBearer, PaymentStatus, PayviaClient, Role, RATE_SCALE, SECONDS_PER_DAY};
+
+const USERS: usize = 4;
+
+#[derive(Clone, Debug)]
+enum Op {
+    Deposit { user: usize, amount: i128 },
+    Send { from: usize, to: usize, amount: i128 },
+    PayBill { user: usize, amount: i128 },
+    Withdraw { user: usize, amount: i128 },
+    SetBillStatus { index: usize, status: PaymentStatus },
+    SetWithdrawalStatus { index: usize, status: PaymentStatus },
+    AdvanceTime { secs: u64 },
+}
+
+fn status() -> impl Strategy<Value = PaymentStatus> {
+    prop_oneof![
+        Just(PaymentStatus::Pending),
+        Just(PaymentStatus::Processing),
+        Just(PaymentStatus::Completed),
+        Just(PaymentStatus::Failed),
+    ]
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    let user = 0..USERS;
+    let amount = 1i128..5_000;
+    prop_oneof![
+        (user.clone(), amount.clone()).prop_map(|(user, amount)| Op::Deposit { user, amount }),
+        (user.clone(), user.clone(), amount.clone()).prop_map(|(from, to, amount)| Op::Send { from, to, amount }),
+        (user.clone(), amount.clone()).prop_map(|(user, amount)| Op::PayBill { user, amount }),
+        (user, amount).prop_map(|(user, amount)| Op::Withdraw { user, amount }),
+        (0usize..16, status()).prop_map(|(index, status)| Op::SetBillStatus { index, status }),
+        (0usize..16, status()).prop_map(|(index, status)| Op::SetWithdrawalStatus { index, status }),
+        (0u64..2 * SECONDS_PER_DAY).prop_map(|secs| Op::AdvanceTime { secs }),
+    ]
+}
+
+// Every record a paged list endpoint returns, across all pages
+fn all_pages<T, F>(mut fetch: F) -> std::vec::Vec<T>
+where
+    F: FnMut(u32) -> (Vec<T>, Option<u32>),
+    T: soroban_sdk::IntoVal<soroban_sdk::Env, soroban_sdk::Val> + soroban_sdk::TryFromVal<soroban_sdk::Env, soroban_sdk::Val>,
+{
+    let mut items = std::vec::Vec::new();
+    let mut cursor = Some(0);
+    while let Some(next) = cursor {
+        let (page, next_cursor) = fetch(next);
+        items.extend(page.iter());
+        cursor = next_cursor;
+    }
+    items
+}
+
+fn is_final(status: &String, env: &soroban_sdk::Env) -> bool {
+    ["completed", "failed", "expired", "cancelled"].iter().any(|s| *status == String::from_str(env, s))
+}
+
+// Check the global invariants, and that no payment left a final status since the last check
+fn check_invariants(fixture: &Fixture, client: &PayviaClient, users: &[Address], deposited: i128, seen: &mut Map<String, String>) {
+    let env = &fixture.env;
+    let mut balances = 0;
+    let mut open_withdrawals = 0;
+
+    for user in users {
+        let balance = client.get_balance(user);
+        assert!(balance >= 0, "negative balance {balance}");
+        balances += balance;
+
+        let bills = all_pages(|cursor| client.get_bill_payments(user, &cursor, &50));
+        let withdrawals = all_pages(|cursor| client.get_withdrawals(user, &cursor, &50));
+        let records = bills.iter().map(|b| (b.id.clone(), b.status.clone()));
+        let records = records.chain(withdrawals.iter().map(|w| (w.id.clone(), w.status.clone())));
+
+        for (id, status) in records {
+            if let Some(previous) = seen.get(id.clone()) {
+                if is_final(&previous, env) {
+                    assert_eq!(previous, status, "payment left a final status");
+                }
+            }
+            seen.set(id, status);
+        }
+
+        for withdrawal in withdrawals.iter() {
+            if !is_final(&withdrawal.status, env) {
+                open_withdrawals += withdrawal.usdc_amount;
+            }
+        }
+    }
+
+    let stats = client.get_stats();
+    assert_eq!(stats.custodied, balances, "custodied total drifted from user balances");
+    assert!(client.is_ledger_balanced(), "ledger postings do not balance");
+    assert_eq!(client.get_ledger().get(Bucket::Spendable).unwrap_or(0), balances, "spendable bucket drifted from user balances");
+    assert!(balances + open_withdrawals <= deposited, "liabilities exceed deposits");
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn balances_are_conserved(ops in prop::collection::vec(op(), 1..40)) {
+        let fixture = Fixture::new();
+        let client = fixture.client();
+        let users: std::vec::Vec<Address> = (0..USERS).map(|_| fixture.user()).collect();
+        let updater = Address::generate(&fixture.env);
+        client.grant_role(&updater, &Role::StatusUpdater);
+
+        let mut deposited = 0;
+        let mut bills = std::vec::Vec::new();
+        let mut withdrawals = std::vec::Vec::new();
+        let mut seen = Map::new(&fixture.env);
+
+        for op in ops {
+            match op {
+                Op::Deposit { user, amount } => {
+                    fixture.mint(&users[user], amount);
+                    if client.try_deposit(&users[user], &amount).is_ok() {
+                        deposited += amount;
+                    }
+                }
+                Op::Send { from, to, amount } => {
+                    let _ = client.try_send_usdc(&users[from], &users[to], &amount, &FeeBearer::Payer, &None, &None, &None);
+                }
+                Op::PayBill { user, amount } => {
+                    if let Ok(Ok(id)) = client.try_pay_bill(
+                        &users[user],
+                        &String::from_str(&fixture.env, TEST_BILLER),
+                        &String::from_str(&fixture.env, "0123456789"),
+                        &amount,
+                        &None,
+                        &None,
+                    ) {
+                        bills.push(id);
+                    }
+                }
+                Op::Withdraw { user, amount } => {
+                    client.set_rate(&TEST_CURRENCY, &RATE_SCALE, &fixture.now());
+                    if let Ok(Ok(id)) = client.try_withdraw(
+                        &users[user],
+                        &String::from_str(&fixture.env, TEST_RAIL),
+                        &String::from_str(&fixture.env, "256700000000"),
+                        &amount,
+                        &TEST_CURRENCY,
+                        &None,
+                        &None,
+                        &None,
+                    ) {
+                        withdrawals.push(id);
+                    }
+                }
+                Op::SetBillStatus { index, status } => {
+                    if let Some(id) = bills.get(index) {
+                        let _ = client.try_set_bill_status(&updater, id, &status);
+                    }
+                }
+                Op::SetWithdrawalStatus { index, status } => {
+                    if let Some(id) = withdrawals.get(index) {
+                        let reference = Some(String::from_str(&fixture.env, "payout-ref"));
+                        let _ = client.try_set_withdrawal_status(&updater, id, &status, &reference);
+                    }
+                }
+                Op::AdvanceTime { secs } => fixture.advance_time(secs),
+            }
+
+            check_invariants(&fixture, &client, &users, deposited, &mut seen);
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, token, vec, Address, Env, Map, String, Symbol, Vec};
+
+use crate::{AuditTarget, Bucket, CashOut, ComplianceKey, ConfigKey, CounterKey, DataKey, Error, Payvia, Role, SavingsGroup, ScheduledPayment, Users, WithdrawalHold};
+
+// How long a claimed task stays reserved for the claiming keeper
+pub const TASK_LEASE_SECS: u64 = 60;
+
+// Maintenance work anyone can perform once it is due
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum KeeperTask {
+    // Execute a due scheduled payment
+    Scheduled(u64),
+    // Close an overdue savings group round
+    GroupRound(u64),
+    // Release an expired agent cash-out hold
+    CashOutExpiry(u64),
+    // Refund an expired withdrawal hold
+    WithdrawalExpiry(String),
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct KeeperConfig {
+    pub bounty: i128,
+    // Minimum time between two rewarded executions of this task kind by the same keeper
+    pub cooldown_secs: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TaskLease {
+    pub keeper: Address,
+    pub expires_at: u64,
+}
+
+#[contractimpl]
+impl Payvia {
+    // Execute due maintenance work and pay the keeper a bounty from the keeper pool.
+    // The task runs regardless; the bounty is skipped while the keeper's cooldown for the kind is active
+    // or the pool cannot cover it. Returns the bounty paid.
+    pub fn keeper_execute(env: Env, keeper: Address, task: KeeperTask) -> Result<i128, Error> {
+        keeper.require_auth();
+
+        // Tasks leased to another keeper are off-limits until the lease runs out
+        let mut leases: Map<KeeperTask, TaskLease> = env.storage().instance().get(&DataKey::KeeperLeases).unwrap_or(Map::new(&env));
+        if let Some(lease) = leases.get(task.clone()) {
+            if lease.keeper != keeper && lease.expires_at > env.ledger().timestamp() {
+                return Err(Error::TaskLeasedToAnotherKeeper);
+            }
+            leases.remove(task.clone());
+            env.storage().instance().set(&DataKey::KeeperLeases, &leases);
+        }
+
+        let kind = Self::keeper_task_kind(&task);
+        match task {
+            KeeperTask::Scheduled(schedule_id) => Self::execute_scheduled(env.clone(), schedule_id)?,
+            KeeperTask::GroupRound(group_id) => Self::close_group_round(env.clone(), group_id)?,
+            KeeperTask::CashOutExpiry(cash_out_id) => Self::release_expired_cash_out(env.clone(), cash_out_id)?,
+            KeeperTask::WithdrawalExpiry(withdrawal_id) => Self::refund_expired_withdrawal(env.clone(), withdrawal_id)?,
+        }
+
+        let configs: Map<Symbol, KeeperConfig> = env.storage().instance().get(&ConfigKey::KeeperConfigs).unwrap_or(Map::new(&env));
+        let config = match configs.get(kind.clone()) {
+            Some(config) if config.bounty > 0 => config,
+            _ => return Ok(0),
+        };
+
+        let now = env.ledger().timestamp();
+        let mut last_paid: Map<(Address, Symbol), u64> = env.storage().instance().get(&DataKey::KeeperLastPaid).unwrap_or(Map::new(&env));
+        if let Some(last) = last_paid.get((keeper.clone(), kind.clone())) {
+            if now < last + config.cooldown_secs {
+                return Ok(0);
+            }
+        }
+
+        let pool: i128 = env.storage().instance().get(&CounterKey::KeeperPool).unwrap_or(0);
+        if pool < config.bounty {
+            return Ok(0);
+        }
+
+        let mut users = Users::load(&env);
+        let mut user = match users.get(keeper.clone()) {
+            Some(user) => user,
+            None => return Ok(0),
+        };
+        user.balance = Self::checked_credit(user.balance, config.bounty)?;
+        Self::track_balance(&env, &user, config.bounty, Bucket::Treasury);
+        users.set(keeper.clone(), user);
+        users.save();
+
+        env.storage().instance().set(&CounterKey::KeeperPool, &(pool - config.bounty));
+        last_paid.set((keeper.clone(), kind.clone()), now);
+        env.storage().instance().set(&DataKey::KeeperLastPaid, &last_paid);
+
+        env.events().publish((symbol_short!("kpr_paid"), keeper, kind), config.bounty);
+
+        Ok(config.bounty)
+    }
+
+    // Set the bounty and cooldown for a task kind (`sched`, `grp_round`, `cash_exp`, `wd_exp`) (admin only)
+    pub fn set_keeper_config(env: Env, kind: Symbol, bounty: i128, cooldown_secs: u64) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("kpr_cfg"), AuditTarget::Key(kind.clone()), bounty);
+
+        if bounty < 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        let mut configs: Map<Symbol, KeeperConfig> = env.storage().instance().get(&ConfigKey::KeeperConfigs).unwrap_or(Map::new(&env));
+        configs.set(kind, KeeperConfig { bounty, cooldown_secs });
+        env.storage().instance().set(&ConfigKey::KeeperConfigs, &configs);
+
+        Ok(())
+    }
+
+    // Add funds to the pool keeper bounties are paid from (treasurer only)
+    pub fn fund_keeper_pool(env: Env, caller: Address, amount: i128) -> Result<(), Error> {
+        Self::require_role(&env, &caller, Role::Treasurer)?;
+        Self::audit(&env, &caller, symbol_short!("kpr_fund"), AuditTarget::Global, amount);
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        let usdc: Address = env.storage().instance().get(&ConfigKey::UsdcToken).ok_or(Error::UsdcTokenNotConfigured)?;
+
+        let pool: i128 = env.storage().instance().get(&CounterKey::KeeperPool).unwrap_or(0);
+        env.storage().instance().set(&CounterKey::KeeperPool, &(pool + amount));
+        Self::post(&env, &caller, Bucket::External, Bucket::Treasury, amount);
+
+        // Keeper rewards are only paid out of USDC the treasurer actually sent
+        token::Client::new(&env, &usdc).transfer(&caller, &env.current_contract_address(), &amount);
+
+        Ok(())
+    }
+
+    // List due tasks of a kind (`sched`, `grp_round`, `cash_exp`, `wd_exp`) that are not leased
+    pub fn get_due_tasks(env: Env, kind: Symbol, cursor: u32, limit: u32) -> (Vec<KeeperTask>, Option<u32>) {
+        let now = env.ledger().timestamp();
+        let pending = String::from_str(&env, "pending");
+        let leases: Map<KeeperTask, TaskLease> = env.storage().instance().get(&DataKey::KeeperLeases).unwrap_or(Map::new(&env));
+        let mut candidates: Vec<KeeperTask> = vec![&env];
+
+        if kind == symbol_short!("sched") {
+            let scheduled: Map<u64, ScheduledPayment> = env.storage().instance().get(&DataKey::Scheduled).unwrap_or(Map::new(&env));
+            for (schedule_id, payment) in scheduled.iter() {
+                if payment.status == pending && payment.execute_at <= now {
+                    candidates.push_back(KeeperTask::Scheduled(schedule_id));
+                }
+            }
+        } else if kind == symbol_short!("grp_round") {
+            let groups: Map<u64, SavingsGroup> = env.storage().instance().get(&DataKey::Groups).unwrap_or(Map::new(&env));
+            for (group_id, group) in groups.iter() {
+                if group.active && group.round_deadline < now {
+                    candidates.push_back(KeeperTask::GroupRound(group_id));
+                }
+            }
+        } else if kind == symbol_short!("cash_exp") {
+            let cash_outs: Map<u64, CashOut> = env.storage().instance().get(&DataKey::CashOuts).unwrap_or(Map::new(&env));
+            for (cash_out_id, cash_out) in cash_outs.iter() {
+                if cash_out.status == pending && cash_out.expires_at < now {
+                    candidates.push_back(KeeperTask::CashOutExpiry(cash_out_id));
+                }
+            }
+        } else if kind == symbol_short!("wd_exp") {
+            let holds: Map<String, WithdrawalHold> = env.storage().instance().get(&ComplianceKey::WithdrawalHolds).unwrap_or(Map::new(&env));
+            for (withdrawal_id, hold) in holds.iter() {
+                if hold.expires_at < now {
+                    candidates.push_back(KeeperTask::WithdrawalExpiry(withdrawal_id));
+                }
+            }
+        }
+
+        let mut tasks = vec![&env];
+        for task in candidates.iter() {
+            match leases.get(task.clone()) {
+                Some(lease) if lease.expires_at > now => continue,
+                _ => tasks.push_back(task),
+            }
+        }
+
+        Self::page(tasks, cursor, limit)
+    }
+
+    // Reserve a task for a short lease so parallel keepers do not duplicate work.
+    // Returns when the lease expires.
+    pub fn claim_task(env: Env, keeper: Address, task: KeeperTask) -> Result<u64, Error> {
+        keeper.require_auth();
+
+        let now = env.ledger().timestamp();
+        let mut leases: Map<KeeperTask, TaskLease> = env.storage().instance().get(&DataKey::KeeperLeases).unwrap_or(Map::new(&env));
+
+        if let Some(lease) = leases.get(task.clone()) {
+            if lease.keeper != keeper && lease.expires_at > now {
+                return Err(Error::TaskLeasedToAnotherKeeper);
+            }
+        }
+
+        // Drop expired leases so the table only holds live reservations
+        for (leased_task, lease) in leases.clone().iter() {
+            if lease.expires_at <= now {
+                leases.remove(leased_task);
+            }
+        }
+
+        let expires_at = now + TASK_LEASE_SECS;
+        leases.set(task.clone(), TaskLease { keeper: keeper.clone(), expires_at });
+        env.storage().instance().set(&DataKey::KeeperLeases, &leases);
+
+        env.events().publish((symbol_short!("kpr_claim"), keeper), (task, expires_at));
+
+        Ok(expires_at)
+    }
+
+    // Get the keeper pool balance
+    pub fn get_keeper_pool(env: Env) -> i128 {
+        env.storage().instance().get(&CounterKey::KeeperPool).unwrap_or(0)
+    }
+}
+
+impl Payvia {
+    fn keeper_task_kind(task: &KeeperTask) -> Symbol {
+        match task {
+            KeeperTask::Scheduled(_) => symbol_short!("sched"),
+            KeeperTask::GroupRound(_) => symbol_short!("grp_round"),
+            KeeperTask::CashOutExpiry(_) => symbol_short!("cash_exp"),
+            KeeperTask::WithdrawalExpiry(_) => symbol_short!("wd_exp"),
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, BytesN, Env, Map, String};
+
+use crate::{
+    BillPayment, BillSplit, CardHold, CashOut, ComplianceKey, ContactClaim, CounterKey, CreditLine, DataKey, PaymentStream, Payroll, Payvia,
+    QuarantinedTransfer, RecordKey, RemittanceV9, SavingsGoal, SavingsGroup, ScheduledPayment, Voucher, WithdrawalHold,
+};
+
+// Where a unit of USDC the contract accounts for sits. Every balance change is posted as a move
+// from one bucket to another, so the bucket totals always sum to zero.
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Bucket {
+    // Users' available balances
+    Spendable,
+    // Savings goals, savings group pots and credit line collateral
+    LockedSavings,
+    // Held for a counterparty: scheduled payments, streams, splits, vouchers, card holds, payroll,
+    // agent cash-outs, remittances, contact sends, quarantined transfers and charity round-ups
+    Escrowed,
+    // Withdrawal amounts and fees until the payout completes or is refunded
+    HeldForWithdrawal,
+    // Unredeemed cashback points
+    Rewards,
+    // Bill payments owed to billers until settled
+    Payable,
+    // Funds between two users within one operation; always back to zero when it returns
+    Clearing,
+    // Fees, penalties and the funded reward, referral, keeper and loan pools
+    Treasury,
+    // USDC outside the contract; as negative as the total that came in
+    External,
+}
+
+// Buckets that are owed to users or billers and count as liabilities
+const OWED: [Bucket; 6] = [
+    Bucket::Spendable,
+    Bucket::LockedSavings,
+    Bucket::Escrowed,
+    Bucket::HeldForWithdrawal,
+    Bucket::Rewards,
+    Bucket::Payable,
+];
+
+#[contractimpl]
+impl Payvia {
+    // Get the running total of every bucket
+    pub fn get_ledger(env: Env) -> Map<Bucket, i128> {
+        env.storage().instance().get(&CounterKey::Ledger).unwrap_or(Map::new(&env))
+    }
+
+    // Whether the buckets sum to zero and nothing is left in clearing
+    pub fn is_ledger_balanced(env: Env) -> bool {
+        let ledger = Self::get_ledger(env);
+        let mut sum: i128 = 0;
+        for (_, total) in ledger.iter() {
+            sum += total;
+        }
+
+        sum == 0 && ledger.get(Bucket::Clearing).unwrap_or(0) == 0
+    }
+}
+
+impl Payvia {
+    // Move `amount` from one bucket to another on behalf of `address`
+    pub(crate) fn post(env: &Env, address: &Address, from: Bucket, to: Bucket, amount: i128) {
+        if amount == 0 || from == to {
+            return;
+        }
+
+        let mut ledger = Self::get_ledger(env.clone());
+        ledger.set(from, ledger.get(from).unwrap_or(0) - amount);
+        ledger.set(to, ledger.get(to).unwrap_or(0) + amount);
+        env.storage().instance().set(&CounterKey::Ledger, &ledger);
+
+        env.events().publish((symbol_short!("posting"), address.clone()), (from, to, amount));
+    }
+
+    // Add a fee or penalty to the collected fees, taken out of `from`
+    pub(crate) fn collect_fee(env: &Env, address: &Address, from: Bucket, amount: i128) {
+        let fees: i128 = env.storage().instance().get(&CounterKey::Fees).unwrap_or(0);
+        env.storage().instance().set(&CounterKey::Fees, &(fees + amount));
+        Self::post(env, address, from, Bucket::Treasury, amount);
+    }
+
+    // What the contract owes users and billers
+    pub(crate) fn ledger_liabilities(env: &Env) -> i128 {
+        let ledger = Self::get_ledger(env.clone());
+        let mut total: i128 = 0;
+        for bucket in OWED {
+            total += ledger.get(bucket).unwrap_or(0);
+        }
+
+        total
+    }
+
+    // Opening bucket totals rebuilt from the records, for contracts that predate the ledger
+    pub(crate) fn opening_ledger(env: &Env) -> Map<Bucket, i128> {
+        let pending = String::from_str(env, "pending");
+        let processing = String::from_str(env, "processing");
+        let active = String::from_str(env, "active");
+        let mut locked: i128 = 0;
+        let mut escrowed: i128 = 0;
+        let mut held: i128 = 0;
+        let mut rewards: i128 = 0;
+        let mut payable: i128 = 0;
+
+        let goals: Map<u64, SavingsGoal> = env.storage().instance().get(&DataKey::Goals).unwrap_or(Map::new(env));
+        for (_, goal) in goals.iter() {
+            locked += goal.saved;
+        }
+
+        let groups: Map<u64, SavingsGroup> = env.storage().instance().get(&DataKey::Groups).unwrap_or(Map::new(env));
+        for (_, group) in groups.iter() {
+            if group.active {
+                locked += group.pot;
+            }
+        }
+
+        let lines: Map<Address, CreditLine> = env.storage().instance().get(&DataKey::CreditLines).unwrap_or(Map::new(env));
+        for (_, line) in lines.iter() {
+            locked += line.collateral;
+        }
+
+        let withdrawal_holds: Map<String, WithdrawalHold> = env.storage().instance().get(&ComplianceKey::WithdrawalHolds).unwrap_or(Map::new(env));
+        for (_, hold) in withdrawal_holds.iter() {
+            held += hold.amount + hold.fee;
+        }
+
+        let card_holds: Map<u64, CardHold> = env.storage().instance().get(&DataKey::CardHolds).unwrap_or(Map::new(env));
+        for (_, hold) in card_holds.iter() {
+            if hold.status == active {
+                escrowed += hold.amount;
+            }
+        }
+
+        let payrolls: Map<u64, Payroll> = env.storage().instance().get(&DataKey::Payrolls).unwrap_or(Map::new(env));
+        for (_, payroll) in payrolls.iter() {
+            if payroll.status == pending {
+                escrowed += payroll.total;
+            }
+        }
+
+        let scheduled: Map<u64, ScheduledPayment> = env.storage().instance().get(&DataKey::Scheduled).unwrap_or(Map::new(env));
+        for (_, payment) in scheduled.iter() {
+            if payment.status == pending {
+                escrowed += payment.amount;
+            }
+        }
+
+        let streams: Map<u64, PaymentStream> = env.storage().instance().get(&DataKey::Streams).unwrap_or(Map::new(env));
+        for (_, stream) in streams.iter() {
+            if stream.status == active {
+                escrowed += stream.total_amount - stream.withdrawn;
+            }
+        }
+
+        let splits: Map<u64, BillSplit> = env.storage().instance().get(&DataKey::Splits).unwrap_or(Map::new(env));
+        for (_, split) in splits.iter() {
+            if split.status == String::from_str(env, "open") {
+                escrowed += split.funded;
+            }
+        }
+
+        let vouchers: Map<BytesN<32>, Voucher> = env.storage().instance().get(&DataKey::Vouchers).unwrap_or(Map::new(env));
+        for (_, voucher) in vouchers.iter() {
+            if voucher.status == active {
+                escrowed += voucher.amount;
+            }
+        }
+
+        let contact_claims: Map<u64, ContactClaim> = env.storage().instance().get(&RecordKey::ContactClaims).unwrap_or(Map::new(env));
+        for (_, claim) in contact_claims.iter() {
+            if claim.status == active {
+                escrowed += claim.amount;
+            }
+        }
+
+        let cash_outs: Map<u64, CashOut> = env.storage().instance().get(&DataKey::CashOuts).unwrap_or(Map::new(env));
+        for (_, cash_out) in cash_outs.iter() {
+            if cash_out.status == pending {
+                escrowed += cash_out.amount;
+            }
+        }
+
+        let remittances: Map<u64, RemittanceV9> = env.storage().instance().get(&DataKey::Remittances).unwrap_or(Map::new(env));
+        for (_, remittance) in remittances.iter() {
+            if remittance.status == pending || remittance.status == processing {
+                escrowed += remittance.usdc_amount + remittance.fee;
+            }
+        }
+
+        let quarantined: Map<u64, QuarantinedTransfer> = env.storage().instance().get(&ComplianceKey::Quarantined).unwrap_or(Map::new(env));
+        for (_, transfer) in quarantined.iter() {
+            escrowed += transfer.amount;
+        }
+
+        let points: Map<Address, i128> = env.storage().instance().get(&DataKey::Rewards).unwrap_or(Map::new(env));
+        for (_, balance) in points.iter() {
+            rewards += balance;
+        }
+
+        // Open bill payments, and completed ones not yet settled to their provider
+        let bills: Map<String, BillPayment> = env.storage().instance().get(&DataKey::Bills).unwrap_or(Map::new(env));
+        for (_, bill) in bills.iter() {
+            if bill.status == pending || bill.status == processing {
+                payable += bill.amount;
+            }
+        }
+        let due: Map<String, i128> = env.storage().instance().get(&DataKey::SettlementDue).unwrap_or(Map::new(env));
+        for (_, amount) in due.iter() {
+            payable += amount;
+        }
+
+        let mut treasury: i128 = 0;
+        for key in [CounterKey::Fees, CounterKey::KeeperPool, CounterKey::LoanPool, CounterKey::ReferralPool, CounterKey::RewardPool] {
+            treasury += env.storage().instance().get::<_, i128>(&key).unwrap_or(0);
+        }
+
+        let spendable = Self::load_stats(env).custodied;
+        let mut ledger = Map::new(env);
+        ledger.set(Bucket::Spendable, spendable);
+        ledger.set(Bucket::LockedSavings, locked);
+        ledger.set(Bucket::Escrowed, escrowed);
+        ledger.set(Bucket::HeldForWithdrawal, held);
+        ledger.set(Bucket::Rewards, rewards);
+        ledger.set(Bucket::Payable, payable);
+        ledger.set(Bucket::Clearing, 0);
+        ledger.set(Bucket::Treasury, treasury);
+        ledger.set(Bucket::External, -(spendable + locked + escrowed + held + rewards + payable + treasury));
+
+        ledger
+    }
+}
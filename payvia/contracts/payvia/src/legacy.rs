@@ -0,0 +1,42 @@
+use soroban_sdk::{contractimpl, symbol_short, Address, Env, String, Symbol};
+
+use crate::{Error, PaymentStatus, Payvia};
+
+// String-status entrypoints kept for older app builds; disable with `--no-default-features`
+#[contractimpl]
+impl Payvia {
+    // Deprecated: use `set_bill_status`
+    pub fn update_bill_status(env: Env, caller: Address, payment_id: String, status: String) -> Result<(), Error> {
+        Self::log_deprecated(&env, &caller, symbol_short!("upd_bill"));
+
+        let status = Self::parse_status(&env, &status)?;
+        Self::set_bill_status(env, caller, payment_id, status)
+    }
+
+    // Deprecated: use `set_withdrawal_status`. Cannot complete withdrawals since it has no way
+    // to pass the payout reference.
+    pub fn update_withdrawal_status(env: Env, caller: Address, withdrawal_id: String, status: String) -> Result<(), Error> {
+        Self::log_deprecated(&env, &caller, symbol_short!("upd_wdraw"));
+
+        let status = Self::parse_status(&env, &status)?;
+        Self::set_withdrawal_status(env, caller, withdrawal_id, status, None)
+    }
+}
+
+impl Payvia {
+    // Record which caller still depends on a legacy entrypoint
+    fn log_deprecated(env: &Env, caller: &Address, entrypoint: Symbol) {
+        env.events().publish((symbol_short!("deprecate"), entrypoint), caller.clone());
+    }
+
+    // Parse a free-form status string from a legacy caller
+    fn parse_status(env: &Env, status: &String) -> Result<PaymentStatus, Error> {
+        for candidate in [PaymentStatus::Pending, PaymentStatus::Processing, PaymentStatus::Completed, PaymentStatus::Failed] {
+            if *status == Self::status_string(env, candidate) {
+                return Ok(candidate);
+            }
+        }
+
+        Err(Error::UnknownStatus)
+    }
+}
@@ -0,0 +1,201 @@
+use soroban_sdk::{contractclient, contractimpl, contracttype, symbol_short, token, Address, Env, Map};
+
+use crate::{AuditTarget, Bucket, ConfigKey, CounterKey, DataKey, Error, Payvia, SavingsGoal, Users};
+
+// Interface of the lending pool idle savings are supplied to
+#[contractclient(name = "LendingPoolClient")]
+pub trait LendingPool {
+    // Mint pool shares to `on_behalf_of` for `amount` already transferred to the pool
+    fn supply(env: Env, on_behalf_of: Address, amount: i128) -> i128;
+    // Burn `shares` and send the underlying to `to`; returns the amount sent
+    fn redeem(env: Env, to: Address, shares: i128) -> i128;
+    // Current underlying value of `shares`
+    fn preview_redeem(env: Env, shares: i128) -> i128;
+}
+
+// A user's savings supplied to the lending pool
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct YieldPosition {
+    // Savings goal funds backing the position
+    pub principal: i128,
+    pub shares: i128,
+}
+
+#[contractimpl]
+impl Payvia {
+    // Set the lending pool savings are supplied to (admin only)
+    pub fn set_yield_pool(env: Env, pool: Address) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        let total_shares: i128 = env.storage().instance().get(&CounterKey::YieldShares).unwrap_or(0);
+        if total_shares > 0 {
+            return Err(Error::PoolHasOpenPositions);
+        }
+        Self::audit(&env, &admin, symbol_short!("yield_pl"), AuditTarget::Address(pool.clone()), 0);
+
+        env.storage().instance().set(&ConfigKey::YieldPool, &pool);
+        env.storage().instance().remove(&DataKey::YieldEscrow);
+
+        Ok(())
+    }
+
+    // Supply the user's savings goal funds that are not yet earning to the lending pool.
+    // Returns the amount supplied. Goal withdrawals are blocked until yield is disabled.
+    pub fn enable_yield(env: Env, user_address: Address) -> Result<i128, Error> {
+        user_address.require_auth();
+        Self::ensure_module_active(&env, &user_address, symbol_short!("savings"))?;
+
+        let pool: Address = env.storage().instance().get(&ConfigKey::YieldPool).ok_or(Error::YieldPoolNotConfigured)?;
+        if env.storage().instance().has(&DataKey::YieldEscrow) {
+            return Err(Error::YieldSuspended);
+        }
+        let usdc: Address = env.storage().instance().get(&ConfigKey::UsdcToken).ok_or(Error::UsdcTokenNotConfigured)?;
+
+        let mut saved: i128 = 0;
+        for goal in Self::goals_for(&env, &user_address).iter() {
+            saved += goal.saved;
+        }
+
+        let mut positions: Map<Address, YieldPosition> = env.storage().instance().get(&DataKey::YieldPositions).unwrap_or(Map::new(&env));
+        let mut position = positions.get(user_address.clone()).unwrap_or(YieldPosition { principal: 0, shares: 0 });
+        let amount = saved - position.principal;
+        if amount <= 0 {
+            return Err(Error::NoIdleSavings);
+        }
+
+        let contract = env.current_contract_address();
+        token::Client::new(&env, &usdc).transfer(&contract, &pool, &amount);
+        let shares = LendingPoolClient::new(&env, &pool).supply(&contract, &amount);
+
+        position.principal += amount;
+        position.shares += shares;
+        positions.set(user_address.clone(), position);
+        env.storage().instance().set(&DataKey::YieldPositions, &positions);
+
+        let total_shares: i128 = env.storage().instance().get(&CounterKey::YieldShares).unwrap_or(0);
+        env.storage().instance().set(&CounterKey::YieldShares, &(total_shares + shares));
+
+        env.events().publish((symbol_short!("yield_on"), user_address), (amount, shares));
+
+        Ok(amount)
+    }
+
+    // Get the interest earned on the user's supplied savings so far
+    pub fn accrued_interest(env: Env, user_address: Address) -> i128 {
+        let positions: Map<Address, YieldPosition> = env.storage().instance().get(&DataKey::YieldPositions).unwrap_or(Map::new(&env));
+        match positions.get(user_address) {
+            Some(position) => Self::position_value(&env, &position) - position.principal,
+            None => 0,
+        }
+    }
+
+    // Withdraw the user's position from the pool. The principal goes back to backing their goals
+    // and the interest is credited to their balance; a loss is taken out of the goals instead.
+    pub fn disable_yield(env: Env, user_address: Address) -> Result<i128, Error> {
+        user_address.require_auth();
+
+        let mut positions: Map<Address, YieldPosition> = env.storage().instance().get(&DataKey::YieldPositions).unwrap_or(Map::new(&env));
+        let position = positions.get(user_address.clone()).ok_or(Error::YieldNotEnabled)?;
+
+        let value = match env.storage().instance().get::<_, (i128, i128)>(&DataKey::YieldEscrow) {
+            // The pool was already emptied by an emergency withdrawal
+            Some(_) => Self::position_value(&env, &position),
+            None => {
+                let pool: Address = env.storage().instance().get(&ConfigKey::YieldPool).ok_or(Error::YieldPoolNotConfigured)?;
+                LendingPoolClient::new(&env, &pool).redeem(&env.current_contract_address(), &position.shares)
+            }
+        };
+        let interest = value - position.principal;
+
+        if interest >= 0 {
+            let mut users = Users::load(&env);
+            let mut user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+            user.balance = Self::checked_credit(user.balance, interest)?;
+            Self::track_balance(&env, &user, interest, Bucket::External);
+            users.set(user_address.clone(), user);
+            users.save();
+        } else {
+            Self::absorb_yield_loss(&env, &user_address, -interest);
+        }
+
+        positions.remove(user_address.clone());
+        env.storage().instance().set(&DataKey::YieldPositions, &positions);
+        let total_shares: i128 = env.storage().instance().get(&CounterKey::YieldShares).unwrap_or(0);
+        env.storage().instance().set(&CounterKey::YieldShares, &(total_shares - position.shares));
+
+        env.events().publish((symbol_short!("yield_off"), user_address), (position.principal, interest));
+
+        Ok(interest)
+    }
+
+    // Pull the whole pooled position out of the lending pool and suspend yield. Users are paid out
+    // pro rata from the recovered amount when they disable yield (admin only).
+    pub fn emergency_withdraw_yield(env: Env) -> Result<i128, Error> {
+        let admin = Self::require_admin(&env)?;
+        if env.storage().instance().has(&DataKey::YieldEscrow) {
+            return Err(Error::YieldSuspended);
+        }
+
+        let pool: Address = env.storage().instance().get(&ConfigKey::YieldPool).ok_or(Error::YieldPoolNotConfigured)?;
+        let total_shares: i128 = env.storage().instance().get(&CounterKey::YieldShares).unwrap_or(0);
+        let recovered = if total_shares > 0 {
+            LendingPoolClient::new(&env, &pool).redeem(&env.current_contract_address(), &total_shares)
+        } else {
+            0
+        };
+
+        env.storage().instance().set(&DataKey::YieldEscrow, &(recovered, total_shares));
+        Self::audit(&env, &admin, symbol_short!("yield_esc"), AuditTarget::Address(pool), recovered);
+        env.events().publish((symbol_short!("yield_esc"),), (recovered, total_shares));
+
+        Ok(recovered)
+    }
+}
+
+impl Payvia {
+    // Current value of a position, from the pool or from the emergency recovery
+    fn position_value(env: &Env, position: &YieldPosition) -> i128 {
+        if let Some((recovered, total_shares)) = env.storage().instance().get::<_, (i128, i128)>(&DataKey::YieldEscrow) {
+            if total_shares == 0 {
+                return 0;
+            }
+            return recovered * position.shares / total_shares;
+        }
+
+        match env.storage().instance().get::<_, Address>(&ConfigKey::YieldPool) {
+            Some(pool) => LendingPoolClient::new(env, &pool).preview_redeem(&position.shares),
+            None => 0,
+        }
+    }
+
+    // Take a pool loss out of the savings goals that backed the position, oldest goal first.
+    // Goal withdrawals are blocked while yield is on, so the goals still hold the principal.
+    fn absorb_yield_loss(env: &Env, user_address: &Address, loss: i128) {
+        let mut goals: Map<u64, SavingsGoal> = env.storage().instance().get(&DataKey::Goals).unwrap_or(Map::new(env));
+        let mut remaining = loss;
+
+        for (goal_id, mut goal) in goals.clone().iter() {
+            if remaining == 0 {
+                break;
+            }
+            if goal.owner != *user_address || goal.saved == 0 {
+                continue;
+            }
+            let taken = goal.saved.min(remaining);
+            goal.saved -= taken;
+            remaining -= taken;
+            goals.set(goal_id, goal);
+            Self::post(env, user_address, Bucket::LockedSavings, Bucket::External, taken);
+        }
+        env.storage().instance().set(&DataKey::Goals, &goals);
+
+        env.events().publish((symbol_short!("yield_los"), user_address.clone()), loss - remaining);
+    }
+
+    // Whether the user's savings are currently supplied to the lending pool
+    pub(crate) fn yield_enabled(env: &Env, user_address: &Address) -> bool {
+        let positions: Map<Address, YieldPosition> = env.storage().instance().get(&DataKey::YieldPositions).unwrap_or(Map::new(env));
+
+        positions.contains_key(user_address.clone())
+    }
+}
@@ -1,280 +1,10173 @@
 #![no_std]
+#[cfg(test)]
+extern crate std;
+
 use soroban_sdk::{
-    contract, contractimpl, contracttype, symbol_short, vec, Address, Env, Map, String, Symbol, Vec,
+    contract, contractimpl, contracttype, symbol_short, token, vec, xdr::ToXdr, Address, Bytes, BytesN, Env, IntoVal, Map, String, Symbol, TryFromVal,
+    Val, Vec,
 };
 
-#[contract]
-pub struct Payvia;
+// Fixed-point scale used for oracle rates (units of the asset per 1 USDC)
+pub const RATE_SCALE: i128 = 10_000_000;
+
+// Denominator for fees and penalties expressed in basis points
+pub const BPS_DENOMINATOR: i128 = 10_000;
+
+// Early-withdrawal penalty applied to savings goals unless the admin overrides it
+pub const DEFAULT_GOAL_PENALTY_BPS: u32 = 500;
+
+// Late or missed savings-group contributions pay this penalty on top of the contribution
+pub const DEFAULT_GROUP_PENALTY_BPS: u32 = 1_000;
+
+pub const SECONDS_PER_DAY: u64 = 86_400;
+
+// Longest memo accepted on sends, bills and withdrawals
+pub const MAX_MEMO_LEN: u32 = 64;
+
+// How long a processed idempotency key keeps returning the original result
+pub const IDEMPOTENCY_TTL_SECS: u64 = SECONDS_PER_DAY;
+
+// Ledgers a processed idempotency key is kept for; covers `IDEMPOTENCY_TTL_SECS`
+pub const IDEMPOTENCY_TTL_LEDGERS: u32 = DAY_IN_LEDGERS;
+
+// Number of daily balance snapshots retained per user
+pub const MAX_BALANCE_SNAPSHOTS: u32 = 366;
+
+// Intra-day balance change (vs. the day's opening balance) that triggers a `bal_alert` event
+pub const DEFAULT_BALANCE_ALERT_BPS: u32 = 5_000;
+
+// Largest amount a single sandbox faucet call may mint
+pub const SANDBOX_FAUCET_MAX: i128 = 1_000_000_000;
+
+// Length of a merchant fee statement period
+pub const FEE_PERIOD_SECS: u64 = 30 * SECONDS_PER_DAY;
+
+// Time an agent has to complete a cash-out before the hold is released back to the user
+pub const DEFAULT_CASH_OUT_WINDOW_SECS: u64 = 15 * 60;
+
+// Most items a single `claim_all` call will process
+pub const MAX_CLAIMS_PER_CALL: u32 = 20;
+
+// Storage layout version written by this build; `migrate` upgrades older layouts to it
+pub const CONTRACT_VERSION: u32 = 19;
+
+// Longest display name accepted on a profile
+pub const MAX_DISPLAY_NAME_LEN: u32 = 64;
+
+// Fiat rates older than this are refused for conversions
+pub const MAX_RATE_AGE_SECS: u64 = SECONDS_PER_DAY;
+
+// How long a withdrawal hold waits for the off-ramp before it is refunded
+pub const DEFAULT_WITHDRAWAL_HOLD_SECS: u64 = SECONDS_PER_DAY;
+
+// How long the off-ramp has to complete a withdrawal before the user may cancel it
+pub const DEFAULT_WITHDRAWAL_SLA_SECS: u64 = 4 * 60 * 60;
+
+// Sends, bill payments and withdrawals above this amount need a verified PIN (100 USDC)
+pub const DEFAULT_PIN_THRESHOLD: i128 = 1_000_000_000;
+
+// Wrong PIN attempts allowed before the PIN is locked
+pub const MAX_PIN_ATTEMPTS: u32 = 5;
+
+// How long a PIN stays locked after too many wrong attempts
+pub const PIN_LOCKOUT_SECS: u64 = 60 * 60;
+
+// How long a verified PIN authorizes the next high-value operation
+pub const PIN_APPROVAL_SECS: u64 = 5 * 60;
+
+// Time between guardians approving a recovery and it becoming executable, so the owner can cancel
+pub const RECOVERY_DELAY_SECS: u64 = 2 * SECONDS_PER_DAY;
+
+// Ledgers an audit entry is kept alive for unless the admin changes the retention (~180 days)
+pub const DEFAULT_AUDIT_TTL_LEDGERS: u32 = 3_110_400;
+
+// Most audit entries returned by one `get_audit_log` call
+pub const MAX_AUDIT_PAGE: u32 = 100;
+
+// Most items any other list endpoint returns per call; callers follow the returned cursor for more.
+// Lists bounded by design (roles, paused modules, spending categories) are returned whole.
+pub const MAX_PAGE_SIZE: u32 = 50;
+
+// Ledgers closed per day at ~5 seconds per ledger
+pub const DAY_IN_LEDGERS: u32 = 17_280;
+
+// Instance storage, and the per-entry user records when they are written, are topped up to this TTL
+pub const INSTANCE_BUMP_AMOUNT: u32 = 30 * DAY_IN_LEDGERS;
+
+// Remaining instance TTL below which an access tops it up
+pub const INSTANCE_LIFETIME_THRESHOLD: u32 = INSTANCE_BUMP_AMOUNT - DAY_IN_LEDGERS;
+
+// Time participants have to fund a split bill before contributions are refunded
+pub const SPLIT_WINDOW_SECS: u64 = 3 * SECONDS_PER_DAY;
+
+// Flat fee charged on a micro-loan, added to what the borrower owes
+pub const DEFAULT_LOAN_FEE_BPS: u32 = 500;
+
+// Share of the last 90 days' spending a user can borrow
+pub const LOAN_LIMIT_BPS: u32 = 5_000;
+
+// Window of spending history used to size loans
+pub const LOAN_HISTORY_SECS: u64 = 90 * SECONDS_PER_DAY;
+
+pub const SECONDS_PER_YEAR: u64 = 365 * SECONDS_PER_DAY;
+
+// Rolling window the per-user send and bill payment limits apply to
+pub const RATE_LIMIT_WINDOW_SECS: u64 = 60 * 60;
+
+// Most contacts a user can save
+pub const MAX_CONTACTS: u32 = 100;
+
+// Most standing instructions a user can have
+pub const MAX_AUTO_RULES: u32 = 10;
+
+// Most legs a single batch send may carry
+pub const MAX_BATCH_LEGS: u32 = 20;
+
+// Window send volume is measured over for velocity flags
+pub const VELOCITY_WINDOW_SECS: u64 = SECONDS_PER_DAY;
+
+// Most bills a single `pay_bills_batch` call may pay
+pub const MAX_BILL_BATCH: u32 = 10;
+
+// Most employees a single payroll can pay
+pub const MAX_PAYROLL_ENTRIES: u32 = 50;
+
+// Notifications kept per user; the oldest are dropped beyond this
+pub const MAX_INBOX_LEN: u32 = 50;
+
+// Fee on tips; capped at the P2P transfer fee so small appreciation payments stay worthwhile
+pub const DEFAULT_TIP_FEE_BPS: u32 = 50;
+
+// Risk scores run from 0 (no concern) to this value
+pub const MAX_RISK_SCORE: u32 = 100;
+
+// Scores at or above this get extra friction: reduced limits, held sends and a review listing
+pub const HIGH_RISK_SCORE: u32 = 70;
+
+// Share of the normal per-transaction limit a high-risk account may move
+pub const HIGH_RISK_LIMIT_BPS: u32 = 2_500;
+
+// Most recent transfers, bill payments and withdrawals included in an account export
+pub const EXPORT_HISTORY_LEN: u32 = 20;
+
+// Highest FX spread the admin can set on withdrawals and remittances
+pub const MAX_FX_SPREAD_BPS: u32 = 500;
+
+// Attempts allowed per bill payment, counting the original one
+pub const MAX_BILL_ATTEMPTS: u32 = 5;
+
+// Wait before the first bill retry; doubles with every attempt after that
+pub const BILL_RETRY_BACKOFF_SECS: u64 = 60;
+
+#[contract]
+pub struct Payvia;
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct User {
+    pub address: Address,
+    pub phone: String,
+    pub is_verified: bool,
+    pub balance: i128,
+    pub display_name: Option<String>,
+    pub country: Option<Symbol>,
+    pub preferred_currency: Option<Symbol>,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BillPayment {
+    pub id: String,
+    pub user_address: Address,
+    pub bill_type: String,
+    pub account_number: String,
+    pub amount: i128,
+    pub status: String,
+    pub timestamp: u64,
+    pub memo: Option<String>,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Withdrawal {
+    pub id: String,
+    pub user_address: Address,
+    pub method: String,
+    pub account_number: String,
+    pub usdc_amount: i128,
+    pub local_amount: i128,
+    pub currency: Symbol,
+    pub status: String,
+    pub timestamp: u64,
+    pub memo: Option<String>,
+    // Off-ramp transaction id (MoMo transaction id, bank reference) recorded on completion
+    pub payout_reference: Option<String>,
+    // After this the user may cancel the withdrawal if the off-ramp has not completed it
+    pub processing_deadline: u64,
+    // Oracle rate less the FX spread, at the scale of `RATE_SCALE`; `local_amount` is paid at this rate
+    pub effective_rate: i128,
+    // USDC kept as FX spread revenue once the withdrawal completes
+    pub spread: i128,
+}
+
+// Storage layout of withdrawals before FX spreads (version 9)
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WithdrawalV9 {
+    pub id: String,
+    pub user_address: Address,
+    pub method: String,
+    pub account_number: String,
+    pub usdc_amount: i128,
+    pub local_amount: i128,
+    pub currency: Symbol,
+    pub status: String,
+    pub timestamp: u64,
+    pub memo: Option<String>,
+    pub payout_reference: Option<String>,
+    pub processing_deadline: u64,
+}
+
+// Storage layout of billers before amount policies (version 7)
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BillProviderV7 {
+    pub id: String,
+    pub name: String,
+    pub category: Symbol,
+    pub min_amount: i128,
+    pub max_amount: i128,
+    pub account_min_len: u32,
+    pub account_max_len: u32,
+    pub numeric_account: bool,
+    pub active: bool,
+}
+
+// Storage layout of withdrawals before processing deadlines (version 6)
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WithdrawalV4 {
+    pub id: String,
+    pub user_address: Address,
+    pub method: String,
+    pub account_number: String,
+    pub usdc_amount: i128,
+    pub local_amount: i128,
+    pub currency: Symbol,
+    pub status: String,
+    pub timestamp: u64,
+    pub memo: Option<String>,
+    pub payout_reference: Option<String>,
+}
+
+// Storage layout of withdrawals before payout references were recorded (version 3)
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WithdrawalV3 {
+    pub id: String,
+    pub user_address: Address,
+    pub method: String,
+    pub account_number: String,
+    pub usdc_amount: i128,
+    pub local_amount: i128,
+    pub currency: Symbol,
+    pub status: String,
+    pub timestamp: u64,
+    pub memo: Option<String>,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Allowance {
+    pub amount: i128,
+    pub expiry: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OracleRate {
+    pub rate: i128,
+    pub timestamp: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PendingSummary {
+    pub bill_count: u32,
+    pub bill_total: i128,
+    pub withdrawal_count: u32,
+    pub withdrawal_total: i128,
+    pub scheduled_count: u32,
+    pub scheduled_total: i128,
+    pub hold_count: u32,
+    pub hold_total: i128,
+    pub card_hold_count: u32,
+    pub card_hold_total: i128,
+    pub payroll_count: u32,
+    pub payroll_total: i128,
+    pub split_count: u32,
+    pub split_total: i128,
+    pub voucher_count: u32,
+    pub voucher_total: i128,
+    pub contact_send_count: u32,
+    pub contact_send_total: i128,
+    pub remittance_count: u32,
+    pub remittance_total: i128,
+    pub count: u32,
+    pub total: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SavingsGoal {
+    pub id: u64,
+    pub owner: Address,
+    pub name: String,
+    pub target_amount: i128,
+    pub saved: i128,
+    pub unlock_time: u64,
+    pub created_at: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SavingsGroup {
+    pub id: u64,
+    pub name: String,
+    pub members: Vec<Address>,
+    pub contribution: i128,
+    pub cycle_secs: u64,
+    pub round: u32,
+    pub round_deadline: u64,
+    pub pot: i128,
+    pub paid: Vec<Address>,
+    pub missed: Vec<u32>,
+    // Missed contributions plus penalty still owed by each member, collected with their next one
+    pub arrears: Vec<i128>,
+    pub active: bool,
+}
+
+// Storage layout of savings groups before arrears (version 14)
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SavingsGroupV14 {
+    pub id: u64,
+    pub name: String,
+    pub members: Vec<Address>,
+    pub contribution: i128,
+    pub cycle_secs: u64,
+    pub round: u32,
+    pub round_deadline: u64,
+    pub pot: i128,
+    pub paid: Vec<Address>,
+    pub missed: Vec<u32>,
+    pub active: bool,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BalanceSnapshot {
+    // Start of the day (UTC) this snapshot opens
+    pub timestamp: u64,
+    pub balance: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ScheduledPayment {
+    pub id: u64,
+    pub from_address: Address,
+    pub to_address: Address,
+    pub amount: i128,
+    pub execute_at: u64,
+    pub status: String,
+    pub timestamp: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SandboxStatus {
+    pub enabled: bool,
+    // Play money minted by the faucet; excluded from reserves and reporting
+    pub minted: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PaymentStream {
+    pub id: u64,
+    pub from_address: Address,
+    pub to_address: Address,
+    pub total_amount: i128,
+    pub withdrawn: i128,
+    pub start: u64,
+    pub end: u64,
+    pub status: String,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReferralStats {
+    pub referred_count: u32,
+    pub qualified_count: u32,
+    pub rewards_earned: i128,
+}
+
+// Details of a rejected check. A failed invocation rolls back its writes, so these are only
+// seen by callers that run the check without failing, e.g. the `simulate_*` previews.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FailureRecord {
+    pub user_address: Address,
+    pub operation: Symbol,
+    // Machine-readable reason code, e.g. `balance`, `allowance`, `locked`
+    pub reason: Symbol,
+    pub error: Error,
+    pub requested: i128,
+    pub available: i128,
+    // Earliest timestamp at which retrying can succeed, 0 if unknown or not time-bound
+    pub retry_after: u64,
+    pub timestamp: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Agent {
+    pub address: Address,
+    pub name: String,
+    pub float_balance: i128,
+    pub commission_earned: i128,
+    pub active: bool,
+    pub registered_at: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CashOut {
+    pub id: u64,
+    pub user_address: Address,
+    pub agent: Address,
+    pub amount: i128,
+    // sha256 of the one-time code the user reads out to the agent
+    pub code_hash: BytesN<32>,
+    pub expires_at: u64,
+    pub status: String,
+    pub timestamp: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Merchant {
+    pub address: Address,
+    pub name: String,
+    pub fee_bps: u32,
+    // Fees accumulate and are invoiced per period instead of being deducted per payment
+    pub accrual: bool,
+    pub active: bool,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeeStatement {
+    pub merchant: Address,
+    pub period: u64,
+    pub payment_count: u32,
+    pub volume: i128,
+    pub fees: i128,
+    pub settled: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FeeBearer {
+    // Fee is added on top of the amount
+    Payer,
+    // Fee is deducted from what the payee receives
+    Payee,
+    // Each side pays half; an odd unit goes to the payer
+    Split,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeeQuote {
+    pub amount: i128,
+    pub fee: i128,
+    pub payer_pays: i128,
+    pub payee_receives: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Transfer {
+    pub id: u64,
+    pub from_address: Address,
+    pub to_address: Address,
+    pub amount: i128,
+    pub fee: i128,
+    pub memo: Option<String>,
+    pub timestamp: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum IdempotentResult {
+    Transfer(u64),
+    Bill(String),
+    Withdrawal(String),
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct IdempotencyRecord {
+    pub result: IdempotentResult,
+    pub expires_at: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ScreeningMode {
+    Off,
+    Reject,
+    Quarantine,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ScreeningPolicy {
+    pub mode: ScreeningMode,
+    // First-time transfers from unknown senders below this amount are screened
+    pub threshold: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct QuarantinedTransfer {
+    pub transfer_id: u64,
+    pub from_address: Address,
+    pub to_address: Address,
+    pub amount: i128,
+    pub timestamp: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ClaimKind {
+    // Due scheduled payments addressed to the user
+    Scheduled,
+    // Vested stream balance
+    Stream,
+    // Cash-outs an agent failed to confirm in time
+    CashOut,
+    // Quarantined incoming transfers (accepted)
+    Quarantine,
+    // Cashback points
+    Rewards,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ClaimResult {
+    pub kind: ClaimKind,
+    pub reference: u64,
+    pub amount: i128,
+    pub success: bool,
+}
+
+// Storage layout of bill payments before memos were added (version 0)
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BillPaymentV0 {
+    pub id: String,
+    pub user_address: Address,
+    pub bill_type: String,
+    pub account_number: String,
+    pub amount: i128,
+    pub status: String,
+    pub timestamp: u64,
+}
+
+// Storage layout of withdrawals before memos were added (version 0)
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WithdrawalV0 {
+    pub id: String,
+    pub user_address: Address,
+    pub method: String,
+    pub account_number: String,
+    pub usdc_amount: i128,
+    pub ugx_amount: i128,
+    pub status: String,
+    pub timestamp: u64,
+}
+
+// Storage layout of users before profile fields were added (version 2)
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UserV2 {
+    pub address: Address,
+    pub phone: String,
+    pub is_verified: bool,
+    pub balance: i128,
+}
+
+// Storage layout of withdrawals before payouts were priced per currency (version 1)
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WithdrawalV1 {
+    pub id: String,
+    pub user_address: Address,
+    pub method: String,
+    pub account_number: String,
+    pub usdc_amount: i128,
+    pub ugx_amount: i128,
+    pub status: String,
+    pub timestamp: u64,
+    pub memo: Option<String>,
+}
+
+// Sensitive admin calls that require council approval once a council is configured
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AdminAction {
+    UpdateBillStatus(String, PaymentStatus),
+    UpdateWithdrawalStatus(String, PaymentStatus, Option<String>),
+    SetTransferFee(u32),
+    SetAgentCommission(u32),
+    Upgrade(BytesN<32>),
+    SweepFees(Address, i128),
+    SetAdmins(Vec<Address>, u32),
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AdminProposal {
+    pub id: u64,
+    pub action: AdminAction,
+    pub proposer: Address,
+    pub approvals: Vec<Address>,
+    pub executed: bool,
+    pub created_at: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BillProvider {
+    // Used as `bill_type` when paying a bill
+    pub id: String,
+    pub name: String,
+    pub category: Symbol,
+    pub min_amount: i128,
+    pub max_amount: i128,
+    pub account_min_len: u32,
+    pub account_max_len: u32,
+    // Account numbers must consist of digits only
+    pub numeric_account: bool,
+    pub active: bool,
+    // Which amounts between `min_amount` and `max_amount` the biller accepts
+    pub amount_policy: AmountPolicy,
+}
+
+// How a biller constrains the amount of a payment
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AmountPolicy {
+    // Any amount within the biller's limits
+    Range,
+    // Only one of the listed amounts, e.g. TV packages
+    Exact(Vec<i128>),
+    // Any multiple of the given step within the limits
+    Multiple(i128),
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Telco {
+    pub id: Symbol,
+    pub name: String,
+    pub min_airtime: i128,
+    pub max_airtime: i128,
+    pub active: bool,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DataBundle {
+    pub id: u32,
+    pub telco: Symbol,
+    pub name: String,
+    pub price: i128,
+    pub data_mb: u32,
+    pub validity_days: u32,
+    pub active: bool,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TopUp {
+    pub id: u64,
+    pub user_address: Address,
+    pub telco: Symbol,
+    pub phone: String,
+    // `None` for airtime, the bundle for data purchases
+    pub bundle_id: Option<u32>,
+    pub amount: i128,
+    pub status: String,
+    pub timestamp: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SystemStatus {
+    // e.g. `ok`, `degraded`, `maint`
+    pub code: Symbol,
+    // Localization key the app resolves to banner text
+    pub message_key: Symbol,
+    // When the status lapses back to `ok`; 0 means until replaced
+    pub until: u64,
+    pub updated_at: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WithdrawalRail {
+    // Passed as `method` to `withdraw`, e.g. `mtn_momo`, `airtel_money`, `bank`
+    pub id: String,
+    pub name: String,
+    pub min_amount: i128,
+    pub max_amount: i128,
+    pub fee_bps: u32,
+    pub flat_fee: i128,
+    pub enabled: bool,
+}
+
+// Off-chain processing status of a bill payment or withdrawal
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PaymentStatus {
+    Pending,
+    Processing,
+    Completed,
+    Failed,
+}
+
+// Funds locked by a withdrawal until the off-ramp completes or the hold expires
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WithdrawalHold {
+    pub user_address: Address,
+    // Paid out to the off-ramp once the withdrawal completes
+    pub amount: i128,
+    // Rail fee and FX spread, only collected once the withdrawal completes
+    pub fee: i128,
+    pub expires_at: u64,
+}
+
+// Operator permissions that can be granted separately from the admin
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Role {
+    // Moves bill payments and withdrawals through their statuses and sets the status banner
+    StatusUpdater,
+    // Verifies users
+    KycOfficer,
+    // Funds pools, settles merchant fees and sweeps fees
+    Treasurer,
+    // Pauses and resumes modules, billers and withdrawal rails
+    Pauser,
+    // Approves micro-loans
+    Underwriter,
+    // Reviews and clears accounts flagged by the velocity rule
+    Compliance,
+    // Places, captures and releases card authorization holds
+    PaymentProcessor,
+    // Pushes counterparty risk scores from the compliance backend
+    RiskOfficer,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PinState {
+    // sha256 of `salt || PIN`
+    pub hash: BytesN<32>,
+    // Random per-user salt chosen when the PIN is set, so one table of hashed PINs cannot be
+    // matched against every account. None for PINs set before v12, which are hashed bare until
+    // the user sets a new one.
+    pub salt: Option<BytesN<32>>,
+    pub failed_attempts: u32,
+    pub locked_until: u64,
+    // Set by a successful `verify_pin`; consumed by the next high-value operation
+    pub approved_until: u64,
+}
+
+// Storage layout of PINs before salts (version 11)
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PinStateV11 {
+    pub hash: BytesN<32>,
+    pub failed_attempts: u32,
+    pub locked_until: u64,
+    pub approved_until: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GuardianSet {
+    pub guardians: Vec<Address>,
+    // Guardian approvals needed to recover the account
+    pub threshold: u32,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RecoveryRequest {
+    pub new_address: Address,
+    pub approvals: Vec<Address>,
+    // When the recovery can be finalized; 0 until the threshold is reached
+    pub ready_at: u64,
+}
+
+// Running totals for the operator dashboard, maintained as operations happen
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Stats {
+    pub user_count: u32,
+    // Sum of all user balances
+    pub custodied: i128,
+    pub bill_volume: i128,
+    pub withdrawal_volume: i128,
+    pub bill_statuses: Map<String, u32>,
+    pub withdrawal_statuses: Map<String, u32>,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AuditTarget {
+    Global,
+    Address(Address),
+    Id(String),
+    Key(Symbol),
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AuditEntry {
+    pub seq: u64,
+    pub actor: Address,
+    pub action: Symbol,
+    pub target: AuditTarget,
+    pub amount: i128,
+    pub timestamp: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SpendCategory {
+    // Bill payments by bill type
+    Bill(String),
+    Transfer,
+    Withdrawal,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SpendingTotal {
+    pub category: SpendCategory,
+    pub amount: i128,
+}
+
+// A bill shared between several users, paid once every participant has funded their share
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BillSplit {
+    pub id: u64,
+    pub creator: Address,
+    pub bill_type: String,
+    pub account_number: String,
+    pub total: i128,
+    pub shares: Map<Address, i128>,
+    pub funded_by: Vec<Address>,
+    pub funded: i128,
+    pub expires_at: u64,
+    // `open`, `paid` or `expired`
+    pub status: String,
+    pub payment_id: Option<String>,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Voucher {
+    pub funder: Address,
+    pub amount: i128,
+    pub expires_at: u64,
+    // `active`, `redeemed` or `reclaimed`
+    pub status: String,
+    pub redeemed_by: Option<Address>,
+}
+
+// A redeemer's sha256 of the XDR of `(code_preimage, redeemer)`, sent a ledger before the code
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VoucherCommitment {
+    pub commitment: BytesN<32>,
+    pub ledger: u32,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Loan {
+    pub id: u64,
+    pub borrower: Address,
+    pub amount: i128,
+    // Principal plus fee
+    pub owed: i128,
+    pub repaid: i128,
+    pub term_secs: u64,
+    // Set when the loan is approved
+    pub due_at: u64,
+    // `requested`, `active`, `repaid` or `rejected`
+    pub status: String,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CreditConfig {
+    // Largest debt-to-collateral ratio a borrow may reach
+    pub max_ltv_bps: u32,
+    // Ratio above which a position can be liquidated
+    pub liquidation_ltv_bps: u32,
+    // Annual interest on outstanding debt
+    pub interest_bps: u32,
+    // Taken from the collateral on top of the debt when liquidating
+    pub liquidation_penalty_bps: u32,
+}
+
+// A credit line backed by locked USDC
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CreditLine {
+    pub collateral: i128,
+    pub debt: i128,
+    // When interest was last added to the debt
+    pub accrued_at: u64,
+}
+
+// Payout route for remittances to one country
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Corridor {
+    pub country: Symbol,
+    // Payout currency; converted at its `set_rate` rate
+    pub currency: Symbol,
+    pub fee_bps: u32,
+    pub flat_fee: i128,
+    pub active: bool,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Beneficiary {
+    pub id: u64,
+    pub owner: Address,
+    pub name: String,
+    pub phone: String,
+    pub country: Symbol,
+    pub payout_method: String,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Remittance {
+    pub id: u64,
+    pub sender: Address,
+    pub beneficiary_id: u64,
+    pub usdc_amount: i128,
+    pub fee: i128,
+    pub local_amount: i128,
+    pub currency: Symbol,
+    pub status: String,
+    pub timestamp: u64,
+    // USDC kept as FX spread revenue once the remittance completes
+    pub spread: i128,
+}
+
+// Storage layout of remittances before FX spreads (version 9)
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RemittanceV9 {
+    pub id: u64,
+    pub sender: Address,
+    pub beneficiary_id: u64,
+    pub usdc_amount: i128,
+    pub fee: i128,
+    pub local_amount: i128,
+    pub currency: Symbol,
+    pub status: String,
+    pub timestamp: u64,
+}
+
+// Work item for the off-chain fulfillment service
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OperatorTask {
+    pub id: u64,
+    // `bill` or `withdraw`
+    pub kind: Symbol,
+    // Id of the bill payment or withdrawal to fulfill
+    pub reference: String,
+    pub user_address: Address,
+    pub amount: i128,
+    pub created_at: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DisputeOutcome {
+    // Return the disputed amount to the user
+    Refund,
+    // The transaction stands as processed
+    Upheld,
+}
+
+// A user's challenge of a settled bill payment or withdrawal
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Dispute {
+    pub id: u64,
+    pub user_address: Address,
+    // Id of the disputed bill payment or withdrawal
+    pub reference_id: String,
+    pub amount: i128,
+    pub reason: String,
+    // `open`, `refunded` or `upheld`
+    pub status: String,
+    pub opened_at: u64,
+    pub resolved_at: u64,
+}
+
+// Deposit tagged with one of the user's deposit references
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TaggedDeposit {
+    pub id: u64,
+    pub user_address: Address,
+    pub ref_code: String,
+    pub amount: i128,
+    pub timestamp: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DustPolicy {
+    // Smallest amount a single send may move
+    pub min_send: i128,
+    // Balances below this are dust; sends may not leave a non-zero balance under it
+    pub dust_threshold: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UserTier {
+    Unverified,
+    Verified,
+}
+
+// Operations allowed per `RATE_LIMIT_WINDOW_SECS`; 0 means unlimited
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RateLimit {
+    pub max_sends: u32,
+    pub max_bills: u32,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RateLimitStatus {
+    pub tier: UserTier,
+    pub sends_used: u32,
+    pub max_sends: u32,
+    pub bills_used: u32,
+    pub max_bills: u32,
+    // When the oldest counted operation leaves the window; 0 if none are counted
+    pub resets_at: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Contact {
+    pub address: Address,
+    pub nickname: String,
+}
+
+// Standing instruction applied to each incoming deposit
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AutoRule {
+    // Move this share of every deposit, in basis points, into a savings goal
+    SaveToGoal(u64, u32),
+    // Pay `amount` to a biller (bill type, account number) whenever a deposit leaves the balance above `threshold`
+    PayBill(String, String, i128, i128),
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AutoRuleEntry {
+    pub id: u64,
+    pub owner: Address,
+    pub rule: AutoRule,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BatchLeg {
+    pub to_address: Address,
+    pub amount: i128,
+}
+
+// Outcome of one leg of a batch send: the transfer id on success, otherwise why it was rejected
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BatchLegResult {
+    pub to_address: Address,
+    pub amount: i128,
+    pub transfer_id: Option<u64>,
+    pub error: Option<Error>,
+}
+
+// 0 disables either limit
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TransactionLimits {
+    // Largest single send, bill payment or withdrawal
+    pub max_transaction: i128,
+    // Send volume within `VELOCITY_WINDOW_SECS` that flags the account for review
+    pub velocity_limit: i128,
+}
+
+// Fee discount unlocked by a promo code; only the sha256 of the code is stored
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Promo {
+    pub code_hash: BytesN<32>,
+    pub discount_bps: u32,
+    // 0 means unlimited
+    pub max_uses: u32,
+    pub uses: u32,
+    pub expiry: u64,
+    // Fees waived through this code so far
+    pub fees_waived: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BillRequest {
+    pub bill_type: String,
+    pub account_number: String,
+    pub amount: i128,
+    pub memo: Option<String>,
+}
+
+// Card-style authorization: funds reserved by a payment processor until captured or released
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CardHold {
+    pub id: u64,
+    pub user_address: Address,
+    pub processor: Address,
+    pub amount: i128,
+    // Processor's authorization reference
+    pub reference: String,
+    pub expires_at: u64,
+    // `active`, `captured` or `released`
+    pub status: String,
+    pub captured: i128,
+}
+
+// Dependent account managed by a verified parent
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SubAccount {
+    pub dependent: Address,
+    pub parent: Address,
+    pub label: String,
+    // Largest single spend the dependent may make; 0 means no limit
+    pub limit: i128,
+    // Operations the dependent may not perform (`send`, `pay_bill`, `withdraw`, `cash_out`, `pay_mrch`, `remit`)
+    pub blocked: Vec<Symbol>,
+    pub created_at: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SubAccountHistory {
+    pub transfers: Vec<Transfer>,
+    pub bill_payments: Vec<BillPayment>,
+    pub withdrawals: Vec<Withdrawal>,
+}
+
+// Employer payout schedule, escrowed from the employer's balance until payday
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Payroll {
+    pub id: u64,
+    pub employer: Address,
+    pub payouts: Vec<(Address, i128)>,
+    pub total: i128,
+    pub payday: u64,
+    // `pending`, `executed` or `cancelled`
+    pub status: String,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PayrollPayout {
+    pub payroll_id: u64,
+    pub employer: Address,
+    pub employee: Address,
+    pub amount: i128,
+    pub paid_at: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NotifyChannel {
+    Push,
+    Sms,
+}
+
+// How the off-chain notifier should reach a user; sent along with every notification event
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Preferences {
+    // Language code for messages, e.g. `en`, `sw`, `lg`
+    pub language: Symbol,
+    pub channel: NotifyChannel,
+    pub marketing_opt_in: bool,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum NotificationRef {
+    Id(u64),
+    Key(String),
+}
+
+// Inbox entry the app renders as a user-facing message
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Notification {
+    pub id: u64,
+    // What happened, e.g. `received`, `wd_done`, `bill_fail`, `salary`
+    pub kind: Symbol,
+    pub reference: NotificationRef,
+    pub amount: i128,
+    pub timestamp: u64,
+    pub read: bool,
+}
+
+// Funds sent to someone known only by a salted email hash, waiting for them to sign up and claim
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ContactClaim {
+    pub id: u64,
+    pub sender: Address,
+    // sha256 of the salted contact, e.g. `salt || lowercase(email)`
+    pub contact_hash: BytesN<32>,
+    pub amount: i128,
+    pub expires_at: u64,
+    // `active`, `claimed` or `reclaimed`
+    pub status: String,
+    pub claimed_by: Option<Address>,
+}
+
+// Public running totals of the tips a user has received
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TipStats {
+    pub tip_count: u32,
+    pub total_received: i128,
+    pub last_tip_at: u64,
+}
+
+// Planned downtime on a withdrawal rail or biller
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MaintenanceWindow {
+    pub start_ts: u64,
+    pub end_ts: u64,
+    // Reported as the failure reason so the app can show a matching message
+    pub message_code: Symbol,
+}
+
+// Withdrawal hold applied to new accounts and after large deposits, to slow down fraud cash-outs
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CoolingOffPolicy {
+    // How long after registration withdrawals are held; zero disables the hold
+    pub new_account_secs: u64,
+    // Deposits of at least this amount start a hold; zero disables the hold
+    pub large_deposit: i128,
+    pub large_deposit_secs: u64,
+    // Total a user may withdraw while a hold is active; zero blocks withdrawals outright
+    pub withdraw_cap: i128,
+}
+
+// When a user's cooling-off periods started
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CoolingOff {
+    pub registered_at: u64,
+    pub large_deposit_at: Option<u64>,
+}
+
+// An active hold on a user's withdrawals, as shown in the app
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Restriction {
+    // `new_acct` or `deposit`
+    pub kind: Symbol,
+    pub until: u64,
+    pub withdraw_cap: i128,
+    // Withdrawn since the hold started, counted against `withdraw_cap`
+    pub withdrawn: i128,
+}
+
+// Score pushed by the compliance backend for a user
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RiskScore {
+    pub score: u32,
+    pub updated_at: u64,
+}
+
+// Everything held about a user, in one response for support agents and data requests
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AccountExport {
+    pub user: User,
+    pub preferences: Preferences,
+    // Non-USDC balances by token
+    pub asset_balances: Vec<(Address, i128)>,
+    pub rewards_balance: i128,
+    // Per-transaction limits after any risk-based reduction
+    pub max_transaction: i128,
+    pub rate_limits: RateLimitStatus,
+    pub restrictions: Vec<Restriction>,
+    pub risk_score: u32,
+    pub pending: PendingSummary,
+    pub goals: Vec<SavingsGoal>,
+    pub loans: Vec<Loan>,
+    pub credit_line: CreditLine,
+    // The last `EXPORT_HISTORY_LEN` of each, oldest first
+    pub recent_transfers: Vec<Transfer>,
+    pub recent_bills: Vec<BillPayment>,
+    pub recent_withdrawals: Vec<Withdrawal>,
+}
+
+// Operator retries of a bill payment against a flaky biller
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BillAttempts {
+    // Attempts so far, counting the original payment
+    pub attempts: u32,
+    // Earliest time the next retry is accepted
+    pub next_retry_at: u64,
+    // When each attempt was made, oldest first
+    pub history: Vec<u64>,
+}
+
+// Sends above `soft_limit` (and within the hard transaction limit) need a step-up attestation
+// signed by the verifier, e.g. the OTP service once the user has passed a one-time code
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StepUpPolicy {
+    pub soft_limit: i128,
+    // ed25519 public key of the verifier
+    pub verifier: BytesN<32>,
+    // How long after issuing an attestation it can be used
+    pub max_age_secs: u64,
+}
+
+// Verifier's signature over the contract, sender, amount and issue time
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StepUpAttestation {
+    // Largest send the attestation covers
+    pub amount: i128,
+    pub issued_at: u64,
+    pub signature: BytesN<64>,
+}
+
+// A user's latest step-up. `approved_amount` is consumed by the next send above the soft limit;
+// `issued_at` stops an attestation being replayed.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StepUp {
+    pub approved_amount: i128,
+    pub issued_at: u64,
+}
+
+// Regulatory rules for users registered in one country, keyed by ISO 3166-1 alpha-2 code
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CountryPolicy {
+    pub country: Symbol,
+    // Withdrawal rails users in the country may use; empty allows every rail
+    pub allowed_rails: Vec<String>,
+    // Largest withdrawal or remittance; zero for no country limit
+    pub max_transaction: i128,
+    // Withdrawals and remittances need a verified account
+    pub require_kyc: bool,
+}
+
+#[contractimpl]
+impl Payvia {
+    // Initialize the contract with the address that administers it. Only runs once; after an
+    // upgrade the storage layout version is brought forward by `migrate`.
+    pub fn init(env: Env, admin: Address) -> Result<(), Error> {
+        // Before v5 the admin is still under its legacy key
+        if env.storage().instance().has(&ConfigKey::Admin) || env.storage().instance().has(&symbol_short!("admin")) {
+            return Err(Error::AlreadyInitialized);
+        }
+
+        // Set up initial data structures
+        env.storage().instance().set(&ConfigKey::Admin, &admin);
+        env.storage().instance().set(&ConfigKey::Version, &CONTRACT_VERSION);
+
+        Ok(())
+    }
+
+    // Register a new user
+    pub fn register_user(env: Env, user_address: Address, phone: String) -> Result<(), Error> {
+        Self::extend_instance(&env);
+
+        let users = Users::load(&env);
+        
+        if users.contains_key(&user_address) {
+            return Err(Error::UserAlreadyExists);
+        }
+
+        let user = User {
+            address: user_address.clone(),
+            phone,
+            is_verified: false,
+            balance: 0,
+            display_name: None,
+            country: None,
+            preferred_currency: None,
+        };
+
+        let mut updated_users = users;
+        updated_users.set(&user_address, &user);
+        updated_users.save();
+
+        let mut stats = Self::load_stats(&env);
+        stats.user_count += 1;
+        Self::save_stats(&env, &stats);
+        Self::start_cooling_off(&env, &user_address, false);
+        
+        Ok(())
+    }
+
+    // Get user profile
+    pub fn get_user(env: Env, user_address: Address) -> Result<User, Error> {
+        Self::extend_instance(&env);
+
+        let users = Users::load(&env);
+        
+        users.get(&user_address).ok_or(Error::UserNotFound)
+    }
+
+    // Update the user's display name, country and preferred display currency
+    pub fn update_profile(
+        env: Env,
+        user_address: Address,
+        display_name: Option<String>,
+        country: Option<Symbol>,
+        preferred_currency: Option<Symbol>,
+    ) -> Result<(), Error> {
+        user_address.require_auth();
+
+        if let Some(name) = display_name.clone() {
+            if name.len() == 0 || name.len() > MAX_DISPLAY_NAME_LEN {
+                return Err(Error::InvalidDisplayName);
+            }
+        }
+        if let Some(currency) = preferred_currency.clone() {
+            if currency != symbol_short!("USDC") && !Self::is_supported_currency(&currency) {
+                return Err(Error::UnsupportedCurrency);
+            }
+        }
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+        // Moving into or out of a regulated country changes the rules that apply, so it goes through KYC
+        if user.country != country && (Self::is_regulated(&env, &user.country) || Self::is_regulated(&env, &country)) {
+            return Err(Error::CountryChangeRequiresAKycOfficer);
+        }
+        user.display_name = display_name;
+        user.country = country;
+        user.preferred_currency = preferred_currency;
+        users.set(user_address.clone(), user);
+        users.save();
+
+        env.events().publish((symbol_short!("profile"), user_address), ());
+
+        Ok(())
+    }
+
+    // Set the user's language and notification preferences
+    pub fn set_preferences(env: Env, user_address: Address, preferences: Preferences) -> Result<(), Error> {
+        user_address.require_auth();
+
+        let users = Users::load(&env);
+        if !users.contains_key(user_address.clone()) {
+            return Err(Error::UserNotFound);
+        }
+
+        let mut all: Map<Address, Preferences> = env.storage().instance().get(&DataKey::Preferences).unwrap_or(Map::new(&env));
+        all.set(user_address.clone(), preferences.clone());
+        env.storage().instance().set(&DataKey::Preferences, &all);
+
+        env.events().publish((symbol_short!("prefs"), user_address), preferences);
+
+        Ok(())
+    }
+
+    // Get the user's preferences; users who never set them get English push notifications without marketing
+    pub fn get_preferences(env: Env, user_address: Address) -> Preferences {
+        let all: Map<Address, Preferences> = env.storage().instance().get(&DataKey::Preferences).unwrap_or(Map::new(&env));
+
+        all.get(user_address).unwrap_or(Preferences {
+            language: symbol_short!("en"),
+            channel: NotifyChannel::Push,
+            marketing_opt_in: false,
+        })
+    }
+    // Update user verification status (KYC officer only)
+    pub fn verify_user(env: Env, caller: Address, user_address: Address) -> Result<(), Error> {
+        Self::require_role(&env, &caller, Role::KycOfficer)?;
+        Self::audit(&env, &caller, symbol_short!("verify"), AuditTarget::Address(user_address.clone()), 0);
+
+        let mut users = Users::load(&env);
+        
+        let mut user = users.get(&user_address).ok_or(Error::UserNotFound)?;
+        user.is_verified = true;
+        
+        users.set(&user_address, &user);
+        users.save();
+        
+        Ok(())
+    }
+
+    // Move a user to another country after re-checking their documents (KYC officer only)
+    pub fn set_user_country(env: Env, caller: Address, user_address: Address, country: Symbol) -> Result<(), Error> {
+        Self::require_role(&env, &caller, Role::KycOfficer)?;
+        Self::audit(&env, &caller, symbol_short!("ctry_set"), AuditTarget::Address(user_address.clone()), 0);
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+        user.country = Some(country.clone());
+        users.set(user_address.clone(), user);
+        users.save();
+
+        env.events().publish((symbol_short!("ctry_set"), user_address), country);
+
+        Ok(())
+    }
+
+    // Deposit USDC from the user's wallet to their account
+    pub fn deposit(env: Env, user_address: Address, amount: i128) -> Result<(), Error> {
+        Self::extend_instance(&env);
+        user_address.require_auth();
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        let usdc: Address = env.storage().instance().get(&ConfigKey::UsdcToken).ok_or(Error::UsdcTokenNotConfigured)?;
+
+        let mut users = Users::load(&env);
+        
+        let mut user = users.get(&user_address).ok_or(Error::UserNotFound)?;
+        if Self::is_closed(&env, &user_address) {
+            return Err(Error::AccountClosed);
+        }
+        if Self::sunset_initiated(&env) {
+            return Err(Error::ContractWindingDown);
+        }
+        let balance_before = user.balance;
+        user.balance = Self::checked_credit(user.balance, amount)?;
+        Self::track_balance(&env, &user, amount, Bucket::External);
+        Self::claw_back_overdraft(&env, &user, balance_before);
+        Self::note_deposit(&env, &user_address, amount);
+        Self::collect_overdue_loans(&env, &mut user, amount)?;
+        Self::apply_auto_rules(&env, &mut user, amount)?;
+        
+        users.set(&user_address, &user);
+        users.save();
+
+        // Balances are only credited for USDC that actually arrived
+        token::Client::new(&env, &usdc).transfer(&user_address, &env.current_contract_address(), &amount);
+        
+        Ok(())
+    }
+
+    // Get user balance
+    pub fn get_balance(env: Env, user_address: Address) -> Result<i128, Error> {
+        Self::extend_instance(&env);
+
+        let users = Users::load(&env);
+        
+        let user = users.get(&user_address).ok_or(Error::UserNotFound)?;
+        Ok(user.balance)
+    }
+
+    // Send USDC to another user; `fee_bearer` decides who pays the transfer fee, which
+    // `promo_code` can discount
+    pub fn send_usdc(
+        env: Env,
+        from_address: Address,
+        to_address: Address,
+        amount: i128,
+        fee_bearer: FeeBearer,
+        memo: Option<String>,
+        promo_code: Option<Bytes>,
+        idempotency_key: Option<BytesN<32>>,
+    ) -> Result<u64, Error> {
+        Self::extend_instance(&env);
+        from_address.require_auth();
+
+        match Self::idempotent_result(&env, &from_address, &idempotency_key) {
+            Some(IdempotentResult::Transfer(transfer_id)) => return Ok(transfer_id),
+            Some(_) => return Err(Error::IdempotencyKeyAlreadyUsed),
+            None => {}
+        }
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        if from_address == to_address {
+            return Err(Error::CannotSendToYourself);
+        }
+
+        Self::ensure_module_active(&env, &from_address, symbol_short!("transfers"))?;
+        Self::ensure_not_restricted(&env, &from_address, symbol_short!("send"))?;
+        Self::check_subaccount(&env, &from_address, symbol_short!("send"), amount)?;
+        Self::ensure_not_restricted(&env, &to_address, symbol_short!("send"))?;
+        Self::check_memo(&memo)?;
+        Self::require_pin(&env, &from_address, amount, symbol_short!("send"))?;
+        Self::check_transaction_cap(&env, &from_address, amount, symbol_short!("send"))?;
+        Self::check_step_up(&env, &from_address, amount)?;
+        Self::check_velocity(&env, &from_address, amount)?;
+
+        let mut users = Users::load(&env);
+        
+        let mut from_user = users.get(&from_address).ok_or(Error::SenderNotFound)?;
+        let mut to_user = users.get(&to_address).ok_or(Error::RecipientNotFound)?;
+        Self::check_rate_limit(&env, &from_user, symbol_short!("send"))?;
+        
+        let quote = Self::quote_transfer(env.clone(), amount, fee_bearer.clone());
+        let quote = Self::split_fee(amount, Self::apply_promo(&env, &from_address, &promo_code, quote.fee)?, &fee_bearer);
+        Self::check_min_incoming(&env, &from_address, &to_address, quote.payee_receives, symbol_short!("send"))?;
+        
+        // Sends from high-risk accounts are always held until the recipient accepts them
+        let quarantine = Self::screen_incoming(&env, &from_address, &to_address, quote.payee_receives)? || Self::is_high_risk(&env, &from_address);
+        
+        if from_user.balance < quote.payer_pays {
+            return Err(Self::fail(&env, &from_address, symbol_short!("send"), symbol_short!("balance"), quote.payer_pays, from_user.balance, Error::InsufficientBalance));
+        }
+
+        // Sends either empty the account or leave a balance worth spending
+        let dust = Self::dust_policy(&env);
+        if amount < dust.min_send {
+            return Err(Self::fail(&env, &from_address, symbol_short!("send"), symbol_short!("min_send"), amount, dust.min_send, Error::AmountBelowMinimumSend));
+        }
+        let remaining = from_user.balance - quote.payer_pays;
+        if remaining > 0 && remaining < dust.dust_threshold {
+            return Err(Self::fail(&env, &from_address, symbol_short!("send"), symbol_short!("dust"), remaining, dust.dust_threshold, Error::SendWouldLeaveADustBalance));
+        }
+        
+        from_user.balance = Self::checked_debit(from_user.balance, quote.payer_pays)?;
+        Self::track_balance(&env, &from_user, -quote.payer_pays, Bucket::Clearing);
+        Self::accrue_roundup(&env, &mut from_user, amount)?;
+        users.set(&from_address, &from_user);
+
+        // Quarantined transfers are held until the recipient accepts or returns them
+        if !quarantine {
+            to_user.balance = Self::checked_credit(to_user.balance, quote.payee_receives)?;
+            Self::track_balance(&env, &to_user, quote.payee_receives, Bucket::Clearing);
+            users.set(&to_address, &to_user);
+            Self::mark_known_sender(&env, &to_address, &from_address);
+        } else {
+            Self::post(&env, &to_address, Bucket::Clearing, Bucket::Escrowed, quote.payee_receives);
+        }
+        users.save();
+
+        if quote.fee > 0 {
+            Self::collect_fee(&env, &from_address, Bucket::Clearing, quote.fee);
+        }
+
+        Self::qualify_referral(&env, &from_address)?;
+        Self::record_spending(&env, &from_address, SpendCategory::Transfer, quote.payer_pays);
+
+        let transfer_id: u64 = env.storage().instance().get(&CounterKey::TransferId).unwrap_or(0) + 1;
+        Self::save_transfer(&env, &Transfer {
+            id: transfer_id,
+            from_address: from_address.clone(),
+            to_address: to_address.clone(),
+            amount,
+            fee: quote.fee,
+            memo: memo.clone(),
+            timestamp: env.ledger().timestamp(),
+        });
+        env.storage().instance().set(&CounterKey::TransferId, &transfer_id);
+        Self::remember_result(&env, &from_address, &idempotency_key, IdempotentResult::Transfer(transfer_id));
+
+        if quarantine {
+            let mut quarantined: Map<u64, QuarantinedTransfer> = env.storage().instance().get(&ComplianceKey::Quarantined).unwrap_or(Map::new(&env));
+            quarantined.set(transfer_id, QuarantinedTransfer {
+                transfer_id,
+                from_address: from_address.clone(),
+                to_address: to_address.clone(),
+                amount: quote.payee_receives,
+                timestamp: env.ledger().timestamp(),
+            });
+            env.storage().instance().set(&ComplianceKey::Quarantined, &quarantined);
+            env.events().publish((symbol_short!("quarant"), from_address.clone(), to_address.clone()), (transfer_id, quote.payee_receives));
+        }
+
+        let kind = if quarantine { symbol_short!("quarant") } else { symbol_short!("received") };
+        Self::notify(&env, &to_address, kind, NotificationRef::Id(transfer_id), quote.payee_receives);
+        env.events().publish((symbol_short!("send"), from_address, to_address), (transfer_id, amount, memo));
+        
+        Ok(transfer_id)
+    }
+
+    // Pay bill with USDC
+    pub fn pay_bill(
+        env: Env,
+        user_address: Address,
+        bill_type: String,
+        account_number: String,
+        amount: i128,
+        memo: Option<String>,
+        idempotency_key: Option<BytesN<32>>,
+    ) -> Result<String, Error> {
+        Self::extend_instance(&env);
+        user_address.require_auth();
+
+        match Self::idempotent_result(&env, &user_address, &idempotency_key) {
+            Some(IdempotentResult::Bill(payment_id)) => return Ok(payment_id),
+            Some(_) => return Err(Error::IdempotencyKeyAlreadyUsed),
+            None => {}
+        }
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        Self::ensure_module_active(&env, &user_address, symbol_short!("bills"))?;
+        Self::ensure_not_restricted(&env, &user_address, symbol_short!("pay_bill"))?;
+        Self::check_subaccount(&env, &user_address, symbol_short!("pay_bill"), amount)?;
+        Self::check_memo(&memo)?;
+        Self::require_pin(&env, &user_address, amount, symbol_short!("pay_bill"))?;
+        Self::check_transaction_cap(&env, &user_address, amount, symbol_short!("pay_bill"))?;
+        Self::validate_bill(&env, &user_address, &bill_type, &account_number, amount)?;
+
+        let mut users = Users::load(&env);
+        
+        let mut user = users.get(&user_address).ok_or(Error::UserNotFound)?;
+        Self::check_rate_limit(&env, &user, symbol_short!("pay_bill"))?;
+        
+        // Users who opted in to the overdraft may go below zero, up to the buffer
+        let available = user.balance + Self::overdraft_available(&env, &user);
+        if available < amount {
+            return Err(Self::fail(&env, &user_address, symbol_short!("pay_bill"), symbol_short!("balance"), amount, available, Error::InsufficientBalance));
+        }
+        
+        user.balance = Self::checked_debit(user.balance, amount)?;
+        Self::track_balance(&env, &user, -amount, Bucket::Payable);
+        Self::draw_overdraft(&env, &user);
+        Self::accrue_roundup(&env, &mut user, amount)?;
+        users.set(&user_address, &user);
+        users.save();
+
+        Self::qualify_referral(&env, &user_address)?;
+        Self::accrue_cashback(&env, &user_address, &bill_type, amount);
+        Self::record_spending(&env, &user_address, SpendCategory::Bill(bill_type.clone()), amount);
+        
+        let payment_id = Self::record_bill_payment(&env, &user_address, bill_type, account_number, amount, memo);
+        Self::remember_result(&env, &user_address, &idempotency_key, IdempotentResult::Bill(payment_id.clone()));
+        
+        Ok(payment_id)
+    }
+
+    // Withdraw USDC to local currency, paid out in `currency` at its current rate. `promo_code`
+    // can discount the rail fee.
+    pub fn withdraw(
+        env: Env,
+        user_address: Address,
+        method: String,
+        account_number: String,
+        usdc_amount: i128,
+        currency: Symbol,
+        memo: Option<String>,
+        promo_code: Option<Bytes>,
+        idempotency_key: Option<BytesN<32>>,
+    ) -> Result<String, Error> {
+        Self::extend_instance(&env);
+        user_address.require_auth();
+
+        match Self::idempotent_result(&env, &user_address, &idempotency_key) {
+            Some(IdempotentResult::Withdrawal(withdrawal_id)) => return Ok(withdrawal_id),
+            Some(_) => return Err(Error::IdempotencyKeyAlreadyUsed),
+            None => {}
+        }
+
+        if usdc_amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        Self::ensure_module_active(&env, &user_address, symbol_short!("withdraw"))?;
+        Self::ensure_not_restricted(&env, &user_address, symbol_short!("withdraw"))?;
+        Self::check_subaccount(&env, &user_address, symbol_short!("withdraw"), usdc_amount)?;
+        Self::check_memo(&memo)?;
+        Self::require_pin(&env, &user_address, usdc_amount, symbol_short!("withdraw"))?;
+        Self::check_transaction_cap(&env, &user_address, usdc_amount, symbol_short!("withdraw"))?;
+        Self::check_cooling_off(&env, &user_address, usdc_amount)?;
+        Self::check_country(&env, &user_address, usdc_amount, Some(&method), symbol_short!("withdraw"))?;
+        let fee = Self::withdrawal_rail_fee(&env, &user_address, &method, usdc_amount)?;
+        let fee = Self::apply_promo(&env, &user_address, &promo_code, fee)?;
+        let (local_amount, effective_rate, spread) = Self::fx_quote(&env, usdc_amount, &currency)?;
+
+        let mut users = Users::load(&env);
+        
+        let mut user = users.get(&user_address).ok_or(Error::UserNotFound)?;
+        
+        // The amount and rail fee stay on hold until the off-ramp settles or the hold expires
+        if user.balance < usdc_amount + fee {
+            return Err(Self::fail(&env, &user_address, symbol_short!("withdraw"), symbol_short!("balance"), usdc_amount + fee, user.balance, Error::InsufficientBalance));
+        }
+        
+        user.balance = Self::checked_debit(user.balance, usdc_amount + fee)?;
+        Self::track_balance(&env, &user, -(usdc_amount + fee), Bucket::HeldForWithdrawal);
+        users.set(&user_address, &user);
+        users.save();
+        Self::record_spending(&env, &user_address, SpendCategory::Withdrawal, usdc_amount + fee);
+        
+        // Sequential ids keep several withdrawals in one ledger apart
+        let withdrawal_seq: u32 = env.storage().instance().get(&CounterKey::WithdrawalSeq).unwrap_or(0) + 1;
+        env.storage().instance().set(&CounterKey::WithdrawalSeq, &withdrawal_seq);
+        let withdrawal_id = Self::demo_string(&env, b"withdraw_", withdrawal_seq);
+        let sla: u64 = env.storage().instance().get(&ConfigKey::WithdrawalSla).unwrap_or(DEFAULT_WITHDRAWAL_SLA_SECS);
+        let hold_window: u64 = env.storage().instance().get(&ConfigKey::WithdrawalHoldWindow).unwrap_or(DEFAULT_WITHDRAWAL_HOLD_SECS);
+        let mut holds: Map<String, WithdrawalHold> = env.storage().instance().get(&ComplianceKey::WithdrawalHolds).unwrap_or(Map::new(&env));
+        holds.set(withdrawal_id.clone(), WithdrawalHold {
+            user_address: user_address.clone(),
+            amount: usdc_amount - spread,
+            fee: fee + spread,
+            expires_at: env.ledger().timestamp() + hold_window,
+        });
+        env.storage().instance().set(&ComplianceKey::WithdrawalHolds, &holds);
+
+        Self::remember_result(&env, &user_address, &idempotency_key, IdempotentResult::Withdrawal(withdrawal_id.clone()));
+        env.events().publish((symbol_short!("withdraw"), user_address.clone()), (withdrawal_id.clone(), method.clone(), usdc_amount, fee, memo.clone()));
+
+        let withdrawal = Withdrawal {
+            id: withdrawal_id.clone(),
+            user_address,
+            method,
+            account_number,
+            usdc_amount,
+            local_amount,
+            currency,
+            status: "pending".into(),
+            timestamp: env.ledger().timestamp(),
+            memo,
+            payout_reference: None,
+            processing_deadline: env.ledger().timestamp() + sla,
+            effective_rate,
+            spread,
+        };
+        
+        let mut stats = Self::load_stats(&env);
+        stats.withdrawal_volume += withdrawal.usdc_amount;
+        Self::count_status(&mut stats.withdrawal_statuses, None, withdrawal.status.clone());
+        Self::save_stats(&env, &stats);
+
+        Self::save_withdrawal(&env, &withdrawal);
+        Self::add_to_index(&env, DataKey::UserWithdrawals(withdrawal.user_address.clone()), withdrawal_id.clone());
+        Self::enqueue_task(&env, symbol_short!("withdraw"), withdrawal_id.clone(), &withdrawal.user_address, withdrawal.usdc_amount);
+        
+        Ok(withdrawal_id)
+    }
+
+    // Get bill payment history
+    pub fn get_bill_payments(env: Env, user_address: Address, cursor: u32, limit: u32) -> (Vec<BillPayment>, Option<u32>) {
+        Self::extend_instance(&env);
+
+        let (payment_ids, next_cursor) = Self::page(Self::user_index(&env, DataKey::UserBills(user_address)), cursor, limit);
+
+        (Self::load_each(&env, payment_ids, Self::load_bill), next_cursor)
+    }
+
+    // Get withdrawal history
+    pub fn get_withdrawals(env: Env, user_address: Address, cursor: u32, limit: u32) -> (Vec<Withdrawal>, Option<u32>) {
+        Self::extend_instance(&env);
+
+        let (withdrawal_ids, next_cursor) = Self::page(Self::user_index(&env, DataKey::UserWithdrawals(user_address)), cursor, limit);
+
+        (Self::load_each(&env, withdrawal_ids, Self::load_withdrawal), next_cursor)
+    }
+
+    // Authorize a spender to move up to `amount` from the owner's balance until `expiry`
+    pub fn approve(env: Env, owner: Address, spender: Address, amount: i128, expiry: u64) -> Result<(), Error> {
+        owner.require_auth();
+
+        if amount < 0 {
+            return Err(Error::InvalidAmount);
+        }
+        if amount > 0 && expiry <= env.ledger().timestamp() {
+            return Err(Error::ExpiryMustBeInTheFuture);
+        }
+
+        let users = Users::load(&env);
+        if !users.contains_key(owner.clone()) {
+            return Err(Error::UserNotFound);
+        }
+
+        let mut allowances: Map<(Address, Address), Allowance> = env.storage().instance().get(&DataKey::Allowances).unwrap_or(Map::new(&env));
+        let key = (owner.clone(), spender.clone());
+
+        if amount == 0 {
+            allowances.remove(key);
+        } else {
+            allowances.set(key, Allowance { amount, expiry });
+        }
+        env.storage().instance().set(&DataKey::Allowances, &allowances);
+
+        env.events().publish((symbol_short!("approve"), owner, spender), (amount, expiry));
+
+        Ok(())
+    }
+
+    // Get the remaining allowance a spender has over the owner's balance
+    pub fn allowance(env: Env, owner: Address, spender: Address) -> i128 {
+        let allowances: Map<(Address, Address), Allowance> = env.storage().instance().get(&DataKey::Allowances).unwrap_or(Map::new(&env));
+
+        match allowances.get((owner, spender)) {
+            Some(allowance) if allowance.expiry > env.ledger().timestamp() => allowance.amount,
+            _ => 0,
+        }
+    }
+
+    // Move USDC from the owner to a recipient on behalf of an approved spender
+    pub fn transfer_from(
+        env: Env,
+        spender: Address,
+        owner: Address,
+        to_address: Address,
+        amount: i128,
+    ) -> Result<(), Error> {
+        spender.require_auth();
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        if owner == to_address {
+            return Err(Error::CannotSendToYourself);
+        }
+
+        Self::ensure_module_active(&env, &spender, symbol_short!("transfers"))?;
+        Self::ensure_not_restricted(&env, &owner, symbol_short!("xfer_from"))?;
+        Self::ensure_not_restricted(&env, &spender, symbol_short!("xfer_from"))?;
+        Self::ensure_not_restricted(&env, &to_address, symbol_short!("xfer_from"))?;
+
+        let mut allowances: Map<(Address, Address), Allowance> = env.storage().instance().get(&DataKey::Allowances).unwrap_or(Map::new(&env));
+        let key = (owner.clone(), spender.clone());
+
+        let mut allowance = allowances.get(key.clone()).ok_or(Error::AllowanceNotFound)?;
+        if allowance.expiry <= env.ledger().timestamp() {
+            return Err(Self::fail(&env, &spender, symbol_short!("xfer_from"), symbol_short!("expired"), amount, 0, Error::AllowanceExpired));
+        }
+        if allowance.amount < amount {
+            return Err(Self::fail(&env, &spender, symbol_short!("xfer_from"), symbol_short!("allowance"), amount, allowance.amount, Error::InsufficientAllowance));
+        }
+
+        let mut users = Users::load(&env);
+
+        let mut from_user = users.get(owner.clone()).ok_or(Error::SenderNotFound)?;
+        if !users.contains_key(to_address.clone()) {
+            return Err(Error::RecipientNotFound);
+        }
+
+        Self::check_min_incoming(&env, &owner, &to_address, amount, symbol_short!("xfer_from"))?;
+
+        if from_user.balance < amount {
+            return Err(Self::fail(&env, &owner, symbol_short!("xfer_from"), symbol_short!("balance"), amount, from_user.balance, Error::InsufficientBalance));
+        }
+
+        from_user.balance = Self::checked_debit(from_user.balance, amount)?;
+        Self::track_balance(&env, &from_user, -amount, Bucket::Clearing);
+        users.set(owner.clone(), from_user);
+
+        let mut to_user = users.get(to_address.clone()).unwrap();
+        to_user.balance = Self::checked_credit(to_user.balance, amount)?;
+        Self::track_balance(&env, &to_user, amount, Bucket::Clearing);
+        users.set(to_address.clone(), to_user);
+        users.save();
+
+        allowance.amount -= amount;
+        if allowance.amount == 0 {
+            allowances.remove(key);
+        } else {
+            allowances.set(key, allowance);
+        }
+        env.storage().instance().set(&DataKey::Allowances, &allowances);
+
+        env.events().publish((symbol_short!("xfer_from"), spender, owner), (to_address, amount));
+
+        Ok(())
+    }
+
+    // Set the oracle rate for an asset, expressed in units of the asset per 1 USDC (admin only)
+    pub fn set_oracle_rate(env: Env, asset: Symbol, rate: i128) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("rate"), AuditTarget::Key(asset.clone()), rate);
+
+        if rate <= 0 {
+            return Err(Error::InvalidRate);
+        }
+
+        let mut rates: Map<Symbol, OracleRate> = env.storage().instance().get(&ConfigKey::AssetRates).unwrap_or(Map::new(&env));
+        rates.set(asset.clone(), OracleRate { rate, timestamp: env.ledger().timestamp() });
+        env.storage().instance().set(&ConfigKey::AssetRates, &rates);
+
+        env.events().publish((symbol_short!("rate"), asset), rate);
+
+        Ok(())
+    }
+
+    // Get the current oracle rate for an asset
+    pub fn get_oracle_rate(env: Env, asset: Symbol) -> Result<OracleRate, Error> {
+        if asset == symbol_short!("USDC") {
+            return Ok(OracleRate { rate: RATE_SCALE, timestamp: env.ledger().timestamp() });
+        }
+
+        let rates: Map<Symbol, OracleRate> = env.storage().instance().get(&ConfigKey::AssetRates).unwrap_or(Map::new(&env));
+        rates.get(asset).ok_or(Error::RateNotFound)
+    }
+
+    // Publish the fiat rate for a supported currency (UGX, KES, TZS, NGN), in units of the currency
+    // per 1 USDC scaled by `RATE_SCALE`, as observed at `timestamp` (admin only)
+    pub fn set_rate(env: Env, currency_code: Symbol, rate: i128, timestamp: u64) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("fx_rate"), AuditTarget::Key(currency_code.clone()), rate);
+
+        if !Self::is_supported_currency(&currency_code) {
+            return Err(Error::UnsupportedCurrency);
+        }
+        if rate <= 0 {
+            return Err(Error::InvalidRate);
+        }
+        if timestamp > env.ledger().timestamp() {
+            return Err(Error::RateTimestampInTheFuture);
+        }
+
+        let mut rates: Map<Symbol, OracleRate> = env.storage().instance().get(&ConfigKey::FxRates).unwrap_or(Map::new(&env));
+        if let Some(current) = rates.get(currency_code.clone()) {
+            if timestamp < current.timestamp {
+                return Err(Error::RateOlderThanTheCurrentOne);
+            }
+        }
+        rates.set(currency_code.clone(), OracleRate { rate, timestamp });
+        env.storage().instance().set(&ConfigKey::FxRates, &rates);
+
+        env.events().publish((symbol_short!("fx_rate"), currency_code), (rate, timestamp));
+
+        Ok(())
+    }
+
+    // Get the current fiat rate for a currency
+    pub fn get_rate(env: Env, currency_code: Symbol) -> Result<OracleRate, Error> {
+        let rates: Map<Symbol, OracleRate> = env.storage().instance().get(&ConfigKey::FxRates).unwrap_or(Map::new(&env));
+        rates.get(currency_code).ok_or(Error::RateNotFound)
+    }
+
+    // Convert a USDC amount to a fiat currency at its current rate. Rates older than
+    // `MAX_RATE_AGE_SECS` are refused.
+    pub fn convert(env: Env, amount: i128, currency_code: Symbol) -> Result<i128, Error> {
+        let rate = Self::get_rate(env.clone(), currency_code)?;
+        if rate.timestamp + MAX_RATE_AGE_SECS < env.ledger().timestamp() {
+            return Err(Error::RateStale);
+        }
+
+        Ok(amount * rate.rate / RATE_SCALE)
+    }
+
+    // Set the spread taken off the oracle rate on withdrawals and remittances (admin only)
+    pub fn set_fx_spread(env: Env, spread_bps: u32) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("fx_spread"), AuditTarget::Global, spread_bps as i128);
+
+        if spread_bps > MAX_FX_SPREAD_BPS {
+            return Err(Error::InvalidSpread);
+        }
+
+        env.storage().instance().set(&ConfigKey::FxSpreadBps, &spread_bps);
+
+        Ok(())
+    }
+
+    // Get the FX spread in basis points
+    pub fn get_fx_spread(env: Env) -> u32 {
+        env.storage().instance().get(&ConfigKey::FxSpreadBps).unwrap_or(0)
+    }
+
+    // Value everything a user holds in a single quote currency using current oracle rates
+    pub fn get_portfolio_value(env: Env, user_address: Address, quote_asset: Symbol) -> Result<i128, Error> {
+        let users = Users::load(&env);
+        let user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+
+        let mut total = user.balance;
+
+        for goal in Self::goals_for(&env, &user_address).iter() {
+            total += goal.saved;
+        }
+
+        // Withdrawals on hold remain the user's funds until completed
+        let holds: Map<String, WithdrawalHold> = env.storage().instance().get(&ComplianceKey::WithdrawalHolds).unwrap_or(Map::new(&env));
+        for withdrawal_id in Self::user_index(&env, DataKey::UserWithdrawals(user_address.clone())).iter() {
+            if let Some(hold) = holds.get(withdrawal_id) {
+                total += hold.amount + hold.fee;
+            }
+        }
+
+        // Card authorizations are reserved, not spent, until captured
+        for hold in Self::card_holds_for(&env, &user_address).iter() {
+            if hold.status == String::from_str(&env, "active") {
+                total += hold.amount;
+            }
+        }
+
+        // Escrowed payrolls remain the employer's until payday
+        for payroll in Self::payrolls_for(&env, &user_address).iter() {
+            if payroll.status == String::from_str(&env, "pending") {
+                total += payroll.total;
+            }
+        }
+
+        // Scheduled payments stay reserved for the sender until executed
+        for payment in Self::scheduled_payments_for(&env, &user_address).iter() {
+            if payment.status == String::from_str(&env, "pending") {
+                total += payment.amount;
+            }
+        }
+
+        // Active streams: the sender still owns the unvested part, the recipient the vested remainder
+        for stream in Self::streams_for(&env, &user_address).iter() {
+            if stream.status != String::from_str(&env, "active") {
+                continue;
+            }
+            let vested = Self::stream_vested(&env, &stream);
+            if stream.from_address == user_address {
+                total += stream.total_amount - vested;
+            } else {
+                total += vested - stream.withdrawn;
+            }
+        }
+
+        // Contributions to split bills that are still being funded
+        for split in Self::splits_for(&env, &user_address).iter() {
+            if split.status == String::from_str(&env, "open") && split.funded_by.contains(user_address.clone()) {
+                total += split.shares.get(user_address.clone()).unwrap_or(0);
+            }
+        }
+
+        // Unredeemed vouchers stay the funder's until someone redeems them
+        for voucher in Self::vouchers_for(&env, &user_address).iter() {
+            if voucher.status == String::from_str(&env, "active") {
+                total += voucher.amount;
+            }
+        }
+
+        // Sends to contacts who have not signed up yet stay the sender's until claimed
+        for claim in Self::contact_claims_for(&env, &user_address).iter() {
+            if claim.status == String::from_str(&env, "active") {
+                total += claim.amount;
+            }
+        }
+
+        // Non-USDC assets, converted at their oracle rate
+        let tokens: Map<Address, Symbol> = env.storage().instance().get(&ConfigKey::SwapTokens).unwrap_or(Map::new(&env));
+        let assets: Map<(Address, Address), i128> = env.storage().instance().get(&DataKey::Assets).unwrap_or(Map::new(&env));
+        for (token, asset) in tokens.iter() {
+            if let Some(amount) = assets.get((user_address.clone(), token)) {
+                let rate = Self::get_oracle_rate(env.clone(), asset)?;
+                total += amount * RATE_SCALE / rate.rate;
+            }
+        }
+
+        // Cash-outs held until the agent confirms
+        for cash_out in Self::cash_outs_for(&env, &user_address).iter() {
+            if cash_out.status == String::from_str(&env, "pending") {
+                total += cash_out.amount;
+            }
+        }
+
+        // Remittances the payout partner has not completed yet
+        for remittance in Self::remittances_for(&env, &user_address).iter() {
+            if remittance.status == String::from_str(&env, "pending") || remittance.status == String::from_str(&env, "processing") {
+                total += remittance.usdc_amount + remittance.fee;
+            }
+        }
+
+        // Locked collateral net of the debt it backs
+        let line = Self::accrued_credit_line(&env, &user_address);
+        total += line.collateral - line.debt;
+
+        // Outstanding loans are owed back
+        let active = String::from_str(&env, "active");
+        for loan in Self::loans_for(&env, &user_address).iter() {
+            if loan.status == active {
+                total -= loan.owed - loan.repaid;
+            }
+        }
+
+        let quote = Self::get_oracle_rate(env, quote_asset)?;
+        Ok(total * quote.rate / RATE_SCALE)
+    }
+
+    // Get counts and totals of everything a user has in flight: funds that have left the spendable
+    // balance but are not settled yet
+    pub fn get_pending_summary(env: Env, user_address: Address) -> PendingSummary {
+        let pending = String::from_str(&env, "pending");
+        let active = String::from_str(&env, "active");
+        let mut summary = PendingSummary {
+            bill_count: 0,
+            bill_total: 0,
+            withdrawal_count: 0,
+            withdrawal_total: 0,
+            scheduled_count: 0,
+            scheduled_total: 0,
+            hold_count: 0,
+            hold_total: 0,
+            card_hold_count: 0,
+            card_hold_total: 0,
+            payroll_count: 0,
+            payroll_total: 0,
+            split_count: 0,
+            split_total: 0,
+            voucher_count: 0,
+            voucher_total: 0,
+            contact_send_count: 0,
+            contact_send_total: 0,
+            remittance_count: 0,
+            remittance_total: 0,
+            count: 0,
+            total: 0,
+        };
+
+        for payment in Self::bill_payments_for(&env, &user_address).iter() {
+            if payment.status == pending {
+                summary.bill_count += 1;
+                summary.bill_total += payment.amount;
+            }
+        }
+
+        for withdrawal in Self::withdrawals_for(&env, &user_address).iter() {
+            if withdrawal.status == pending {
+                summary.withdrawal_count += 1;
+                summary.withdrawal_total += withdrawal.usdc_amount;
+            }
+        }
+
+        for payment in Self::scheduled_payments_for(&env, &user_address).iter() {
+            if payment.status == pending {
+                summary.scheduled_count += 1;
+                summary.scheduled_total += payment.amount;
+            }
+        }
+
+        for cash_out in Self::cash_outs_for(&env, &user_address).iter() {
+            if cash_out.status == pending {
+                summary.hold_count += 1;
+                summary.hold_total += cash_out.amount;
+            }
+        }
+
+        for hold in Self::card_holds_for(&env, &user_address).iter() {
+            if hold.status == active {
+                summary.card_hold_count += 1;
+                summary.card_hold_total += hold.amount;
+            }
+        }
+
+        for payroll in Self::payrolls_for(&env, &user_address).iter() {
+            if payroll.status == pending {
+                summary.payroll_count += 1;
+                summary.payroll_total += payroll.total;
+            }
+        }
+
+        // Only the user's own funded share of a split is held
+        for split in Self::splits_for(&env, &user_address).iter() {
+            if split.status == String::from_str(&env, "open") && split.funded_by.contains(user_address.clone()) {
+                summary.split_count += 1;
+                summary.split_total += split.shares.get(user_address.clone()).unwrap_or(0);
+            }
+        }
+
+        for voucher in Self::vouchers_for(&env, &user_address).iter() {
+            if voucher.status == active {
+                summary.voucher_count += 1;
+                summary.voucher_total += voucher.amount;
+            }
+        }
+
+        for claim in Self::contact_claims_for(&env, &user_address).iter() {
+            if claim.status == active {
+                summary.contact_send_count += 1;
+                summary.contact_send_total += claim.amount;
+            }
+        }
+
+        for remittance in Self::remittances_for(&env, &user_address).iter() {
+            if remittance.status == pending || remittance.status == String::from_str(&env, "processing") {
+                summary.remittance_count += 1;
+                summary.remittance_total += remittance.usdc_amount + remittance.fee;
+            }
+        }
+
+        summary.count = summary.bill_count
+            + summary.withdrawal_count
+            + summary.scheduled_count
+            + summary.hold_count
+            + summary.card_hold_count
+            + summary.payroll_count
+            + summary.split_count
+            + summary.voucher_count
+            + summary.contact_send_count
+            + summary.remittance_count;
+        summary.total = summary.bill_total
+            + summary.withdrawal_total
+            + summary.scheduled_total
+            + summary.hold_total
+            + summary.card_hold_total
+            + summary.payroll_total
+            + summary.split_total
+            + summary.voucher_total
+            + summary.contact_send_total
+            + summary.remittance_total;
+
+        summary
+    }
+
+    // Create a savings goal whose funds stay locked until `unlock_time`
+    pub fn create_goal(
+        env: Env,
+        user_address: Address,
+        name: String,
+        target_amount: i128,
+        unlock_time: u64,
+    ) -> Result<u64, Error> {
+        user_address.require_auth();
+        Self::ensure_module_active(&env, &user_address, symbol_short!("savings"))?;
+
+        if target_amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        let users = Users::load(&env);
+        if !users.contains_key(user_address.clone()) {
+            return Err(Error::UserNotFound);
+        }
+
+        let mut goals: Map<u64, SavingsGoal> = env.storage().instance().get(&DataKey::Goals).unwrap_or(Map::new(&env));
+        let goal_id: u64 = env.storage().instance().get(&CounterKey::GoalId).unwrap_or(0) + 1;
+
+        let goal = SavingsGoal {
+            id: goal_id,
+            owner: user_address.clone(),
+            name,
+            target_amount,
+            saved: 0,
+            unlock_time,
+            created_at: env.ledger().timestamp(),
+        };
+
+        goals.set(goal_id, goal);
+        env.storage().instance().set(&DataKey::Goals, &goals);
+        env.storage().instance().set(&CounterKey::GoalId, &goal_id);
+        Self::index_record(&env, EntryKey::UserGoals(user_address.clone()), goal_id);
+
+        env.events().publish((symbol_short!("goal_new"), user_address), (goal_id, target_amount, unlock_time));
+
+        Ok(goal_id)
+    }
+
+    // Move spendable balance into a savings goal
+    pub fn contribute_to_goal(env: Env, user_address: Address, goal_id: u64, amount: i128) -> Result<(), Error> {
+        user_address.require_auth();
+        Self::ensure_module_active(&env, &user_address, symbol_short!("savings"))?;
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        let mut users = Users::load(&env);
+        let mut goals: Map<u64, SavingsGoal> = env.storage().instance().get(&DataKey::Goals).unwrap_or(Map::new(&env));
+
+        let mut user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+        let mut goal = goals.get(goal_id).ok_or(Error::GoalNotFound)?;
+
+        if goal.owner != user_address {
+            return Err(Error::Unauthorized);
+        }
+        if user.balance < amount {
+            return Err(Self::fail(&env, &user_address, symbol_short!("goal_add"), symbol_short!("balance"), amount, user.balance, Error::InsufficientBalance));
+        }
+
+        user.balance = Self::checked_debit(user.balance, amount)?;
+        Self::track_balance(&env, &user, -amount, Bucket::LockedSavings);
+        goal.saved += amount;
+
+        users.set(user_address.clone(), user);
+        goals.set(goal_id, goal);
+        users.save();
+        env.storage().instance().set(&DataKey::Goals, &goals);
+
+        env.events().publish((symbol_short!("goal_add"), user_address), (goal_id, amount));
+
+        Ok(())
+    }
+
+    // Withdraw from a savings goal back to spendable balance.
+    // Before the unlock time this is only allowed if the caller accepts the early-withdrawal penalty.
+    pub fn withdraw_from_goal(
+        env: Env,
+        user_address: Address,
+        goal_id: u64,
+        amount: i128,
+        accept_penalty: bool,
+    ) -> Result<i128, Error> {
+        user_address.require_auth();
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        if Self::yield_enabled(&env, &user_address) {
+            return Err(Error::SavingsInYield);
+        }
+
+        let mut users = Users::load(&env);
+        let mut goals: Map<u64, SavingsGoal> = env.storage().instance().get(&DataKey::Goals).unwrap_or(Map::new(&env));
+
+        let mut user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+        let mut goal = goals.get(goal_id).ok_or(Error::GoalNotFound)?;
+
+        if goal.owner != user_address {
+            return Err(Error::Unauthorized);
+        }
+        if goal.saved < amount {
+            return Err(Self::fail(&env, &user_address, symbol_short!("goal_out"), symbol_short!("savings"), amount, goal.saved, Error::InsufficientSavings));
+        }
+
+        let mut penalty: i128 = 0;
+        if env.ledger().timestamp() < goal.unlock_time {
+            if !accept_penalty {
+                return Err(Self::fail_until(&env, &user_address, symbol_short!("goal_out"), symbol_short!("locked"), goal.unlock_time, Error::GoalLocked));
+            }
+            let penalty_bps: u32 = env.storage().instance().get(&ConfigKey::GoalPenaltyBps).unwrap_or(DEFAULT_GOAL_PENALTY_BPS);
+            penalty = amount * penalty_bps as i128 / BPS_DENOMINATOR;
+        }
+
+        goal.saved -= amount;
+        user.balance = Self::checked_credit(user.balance, amount - penalty)?;
+        Self::track_balance(&env, &user, amount - penalty, Bucket::LockedSavings);
+
+        users.set(user_address.clone(), user);
+        goals.set(goal_id, goal);
+        users.save();
+        env.storage().instance().set(&DataKey::Goals, &goals);
+
+        if penalty > 0 {
+            Self::collect_fee(&env, &user_address, Bucket::LockedSavings, penalty);
+        }
+
+        env.events().publish((symbol_short!("goal_out"), user_address), (goal_id, amount, penalty));
+
+        Ok(amount - penalty)
+    }
+
+    // Get all savings goals for a user
+    pub fn get_goals(env: Env, user_address: Address, cursor: u32, limit: u32) -> (Vec<SavingsGoal>, Option<u32>) {
+        Self::page(Self::goals_for(&env, &user_address), cursor, limit)
+    }
+
+    // Set the early-withdrawal penalty for savings goals in basis points (admin only)
+    pub fn set_goal_penalty(env: Env, penalty_bps: u32) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("goal_pen"), AuditTarget::Global, penalty_bps as i128);
+
+        if penalty_bps as i128 > BPS_DENOMINATOR {
+            return Err(Error::InvalidPenalty);
+        }
+
+        env.storage().instance().set(&ConfigKey::GoalPenaltyBps, &penalty_bps);
+
+        Ok(())
+    }
+
+    // Create a rotating savings group (ROSCA / chama); members receive the pot in list order. The
+    // group starts once every other member has joined it with `join_group`.
+    pub fn create_group(
+        env: Env,
+        creator: Address,
+        name: String,
+        members: Vec<Address>,
+        contribution: i128,
+        cycle_secs: u64,
+    ) -> Result<u64, Error> {
+        creator.require_auth();
+        Self::ensure_module_active(&env, &creator, symbol_short!("groups"))?;
+
+        if contribution <= 0 || cycle_secs == 0 {
+            return Err(Error::InvalidGroupParameters);
+        }
+        if members.len() < 2 || !members.contains(creator.clone()) {
+            return Err(Error::InvalidGroupMembers);
+        }
+
+        let users = Users::load(&env);
+        let mut missed = vec![&env];
+        let mut arrears = vec![&env];
+        let mut invited = vec![&env];
+        for (i, member) in members.iter().enumerate() {
+            if !users.contains_key(member.clone()) {
+                return Err(Error::UserNotFound);
+            }
+            if members.first_index_of(member.clone()) != Some(i as u32) {
+                return Err(Error::DuplicateGroupMember);
+            }
+            missed.push_back(0u32);
+            arrears.push_back(0i128);
+            if member != creator {
+                invited.push_back(member);
+            }
+        }
+
+        let mut groups: Map<u64, SavingsGroup> = env.storage().instance().get(&DataKey::Groups).unwrap_or(Map::new(&env));
+        let group_id: u64 = env.storage().instance().get(&CounterKey::GroupId).unwrap_or(0) + 1;
+
+        let group = SavingsGroup {
+            id: group_id,
+            name,
+            members,
+            contribution,
+            cycle_secs,
+            round: 0,
+            round_deadline: env.ledger().timestamp() + cycle_secs,
+            pot: 0,
+            paid: vec![&env],
+            missed,
+            arrears,
+            active: false,
+        };
+
+        groups.set(group_id, group);
+        env.storage().instance().set(&DataKey::Groups, &groups);
+        env.storage().instance().set(&CounterKey::GroupId, &group_id);
+
+        let mut invites: Map<u64, Vec<Address>> = env.storage().instance().get(&RecordKey::GroupInvites).unwrap_or(Map::new(&env));
+        invites.set(group_id, invited);
+        env.storage().instance().set(&RecordKey::GroupInvites, &invites);
+
+        env.events().publish((symbol_short!("grp_new"), creator), (group_id, contribution, cycle_secs));
+
+        Ok(group_id)
+    }
+
+    // Join a savings group the member was added to. Once the last member joins, the group starts
+    // and the first round runs for a full cycle from now.
+    pub fn join_group(env: Env, group_id: u64, member: Address) -> Result<(), Error> {
+        member.require_auth();
+        Self::ensure_module_active(&env, &member, symbol_short!("groups"))?;
+
+        let mut invites: Map<u64, Vec<Address>> = env.storage().instance().get(&RecordKey::GroupInvites).unwrap_or(Map::new(&env));
+        let mut invited = invites.get(group_id).ok_or(Error::NotInvitedToGroup)?;
+        let index = invited.first_index_of(member.clone()).ok_or(Error::NotInvitedToGroup)?;
+        invited.remove(index);
+
+        env.events().publish((symbol_short!("grp_join"), member), group_id);
+
+        if !invited.is_empty() {
+            invites.set(group_id, invited);
+            env.storage().instance().set(&RecordKey::GroupInvites, &invites);
+            return Ok(());
+        }
+        invites.remove(group_id);
+        env.storage().instance().set(&RecordKey::GroupInvites, &invites);
+
+        let mut groups: Map<u64, SavingsGroup> = env.storage().instance().get(&DataKey::Groups).unwrap_or(Map::new(&env));
+        let mut group = groups.get(group_id).ok_or(Error::GroupNotFound)?;
+        group.active = true;
+        group.round_deadline = env.ledger().timestamp() + group.cycle_secs;
+        groups.set(group_id, group);
+        env.storage().instance().set(&DataKey::Groups, &groups);
+
+        env.events().publish((symbol_short!("grp_start"),), group_id);
+
+        Ok(())
+    }
+
+    // Get the members of a savings group who have not joined it yet
+    pub fn get_group_invites(env: Env, group_id: u64) -> Vec<Address> {
+        let invites: Map<u64, Vec<Address>> = env.storage().instance().get(&RecordKey::GroupInvites).unwrap_or(Map::new(&env));
+
+        invites.get(group_id).unwrap_or(vec![&env])
+    }
+
+    // Pay a member's contribution for the current round plus any arrears; late payers also pay the
+    // penalty. The pot is paid out automatically once every member has contributed.
+    pub fn contribute(env: Env, group_id: u64, member: Address) -> Result<(), Error> {
+        member.require_auth();
+        Self::ensure_module_active(&env, &member, symbol_short!("groups"))?;
+
+        let mut groups: Map<u64, SavingsGroup> = env.storage().instance().get(&DataKey::Groups).unwrap_or(Map::new(&env));
+        let mut users = Users::load(&env);
+
+        let mut group = groups.get(group_id).ok_or(Error::GroupNotFound)?;
+        if !group.active {
+            return Err(Error::GroupNotActive);
+        }
+        let index = group.members.first_index_of(member.clone()).ok_or(Error::NotAGroupMember)?;
+        if group.paid.contains(member.clone()) {
+            return Err(Error::AlreadyContributedThisRound);
+        }
+
+        let mut amount = group.contribution + group.arrears.get(index).unwrap_or(0);
+        if env.ledger().timestamp() > group.round_deadline {
+            let penalty_bps: u32 = env.storage().instance().get(&ConfigKey::GroupPenaltyBps).unwrap_or(DEFAULT_GROUP_PENALTY_BPS);
+            amount += group.contribution * penalty_bps as i128 / BPS_DENOMINATOR;
+        }
+
+        let mut user = users.get(member.clone()).ok_or(Error::UserNotFound)?;
+        if user.balance < amount {
+            return Err(Self::fail(&env, &member, symbol_short!("grp_pay"), symbol_short!("balance"), amount, user.balance, Error::InsufficientBalance));
+        }
+        user.balance = Self::checked_debit(user.balance, amount)?;
+        Self::track_balance(&env, &user, -amount, Bucket::LockedSavings);
+        users.set(member.clone(), user);
+
+        group.pot += amount;
+        group.paid.push_back(member.clone());
+        group.arrears.set(index, 0);
+
+        env.events().publish((symbol_short!("grp_pay"), member), (group_id, group.round, amount));
+
+        if group.paid.len() == group.members.len() {
+            Self::payout_group_round(&env, &mut group, &mut users)?;
+        }
+
+        groups.set(group_id, group);
+        env.storage().instance().set(&DataKey::Groups, &groups);
+        users.save();
+
+        Ok(())
+    }
+
+    // Close an overdue round: unpaid members are charged contribution plus penalty, and any
+    // arrears, where their balance allows. Otherwise a missed contribution is recorded and the
+    // amount added to their arrears. The pot is then paid out.
+    pub fn close_group_round(env: Env, group_id: u64) -> Result<(), Error> {
+        let mut groups: Map<u64, SavingsGroup> = env.storage().instance().get(&DataKey::Groups).unwrap_or(Map::new(&env));
+        let mut users = Users::load(&env);
+
+        let mut group = groups.get(group_id).ok_or(Error::GroupNotFound)?;
+        if !group.active {
+            return Err(Error::GroupNotActive);
+        }
+        if env.ledger().timestamp() <= group.round_deadline {
+            let caller = env.current_contract_address();
+            return Err(Self::fail_until(&env, &caller, symbol_short!("grp_close"), symbol_short!("open"), group.round_deadline + 1, Error::RoundStillOpen));
+        }
+
+        let penalty_bps: u32 = env.storage().instance().get(&ConfigKey::GroupPenaltyBps).unwrap_or(DEFAULT_GROUP_PENALTY_BPS);
+        let amount = group.contribution + group.contribution * penalty_bps as i128 / BPS_DENOMINATOR;
+
+        for (i, member) in group.members.clone().iter().enumerate() {
+            if group.paid.contains(member.clone()) {
+                continue;
+            }
+
+            let mut user = users.get(member.clone()).ok_or(Error::UserNotFound)?;
+            let arrears = group.arrears.get(i as u32).unwrap_or(0);
+            let due = if user.balance >= amount + arrears { amount + arrears } else { amount };
+            if user.balance >= due {
+                user.balance = Self::checked_debit(user.balance, due)?;
+                Self::track_balance(&env, &user, -due, Bucket::LockedSavings);
+                users.set(member.clone(), user);
+                group.pot += due;
+                group.arrears.set(i as u32, arrears + amount - due);
+            } else {
+                let missed = group.missed.get(i as u32).unwrap_or(0);
+                group.missed.set(i as u32, missed + 1);
+                group.arrears.set(i as u32, arrears + amount);
+                env.events().publish((symbol_short!("grp_miss"), member), (group_id, group.round));
+            }
+        }
+
+        Self::payout_group_round(&env, &mut group, &mut users)?;
+
+        groups.set(group_id, group);
+        env.storage().instance().set(&DataKey::Groups, &groups);
+        users.save();
+
+        Ok(())
+    }
+
+    // Get the current state of a savings group
+    pub fn get_group_status(env: Env, group_id: u64) -> Result<SavingsGroup, Error> {
+        let groups: Map<u64, SavingsGroup> = env.storage().instance().get(&DataKey::Groups).unwrap_or(Map::new(&env));
+
+        groups.get(group_id).ok_or(Error::GroupNotFound)
+    }
+
+    // Set the late/missed contribution penalty for savings groups in basis points (admin only)
+    pub fn set_group_penalty(env: Env, penalty_bps: u32) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("group_pen"), AuditTarget::Global, penalty_bps as i128);
+
+        if penalty_bps as i128 > BPS_DENOMINATOR {
+            return Err(Error::InvalidPenalty);
+        }
+
+        env.storage().instance().set(&ConfigKey::GroupPenaltyBps, &penalty_bps);
+
+        Ok(())
+    }
+
+    // Get a user's daily balance snapshots with `from <= timestamp <= to`
+    pub fn get_balance_history(env: Env, user_address: Address, from: u64, to: u64, cursor: u32, limit: u32) -> (Vec<BalanceSnapshot>, Option<u32>) {
+        let snapshots: Vec<BalanceSnapshot> = env.storage().persistent().get(&EntryKey::BalanceHistory(user_address)).unwrap_or(vec![&env]);
+        let mut history = vec![&env];
+
+        for snapshot in snapshots.iter() {
+            if snapshot.timestamp >= from && snapshot.timestamp <= to {
+                history.push_back(snapshot);
+            }
+        }
+
+        Self::page(history, cursor, limit)
+    }
+
+    // Set the intra-day balance change that triggers a `bal_alert` event, in basis points;
+    // zero disables the alerts (admin only)
+    pub fn set_balance_alert_threshold(env: Env, threshold_bps: u32) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("alert_bps"), AuditTarget::Global, threshold_bps as i128);
+
+        env.storage().instance().set(&ConfigKey::AlertThresholdBps, &threshold_bps);
+
+        Ok(())
+    }
+
+    // Schedule a one-time payment; the amount is reserved from the sender's balance immediately
+    pub fn schedule_payment(
+        env: Env,
+        from_address: Address,
+        to_address: Address,
+        amount: i128,
+        execute_at: u64,
+    ) -> Result<u64, Error> {
+        from_address.require_auth();
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        if execute_at <= env.ledger().timestamp() {
+            return Err(Error::ExecutionTimeMustBeInTheFuture);
+        }
+
+        Self::ensure_module_active(&env, &from_address, symbol_short!("scheduled"))?;
+        Self::ensure_not_restricted(&env, &from_address, symbol_short!("schedule"))?;
+
+        let mut users = Users::load(&env);
+
+        let mut from_user = users.get(from_address.clone()).ok_or(Error::SenderNotFound)?;
+        if !users.contains_key(to_address.clone()) {
+            return Err(Error::RecipientNotFound);
+        }
+
+        Self::check_min_incoming(&env, &from_address, &to_address, amount, symbol_short!("schedule"))?;
+
+        if from_user.balance < amount {
+            return Err(Self::fail(&env, &from_address, symbol_short!("schedule"), symbol_short!("balance"), amount, from_user.balance, Error::InsufficientBalance));
+        }
+
+        from_user.balance = Self::checked_debit(from_user.balance, amount)?;
+        Self::track_balance(&env, &from_user, -amount, Bucket::Escrowed);
+        users.set(from_address.clone(), from_user);
+        users.save();
+
+        let mut scheduled: Map<u64, ScheduledPayment> = env.storage().instance().get(&DataKey::Scheduled).unwrap_or(Map::new(&env));
+        let schedule_id: u64 = env.storage().instance().get(&CounterKey::ScheduleId).unwrap_or(0) + 1;
+
+        let payment = ScheduledPayment {
+            id: schedule_id,
+            from_address: from_address.clone(),
+            to_address,
+            amount,
+            execute_at,
+            status: String::from_str(&env, "pending"),
+            timestamp: env.ledger().timestamp(),
+        };
+
+        scheduled.set(schedule_id, payment);
+        env.storage().instance().set(&DataKey::Scheduled, &scheduled);
+        env.storage().instance().set(&CounterKey::ScheduleId, &schedule_id);
+        Self::index_record(&env, EntryKey::UserScheduled(from_address.clone()), schedule_id);
+
+        env.events().publish((symbol_short!("sched_new"), from_address), (schedule_id, amount, execute_at));
+
+        Ok(schedule_id)
+    }
+
+    // Execute a due scheduled payment. Anyone (typically a keeper) may call this after `execute_at`.
+    pub fn execute_scheduled(env: Env, schedule_id: u64) -> Result<(), Error> {
+        let mut scheduled: Map<u64, ScheduledPayment> = env.storage().instance().get(&DataKey::Scheduled).unwrap_or(Map::new(&env));
+
+        let mut payment = scheduled.get(schedule_id).ok_or(Error::ScheduledPaymentNotFound)?;
+        if payment.status != String::from_str(&env, "pending") {
+            return Err(Error::ScheduledPaymentNotPending);
+        }
+        if env.ledger().timestamp() < payment.execute_at {
+            return Err(Self::fail_until(&env, &payment.from_address, symbol_short!("sched_run"), symbol_short!("not_due"), payment.execute_at, Error::ScheduledPaymentNotDueYet));
+        }
+
+        Self::ensure_not_restricted(&env, &payment.to_address, symbol_short!("sched_run"))?;
+
+        let mut users = Users::load(&env);
+        let mut to_user = users.get(payment.to_address.clone()).ok_or(Error::RecipientNotFound)?;
+
+        to_user.balance = Self::checked_credit(to_user.balance, payment.amount)?;
+        Self::track_balance(&env, &to_user, payment.amount, Bucket::Escrowed);
+        users.set(payment.to_address.clone(), to_user);
+        users.save();
+
+        payment.status = String::from_str(&env, "executed");
+        scheduled.set(schedule_id, payment.clone());
+        env.storage().instance().set(&DataKey::Scheduled, &scheduled);
+
+        env.events().publish((symbol_short!("sched_run"), payment.from_address), (schedule_id, payment.to_address, payment.amount));
+
+        Ok(())
+    }
+
+    // Cancel a pending scheduled payment and release the reserved funds back to the sender
+    pub fn cancel_scheduled(env: Env, schedule_id: u64) -> Result<(), Error> {
+        let mut scheduled: Map<u64, ScheduledPayment> = env.storage().instance().get(&DataKey::Scheduled).unwrap_or(Map::new(&env));
+
+        let mut payment = scheduled.get(schedule_id).ok_or(Error::ScheduledPaymentNotFound)?;
+        payment.from_address.require_auth();
+
+        if payment.status != String::from_str(&env, "pending") {
+            return Err(Error::ScheduledPaymentNotPending);
+        }
+
+        let mut users = Users::load(&env);
+        let mut from_user = users.get(payment.from_address.clone()).ok_or(Error::SenderNotFound)?;
+
+        from_user.balance = Self::checked_credit(from_user.balance, payment.amount)?;
+        Self::track_balance(&env, &from_user, payment.amount, Bucket::Escrowed);
+        users.set(payment.from_address.clone(), from_user);
+        users.save();
+
+        payment.status = String::from_str(&env, "cancelled");
+        scheduled.set(schedule_id, payment.clone());
+        env.storage().instance().set(&DataKey::Scheduled, &scheduled);
+
+        env.events().publish((symbol_short!("sched_end"), payment.from_address), (schedule_id, payment.amount));
+
+        Ok(())
+    }
+
+    // Get scheduled payments created by a user
+    pub fn get_scheduled_payments(env: Env, user_address: Address, cursor: u32, limit: u32) -> (Vec<ScheduledPayment>, Option<u32>) {
+        Self::page(Self::scheduled_payments_for(&env, &user_address), cursor, limit)
+    }
+
+    // Turn this deployment into a sandbox with a play-money faucet (admin only).
+    // Only possible before any user registers, so sandbox balances never mix with real funds.
+    pub fn enable_sandbox(env: Env) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("sandbox"), AuditTarget::Global, 0);
+
+        let users = Users::load(&env);
+        if !users.is_empty() {
+            return Err(Error::SandboxAfterRegistration);
+        }
+
+        env.storage().instance().set(&ConfigKey::Sandbox, &true);
+        env.events().publish((symbol_short!("sandbox"),), true);
+
+        Ok(())
+    }
+
+    // Mint play-money balance to a user (sandbox deployments only)
+    pub fn faucet(env: Env, user_address: Address, amount: i128) -> Result<(), Error> {
+        let sandbox: bool = env.storage().instance().get(&ConfigKey::Sandbox).unwrap_or(false);
+        if !sandbox {
+            return Err(Error::FaucetRequiresSandbox);
+        }
+
+        if amount <= 0 || amount > SANDBOX_FAUCET_MAX {
+            return Err(Error::InvalidAmount);
+        }
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+
+        user.balance = Self::checked_credit(user.balance, amount)?;
+        Self::track_balance(&env, &user, amount, Bucket::External);
+        users.set(user_address.clone(), user);
+        users.save();
+
+        let minted: i128 = env.storage().instance().get(&CounterKey::SandboxMinted).unwrap_or(0);
+        env.storage().instance().set(&CounterKey::SandboxMinted, &(minted + amount));
+
+        env.events().publish((symbol_short!("faucet"), user_address), amount);
+
+        Ok(())
+    }
+
+    // Get whether this deployment is a sandbox and how much play money has been minted
+    pub fn get_sandbox_status(env: Env) -> SandboxStatus {
+        SandboxStatus {
+            enabled: env.storage().instance().get(&ConfigKey::Sandbox).unwrap_or(false),
+            minted: env.storage().instance().get(&CounterKey::SandboxMinted).unwrap_or(0),
+        }
+    }
+
+    // Create a payment stream that vests `total_amount` linearly between `start` and `end`.
+    // The full amount is escrowed from the sender up front.
+    pub fn create_stream(
+        env: Env,
+        from_address: Address,
+        to_address: Address,
+        total_amount: i128,
+        start: u64,
+        end: u64,
+    ) -> Result<u64, Error> {
+        from_address.require_auth();
+
+        if total_amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        if end <= start || end <= env.ledger().timestamp() {
+            return Err(Error::InvalidStreamPeriod);
+        }
+        if from_address == to_address {
+            return Err(Error::CannotStreamToYourself);
+        }
+
+        Self::ensure_module_active(&env, &from_address, symbol_short!("streams"))?;
+        Self::ensure_not_restricted(&env, &from_address, symbol_short!("stream"))?;
+
+        let mut users = Users::load(&env);
+
+        let mut from_user = users.get(from_address.clone()).ok_or(Error::SenderNotFound)?;
+        if !users.contains_key(to_address.clone()) {
+            return Err(Error::RecipientNotFound);
+        }
+
+        if from_user.balance < total_amount {
+            return Err(Self::fail(&env, &from_address, symbol_short!("stream"), symbol_short!("balance"), total_amount, from_user.balance, Error::InsufficientBalance));
+        }
+
+        from_user.balance = Self::checked_debit(from_user.balance, total_amount)?;
+        Self::track_balance(&env, &from_user, -total_amount, Bucket::Escrowed);
+        users.set(from_address.clone(), from_user);
+        users.save();
+
+        let mut streams: Map<u64, PaymentStream> = env.storage().instance().get(&DataKey::Streams).unwrap_or(Map::new(&env));
+        let stream_id: u64 = env.storage().instance().get(&CounterKey::StreamId).unwrap_or(0) + 1;
+
+        let stream = PaymentStream {
+            id: stream_id,
+            from_address: from_address.clone(),
+            to_address: to_address.clone(),
+            total_amount,
+            withdrawn: 0,
+            start,
+            end,
+            status: String::from_str(&env, "active"),
+        };
+
+        streams.set(stream_id, stream);
+        env.storage().instance().set(&DataKey::Streams, &streams);
+        env.storage().instance().set(&CounterKey::StreamId, &stream_id);
+        Self::index_record(&env, EntryKey::UserStreams(from_address.clone()), stream_id);
+        Self::index_record(&env, EntryKey::UserStreams(to_address.clone()), stream_id);
+
+        env.events().publish((symbol_short!("strm_new"), from_address, to_address), (stream_id, total_amount, start, end));
+
+        Ok(stream_id)
+    }
+
+    // Release everything vested so far across the recipient's active streams to their balance
+    pub fn withdraw_from_stream(env: Env, to_address: Address) -> Result<i128, Error> {
+        to_address.require_auth();
+
+        Self::ensure_not_restricted(&env, &to_address, symbol_short!("strm_out"))?;
+
+        let mut users = Users::load(&env);
+        let mut streams: Map<u64, PaymentStream> = env.storage().instance().get(&DataKey::Streams).unwrap_or(Map::new(&env));
+
+        let mut user = users.get(to_address.clone()).ok_or(Error::UserNotFound)?;
+        let active = String::from_str(&env, "active");
+        let mut released: i128 = 0;
+        let mut next_start: u64 = 0;
+
+        for (stream_id, mut stream) in streams.clone().iter() {
+            if stream.to_address != to_address || stream.status != active {
+                continue;
+            }
+
+            let available = Self::stream_vested(&env, &stream) - stream.withdrawn;
+            if available <= 0 {
+                if next_start == 0 || stream.start < next_start {
+                    next_start = stream.start;
+                }
+                continue;
+            }
+
+            stream.withdrawn += available;
+            if stream.withdrawn == stream.total_amount {
+                stream.status = String::from_str(&env, "completed");
+            }
+            released += available;
+
+            env.events().publish((symbol_short!("strm_out"), to_address.clone()), (stream_id, available));
+            streams.set(stream_id, stream);
+        }
+
+        if released == 0 {
+            // Streams that have not started yet can be retried once the earliest one begins vesting
+            let retry_after = if next_start > env.ledger().timestamp() { next_start + 1 } else { 0 };
+            return Err(Self::fail_until(&env, &to_address, symbol_short!("strm_out"), symbol_short!("vesting"), retry_after, Error::NothingToWithdraw));
+        }
+
+        user.balance = Self::checked_credit(user.balance, released)?;
+        Self::track_balance(&env, &user, released, Bucket::Escrowed);
+        users.set(to_address.clone(), user);
+        users.save();
+        env.storage().instance().set(&DataKey::Streams, &streams);
+
+        Ok(released)
+    }
+
+    // Cancel a stream: the vested but unwithdrawn part goes to the recipient,
+    // the unvested remainder returns to the sender
+    pub fn cancel_stream(env: Env, stream_id: u64) -> Result<(), Error> {
+        let mut streams: Map<u64, PaymentStream> = env.storage().instance().get(&DataKey::Streams).unwrap_or(Map::new(&env));
+
+        let mut stream = streams.get(stream_id).ok_or(Error::StreamNotFound)?;
+        stream.from_address.require_auth();
+
+        if stream.status != String::from_str(&env, "active") {
+            return Err(Error::StreamNotActive);
+        }
+
+        let vested = Self::stream_vested(&env, &stream);
+        let to_recipient = vested - stream.withdrawn;
+        let to_sender = stream.total_amount - vested;
+
+        let mut users = Users::load(&env);
+
+        if to_recipient > 0 {
+            let mut to_user = users.get(stream.to_address.clone()).ok_or(Error::RecipientNotFound)?;
+            to_user.balance = Self::checked_credit(to_user.balance, to_recipient)?;
+            Self::track_balance(&env, &to_user, to_recipient, Bucket::Escrowed);
+            users.set(stream.to_address.clone(), to_user);
+        }
+        if to_sender > 0 {
+            let mut from_user = users.get(stream.from_address.clone()).ok_or(Error::SenderNotFound)?;
+            from_user.balance = Self::checked_credit(from_user.balance, to_sender)?;
+            Self::track_balance(&env, &from_user, to_sender, Bucket::Escrowed);
+            users.set(stream.from_address.clone(), from_user);
+        }
+        users.save();
+
+        stream.withdrawn = vested;
+        stream.status = String::from_str(&env, "cancelled");
+        streams.set(stream_id, stream.clone());
+        env.storage().instance().set(&DataKey::Streams, &streams);
+
+        env.events().publish((symbol_short!("strm_end"), stream.from_address, stream.to_address), (stream_id, to_recipient, to_sender));
+
+        Ok(())
+    }
+
+    // Get streams a user sends or receives
+    pub fn get_streams(env: Env, user_address: Address, cursor: u32, limit: u32) -> (Vec<PaymentStream>, Option<u32>) {
+        Self::page(Self::streams_for(&env, &user_address), cursor, limit)
+    }
+
+    // Seed a deterministic set of demo users, bills, withdrawals and a savings group
+    // (sandbox deployments only, admin only). `profile` is `small` or `full`.
+    pub fn seed_demo_data(env: Env, profile: Symbol) -> Result<Vec<Address>, Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("demo_seed"), AuditTarget::Key(profile.clone()), 0);
+
+        let sandbox: bool = env.storage().instance().get(&ConfigKey::Sandbox).unwrap_or(false);
+        if !sandbox {
+            return Err(Error::DemoRequiresSandbox);
+        }
+
+        let user_count: u32 = if profile == symbol_short!("small") {
+            3
+        } else if profile == symbol_short!("full") {
+            8
+        } else {
+            return Err(Error::UnknownDemoProfile);
+        };
+
+        let mut users = Users::load(&env);
+        // Demo addresses are fixed, so a second seed would count and mint the same users twice
+        if users.contains_key(Self::demo_address(&env, 0)) {
+            return Err(Error::DemoDataAlreadySeeded);
+        }
+        let now = env.ledger().timestamp();
+        let mut seeded = vec![&env];
+        let mut minted: i128 = 0;
+        let mut stats = Self::load_stats(&env);
+
+        for i in 0..user_count {
+            let address = Self::demo_address(&env, i);
+            let balance = 100_000_000 * (i as i128 + 1);
+            let bill_amount = 10_000_000;
+            let withdrawal_amount = 20_000_000;
+
+            let user = User {
+                address: address.clone(),
+                phone: Self::demo_string(&env, b"+25670000000", i),
+                is_verified: i % 2 == 0,
+                balance: balance - bill_amount - withdrawal_amount,
+                display_name: None,
+                country: None,
+                preferred_currency: None,
+            };
+            stats.user_count += 1;
+            stats.custodied += user.balance;
+            Self::post(&env, &address, Bucket::External, Bucket::Spendable, user.balance);
+            Self::post(&env, &address, Bucket::External, Bucket::Payable, bill_amount);
+            users.set(address.clone(), user);
+            minted += balance;
+
+            let bill_id = Self::demo_string(&env, b"demo_bill_", i);
+            let bill_status = String::from_str(&env, if i % 3 == 0 { "completed" } else { "pending" });
+            stats.bill_volume += bill_amount;
+            Self::count_status(&mut stats.bill_statuses, None, bill_status.clone());
+            Self::add_to_index(&env, DataKey::UserBills(address.clone()), bill_id.clone());
+            Self::save_bill(&env, &BillPayment {
+                id: bill_id,
+                user_address: address.clone(),
+                bill_type: String::from_str(&env, if i % 2 == 0 { "electricity" } else { "water" }),
+                account_number: Self::demo_string(&env, b"ACC", i),
+                amount: bill_amount,
+                status: bill_status,
+                timestamp: now,
+                memo: Some(String::from_str(&env, "Demo bill")),
+            });
+
+            let withdrawal_id = Self::demo_string(&env, b"demo_withdraw_", i);
+            stats.withdrawal_volume += withdrawal_amount;
+            Self::count_status(&mut stats.withdrawal_statuses, None, String::from_str(&env, "pending"));
+            Self::add_to_index(&env, DataKey::UserWithdrawals(address.clone()), withdrawal_id.clone());
+            Self::save_withdrawal(&env, &Withdrawal {
+                id: withdrawal_id,
+                user_address: address.clone(),
+                method: String::from_str(&env, if i % 2 == 0 { "mtn_momo" } else { "airtel_money" }),
+                account_number: Self::demo_string(&env, b"+25677000000", i),
+                usdc_amount: withdrawal_amount,
+                local_amount: withdrawal_amount * 3_700,
+                currency: symbol_short!("UGX"),
+                status: String::from_str(&env, "pending"),
+                timestamp: now,
+                memo: None,
+                payout_reference: None,
+                processing_deadline: now + DEFAULT_WITHDRAWAL_SLA_SECS,
+                effective_rate: 3_700 * RATE_SCALE,
+                spread: 0,
+            });
+
+            seeded.push_back(address);
+        }
+
+        users.save();
+        Self::save_stats(&env, &stats);
+
+        // One savings group made up of all demo users
+        let mut groups: Map<u64, SavingsGroup> = env.storage().instance().get(&DataKey::Groups).unwrap_or(Map::new(&env));
+        let group_id: u64 = env.storage().instance().get(&CounterKey::GroupId).unwrap_or(0) + 1;
+        let mut missed = vec![&env];
+        let mut arrears = vec![&env];
+        for _ in seeded.iter() {
+            missed.push_back(0u32);
+            arrears.push_back(0i128);
+        }
+        groups.set(group_id, SavingsGroup {
+            id: group_id,
+            name: String::from_str(&env, "Demo Chama"),
+            members: seeded.clone(),
+            contribution: 5_000_000,
+            cycle_secs: 7 * SECONDS_PER_DAY,
+            round: 0,
+            round_deadline: now + 7 * SECONDS_PER_DAY,
+            pot: 0,
+            paid: vec![&env],
+            missed,
+            arrears,
+            active: true,
+        });
+        env.storage().instance().set(&DataKey::Groups, &groups);
+        env.storage().instance().set(&CounterKey::GroupId, &group_id);
+
+        let total_minted: i128 = env.storage().instance().get(&CounterKey::SandboxMinted).unwrap_or(0);
+        env.storage().instance().set(&CounterKey::SandboxMinted, &(total_minted + minted));
+
+        env.events().publish((symbol_short!("demo_seed"), profile), user_count);
+
+        Ok(seeded)
+    }
+
+    // Register a new user who was referred by an existing user
+    pub fn register_user_with_referrer(
+        env: Env,
+        user_address: Address,
+        phone: String,
+        referrer: Address,
+    ) -> Result<(), Error> {
+        if user_address == referrer {
+            return Err(Error::CannotReferYourself);
+        }
+
+        let users = Users::load(&env);
+        if !users.contains_key(referrer.clone()) {
+            return Err(Error::ReferrerNotFound);
+        }
+
+        Self::register_user(env.clone(), user_address.clone(), phone)?;
+
+        // Referred users awaiting their first qualifying transaction
+        let mut pending: Map<Address, Address> = env.storage().instance().get(&DataKey::ReferralPending).unwrap_or(Map::new(&env));
+        pending.set(user_address.clone(), referrer.clone());
+        env.storage().instance().set(&DataKey::ReferralPending, &pending);
+
+        let mut stats: Map<Address, ReferralStats> = env.storage().instance().get(&DataKey::ReferralStats).unwrap_or(Map::new(&env));
+        let mut referrer_stats = stats.get(referrer.clone()).unwrap_or(ReferralStats {
+            referred_count: 0,
+            qualified_count: 0,
+            rewards_earned: 0,
+        });
+        referrer_stats.referred_count += 1;
+        stats.set(referrer.clone(), referrer_stats);
+        env.storage().instance().set(&DataKey::ReferralStats, &stats);
+
+        env.events().publish((symbol_short!("referred"), referrer), user_address);
+
+        Ok(())
+    }
+
+    // Get referral counts and rewards earned by a user
+    pub fn get_referral_stats(env: Env, user_address: Address) -> ReferralStats {
+        let stats: Map<Address, ReferralStats> = env.storage().instance().get(&DataKey::ReferralStats).unwrap_or(Map::new(&env));
+
+        stats.get(user_address).unwrap_or(ReferralStats {
+            referred_count: 0,
+            qualified_count: 0,
+            rewards_earned: 0,
+        })
+    }
+
+    // Add funds to the referral rewards pool (treasurer only)
+    pub fn fund_referral_pool(env: Env, caller: Address, amount: i128) -> Result<(), Error> {
+        Self::require_role(&env, &caller, Role::Treasurer)?;
+        Self::audit(&env, &caller, symbol_short!("fund_ref"), AuditTarget::Global, amount);
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        let usdc: Address = env.storage().instance().get(&ConfigKey::UsdcToken).ok_or(Error::UsdcTokenNotConfigured)?;
+
+        let pool: i128 = env.storage().instance().get(&CounterKey::ReferralPool).unwrap_or(0);
+        env.storage().instance().set(&CounterKey::ReferralPool, &(pool + amount));
+        Self::post(&env, &caller, Bucket::External, Bucket::Treasury, amount);
+
+        // The pool only grows by USDC the treasurer actually sent
+        token::Client::new(&env, &usdc).transfer(&caller, &env.current_contract_address(), &amount);
+
+        Ok(())
+    }
+
+    // Set the reward paid to a referrer once their referral qualifies (admin only)
+    pub fn set_referral_reward(env: Env, reward: i128) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("ref_rwd"), AuditTarget::Global, reward);
+
+        if reward < 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        env.storage().instance().set(&ConfigKey::ReferralReward, &reward);
+
+        Ok(())
+    }
+
+    // Get the referral rewards pool balance and the current reward per referral
+    pub fn get_referral_pool(env: Env) -> (i128, i128) {
+        (
+            env.storage().instance().get(&CounterKey::ReferralPool).unwrap_or(0),
+            env.storage().instance().get(&ConfigKey::ReferralReward).unwrap_or(0),
+        )
+    }
+
+    // Set the cashback rate for a bill type in basis points (admin only)
+    pub fn set_cashback_rate(env: Env, bill_type: String, rate_bps: u32) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("cashback"), AuditTarget::Id(bill_type.clone()), rate_bps as i128);
+
+        if rate_bps as i128 > BPS_DENOMINATOR {
+            return Err(Error::InvalidRate);
+        }
+
+        let mut rates: Map<String, u32> = env.storage().instance().get(&ConfigKey::CashbackRates).unwrap_or(Map::new(&env));
+        if rate_bps == 0 {
+            rates.remove(bill_type);
+        } else {
+            rates.set(bill_type, rate_bps);
+        }
+        env.storage().instance().set(&ConfigKey::CashbackRates, &rates);
+
+        Ok(())
+    }
+
+    // Add funds to the rewards pool that backs cashback redemptions (treasurer only)
+    pub fn fund_rewards_pool(env: Env, caller: Address, amount: i128) -> Result<(), Error> {
+        Self::require_role(&env, &caller, Role::Treasurer)?;
+        Self::audit(&env, &caller, symbol_short!("fund_rwd"), AuditTarget::Global, amount);
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        let usdc: Address = env.storage().instance().get(&ConfigKey::UsdcToken).ok_or(Error::UsdcTokenNotConfigured)?;
+
+        let pool: i128 = env.storage().instance().get(&CounterKey::RewardPool).unwrap_or(0);
+        env.storage().instance().set(&CounterKey::RewardPool, &(pool + amount));
+        Self::post(&env, &caller, Bucket::External, Bucket::Treasury, amount);
+
+        // The pool only grows by USDC the treasurer actually sent
+        token::Client::new(&env, &usdc).transfer(&caller, &env.current_contract_address(), &amount);
+
+        Ok(())
+    }
+
+    // Get a user's unredeemed cashback points
+    pub fn get_rewards_balance(env: Env, user_address: Address) -> i128 {
+        let rewards: Map<Address, i128> = env.storage().instance().get(&DataKey::Rewards).unwrap_or(Map::new(&env));
+
+        rewards.get(user_address).unwrap_or(0)
+    }
+
+    // Convert cashback points into spendable balance, paid from the rewards pool
+    pub fn redeem_rewards(env: Env, user_address: Address) -> Result<i128, Error> {
+        user_address.require_auth();
+
+        let mut rewards: Map<Address, i128> = env.storage().instance().get(&DataKey::Rewards).unwrap_or(Map::new(&env));
+        let points = rewards.get(user_address.clone()).unwrap_or(0);
+        if points <= 0 {
+            return Err(Error::NoRewardsToRedeem);
+        }
+
+        let pool: i128 = env.storage().instance().get(&CounterKey::RewardPool).unwrap_or(0);
+        if pool < points {
+            return Err(Self::fail(&env, &user_address, symbol_short!("rw_redeem"), symbol_short!("pool"), points, pool, Error::RewardsPoolUnderfunded));
+        }
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+
+        user.balance = Self::checked_credit(user.balance, points)?;
+        Self::track_balance(&env, &user, points, Bucket::Rewards);
+        users.set(user_address.clone(), user);
+        users.save();
+
+        rewards.remove(user_address.clone());
+        env.storage().instance().set(&DataKey::Rewards, &rewards);
+        env.storage().instance().set(&CounterKey::RewardPool, &(pool - points));
+
+        env.events().publish((symbol_short!("rw_redeem"), user_address), points);
+
+        Ok(points)
+    }
+
+    // Register a cash-in/cash-out agent (admin only)
+    pub fn register_agent(env: Env, agent: Address, name: String) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("agent_add"), AuditTarget::Address(agent.clone()), 0);
+
+        let mut agents: Map<Address, Agent> = env.storage().instance().get(&DataKey::Agents).unwrap_or(Map::new(&env));
+        if agents.contains_key(agent.clone()) {
+            return Err(Error::AgentAlreadyExists);
+        }
+
+        agents.set(agent.clone(), Agent {
+            address: agent.clone(),
+            name,
+            float_balance: 0,
+            commission_earned: 0,
+            active: true,
+            registered_at: env.ledger().timestamp(),
+        });
+        env.storage().instance().set(&DataKey::Agents, &agents);
+
+        env.events().publish((symbol_short!("agent_new"), agent), ());
+
+        Ok(())
+    }
+
+    // Activate or deactivate an agent (admin only)
+    pub fn set_agent_active(env: Env, agent: Address, active: bool) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("agent_act"), AuditTarget::Address(agent.clone()), active as i128);
+
+        let mut agents: Map<Address, Agent> = env.storage().instance().get(&DataKey::Agents).unwrap_or(Map::new(&env));
+        let mut record = agents.get(agent.clone()).ok_or(Error::AgentNotFound)?;
+        record.active = active;
+        agents.set(agent, record);
+        env.storage().instance().set(&DataKey::Agents, &agents);
+
+        Ok(())
+    }
+
+    // Credit an agent's float with the USDC they deposited with the operator, which the treasurer
+    // sends in here (treasurer only)
+    pub fn fund_agent_float(env: Env, caller: Address, agent: Address, amount: i128) -> Result<(), Error> {
+        Self::require_role(&env, &caller, Role::Treasurer)?;
+        Self::audit(&env, &caller, symbol_short!("agt_float"), AuditTarget::Address(agent.clone()), amount);
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        let usdc: Address = env.storage().instance().get(&ConfigKey::UsdcToken).ok_or(Error::UsdcTokenNotConfigured)?;
+
+        let mut agents: Map<Address, Agent> = env.storage().instance().get(&DataKey::Agents).unwrap_or(Map::new(&env));
+        let mut record = agents.get(agent.clone()).ok_or(Error::AgentNotFound)?;
+        record.float_balance = Self::checked_credit(record.float_balance, amount)?;
+        agents.set(agent, record);
+        env.storage().instance().set(&DataKey::Agents, &agents);
+
+        // Cash-ins are credited against the float, so it has to be backed by USDC held here
+        token::Client::new(&env, &usdc).transfer(&caller, &env.current_contract_address(), &amount);
+
+        Ok(())
+    }
+
+    // Set the commission agents earn on cash-in and cash-out, in basis points (admin only)
+    pub fn set_agent_commission(env: Env, commission_bps: u32) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::require_council_approval(&env)?;
+        Self::audit(&env, &admin, symbol_short!("agent_com"), AuditTarget::Global, commission_bps as i128);
+
+        if commission_bps as i128 > BPS_DENOMINATOR {
+            return Err(Error::InvalidRate);
+        }
+
+        env.storage().instance().set(&ConfigKey::AgentCommissionBps, &commission_bps);
+
+        Ok(())
+    }
+
+    // Agent receives cash from a user and credits them from the agent's float
+    pub fn agent_cash_in(env: Env, agent: Address, user_address: Address, amount: i128) -> Result<(), Error> {
+        agent.require_auth();
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        Self::ensure_module_active(&env, &agent, symbol_short!("agents"))?;
+        Self::ensure_not_restricted(&env, &user_address, symbol_short!("cash_in"))?;
+
+        let mut agents: Map<Address, Agent> = env.storage().instance().get(&DataKey::Agents).unwrap_or(Map::new(&env));
+        let mut record = agents.get(agent.clone()).ok_or(Error::AgentNotFound)?;
+        if !record.active {
+            return Err(Error::AgentNotActive);
+        }
+        if record.float_balance < amount {
+            return Err(Self::fail(&env, &agent, symbol_short!("cash_in"), symbol_short!("float"), amount, record.float_balance, Error::InsufficientAgentFloat));
+        }
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+
+        let balance_before = user.balance;
+        user.balance = Self::checked_credit(user.balance, amount)?;
+        Self::track_balance(&env, &user, amount, Bucket::External);
+        Self::claw_back_overdraft(&env, &user, balance_before);
+        Self::note_deposit(&env, &user_address, amount);
+        Self::collect_overdue_loans(&env, &mut user, amount)?;
+        users.set(user_address.clone(), user);
+        users.save();
+
+        record.float_balance = Self::checked_debit(record.float_balance, amount)?;
+        record.commission_earned += Self::agent_commission(&env, amount);
+        agents.set(agent.clone(), record);
+        env.storage().instance().set(&DataKey::Agents, &agents);
+
+        env.events().publish((symbol_short!("cash_in"), agent, user_address), amount);
+
+        Ok(())
+    }
+
+    // User requests cash from an agent with the hash of a secret code. The amount is held until the
+    // agent proves the cash was handed over by submitting the code within the confirmation window.
+    pub fn agent_cash_out(
+        env: Env,
+        user_address: Address,
+        agent: Address,
+        amount: i128,
+        code_hash: BytesN<32>,
+    ) -> Result<u64, Error> {
+        user_address.require_auth();
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        Self::ensure_module_active(&env, &user_address, symbol_short!("agents"))?;
+        Self::ensure_not_restricted(&env, &user_address, symbol_short!("cash_out"))?;
+        Self::check_subaccount(&env, &user_address, symbol_short!("cash_out"), amount)?;
+
+        let agents: Map<Address, Agent> = env.storage().instance().get(&DataKey::Agents).unwrap_or(Map::new(&env));
+        let record = agents.get(agent.clone()).ok_or(Error::AgentNotFound)?;
+        if !record.active {
+            return Err(Error::AgentNotActive);
+        }
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+
+        if user.balance < amount {
+            return Err(Self::fail(&env, &user_address, symbol_short!("cash_out"), symbol_short!("balance"), amount, user.balance, Error::InsufficientBalance));
+        }
+
+        user.balance = Self::checked_debit(user.balance, amount)?;
+        Self::track_balance(&env, &user, -amount, Bucket::Escrowed);
+        users.set(user_address.clone(), user);
+        users.save();
+
+        let mut cash_outs: Map<u64, CashOut> = env.storage().instance().get(&DataKey::CashOuts).unwrap_or(Map::new(&env));
+        let cash_out_id: u64 = env.storage().instance().get(&CounterKey::CashOutId).unwrap_or(0) + 1;
+        let window: u64 = env.storage().instance().get(&ConfigKey::CashOutWindow).unwrap_or(DEFAULT_CASH_OUT_WINDOW_SECS);
+
+        cash_outs.set(cash_out_id, CashOut {
+            id: cash_out_id,
+            user_address: user_address.clone(),
+            agent: agent.clone(),
+            amount,
+            code_hash,
+            expires_at: env.ledger().timestamp() + window,
+            status: String::from_str(&env, "pending"),
+            timestamp: env.ledger().timestamp(),
+        });
+        env.storage().instance().set(&DataKey::CashOuts, &cash_outs);
+        env.storage().instance().set(&CounterKey::CashOutId, &cash_out_id);
+        Self::index_record(&env, EntryKey::UserCashOuts(user_address.clone()), cash_out_id);
+
+        env.events().publish((symbol_short!("cash_req"), user_address, agent), (cash_out_id, amount));
+
+        Ok(cash_out_id)
+    }
+
+    // Agent confirms the cash was handed over by submitting the user's code; the held amount moves into the agent's float
+    pub fn confirm_cash_out(env: Env, agent: Address, cash_out_id: u64, code: Bytes) -> Result<(), Error> {
+        agent.require_auth();
+
+        let mut cash_outs: Map<u64, CashOut> = env.storage().instance().get(&DataKey::CashOuts).unwrap_or(Map::new(&env));
+        let mut cash_out = cash_outs.get(cash_out_id).ok_or(Error::CashOutNotFound)?;
+
+        if cash_out.agent != agent {
+            return Err(Error::Unauthorized);
+        }
+        if cash_out.status != String::from_str(&env, "pending") {
+            return Err(Error::CashOutNotPending);
+        }
+        if env.ledger().timestamp() > cash_out.expires_at {
+            return Err(Error::CashOutHasExpired);
+        }
+        if env.crypto().sha256(&code).to_bytes() != cash_out.code_hash {
+            return Err(Self::fail(&env, &agent, symbol_short!("cash_out"), symbol_short!("code"), 0, 0, Error::InvalidConfirmationCode));
+        }
+
+        let mut agents: Map<Address, Agent> = env.storage().instance().get(&DataKey::Agents).unwrap_or(Map::new(&env));
+        let mut record = agents.get(agent.clone()).ok_or(Error::AgentNotFound)?;
+        record.float_balance = Self::checked_credit(record.float_balance, cash_out.amount)?;
+        record.commission_earned += Self::agent_commission(&env, cash_out.amount);
+        agents.set(agent.clone(), record);
+        env.storage().instance().set(&DataKey::Agents, &agents);
+
+        cash_out.status = String::from_str(&env, "completed");
+        cash_outs.set(cash_out_id, cash_out.clone());
+        env.storage().instance().set(&DataKey::CashOuts, &cash_outs);
+        Self::post(&env, &cash_out.user_address, Bucket::Escrowed, Bucket::External, cash_out.amount);
+
+        env.events().publish((symbol_short!("cash_out"), agent, cash_out.user_address), (cash_out_id, cash_out.amount));
+
+        Ok(())
+    }
+
+    // User cancels a cash-out the agent has not confirmed yet and gets the held amount back
+    pub fn cancel_cash_out(env: Env, user_address: Address, cash_out_id: u64) -> Result<(), Error> {
+        user_address.require_auth();
+
+        let mut cash_outs: Map<u64, CashOut> = env.storage().instance().get(&DataKey::CashOuts).unwrap_or(Map::new(&env));
+        let mut cash_out = cash_outs.get(cash_out_id).ok_or(Error::CashOutNotFound)?;
+
+        if cash_out.user_address != user_address {
+            return Err(Error::Unauthorized);
+        }
+        if cash_out.status != String::from_str(&env, "pending") {
+            return Err(Error::CashOutNotPending);
+        }
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+        user.balance = Self::checked_credit(user.balance, cash_out.amount)?;
+        Self::track_balance(&env, &user, cash_out.amount, Bucket::Escrowed);
+        users.set(user_address.clone(), user);
+        users.save();
+
+        cash_out.status = String::from_str(&env, "cancelled");
+        cash_outs.set(cash_out_id, cash_out.clone());
+        env.storage().instance().set(&DataKey::CashOuts, &cash_outs);
+
+        env.events().publish((symbol_short!("cash_end"), user_address, cash_out.agent), (cash_out_id, cash_out.amount));
+
+        Ok(())
+    }
+
+    // Release a cash-out the agent did not confirm within the window back to the user. Callable by anyone.
+    pub fn release_expired_cash_out(env: Env, cash_out_id: u64) -> Result<(), Error> {
+        let mut cash_outs: Map<u64, CashOut> = env.storage().instance().get(&DataKey::CashOuts).unwrap_or(Map::new(&env));
+        let mut cash_out = cash_outs.get(cash_out_id).ok_or(Error::CashOutNotFound)?;
+
+        if cash_out.status != String::from_str(&env, "pending") {
+            return Err(Error::CashOutNotPending);
+        }
+        if env.ledger().timestamp() <= cash_out.expires_at {
+            return Err(Self::fail_until(&env, &cash_out.user_address, symbol_short!("cash_rel"), symbol_short!("not_due"), cash_out.expires_at + 1, Error::CashOutHasNotExpired));
+        }
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(cash_out.user_address.clone()).ok_or(Error::UserNotFound)?;
+        user.balance = Self::checked_credit(user.balance, cash_out.amount)?;
+        Self::track_balance(&env, &user, cash_out.amount, Bucket::Escrowed);
+        users.set(cash_out.user_address.clone(), user);
+        users.save();
+
+        cash_out.status = String::from_str(&env, "expired");
+        cash_outs.set(cash_out_id, cash_out.clone());
+        env.storage().instance().set(&DataKey::CashOuts, &cash_outs);
+
+        env.events().publish((symbol_short!("cash_exp"), cash_out.user_address, cash_out.agent), (cash_out_id, cash_out.amount));
+
+        Ok(())
+    }
+
+    // Set how long agents have to confirm a cash-out (admin only)
+    pub fn set_cash_out_window(env: Env, window_secs: u64) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("cash_win"), AuditTarget::Global, window_secs as i128);
+
+        if window_secs == 0 {
+            return Err(Error::InvalidWindow);
+        }
+
+        env.storage().instance().set(&ConfigKey::CashOutWindow, &window_secs);
+
+        Ok(())
+    }
+
+    // Move an agent's earned commission into their float, paid out of collected fees
+    pub fn claim_agent_commission(env: Env, agent: Address) -> Result<i128, Error> {
+        agent.require_auth();
+
+        let mut agents: Map<Address, Agent> = env.storage().instance().get(&DataKey::Agents).unwrap_or(Map::new(&env));
+        let mut record = agents.get(agent.clone()).ok_or(Error::AgentNotFound)?;
+
+        let commission = record.commission_earned;
+        if commission <= 0 {
+            return Err(Error::NoCommissionToClaim);
+        }
+
+        let fees: i128 = env.storage().instance().get(&CounterKey::Fees).unwrap_or(0);
+        if fees < commission {
+            return Err(Self::fail(&env, &agent, symbol_short!("agt_claim"), symbol_short!("pool"), commission, fees, Error::InsufficientFeesToPayCommission));
+        }
+
+        record.commission_earned = 0;
+        record.float_balance = Self::checked_credit(record.float_balance, commission)?;
+        agents.set(agent.clone(), record);
+        env.storage().instance().set(&DataKey::Agents, &agents);
+        env.storage().instance().set(&CounterKey::Fees, &(fees - commission));
+        Self::post(&env, &agent, Bucket::Treasury, Bucket::External, commission);
+
+        env.events().publish((symbol_short!("agt_claim"), agent), commission);
+
+        Ok(commission)
+    }
+
+    // Get an agent's float and commission record
+    pub fn get_agent(env: Env, agent: Address) -> Result<Agent, Error> {
+        let agents: Map<Address, Agent> = env.storage().instance().get(&DataKey::Agents).unwrap_or(Map::new(&env));
+
+        agents.get(agent).ok_or(Error::AgentNotFound)
+    }
+
+    // Get cash-outs requested by a user
+    pub fn get_cash_outs(env: Env, user_address: Address, cursor: u32, limit: u32) -> (Vec<CashOut>, Option<u32>) {
+        Self::page(Self::cash_outs_for(&env, &user_address), cursor, limit)
+    }
+
+    // Register a user as a merchant with its fee rate and fee mode (admin only)
+    pub fn register_merchant(env: Env, merchant: Address, name: String, fee_bps: u32, accrual: bool) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("merch_add"), AuditTarget::Address(merchant.clone()), fee_bps as i128);
+
+        if fee_bps as i128 > BPS_DENOMINATOR {
+            return Err(Error::InvalidRate);
+        }
+
+        let users = Users::load(&env);
+        if !users.contains_key(merchant.clone()) {
+            return Err(Error::UserNotFound);
+        }
+
+        let mut merchants: Map<Address, Merchant> = env.storage().instance().get(&DataKey::Merchants).unwrap_or(Map::new(&env));
+        if merchants.contains_key(merchant.clone()) {
+            return Err(Error::MerchantAlreadyExists);
+        }
+
+        merchants.set(merchant.clone(), Merchant {
+            address: merchant.clone(),
+            name,
+            fee_bps,
+            accrual,
+            active: true,
+        });
+        env.storage().instance().set(&DataKey::Merchants, &merchants);
+
+        env.events().publish((symbol_short!("mrch_new"), merchant), (fee_bps, accrual));
+
+        Ok(())
+    }
+
+    // Switch a merchant between per-payment fee deduction and accrual invoicing (admin only)
+    pub fn set_merchant_fee_mode(env: Env, merchant: Address, accrual: bool) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("merch_fee"), AuditTarget::Address(merchant.clone()), accrual as i128);
+
+        let mut merchants: Map<Address, Merchant> = env.storage().instance().get(&DataKey::Merchants).unwrap_or(Map::new(&env));
+        let mut record = merchants.get(merchant.clone()).ok_or(Error::MerchantNotFound)?;
+        record.accrual = accrual;
+        merchants.set(merchant, record);
+        env.storage().instance().set(&DataKey::Merchants, &merchants);
+
+        Ok(())
+    }
+
+    // Pay a merchant. In per-payment mode the fee is deducted from the merchant's proceeds
+    // immediately; in accrual mode it is added to the current period's statement.
+    pub fn pay_merchant(
+        env: Env,
+        user_address: Address,
+        merchant: Address,
+        amount: i128,
+        fee_bearer: FeeBearer,
+    ) -> Result<(), Error> {
+        user_address.require_auth();
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        Self::ensure_module_active(&env, &user_address, symbol_short!("merchants"))?;
+        Self::ensure_not_restricted(&env, &user_address, symbol_short!("pay_mrch"))?;
+        Self::check_subaccount(&env, &user_address, symbol_short!("pay_mrch"), amount)?;
+        Self::ensure_not_restricted(&env, &merchant, symbol_short!("pay_mrch"))?;
+
+        let merchants: Map<Address, Merchant> = env.storage().instance().get(&DataKey::Merchants).unwrap_or(Map::new(&env));
+        let record = merchants.get(merchant.clone()).ok_or(Error::MerchantNotFound)?;
+        if !record.active {
+            return Err(Error::MerchantNotActive);
+        }
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+
+        let fee = amount * record.fee_bps as i128 / BPS_DENOMINATOR;
+        let quote = Self::split_fee(amount, fee, &fee_bearer);
+        let payer_fee = quote.payer_pays - amount;
+        let payee_fee = amount - quote.payee_receives;
+
+        if user.balance < quote.payer_pays {
+            return Err(Self::fail(&env, &user_address, symbol_short!("pay_mrch"), symbol_short!("balance"), quote.payer_pays, user.balance, Error::InsufficientBalance));
+        }
+
+        // In accrual mode the merchant's share of the fee is invoiced later instead of withheld now
+        let proceeds = if record.accrual { amount } else { quote.payee_receives };
+        let collected = if record.accrual { payer_fee } else { fee };
+
+        user.balance = Self::checked_debit(user.balance, quote.payer_pays)?;
+        Self::track_balance(&env, &user, -quote.payer_pays, Bucket::Clearing);
+        users.set(user_address.clone(), user);
+
+        let mut merchant_user = users.get(merchant.clone()).ok_or(Error::MerchantNotFound)?;
+        merchant_user.balance = Self::checked_credit(merchant_user.balance, proceeds)?;
+        Self::track_balance(&env, &merchant_user, proceeds, Bucket::Clearing);
+        users.set(merchant.clone(), merchant_user);
+        users.save();
+
+        let period = env.ledger().timestamp() / FEE_PERIOD_SECS;
+        let mut statements: Map<(Address, u64), FeeStatement> = env.storage().instance().get(&DataKey::FeeStatements).unwrap_or(Map::new(&env));
+        let mut statement = statements.get((merchant.clone(), period)).unwrap_or(FeeStatement {
+            merchant: merchant.clone(),
+            period,
+            payment_count: 0,
+            volume: 0,
+            fees: 0,
+            settled: 0,
+        });
+        statement.payment_count += 1;
+        statement.volume += amount;
+        statement.fees += payer_fee + payee_fee;
+        statement.settled += collected;
+        if collected > 0 {
+            Self::collect_fee(&env, &merchant, Bucket::Clearing, collected);
+        }
+        statements.set((merchant.clone(), period), statement);
+        env.storage().instance().set(&DataKey::FeeStatements, &statements);
+
+        env.events().publish((symbol_short!("pay_mrch"), user_address, merchant), (amount, fee));
+
+        Ok(())
+    }
+
+    // Settle a closed period's accrued fees from the merchant's balance (treasurer only).
+    // If the balance does not cover the invoice, what is available is collected and the rest stays outstanding.
+    pub fn settle_merchant_fees(env: Env, caller: Address, merchant: Address, period: u64) -> Result<i128, Error> {
+        Self::require_role(&env, &caller, Role::Treasurer)?;
+        Self::audit(&env, &caller, symbol_short!("merch_set"), AuditTarget::Address(merchant.clone()), period as i128);
+
+        if period >= env.ledger().timestamp() / FEE_PERIOD_SECS {
+            return Err(Error::FeePeriodStillOpen);
+        }
+
+        let mut statements: Map<(Address, u64), FeeStatement> = env.storage().instance().get(&DataKey::FeeStatements).unwrap_or(Map::new(&env));
+        let mut statement = statements.get((merchant.clone(), period)).ok_or(Error::FeeStatementNotFound)?;
+
+        let outstanding = statement.fees - statement.settled;
+        if outstanding <= 0 {
+            return Err(Error::FeeStatementAlreadySettled);
+        }
+
+        let mut users = Users::load(&env);
+        let mut merchant_user = users.get(merchant.clone()).ok_or(Error::MerchantNotFound)?;
+
+        let collected = if merchant_user.balance < outstanding { merchant_user.balance } else { outstanding };
+        if collected <= 0 {
+            return Err(Error::InsufficientBalance);
+        }
+
+        merchant_user.balance = Self::checked_debit(merchant_user.balance, collected)?;
+        Self::track_balance(&env, &merchant_user, -collected, Bucket::Clearing);
+        users.set(merchant.clone(), merchant_user);
+        users.save();
+
+        statement.settled += collected;
+        statements.set((merchant.clone(), period), statement);
+        env.storage().instance().set(&DataKey::FeeStatements, &statements);
+
+        Self::collect_fee(&env, &merchant, Bucket::Clearing, collected);
+
+        env.events().publish((symbol_short!("fee_settl"), merchant), (period, collected));
+
+        Ok(collected)
+    }
+
+    // Get a merchant's fee statements for periods `from_period..=to_period`
+    pub fn get_fee_statements(env: Env, merchant: Address, from_period: u64, to_period: u64, cursor: u32, limit: u32) -> (Vec<FeeStatement>, Option<u32>) {
+        let statements: Map<(Address, u64), FeeStatement> = env.storage().instance().get(&DataKey::FeeStatements).unwrap_or(Map::new(&env));
+        let mut merchant_statements = vec![&env];
+
+        for (_, statement) in statements.iter() {
+            if statement.merchant == merchant && statement.period >= from_period && statement.period <= to_period {
+                merchant_statements.push_back(statement);
+            }
+        }
+
+        Self::page(merchant_statements, cursor, limit)
+    }
+
+    // Get a merchant's configuration
+    pub fn get_merchant(env: Env, merchant: Address) -> Result<Merchant, Error> {
+        let merchants: Map<Address, Merchant> = env.storage().instance().get(&DataKey::Merchants).unwrap_or(Map::new(&env));
+
+        merchants.get(merchant).ok_or(Error::MerchantNotFound)
+    }
+
+    // Set the P2P transfer fee in basis points (admin only)
+    pub fn set_transfer_fee(env: Env, fee_bps: u32) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::require_council_approval(&env)?;
+        Self::audit(&env, &admin, symbol_short!("xfer_fee"), AuditTarget::Global, fee_bps as i128);
+
+        if fee_bps as i128 > BPS_DENOMINATOR {
+            return Err(Error::InvalidRate);
+        }
+
+        env.storage().instance().set(&ConfigKey::TransferFeeBps, &fee_bps);
+
+        Ok(())
+    }
+
+    // Quote what each side pays and receives for a P2P transfer
+    pub fn quote_transfer(env: Env, amount: i128, fee_bearer: FeeBearer) -> FeeQuote {
+        let fee_bps: u32 = env.storage().instance().get(&ConfigKey::TransferFeeBps).unwrap_or(0);
+        let fee = amount * fee_bps as i128 / BPS_DENOMINATOR;
+
+        Self::split_fee(amount, fee, &fee_bearer)
+    }
+
+    // Quote what each side pays and receives for a merchant payment
+    pub fn quote_merchant_payment(env: Env, merchant: Address, amount: i128, fee_bearer: FeeBearer) -> Result<FeeQuote, Error> {
+        let merchants: Map<Address, Merchant> = env.storage().instance().get(&DataKey::Merchants).unwrap_or(Map::new(&env));
+        let record = merchants.get(merchant).ok_or(Error::MerchantNotFound)?;
+        let fee = amount * record.fee_bps as i128 / BPS_DENOMINATOR;
+
+        Ok(Self::split_fee(amount, fee, &fee_bearer))
+    }
+
+    // Get P2P transfers a user sent or received
+    pub fn get_transfers(env: Env, user_address: Address, cursor: u32, limit: u32) -> (Vec<Transfer>, Option<u32>) {
+        let (transfer_ids, next_cursor) = Self::page(Self::transfer_index(&env, &user_address), cursor, limit);
+
+        (Self::load_transfers(&env, transfer_ids), next_cursor)
+    }
+
+    // Set the smallest incoming payment a user accepts; zero removes the minimum
+    pub fn set_min_incoming(env: Env, user_address: Address, min_amount: i128) -> Result<(), Error> {
+        user_address.require_auth();
+
+        if min_amount < 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        let mut minimums: Map<Address, i128> = env.storage().instance().get(&ComplianceKey::MinIncoming).unwrap_or(Map::new(&env));
+        if min_amount == 0 {
+            minimums.remove(user_address);
+        } else {
+            minimums.set(user_address, min_amount);
+        }
+        env.storage().instance().set(&ComplianceKey::MinIncoming, &minimums);
+
+        Ok(())
+    }
+
+    // Get the smallest incoming payment a user accepts
+    pub fn get_min_incoming(env: Env, user_address: Address) -> i128 {
+        let minimums: Map<Address, i128> = env.storage().instance().get(&ComplianceKey::MinIncoming).unwrap_or(Map::new(&env));
+
+        minimums.get(user_address).unwrap_or(0)
+    }
+
+    // Allow or stop allowing a sender to bypass the user's incoming minimum
+    pub fn set_sender_whitelisted(env: Env, user_address: Address, sender: Address, whitelisted: bool) -> Result<(), Error> {
+        user_address.require_auth();
+
+        let mut whitelist: Map<(Address, Address), bool> = env.storage().instance().get(&ComplianceKey::MinIncomingWhitelist).unwrap_or(Map::new(&env));
+        if whitelisted {
+            whitelist.set((user_address, sender), true);
+        } else {
+            whitelist.remove((user_address, sender));
+        }
+        env.storage().instance().set(&ComplianceKey::MinIncomingWhitelist, &whitelist);
+
+        Ok(())
+    }
+
+    // Freeze an account: it can no longer send, pay bills, withdraw or receive direct transfers
+    // (compliance only)
+    pub fn freeze_account(env: Env, caller: Address, user_address: Address) -> Result<(), Error> {
+        Self::set_compliance_flag(&env, caller, user_address, symbol_short!("frozen"), true)
+    }
+
+    // Lift a freeze on an account (compliance only)
+    pub fn unfreeze_account(env: Env, caller: Address, user_address: Address) -> Result<(), Error> {
+        Self::set_compliance_flag(&env, caller, user_address, symbol_short!("frozen"), false)
+    }
+
+    // Add a sanctioned address to the blacklist; restrictions match a frozen account (compliance only)
+    pub fn blacklist_address(env: Env, caller: Address, address: Address) -> Result<(), Error> {
+        Self::set_compliance_flag(&env, caller, address, symbol_short!("blacklist"), true)
+    }
+
+    // Remove an address from the blacklist (compliance only)
+    pub fn unblacklist_address(env: Env, caller: Address, address: Address) -> Result<(), Error> {
+        Self::set_compliance_flag(&env, caller, address, symbol_short!("blacklist"), false)
+    }
+
+    // Check whether an account is frozen
+    pub fn is_frozen(env: Env, user_address: Address) -> bool {
+        let frozen: Map<Address, bool> = env.storage().instance().get(&ComplianceKey::Frozen).unwrap_or(Map::new(&env));
+
+        frozen.contains_key(user_address)
+    }
+
+    // Check whether an address is blacklisted
+    pub fn is_blacklisted(env: Env, address: Address) -> bool {
+        let blacklist: Map<Address, bool> = env.storage().instance().get(&ComplianceKey::Blacklist).unwrap_or(Map::new(&env));
+
+        blacklist.contains_key(address)
+    }
+
+    // Configure how a user screens small first-time transfers from unknown senders
+    pub fn set_spam_screening(env: Env, user_address: Address, mode: ScreeningMode, threshold: i128) -> Result<(), Error> {
+        user_address.require_auth();
+
+        if threshold < 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        let mut policies: Map<Address, ScreeningPolicy> = env.storage().instance().get(&ComplianceKey::Screening).unwrap_or(Map::new(&env));
+        if mode == ScreeningMode::Off {
+            policies.remove(user_address);
+        } else {
+            policies.set(user_address, ScreeningPolicy { mode, threshold });
+        }
+        env.storage().instance().set(&ComplianceKey::Screening, &policies);
+
+        Ok(())
+    }
+
+    // Get a user's spam screening policy
+    pub fn get_spam_screening(env: Env, user_address: Address) -> ScreeningPolicy {
+        let policies: Map<Address, ScreeningPolicy> = env.storage().instance().get(&ComplianceKey::Screening).unwrap_or(Map::new(&env));
+
+        policies.get(user_address).unwrap_or(ScreeningPolicy { mode: ScreeningMode::Off, threshold: 0 })
+    }
+
+    // Get transfers held in quarantine for a user
+    pub fn get_quarantined(env: Env, user_address: Address, cursor: u32, limit: u32) -> (Vec<QuarantinedTransfer>, Option<u32>) {
+        Self::page(Self::quarantined_for(&env, &user_address), cursor, limit)
+    }
+
+    // Accept a quarantined transfer into the user's balance; the sender becomes known
+    pub fn accept_quarantined(env: Env, user_address: Address, transfer_id: u64) -> Result<(), Error> {
+        Self::review_quarantined(&env, user_address, transfer_id, true)
+    }
+
+    // Return a quarantined transfer to its sender
+    pub fn return_quarantined(env: Env, user_address: Address, transfer_id: u64) -> Result<(), Error> {
+        Self::review_quarantined(&env, user_address, transfer_id, false)
+    }
+
+    // Process up to `MAX_CLAIMS_PER_CALL` pending claimable items of the selected kinds,
+    // reporting the outcome of each item
+    pub fn claim_all(env: Env, user_address: Address, kinds: Vec<ClaimKind>) -> Vec<ClaimResult> {
+        user_address.require_auth();
+
+        let now = env.ledger().timestamp();
+        let pending = String::from_str(&env, "pending");
+        let mut results = vec![&env];
+
+        for kind in kinds.iter() {
+            match kind {
+                ClaimKind::Scheduled => {
+                    let scheduled: Map<u64, ScheduledPayment> = env.storage().instance().get(&DataKey::Scheduled).unwrap_or(Map::new(&env));
+                    for (schedule_id, payment) in scheduled.iter() {
+                        if results.len() >= MAX_CLAIMS_PER_CALL {
+                            break;
+                        }
+                        if payment.to_address != user_address || payment.status != pending || payment.execute_at > now {
+                            continue;
+                        }
+                        let success = Self::execute_scheduled(env.clone(), schedule_id).is_ok();
+                        results.push_back(ClaimResult { kind: ClaimKind::Scheduled, reference: schedule_id, amount: payment.amount, success });
+                    }
+                }
+                ClaimKind::Stream => {
+                    if results.len() >= MAX_CLAIMS_PER_CALL {
+                        break;
+                    }
+                    let (amount, success) = match Self::withdraw_from_stream(env.clone(), user_address.clone()) {
+                        Ok(amount) => (amount, true),
+                        Err(_) => (0, false),
+                    };
+                    results.push_back(ClaimResult { kind: ClaimKind::Stream, reference: 0, amount, success });
+                }
+                ClaimKind::CashOut => {
+                    for cash_out in Self::cash_outs_for(&env, &user_address).iter() {
+                        if results.len() >= MAX_CLAIMS_PER_CALL {
+                            break;
+                        }
+                        if cash_out.status != pending || cash_out.expires_at >= now {
+                            continue;
+                        }
+                        let success = Self::release_expired_cash_out(env.clone(), cash_out.id).is_ok();
+                        results.push_back(ClaimResult { kind: ClaimKind::CashOut, reference: cash_out.id, amount: cash_out.amount, success });
+                    }
+                }
+                ClaimKind::Quarantine => {
+                    for transfer in Self::quarantined_for(&env, &user_address).iter() {
+                        if results.len() >= MAX_CLAIMS_PER_CALL {
+                            break;
+                        }
+                        let success = Self::accept_quarantined(env.clone(), user_address.clone(), transfer.transfer_id).is_ok();
+                        results.push_back(ClaimResult { kind: ClaimKind::Quarantine, reference: transfer.transfer_id, amount: transfer.amount, success });
+                    }
+                }
+                ClaimKind::Rewards => {
+                    if results.len() >= MAX_CLAIMS_PER_CALL {
+                        break;
+                    }
+                    let (amount, success) = match Self::redeem_rewards(env.clone(), user_address.clone()) {
+                        Ok(amount) => (amount, true),
+                        Err(_) => (0, false),
+                    };
+                    results.push_back(ClaimResult { kind: ClaimKind::Rewards, reference: 0, amount, success });
+                }
+            }
+        }
+
+        results
+    }
+
+    // Replace the contract code, keeping all storage (admin only). Call `migrate` afterwards.
+    pub fn upgrade(env: Env, new_wasm_hash: BytesN<32>) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::require_council_approval(&env)?;
+        Self::audit(&env, &admin, symbol_short!("upgrade"), AuditTarget::Global, 0);
+
+        env.deployer().update_current_contract_wasm(new_wasm_hash.clone());
+        env.events().publish((symbol_short!("upgrade"),), new_wasm_hash);
+
+        Ok(())
+    }
+
+    // Get the storage layout version currently in place
+    pub fn version(env: Env) -> u32 {
+        env.storage()
+            .instance()
+            .get(&ConfigKey::Version)
+            .or_else(|| env.storage().instance().get(&symbol_short!("version")))
+            .unwrap_or(0)
+    }
+
+    // Bring storage written by an older version up to `CONTRACT_VERSION` (admin only)
+    pub fn migrate(env: Env) -> Result<u32, Error> {
+        // Before v5 the admin is still under its legacy key
+        let admin: Address = env
+            .storage()
+            .instance()
+            .get(&ConfigKey::Admin)
+            .or_else(|| env.storage().instance().get(&symbol_short!("admin")))
+            .unwrap();
+        admin.require_auth();
+
+        let mut version = Self::version(env.clone());
+        if version >= CONTRACT_VERSION {
+            return Err(Error::AlreadyMigrated);
+        }
+
+        // Every step reads through the typed keys, so move the legacy keys before anything else
+        if version < 5 {
+            Self::migrate_storage_keys(&env);
+        }
+        Self::audit(&env, &admin, symbol_short!("migrate"), AuditTarget::Global, 0);
+
+        if version == 0 {
+            Self::migrate_v0_to_v1(&env);
+            version = 1;
+        }
+        if version == 1 {
+            Self::migrate_v1_to_v2(&env);
+            version = 2;
+        }
+        if version == 2 {
+            Self::migrate_v2_to_v3(&env);
+            version = 3;
+        }
+        if version == 3 {
+            Self::migrate_v3_to_v4(&env);
+            version = 4;
+        }
+        if version == 4 {
+            // Keys were relocated above
+            version = 5;
+        }
+        if version == 5 {
+            Self::migrate_v5_to_v6(&env);
+            version = 6;
+        }
+        if version == 6 {
+            Self::migrate_v6_to_v7(&env);
+            version = 7;
+        }
+        if version == 7 {
+            Self::migrate_v7_to_v8(&env);
+            version = 8;
+        }
+        if version == 8 {
+            Self::migrate_v8_to_v9(&env);
+            version = 9;
+        }
+        if version == 9 {
+            Self::migrate_v9_to_v10(&env);
+            version = 10;
+        }
+        if version == 10 {
+            Self::migrate_v10_to_v11(&env);
+            version = 11;
+        }
+        if version == 11 {
+            Self::migrate_v11_to_v12(&env);
+            version = 12;
+        }
+        if version == 12 {
+            Self::migrate_v12_to_v13(&env);
+            version = 13;
+        }
+        if version == 13 {
+            Self::migrate_v13_to_v14(&env);
+            version = 14;
+        }
+        if version == 14 {
+            Self::migrate_v14_to_v15(&env);
+            version = 15;
+        }
+        if version == 15 {
+            Self::migrate_v15_to_v16(&env);
+            version = 16;
+        }
+        if version == 16 {
+            Self::migrate_v16_to_v17(&env);
+            version = 17;
+        }
+        if version == 17 {
+            Self::migrate_v17_to_v18(&env);
+            version = 18;
+        }
+        if version == 18 {
+            Self::migrate_v18_to_v19(&env);
+            version = 19;
+        }
+
+        env.storage().instance().set(&ConfigKey::Version, &version);
+        env.events().publish((symbol_short!("migrate"),), version);
+
+        Ok(version)
+    }
+
+    // Configure the admin council and its approval threshold. The first council is set by the
+    // admin; afterwards changes go through a council proposal.
+    pub fn set_admins(env: Env, admins: Vec<Address>, threshold: u32) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::require_council_approval(&env)?;
+        Self::audit(&env, &admin, symbol_short!("admins"), AuditTarget::Global, threshold as i128);
+
+        if threshold == 0 || threshold > admins.len() {
+            return Err(Error::InvalidThreshold);
+        }
+
+        env.storage().instance().set(&ConfigKey::Council, &admins);
+        env.storage().instance().set(&ConfigKey::CouncilThreshold, &threshold);
+
+        env.events().publish((symbol_short!("council"),), (admins, threshold));
+
+        Ok(())
+    }
+
+    // Get the admin council and its approval threshold
+    pub fn get_admins(env: Env) -> (Vec<Address>, u32) {
+        (
+            env.storage().instance().get(&ConfigKey::Council).unwrap_or(vec![&env]),
+            env.storage().instance().get(&ConfigKey::CouncilThreshold).unwrap_or(0),
+        )
+    }
+
+    // Propose a sensitive admin action; the proposer's approval is counted immediately
+    pub fn propose_admin_action(env: Env, signer: Address, action: AdminAction) -> Result<u64, Error> {
+        signer.require_auth();
+        Self::require_council_member(&env, &signer)?;
+
+        let mut proposals: Map<u64, AdminProposal> = env.storage().instance().get(&DataKey::Proposals).unwrap_or(Map::new(&env));
+        let proposal_id: u64 = env.storage().instance().get(&CounterKey::ProposalId).unwrap_or(0) + 1;
+
+        let proposal = AdminProposal {
+            id: proposal_id,
+            action,
+            proposer: signer.clone(),
+            approvals: vec![&env, signer.clone()],
+            executed: false,
+            created_at: env.ledger().timestamp(),
+        };
+
+        proposals.set(proposal_id, proposal);
+        env.storage().instance().set(&DataKey::Proposals, &proposals);
+        env.storage().instance().set(&CounterKey::ProposalId, &proposal_id);
+
+        env.events().publish((symbol_short!("proposed"), signer), proposal_id);
+
+        Self::execute_if_approved(&env, proposal_id)?;
+
+        Ok(proposal_id)
+    }
+
+    // Approve a pending proposal; it executes as soon as the threshold is reached
+    pub fn approve_admin_action(env: Env, signer: Address, proposal_id: u64) -> Result<(), Error> {
+        signer.require_auth();
+        Self::require_council_member(&env, &signer)?;
+
+        let mut proposals: Map<u64, AdminProposal> = env.storage().instance().get(&DataKey::Proposals).unwrap_or(Map::new(&env));
+        let mut proposal = proposals.get(proposal_id).ok_or(Error::ProposalNotFound)?;
+
+        if proposal.executed {
+            return Err(Error::ProposalAlreadyExecuted);
+        }
+        if proposal.approvals.contains(signer.clone()) {
+            return Err(Error::AlreadyApproved);
+        }
+
+        proposal.approvals.push_back(signer.clone());
+        proposals.set(proposal_id, proposal);
+        env.storage().instance().set(&DataKey::Proposals, &proposals);
+
+        env.events().publish((symbol_short!("approved"), signer), proposal_id);
+
+        Self::execute_if_approved(&env, proposal_id)
+    }
+
+    // Get an admin proposal
+    pub fn get_proposal(env: Env, proposal_id: u64) -> Result<AdminProposal, Error> {
+        let proposals: Map<u64, AdminProposal> = env.storage().instance().get(&DataKey::Proposals).unwrap_or(Map::new(&env));
+
+        proposals.get(proposal_id).ok_or(Error::ProposalNotFound)
+    }
+
+    // Move collected fees to a user's balance, e.g. the treasury account (treasurer only)
+    pub fn sweep_fees(env: Env, caller: Address, to_address: Address, amount: i128) -> Result<(), Error> {
+        Self::require_role(&env, &caller, Role::Treasurer)?;
+        Self::require_council_approval(&env)?;
+        Self::audit(&env, &caller, symbol_short!("sweep"), AuditTarget::Address(to_address.clone()), amount);
+
+        let fees: i128 = env.storage().instance().get(&CounterKey::Fees).unwrap_or(0);
+        if amount <= 0 || amount > fees {
+            return Err(Error::InvalidAmount);
+        }
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(to_address.clone()).ok_or(Error::UserNotFound)?;
+        user.balance = Self::checked_credit(user.balance, amount)?;
+        Self::track_balance(&env, &user, amount, Bucket::Treasury);
+        users.set(to_address.clone(), user);
+        users.save();
+
+        env.storage().instance().set(&CounterKey::Fees, &(fees - amount));
+
+        env.events().publish((symbol_short!("sweep"), to_address), amount);
+
+        Ok(())
+    }
+
+    // Add or replace a supported biller (admin only)
+    pub fn set_bill_provider(env: Env, provider: BillProvider) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("biller"), AuditTarget::Id(provider.id.clone()), 0);
+
+        if provider.min_amount <= 0 || provider.max_amount < provider.min_amount {
+            return Err(Error::InvalidAmountLimits);
+        }
+        if provider.account_min_len == 0 || provider.account_max_len < provider.account_min_len {
+            return Err(Error::InvalidAccountNumberRules);
+        }
+        match &provider.amount_policy {
+            AmountPolicy::Range => {}
+            AmountPolicy::Exact(amounts) => {
+                if amounts.is_empty() || amounts.iter().any(|a| a < provider.min_amount || a > provider.max_amount) {
+                    return Err(Error::InvalidAmountPolicy);
+                }
+            }
+            AmountPolicy::Multiple(step) => {
+                if *step <= 0 {
+                    return Err(Error::InvalidAmountPolicy);
+                }
+            }
+        }
+
+        let mut providers: Map<String, BillProvider> = env.storage().instance().get(&ConfigKey::Providers).unwrap_or(Map::new(&env));
+        providers.set(provider.id.clone(), provider.clone());
+        env.storage().instance().set(&ConfigKey::Providers, &providers);
+
+        env.events().publish((symbol_short!("provider"), provider.id), provider.active);
+
+        Ok(())
+    }
+
+    // Enable or disable a biller (pauser only)
+    pub fn set_bill_provider_active(env: Env, caller: Address, provider_id: String, active: bool) -> Result<(), Error> {
+        Self::require_role(&env, &caller, Role::Pauser)?;
+        Self::audit(&env, &caller, symbol_short!("biller_on"), AuditTarget::Id(provider_id.clone()), active as i128);
+
+        let mut providers: Map<String, BillProvider> = env.storage().instance().get(&ConfigKey::Providers).unwrap_or(Map::new(&env));
+        let mut provider = providers.get(provider_id.clone()).ok_or(Error::BillProviderNotFound)?;
+        provider.active = active;
+        providers.set(provider_id.clone(), provider);
+        env.storage().instance().set(&ConfigKey::Providers, &providers);
+
+        env.events().publish((symbol_short!("provider"), provider_id), active);
+
+        Ok(())
+    }
+
+    // Get all registered billers
+    pub fn get_bill_providers(env: Env, cursor: u32, limit: u32) -> (Vec<BillProvider>, Option<u32>) {
+        let providers: Map<String, BillProvider> = env.storage().instance().get(&ConfigKey::Providers).unwrap_or(Map::new(&env));
+
+        Self::page(providers.values(), cursor, limit)
+    }
+
+    // Add or replace a telco in the top-up catalog (admin only)
+    pub fn set_telco(env: Env, telco: Telco) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("telco"), AuditTarget::Key(telco.id.clone()), 0);
+
+        if telco.min_airtime <= 0 || telco.max_airtime < telco.min_airtime {
+            return Err(Error::InvalidAmountLimits);
+        }
+
+        let mut telcos: Map<Symbol, Telco> = env.storage().instance().get(&ConfigKey::Telcos).unwrap_or(Map::new(&env));
+        telcos.set(telco.id.clone(), telco);
+        env.storage().instance().set(&ConfigKey::Telcos, &telcos);
+
+        Ok(())
+    }
+
+    // Add or replace a data bundle in the top-up catalog (admin only)
+    pub fn set_data_bundle(env: Env, bundle: DataBundle) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("bundle"), AuditTarget::Key(bundle.telco.clone()), bundle.id as i128);
+
+        if bundle.price <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        let telcos: Map<Symbol, Telco> = env.storage().instance().get(&ConfigKey::Telcos).unwrap_or(Map::new(&env));
+        if !telcos.contains_key(bundle.telco.clone()) {
+            return Err(Error::TelcoNotFound);
+        }
+
+        let mut bundles: Map<u32, DataBundle> = env.storage().instance().get(&ConfigKey::Bundles).unwrap_or(Map::new(&env));
+        bundles.set(bundle.id, bundle);
+        env.storage().instance().set(&ConfigKey::Bundles, &bundles);
+
+        Ok(())
+    }
+
+    // Get the telco catalog
+    pub fn get_telcos(env: Env, cursor: u32, limit: u32) -> (Vec<Telco>, Option<u32>) {
+        let telcos: Map<Symbol, Telco> = env.storage().instance().get(&ConfigKey::Telcos).unwrap_or(Map::new(&env));
+
+        Self::page(telcos.values(), cursor, limit)
+    }
+
+    // Get the active data bundles offered by a telco
+    pub fn get_data_bundles(env: Env, telco: Symbol, cursor: u32, limit: u32) -> (Vec<DataBundle>, Option<u32>) {
+        let bundles: Map<u32, DataBundle> = env.storage().instance().get(&ConfigKey::Bundles).unwrap_or(Map::new(&env));
+        let mut telco_bundles = vec![&env];
+
+        for (_, bundle) in bundles.iter() {
+            if bundle.telco == telco && bundle.active {
+                telco_bundles.push_back(bundle);
+            }
+        }
+
+        Self::page(telco_bundles, cursor, limit)
+    }
+
+    // Buy airtime for a phone number; fulfilled off-chain from the `airtime` event
+    pub fn buy_airtime(env: Env, user_address: Address, telco: Symbol, phone: String, amount: i128) -> Result<u64, Error> {
+        user_address.require_auth();
+        Self::ensure_module_active(&env, &user_address, symbol_short!("topups"))?;
+        Self::ensure_not_restricted(&env, &user_address, symbol_short!("airtime"))?;
+
+        let telcos: Map<Symbol, Telco> = env.storage().instance().get(&ConfigKey::Telcos).unwrap_or(Map::new(&env));
+        let record = telcos.get(telco.clone()).ok_or(Error::TelcoNotFound)?;
+        if !record.active {
+            return Err(Error::TelcoNotActive);
+        }
+        if amount < record.min_airtime || amount > record.max_airtime {
+            return Err(Self::fail(&env, &user_address, symbol_short!("airtime"), symbol_short!("amount"), amount, record.max_airtime, Error::InvalidAirtimeAmount));
+        }
+
+        let topup_id = Self::record_topup(&env, &user_address, telco.clone(), phone.clone(), None, amount)?;
+        env.events().publish((symbol_short!("airtime"), user_address, telco), (topup_id, phone, amount));
+
+        Ok(topup_id)
+    }
+
+    // Buy a data bundle for a phone number; fulfilled off-chain from the `data` event
+    pub fn buy_data_bundle(env: Env, user_address: Address, telco: Symbol, phone: String, bundle_id: u32) -> Result<u64, Error> {
+        user_address.require_auth();
+        Self::ensure_module_active(&env, &user_address, symbol_short!("topups"))?;
+        Self::ensure_not_restricted(&env, &user_address, symbol_short!("data"))?;
+
+        let telcos: Map<Symbol, Telco> = env.storage().instance().get(&ConfigKey::Telcos).unwrap_or(Map::new(&env));
+        let record = telcos.get(telco.clone()).ok_or(Error::TelcoNotFound)?;
+        if !record.active {
+            return Err(Error::TelcoNotActive);
+        }
+
+        let bundles: Map<u32, DataBundle> = env.storage().instance().get(&ConfigKey::Bundles).unwrap_or(Map::new(&env));
+        let bundle = bundles.get(bundle_id).ok_or(Error::DataBundleNotFound)?;
+        if bundle.telco != telco || !bundle.active {
+            return Err(Error::DataBundleNotAvailable);
+        }
+
+        let topup_id = Self::record_topup(&env, &user_address, telco.clone(), phone.clone(), Some(bundle_id), bundle.price)?;
+        env.events().publish((symbol_short!("data"), user_address, telco), (topup_id, phone, bundle_id, bundle.price));
+
+        Ok(topup_id)
+    }
+
+    // Get a user's airtime purchases
+    pub fn get_airtime_history(env: Env, user_address: Address, cursor: u32, limit: u32) -> (Vec<TopUp>, Option<u32>) {
+        Self::page(Self::topups_for(&env, &user_address, false), cursor, limit)
+    }
+
+    // Get a user's data bundle purchases
+    pub fn get_data_history(env: Env, user_address: Address, cursor: u32, limit: u32) -> (Vec<TopUp>, Option<u32>) {
+        Self::page(Self::topups_for(&env, &user_address, true), cursor, limit)
+    }
+
+    // Pause a module (`transfers`, `bills`, `withdraw`, `agents`, `merchants`, `topups`, `savings`,
+    // `groups`, `scheduled`, `streams`, `swaps`, `loans`, `credit`, `remit`, `payroll`) or everything
+    // (`all`). `until` is the expected resumption time reported to clients as a retry hint; 0 keeps
+    // it paused until resumed (pauser only).
+    pub fn pause_module(env: Env, caller: Address, module: Symbol, until: u64) -> Result<(), Error> {
+        Self::require_role(&env, &caller, Role::Pauser)?;
+        Self::audit(&env, &caller, symbol_short!("pause"), AuditTarget::Key(module.clone()), until as i128);
+
+        let mut paused: Map<Symbol, u64> = env.storage().instance().get(&ConfigKey::Paused).unwrap_or(Map::new(&env));
+        paused.set(module.clone(), until);
+        env.storage().instance().set(&ConfigKey::Paused, &paused);
+
+        env.events().publish((symbol_short!("paused"), module), until);
+
+        Ok(())
+    }
+
+    // Resume a paused module (pauser only)
+    pub fn resume_module(env: Env, caller: Address, module: Symbol) -> Result<(), Error> {
+        Self::require_role(&env, &caller, Role::Pauser)?;
+        Self::audit(&env, &caller, symbol_short!("resume"), AuditTarget::Key(module.clone()), 0);
+
+        let mut paused: Map<Symbol, u64> = env.storage().instance().get(&ConfigKey::Paused).unwrap_or(Map::new(&env));
+        paused.remove(module.clone());
+        env.storage().instance().set(&ConfigKey::Paused, &paused);
+
+        env.events().publish((symbol_short!("resumed"), module), ());
+
+        Ok(())
+    }
+
+    // Get paused modules with their expected resumption time (0 = until resumed)
+    pub fn get_paused_modules(env: Env) -> Map<Symbol, u64> {
+        let paused: Map<Symbol, u64> = env.storage().instance().get(&ConfigKey::Paused).unwrap_or(Map::new(&env));
+        let now = env.ledger().timestamp();
+        let mut active = Map::new(&env);
+
+        for (module, until) in paused.iter() {
+            if until == 0 || until > now {
+                active.set(module, until);
+            }
+        }
+
+        active
+    }
+
+    // Publish an incident or maintenance banner for all clients (status updater only)
+    pub fn set_system_status(env: Env, caller: Address, code: Symbol, message_key: Symbol, until: u64) -> Result<(), Error> {
+        Self::require_role(&env, &caller, Role::StatusUpdater)?;
+        Self::audit(&env, &caller, symbol_short!("status"), AuditTarget::Key(code.clone()), until as i128);
+
+        let status = SystemStatus {
+            code: code.clone(),
+            message_key: message_key.clone(),
+            until,
+            updated_at: env.ledger().timestamp(),
+        };
+        env.storage().instance().set(&ConfigKey::SystemStatus, &status);
+
+        env.events().publish((symbol_short!("status"), code), (message_key, until));
+
+        Ok(())
+    }
+
+    // Get the current system status; expired banners read as `ok`
+    pub fn get_system_status(env: Env) -> SystemStatus {
+        let status: Option<SystemStatus> = env.storage().instance().get(&ConfigKey::SystemStatus);
+
+        match status {
+            Some(status) if status.until == 0 || status.until > env.ledger().timestamp() => status,
+            _ => SystemStatus {
+                code: symbol_short!("ok"),
+                message_key: symbol_short!("ok"),
+                until: 0,
+                updated_at: env.ledger().timestamp(),
+            },
+        }
+    }
+
+    // Add or replace a withdrawal rail (admin only)
+    pub fn set_withdrawal_rail(env: Env, rail: WithdrawalRail) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("rail"), AuditTarget::Id(rail.id.clone()), 0);
+
+        if rail.min_amount <= 0 || rail.max_amount < rail.min_amount {
+            return Err(Error::InvalidAmountLimits);
+        }
+        if rail.fee_bps as i128 > BPS_DENOMINATOR || rail.flat_fee < 0 {
+            return Err(Error::InvalidFee);
+        }
+
+        let mut rails: Map<String, WithdrawalRail> = env.storage().instance().get(&ConfigKey::Rails).unwrap_or(Map::new(&env));
+        rails.set(rail.id.clone(), rail.clone());
+        env.storage().instance().set(&ConfigKey::Rails, &rails);
+
+        env.events().publish((symbol_short!("rail"), rail.id), rail.enabled);
+
+        Ok(())
+    }
+
+    // Enable or disable a withdrawal rail, e.g. while a mobile money network is down (pauser only)
+    pub fn set_withdrawal_rail_enabled(env: Env, caller: Address, rail_id: String, enabled: bool) -> Result<(), Error> {
+        Self::require_role(&env, &caller, Role::Pauser)?;
+        Self::audit(&env, &caller, symbol_short!("rail_on"), AuditTarget::Id(rail_id.clone()), enabled as i128);
+
+        let mut rails: Map<String, WithdrawalRail> = env.storage().instance().get(&ConfigKey::Rails).unwrap_or(Map::new(&env));
+        let mut rail = rails.get(rail_id.clone()).ok_or(Error::WithdrawalRailNotFound)?;
+        rail.enabled = enabled;
+        rails.set(rail_id.clone(), rail);
+        env.storage().instance().set(&ConfigKey::Rails, &rails);
+
+        env.events().publish((symbol_short!("rail"), rail_id), enabled);
+
+        Ok(())
+    }
+
+    // Get all withdrawal rails
+    pub fn get_withdrawal_rails(env: Env, cursor: u32, limit: u32) -> (Vec<WithdrawalRail>, Option<u32>) {
+        let rails: Map<String, WithdrawalRail> = env.storage().instance().get(&ConfigKey::Rails).unwrap_or(Map::new(&env));
+
+        Self::page(rails.values(), cursor, limit)
+    }
+
+    // Set bill payment status (status updater only)
+    pub fn set_bill_status(env: Env, caller: Address, payment_id: String, status: PaymentStatus) -> Result<(), Error> {
+        Self::extend_instance(&env);
+
+        Self::require_role(&env, &caller, Role::StatusUpdater)?;
+        Self::require_council_approval(&env)?;
+
+        let mut payment = Self::load_bill(&env, &payment_id).ok_or(Error::PaymentNotFound)?;
+        Self::check_status_change(&env, &payment.status)?;
+        let previous = payment.status.clone();
+        payment.status = Self::status_string(&env, status);
+
+        let mut stats = Self::load_stats(&env);
+        Self::count_status(&mut stats.bill_statuses, Some(previous), payment.status.clone());
+        Self::save_stats(&env, &stats);
+
+        let action = match status {
+            PaymentStatus::Pending => symbol_short!("bill_pend"),
+            PaymentStatus::Processing => symbol_short!("bill_proc"),
+            PaymentStatus::Completed => symbol_short!("bill_done"),
+            PaymentStatus::Failed => symbol_short!("bill_fail"),
+        };
+        Self::audit(&env, &caller, action.clone(), AuditTarget::Id(payment_id.clone()), payment.amount);
+
+        if status == PaymentStatus::Completed {
+            Self::accrue_settlement(&env, &payment.bill_type, payment.amount);
+        }
+        // The biller was never paid; the funds stay with the contract unless a dispute refunds them
+        if status == PaymentStatus::Failed {
+            Self::post(&env, &payment.user_address, Bucket::Payable, Bucket::Treasury, payment.amount);
+        }
+        if status == PaymentStatus::Completed || status == PaymentStatus::Failed {
+            Self::notify(&env, &payment.user_address, action, NotificationRef::Key(payment_id.clone()), payment.amount);
+        }
+
+        Self::save_bill(&env, &payment);
+
+        Ok(())
+    }
+
+    // Set withdrawal status (status updater only)
+    pub fn set_withdrawal_status(
+        env: Env,
+        caller: Address,
+        withdrawal_id: String,
+        status: PaymentStatus,
+        payout_reference: Option<String>,
+    ) -> Result<(), Error> {
+        Self::extend_instance(&env);
+
+        Self::require_role(&env, &caller, Role::StatusUpdater)?;
+        Self::require_council_approval(&env)?;
+
+        Self::update_withdrawal_status(&env, &caller, withdrawal_id, status, payout_reference)
+    }
+
+    // Refund a withdrawal whose hold expired before the off-ramp completed it; callable by anyone
+    pub fn refund_expired_withdrawal(env: Env, withdrawal_id: String) -> Result<(), Error> {
+        let mut holds: Map<String, WithdrawalHold> = env.storage().instance().get(&ComplianceKey::WithdrawalHolds).unwrap_or(Map::new(&env));
+        let hold = holds.get(withdrawal_id.clone()).ok_or(Error::WithdrawalHoldNotFound)?;
+
+        if env.ledger().timestamp() <= hold.expires_at {
+            return Err(Self::fail_until(&env, &hold.user_address, symbol_short!("wd_refund"), symbol_short!("not_due"), hold.expires_at + 1, Error::WithdrawalHoldHasNotExpired));
+        }
+
+        let mut withdrawal = Self::load_withdrawal(&env, &withdrawal_id).ok_or(Error::WithdrawalNotFound)?;
+        let previous = withdrawal.status.clone();
+        withdrawal.status = String::from_str(&env, "expired");
+
+        let mut stats = Self::load_stats(&env);
+        Self::count_status(&mut stats.withdrawal_statuses, Some(previous), withdrawal.status.clone());
+        Self::save_stats(&env, &stats);
+        Self::audit(&env, &env.current_contract_address(), symbol_short!("wd_expire"), AuditTarget::Id(withdrawal_id.clone()), withdrawal.usdc_amount);
+        Self::notify(&env, &withdrawal.user_address, symbol_short!("wd_expire"), NotificationRef::Key(withdrawal_id.clone()), withdrawal.usdc_amount);
+        Self::save_withdrawal(&env, &withdrawal);
+
+        holds.remove(withdrawal_id.clone());
+        env.storage().instance().set(&ComplianceKey::WithdrawalHolds, &holds);
+        Self::refund_withdrawal_hold(&env, &withdrawal_id, &hold)
+    }
+
+    // Get the hold backing a withdrawal, if it is still open
+    pub fn get_withdrawal_hold(env: Env, withdrawal_id: String) -> Option<WithdrawalHold> {
+        let holds: Map<String, WithdrawalHold> = env.storage().instance().get(&ComplianceKey::WithdrawalHolds).unwrap_or(Map::new(&env));
+
+        holds.get(withdrawal_id)
+    }
+
+    // Set how long withdrawals stay on hold before they are refunded (admin only)
+    pub fn set_withdrawal_hold_window(env: Env, window_secs: u64) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("wd_win"), AuditTarget::Global, window_secs as i128);
+
+        if window_secs == 0 {
+            return Err(Error::InvalidWindow);
+        }
+
+        env.storage().instance().set(&ConfigKey::WithdrawalHoldWindow, &window_secs);
+
+        Ok(())
+    }
+
+    // Grant an operator role to an address (admin only)
+    pub fn grant_role(env: Env, address: Address, role: Role) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("role_add"), AuditTarget::Address(address.clone()), role as i128);
+
+        let mut roles: Map<Address, Vec<Role>> = env.storage().instance().get(&ConfigKey::Roles).unwrap_or(Map::new(&env));
+        let mut granted = roles.get(address.clone()).unwrap_or(vec![&env]);
+        if !granted.contains(role) {
+            granted.push_back(role);
+        }
+        roles.set(address.clone(), granted);
+        env.storage().instance().set(&ConfigKey::Roles, &roles);
+
+        env.events().publish((symbol_short!("role_add"), address), role);
+
+        Ok(())
+    }
+
+    // Revoke an operator role from an address (admin only)
+    pub fn revoke_role(env: Env, address: Address, role: Role) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("role_del"), AuditTarget::Address(address.clone()), role as i128);
+
+        let mut roles: Map<Address, Vec<Role>> = env.storage().instance().get(&ConfigKey::Roles).unwrap_or(Map::new(&env));
+        let mut granted = roles.get(address.clone()).ok_or(Error::RoleNotGranted)?;
+        let index = granted.first_index_of(role).ok_or(Error::RoleNotGranted)?;
+        granted.remove(index);
+        if granted.is_empty() {
+            roles.remove(address.clone());
+        } else {
+            roles.set(address.clone(), granted);
+        }
+        env.storage().instance().set(&ConfigKey::Roles, &roles);
+
+        env.events().publish((symbol_short!("role_del"), address), role);
+
+        Ok(())
+    }
+
+    // Get the operator roles held by an address
+    pub fn get_roles(env: Env, address: Address) -> Vec<Role> {
+        let roles: Map<Address, Vec<Role>> = env.storage().instance().get(&ConfigKey::Roles).unwrap_or(Map::new(&env));
+
+        roles.get(address).unwrap_or(vec![&env])
+    }
+
+    // Set or rotate the user's PIN. `hash` is sha256 of `salt || PIN` under a freshly generated
+    // `salt`. Replacing an existing PIN needs a fresh `verify_pin`.
+    pub fn set_pin_hash(env: Env, user_address: Address, salt: BytesN<32>, hash: BytesN<32>) -> Result<(), Error> {
+        user_address.require_auth();
+
+        let users = Users::load(&env);
+        if !users.contains_key(user_address.clone()) {
+            return Err(Error::UserNotFound);
+        }
+
+        let mut pins: Map<Address, PinState> = env.storage().instance().get(&ComplianceKey::Pins).unwrap_or(Map::new(&env));
+        if let Some(pin) = pins.get(user_address.clone()) {
+            if pin.approved_until < env.ledger().timestamp() {
+                return Err(Error::PinVerificationRequired);
+            }
+        }
+
+        pins.set(user_address.clone(), PinState { hash, salt: Some(salt), failed_attempts: 0, locked_until: 0, approved_until: 0 });
+        env.storage().instance().set(&ComplianceKey::Pins, &pins);
+
+        env.events().publish((symbol_short!("pin_set"), user_address), ());
+
+        Ok(())
+    }
+
+    // Check a PIN preimage. A match authorizes the next high-value operation for a short window and
+    // optionally rotates the stored hash, which keeps the PIN's salt; a mismatch counts towards the lockout.
+    // Returns whether it matched.
+    pub fn verify_pin(env: Env, user_address: Address, preimage: Bytes, next_hash: Option<BytesN<32>>) -> Result<bool, Error> {
+        user_address.require_auth();
+
+        let mut pins: Map<Address, PinState> = env.storage().instance().get(&ComplianceKey::Pins).unwrap_or(Map::new(&env));
+        let mut pin = pins.get(user_address.clone()).ok_or(Error::PinNotSet)?;
+        let now = env.ledger().timestamp();
+
+        if pin.locked_until > now {
+            return Err(Self::fail_until(&env, &user_address, symbol_short!("pin"), symbol_short!("locked"), pin.locked_until, Error::PinLocked));
+        }
+
+        // A mismatch is reported as `false` rather than an error so the attempt counter persists
+        let matched = Self::pin_commitment(&env, &pin.salt, &preimage) == pin.hash;
+        if matched {
+            pin.failed_attempts = 0;
+            pin.approved_until = now + PIN_APPROVAL_SECS;
+            if let Some(next_hash) = next_hash {
+                pin.hash = next_hash;
+            }
+        } else {
+            pin.failed_attempts += 1;
+            if pin.failed_attempts >= MAX_PIN_ATTEMPTS {
+                pin.failed_attempts = 0;
+                pin.locked_until = now + PIN_LOCKOUT_SECS;
+                env.events().publish((symbol_short!("pin_lock"), user_address.clone()), pin.locked_until);
+            }
+        }
+
+        pins.set(user_address, pin);
+        env.storage().instance().set(&ComplianceKey::Pins, &pins);
+
+        Ok(matched)
+    }
+
+    // Set the amount above which operations need a verified PIN (admin only)
+    pub fn set_pin_threshold(env: Env, threshold: i128) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("pin_min"), AuditTarget::Global, threshold);
+
+        if threshold < 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        env.storage().instance().set(&ConfigKey::PinThreshold, &threshold);
+
+        Ok(())
+    }
+
+    // Nominate the guardians who can recover the account if the user loses their key
+    pub fn set_guardians(env: Env, user_address: Address, guardians: Vec<Address>, threshold: u32) -> Result<(), Error> {
+        user_address.require_auth();
+
+        let users = Users::load(&env);
+        if !users.contains_key(user_address.clone()) {
+            return Err(Error::UserNotFound);
+        }
+        if threshold == 0 || threshold > guardians.len() {
+            return Err(Error::InvalidThreshold);
+        }
+        if guardians.contains(user_address.clone()) {
+            return Err(Error::UserCannotBeTheirOwnGuardian);
+        }
+
+        let mut guardian_sets: Map<Address, GuardianSet> = env.storage().instance().get(&ComplianceKey::Guardians).unwrap_or(Map::new(&env));
+        guardian_sets.set(user_address.clone(), GuardianSet { guardians, threshold });
+        env.storage().instance().set(&ComplianceKey::Guardians, &guardian_sets);
+
+        env.events().publish((symbol_short!("guardians"), user_address), threshold);
+
+        Ok(())
+    }
+
+    // Approve moving an account to a new address. Once enough guardians approve, the recovery
+    // can be finalized after `RECOVERY_DELAY_SECS`.
+    pub fn recover_account(env: Env, guardian: Address, old_address: Address, new_address: Address) -> Result<(), Error> {
+        guardian.require_auth();
+
+        let guardian_sets: Map<Address, GuardianSet> = env.storage().instance().get(&ComplianceKey::Guardians).unwrap_or(Map::new(&env));
+        let guardian_set = guardian_sets.get(old_address.clone()).ok_or(Error::NoGuardiansSet)?;
+        if !guardian_set.guardians.contains(guardian.clone()) {
+            return Err(Error::NotAGuardian);
+        }
+
+        let users = Users::load(&env);
+        if users.contains_key(new_address.clone()) {
+            return Err(Error::NewAddressAlreadyRegistered);
+        }
+
+        let mut recoveries: Map<Address, RecoveryRequest> = env.storage().instance().get(&ComplianceKey::Recoveries).unwrap_or(Map::new(&env));
+        let mut request = recoveries.get(old_address.clone()).unwrap_or(RecoveryRequest {
+            new_address: new_address.clone(),
+            approvals: vec![&env],
+            ready_at: 0,
+        });
+
+        if request.new_address != new_address {
+            return Err(Error::RecoveryAlreadyRequested);
+        }
+        if request.approvals.contains(guardian.clone()) {
+            return Err(Error::AlreadyApproved);
+        }
+
+        request.approvals.push_back(guardian.clone());
+        if request.ready_at == 0 && request.approvals.len() >= guardian_set.threshold {
+            request.ready_at = env.ledger().timestamp() + RECOVERY_DELAY_SECS;
+        }
+        recoveries.set(old_address.clone(), request.clone());
+        env.storage().instance().set(&ComplianceKey::Recoveries, &recoveries);
+
+        env.events().publish((symbol_short!("recovery"), old_address, new_address), (guardian, request.ready_at));
+
+        Ok(())
+    }
+
+    // Cancel a pending recovery of the caller's account
+    pub fn cancel_recovery(env: Env, user_address: Address) -> Result<(), Error> {
+        user_address.require_auth();
+
+        let mut recoveries: Map<Address, RecoveryRequest> = env.storage().instance().get(&ComplianceKey::Recoveries).unwrap_or(Map::new(&env));
+        if !recoveries.contains_key(user_address.clone()) {
+            return Err(Error::RecoveryNotFound);
+        }
+        recoveries.remove(user_address.clone());
+        env.storage().instance().set(&ComplianceKey::Recoveries, &recoveries);
+
+        env.events().publish((symbol_short!("rec_cncl"), user_address), ());
+
+        Ok(())
+    }
+
+    // Move the balance and history of a recovered account to its new address; callable by anyone
+    // once the delay has passed
+    pub fn finalize_recovery(env: Env, old_address: Address) -> Result<(), Error> {
+        let mut recoveries: Map<Address, RecoveryRequest> = env.storage().instance().get(&ComplianceKey::Recoveries).unwrap_or(Map::new(&env));
+        let request = recoveries.get(old_address.clone()).ok_or(Error::RecoveryNotFound)?;
+
+        if request.ready_at == 0 {
+            return Err(Error::NotEnoughGuardianApprovals);
+        }
+        if env.ledger().timestamp() < request.ready_at {
+            return Err(Self::fail_until(&env, &old_address, symbol_short!("recovery"), symbol_short!("not_due"), request.ready_at, Error::RecoveryDelayHasNotPassed));
+        }
+
+        let new_address = request.new_address;
+        let mut users = Users::load(&env);
+        if users.contains_key(new_address.clone()) {
+            return Err(Error::NewAddressAlreadyRegistered);
+        }
+        let mut user = users.get(old_address.clone()).ok_or(Error::UserNotFound)?;
+
+        // Records that name the old address as a counterparty are not moved; they have to be
+        // settled or cancelled before the account can move
+        Self::require_no_open_commitments(&env, &old_address)?;
+
+        user.address = new_address.clone();
+        users.remove(old_address.clone());
+        users.set(new_address.clone(), user);
+        users.save();
+
+        for payment_id in Self::user_index(&env, DataKey::UserBills(old_address.clone())).iter() {
+            if let Some(mut payment) = Self::load_bill(&env, &payment_id) {
+                payment.user_address = new_address.clone();
+                Self::save_bill(&env, &payment);
+            }
+        }
+        for withdrawal_id in Self::user_index(&env, DataKey::UserWithdrawals(old_address.clone())).iter() {
+            if let Some(mut withdrawal) = Self::load_withdrawal(&env, &withdrawal_id) {
+                withdrawal.user_address = new_address.clone();
+                Self::save_withdrawal(&env, &withdrawal);
+            }
+        }
+        Self::move_index(&env, DataKey::UserBills(old_address.clone()), DataKey::UserBills(new_address.clone()));
+        Self::move_index(&env, DataKey::UserWithdrawals(old_address.clone()), DataKey::UserWithdrawals(new_address.clone()));
+
+        let swap_ids: Vec<u64> = env.storage().persistent().get(&EntryKey::UserSwaps(old_address.clone())).unwrap_or(vec![&env]);
+        for swap_id in swap_ids.iter() {
+            if let Some(mut swap) = env.storage().persistent().get::<_, Swap>(&EntryKey::Swap(swap_id)) {
+                swap.user_address = new_address.clone();
+                env.storage().persistent().set(&EntryKey::Swap(swap_id), &swap);
+            }
+        }
+        if !swap_ids.is_empty() {
+            env.storage().persistent().set(&EntryKey::UserSwaps(new_address.clone()), &swap_ids);
+            env.storage().persistent().remove(&EntryKey::UserSwaps(old_address.clone()));
+        }
+
+        let mut assets: Map<(Address, Address), i128> = env.storage().instance().get(&DataKey::Assets).unwrap_or(Map::new(&env));
+        for ((owner, token), amount) in assets.clone().iter() {
+            if owner == old_address {
+                assets.remove((owner, token.clone()));
+                assets.set((new_address.clone(), token), amount);
+            }
+        }
+        env.storage().instance().set(&DataKey::Assets, &assets);
+
+        let mut rewards: Map<Address, i128> = env.storage().instance().get(&DataKey::Rewards).unwrap_or(Map::new(&env));
+        if let Some(points) = rewards.get(old_address.clone()) {
+            rewards.remove(old_address.clone());
+            rewards.set(new_address.clone(), points);
+            env.storage().instance().set(&DataKey::Rewards, &rewards);
+        }
+
+        let mut holds: Map<String, WithdrawalHold> = env.storage().instance().get(&ComplianceKey::WithdrawalHolds).unwrap_or(Map::new(&env));
+        for (withdrawal_id, mut hold) in holds.clone().iter() {
+            if hold.user_address == old_address {
+                hold.user_address = new_address.clone();
+                holds.set(withdrawal_id, hold);
+            }
+        }
+        env.storage().instance().set(&ComplianceKey::WithdrawalHolds, &holds);
+
+        let goal_ids: Vec<u64> = Self::record_index(&env, &EntryKey::UserGoals(old_address.clone()));
+        let mut goals: Map<u64, SavingsGoal> = env.storage().instance().get(&DataKey::Goals).unwrap_or(Map::new(&env));
+        for goal_id in goal_ids.iter() {
+            if let Some(mut goal) = goals.get(goal_id) {
+                goal.owner = new_address.clone();
+                goals.set(goal_id, goal);
+            }
+        }
+        env.storage().instance().set(&DataKey::Goals, &goals);
+        if !goal_ids.is_empty() {
+            env.storage().persistent().set(&EntryKey::UserGoals(new_address.clone()), &goal_ids);
+            env.storage().persistent().remove(&EntryKey::UserGoals(old_address.clone()));
+        }
+
+        // Guardians carry over; the old PIN does not, since it may be compromised
+        let mut guardian_sets: Map<Address, GuardianSet> = env.storage().instance().get(&ComplianceKey::Guardians).unwrap_or(Map::new(&env));
+        if let Some(guardian_set) = guardian_sets.get(old_address.clone()) {
+            guardian_sets.remove(old_address.clone());
+            guardian_sets.set(new_address.clone(), guardian_set);
+            env.storage().instance().set(&ComplianceKey::Guardians, &guardian_sets);
+        }
+        let mut pins: Map<Address, PinState> = env.storage().instance().get(&ComplianceKey::Pins).unwrap_or(Map::new(&env));
+        if pins.contains_key(old_address.clone()) {
+            pins.remove(old_address.clone());
+            env.storage().instance().set(&ComplianceKey::Pins, &pins);
+        }
+
+        recoveries.remove(old_address.clone());
+        env.storage().instance().set(&ComplianceKey::Recoveries, &recoveries);
+
+        env.events().publish((symbol_short!("recovered"), old_address, new_address), ());
+
+        Ok(())
+    }
+
+    // Get the pending recovery of an account, if any
+    pub fn get_recovery(env: Env, user_address: Address) -> Option<RecoveryRequest> {
+        let recoveries: Map<Address, RecoveryRequest> = env.storage().instance().get(&ComplianceKey::Recoveries).unwrap_or(Map::new(&env));
+
+        recoveries.get(user_address)
+    }
+
+    // Get aggregate counters for the operator dashboard
+    pub fn get_stats(env: Env) -> Stats {
+        Self::load_stats(&env)
+    }
+
+    // Get audit entries with sequence numbers after `offset`, and the offset to continue from while
+    // newer entries remain. Entries past their retention are skipped.
+    pub fn get_audit_log(env: Env, offset: u64, limit: u32) -> (Vec<AuditEntry>, Option<u64>) {
+        let last_seq: u64 = env.storage().instance().get(&CounterKey::AuditSeq).unwrap_or(0);
+        let end = last_seq.min(offset + limit.min(MAX_AUDIT_PAGE) as u64);
+        let mut entries = vec![&env];
+
+        for seq in (offset + 1)..=end {
+            if let Some(entry) = Self::load_audit_entry(&env, seq) {
+                entries.push_back(entry);
+            }
+        }
+
+        (entries, if end < last_seq { Some(end) } else { None })
+    }
+
+    // Set how many ledgers new audit entries are retained for (admin only)
+    pub fn set_audit_retention(env: Env, ttl_ledgers: u32) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+
+        if ttl_ledgers == 0 || ttl_ledgers > env.storage().max_ttl() {
+            return Err(Error::InvalidRetention);
+        }
+
+        Self::audit(&env, &admin, symbol_short!("audit_ret"), AuditTarget::Global, ttl_ledgers as i128);
+        env.storage().instance().set(&ConfigKey::AuditTtl, &ttl_ledgers);
+
+        Ok(())
+    }
+
+    // Extend the contract instance TTL; callable by anyone, e.g. a keeper.
+    // If the instance was archived it has to be restored with a `RestoreFootprint` operation
+    // (`stellar contract restore`) before any entrypoint, this one included, can run again.
+    pub fn bump_instance(env: Env) {
+        Self::extend_instance(&env);
+    }
+
+    // Keep a user's records alive: their user entry, the bill, withdrawal, transfer and swap
+    // indexes with every record they point to, and their balance history
+    pub fn bump_user(env: Env, user_address: Address) -> Result<(), Error> {
+        let users = Users::load(&env);
+        if !users.contains_key(user_address.clone()) {
+            return Err(Error::UserNotFound);
+        }
+
+        Self::extend_instance(&env);
+        Self::extend_entry(&env, &EntryKey::User(user_address.clone()));
+        Self::extend_entry(&env, &EntryKey::BalanceHistory(user_address.clone()));
+
+        Self::extend_entry(&env, &DataKey::UserBills(user_address.clone()));
+        for payment_id in Self::user_index(&env, DataKey::UserBills(user_address.clone())).iter() {
+            Self::extend_entry(&env, &EntryKey::Bill(payment_id));
+        }
+        Self::extend_entry(&env, &DataKey::UserWithdrawals(user_address.clone()));
+        for withdrawal_id in Self::user_index(&env, DataKey::UserWithdrawals(user_address.clone())).iter() {
+            Self::extend_entry(&env, &EntryKey::Withdrawal(withdrawal_id));
+        }
+        Self::extend_entry(&env, &EntryKey::UserTransfers(user_address.clone()));
+        for transfer_id in Self::transfer_index(&env, &user_address).iter() {
+            Self::extend_entry(&env, &EntryKey::Transfer(transfer_id));
+        }
+        let swap_ids: Vec<u64> = env.storage().persistent().get(&EntryKey::UserSwaps(user_address.clone())).unwrap_or(vec![&env]);
+        Self::extend_entry(&env, &EntryKey::UserSwaps(user_address));
+        for swap_id in swap_ids.iter() {
+            Self::extend_entry(&env, &EntryKey::Swap(swap_id));
+        }
+
+        Ok(())
+    }
+
+    // Close an account. Any remaining balance is swept to `sweep_to`; without one the balance must
+    // be zero. The record is kept for audits, but the phone number is released and the account
+    // can no longer transact.
+    pub fn close_account(env: Env, user_address: Address, sweep_to: Option<Address>) -> Result<(), Error> {
+        user_address.require_auth();
+
+        if Self::is_closed(&env, &user_address) {
+            return Err(Error::AccountClosed);
+        }
+        // Frozen or blacklisted funds stay put until compliance lifts the restriction
+        Self::ensure_not_restricted(&env, &user_address, symbol_short!("close"))?;
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+
+        // Funds still locked elsewhere have to be settled first
+        let holds: Map<String, WithdrawalHold> = env.storage().instance().get(&ComplianceKey::WithdrawalHolds).unwrap_or(Map::new(&env));
+        for (_, hold) in holds.iter() {
+            if hold.user_address == user_address {
+                return Err(Error::AccountHasPendingWithdrawals);
+            }
+        }
+        for hold in Self::card_holds_for(&env, &user_address).iter() {
+            if hold.status == String::from_str(&env, "active") {
+                return Err(Error::AccountHasPendingCardHolds);
+            }
+        }
+        for payroll in Self::payrolls_for(&env, &user_address).iter() {
+            if payroll.status == String::from_str(&env, "pending") {
+                return Err(Error::AccountHasAPendingPayroll);
+            }
+        }
+        for goal in Self::goals_for(&env, &user_address).iter() {
+            if goal.saved > 0 {
+                return Err(Error::AccountHasFundedSavingsGoals);
+            }
+        }
+        let line = Self::accrued_credit_line(&env, &user_address);
+        if line.collateral > 0 || line.debt > 0 {
+            return Err(Error::AccountHasAnOpenCreditLine);
+        }
+        for loan in Self::loans_for(&env, &user_address).iter() {
+            if loan.status == String::from_str(&env, "active") || loan.status == String::from_str(&env, "requested") {
+                return Err(Error::AccountHasAnOpenLoan);
+            }
+        }
+
+        if user.balance < 0 {
+            return Err(Error::OverdraftNotRepaid);
+        }
+
+        let swept = user.balance;
+        if swept > 0 {
+            let sweep_to = sweep_to.ok_or(Error::BalanceMustBeZeroOrSwept)?;
+            if sweep_to == user_address || Self::is_closed(&env, &sweep_to) {
+                return Err(Error::InvalidSweepAddress);
+            }
+            Self::ensure_not_restricted(&env, &sweep_to, symbol_short!("close"))?;
+            let mut recipient = users.get(sweep_to.clone()).ok_or(Error::RecipientNotFound)?;
+
+            user.balance = 0;
+            Self::track_balance(&env, &user, -swept, Bucket::Clearing);
+            recipient.balance = Self::checked_credit(recipient.balance, swept)?;
+            Self::track_balance(&env, &recipient, swept, Bucket::Clearing);
+            users.set(sweep_to.clone(), recipient);
+        }
+
+        user.phone = String::from_str(&env, "");
+        users.set(user_address.clone(), user);
+        users.save();
+
+        let mut closed: Map<Address, u64> = env.storage().instance().get(&ComplianceKey::Closed).unwrap_or(Map::new(&env));
+        closed.set(user_address.clone(), env.ledger().timestamp());
+        env.storage().instance().set(&ComplianceKey::Closed, &closed);
+
+        let mut stats = Self::load_stats(&env);
+        stats.user_count -= 1;
+        Self::save_stats(&env, &stats);
+
+        Self::audit(&env, &user_address, symbol_short!("close"), AuditTarget::Address(user_address.clone()), swept);
+        env.events().publish((symbol_short!("closed"), user_address), swept);
+
+        Ok(())
+    }
+
+    // Get when an account was closed, if it was
+    pub fn get_closed_at(env: Env, user_address: Address) -> Option<u64> {
+        let closed: Map<Address, u64> = env.storage().instance().get(&ComplianceKey::Closed).unwrap_or(Map::new(&env));
+
+        closed.get(user_address)
+    }
+
+    // Get a user's spending grouped by category for the calendar months (UTC) covering `from`..=`to`
+    pub fn get_spending_summary(env: Env, user_address: Address, from: u64, to: u64) -> Vec<SpendingTotal> {
+        let spending: Map<(Address, u32), Map<SpendCategory, i128>> = env.storage().instance().get(&DataKey::Spending).unwrap_or(Map::new(&env));
+        let mut totals: Map<SpendCategory, i128> = Map::new(&env);
+
+        let last = Self::month_of(to);
+        let mut month = Self::month_of(from);
+        while month <= last {
+            if let Some(categories) = spending.get((user_address.clone(), month)) {
+                for (category, amount) in categories.iter() {
+                    let total = totals.get(category.clone()).unwrap_or(0);
+                    totals.set(category, total + amount);
+                }
+            }
+            month = if month % 100 == 12 { month + 89 } else { month + 1 };
+        }
+
+        let mut summary = vec![&env];
+        for (category, amount) in totals.iter() {
+            summary.push_back(SpendingTotal { category, amount });
+        }
+
+        summary
+    }
+
+    // Split a bill between participants; the shares must add up to the total.
+    // The bill is paid in the creator's name once every share is funded.
+    pub fn create_split(
+        env: Env,
+        creator: Address,
+        bill_type: String,
+        account_number: String,
+        total: i128,
+        participants: Vec<(Address, i128)>,
+    ) -> Result<u64, Error> {
+        creator.require_auth();
+        Self::ensure_module_active(&env, &creator, symbol_short!("bills"))?;
+        Self::ensure_not_restricted(&env, &creator, symbol_short!("split"))?;
+        Self::validate_bill(&env, &creator, &bill_type, &account_number, total)?;
+
+        let users = Users::load(&env);
+        if !users.contains_key(creator.clone()) {
+            return Err(Error::UserNotFound);
+        }
+
+        let mut shares: Map<Address, i128> = Map::new(&env);
+        let mut sum: i128 = 0;
+        for (participant, share) in participants.iter() {
+            if share <= 0 {
+                return Err(Error::InvalidAmount);
+            }
+            if shares.contains_key(participant.clone()) {
+                return Err(Error::DuplicateParticipant);
+            }
+            if !users.contains_key(participant.clone()) {
+                return Err(Error::ParticipantNotFound);
+            }
+            shares.set(participant, share);
+            sum += share;
+        }
+        if shares.is_empty() || sum != total {
+            return Err(Error::SharesMustAddUpToTheTotal);
+        }
+
+        let mut splits: Map<u64, BillSplit> = env.storage().instance().get(&DataKey::Splits).unwrap_or(Map::new(&env));
+        let split_id: u64 = env.storage().instance().get(&CounterKey::SplitId).unwrap_or(0) + 1;
+
+        let split = BillSplit {
+            id: split_id,
+            creator: creator.clone(),
+            bill_type,
+            account_number,
+            total,
+            shares,
+            funded_by: vec![&env],
+            funded: 0,
+            expires_at: env.ledger().timestamp() + SPLIT_WINDOW_SECS,
+            status: String::from_str(&env, "open"),
+            payment_id: None,
+        };
+        splits.set(split_id, split.clone());
+        env.storage().instance().set(&DataKey::Splits, &splits);
+        env.storage().instance().set(&CounterKey::SplitId, &split_id);
+        Self::index_split(&env, &split);
+
+        env.events().publish((symbol_short!("split"), creator), (split_id, total));
+
+        Ok(split_id)
+    }
+
+    // Fund the participant's share of a split bill. Returns the bill payment id once the last share
+    // is funded and the bill is paid.
+    pub fn accept_split(env: Env, participant: Address, split_id: u64) -> Result<Option<String>, Error> {
+        participant.require_auth();
+        Self::ensure_module_active(&env, &participant, symbol_short!("bills"))?;
+        Self::ensure_not_restricted(&env, &participant, symbol_short!("split"))?;
+
+        let mut splits: Map<u64, BillSplit> = env.storage().instance().get(&DataKey::Splits).unwrap_or(Map::new(&env));
+        let mut split = splits.get(split_id).ok_or(Error::SplitNotFound)?;
+
+        if split.status != String::from_str(&env, "open") {
+            return Err(Error::SplitNotOpen);
+        }
+        if env.ledger().timestamp() > split.expires_at {
+            return Err(Error::SplitHasExpired);
+        }
+        let share = split.shares.get(participant.clone()).ok_or(Error::NotAParticipant)?;
+        if split.funded_by.contains(participant.clone()) {
+            return Err(Error::ShareAlreadyFunded);
+        }
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(participant.clone()).ok_or(Error::UserNotFound)?;
+        if user.balance < share {
+            return Err(Self::fail(&env, &participant, symbol_short!("split"), symbol_short!("balance"), share, user.balance, Error::InsufficientBalance));
+        }
+        user.balance = Self::checked_debit(user.balance, share)?;
+        Self::track_balance(&env, &user, -share, Bucket::Escrowed);
+        users.set(participant.clone(), user);
+        users.save();
+
+        Self::record_spending(&env, &participant, SpendCategory::Bill(split.bill_type.clone()), share);
+
+        split.funded_by.push_back(participant.clone());
+        split.funded += share;
+
+        if split.funded == split.total {
+            let payment_id = Self::record_bill_payment(&env, &split.creator, split.bill_type.clone(), split.account_number.clone(), split.total, None);
+            Self::post(&env, &split.creator, Bucket::Escrowed, Bucket::Payable, split.total);
+            split.status = String::from_str(&env, "paid");
+            split.payment_id = Some(payment_id);
+        }
+
+        splits.set(split_id, split.clone());
+        env.storage().instance().set(&DataKey::Splits, &splits);
+
+        env.events().publish((symbol_short!("split_acc"), participant), (split_id, share));
+
+        Ok(split.payment_id)
+    }
+
+    // Refund the funded shares of a split that expired before it was fully funded; callable by anyone
+    pub fn refund_expired_split(env: Env, split_id: u64) -> Result<(), Error> {
+        let mut splits: Map<u64, BillSplit> = env.storage().instance().get(&DataKey::Splits).unwrap_or(Map::new(&env));
+        let mut split = splits.get(split_id).ok_or(Error::SplitNotFound)?;
+
+        if split.status != String::from_str(&env, "open") {
+            return Err(Error::SplitNotOpen);
+        }
+        if env.ledger().timestamp() <= split.expires_at {
+            return Err(Self::fail_until(&env, &split.creator, symbol_short!("split"), symbol_short!("not_due"), split.expires_at + 1, Error::SplitHasNotExpired));
+        }
+
+        let mut users = Users::load(&env);
+        for participant in split.funded_by.iter() {
+            let share = split.shares.get(participant.clone()).unwrap_or(0);
+            let mut user = users.get(participant.clone()).ok_or(Error::UserNotFound)?;
+            user.balance = Self::checked_credit(user.balance, share)?;
+            Self::track_balance(&env, &user, share, Bucket::Escrowed);
+            users.set(participant, user);
+        }
+        users.save();
+
+        split.status = String::from_str(&env, "expired");
+        splits.set(split_id, split.clone());
+        env.storage().instance().set(&DataKey::Splits, &splits);
+
+        env.events().publish((symbol_short!("split_exp"), split.creator), (split_id, split.funded));
+
+        Ok(())
+    }
+
+    // Get split bills a user created or takes part in
+    pub fn get_splits(env: Env, user_address: Address, cursor: u32, limit: u32) -> (Vec<BillSplit>, Option<u32>) {
+        Self::page(Self::splits_for(&env, &user_address), cursor, limit)
+    }
+
+    // Lock funds behind a voucher code; only the sha256 of the code is stored
+    pub fn create_voucher(env: Env, funder: Address, amount: i128, code_hash: BytesN<32>, expiry: u64) -> Result<(), Error> {
+        funder.require_auth();
+        Self::ensure_not_restricted(&env, &funder, symbol_short!("voucher"))?;
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        if expiry <= env.ledger().timestamp() {
+            return Err(Error::InvalidExpiry);
+        }
+
+        let mut vouchers: Map<BytesN<32>, Voucher> = env.storage().instance().get(&DataKey::Vouchers).unwrap_or(Map::new(&env));
+        if vouchers.contains_key(code_hash.clone()) {
+            return Err(Error::VoucherAlreadyExists);
+        }
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(funder.clone()).ok_or(Error::UserNotFound)?;
+        if user.balance < amount {
+            return Err(Self::fail(&env, &funder, symbol_short!("voucher"), symbol_short!("balance"), amount, user.balance, Error::InsufficientBalance));
+        }
+        user.balance = Self::checked_debit(user.balance, amount)?;
+        Self::track_balance(&env, &user, -amount, Bucket::Escrowed);
+        users.set(funder.clone(), user);
+        users.save();
+
+        vouchers.set(code_hash.clone(), Voucher {
+            funder: funder.clone(),
+            amount,
+            expires_at: expiry,
+            status: String::from_str(&env, "active"),
+            redeemed_by: None,
+        });
+        env.storage().instance().set(&DataKey::Vouchers, &vouchers);
+        Self::index_record(&env, EntryKey::UserVouchers(funder.clone()), code_hash.clone());
+
+        env.events().publish((symbol_short!("voucher"), funder), (code_hash, amount, expiry));
+
+        Ok(())
+    }
+
+    // Commit to redeeming a voucher without revealing its code, so a redemption seen before it
+    // lands cannot be copied by another account
+    pub fn commit_voucher(env: Env, user_address: Address, commitment: BytesN<32>) -> Result<(), Error> {
+        user_address.require_auth();
+
+        let mut commitments: Map<Address, VoucherCommitment> = env.storage().instance().get(&RecordKey::VoucherCommitments).unwrap_or(Map::new(&env));
+        commitments.set(user_address.clone(), VoucherCommitment {
+            commitment,
+            ledger: env.ledger().sequence(),
+        });
+        env.storage().instance().set(&RecordKey::VoucherCommitments, &commitments);
+
+        Ok(())
+    }
+
+    // Redeem a voucher code into the user's balance, against a commitment from an earlier ledger.
+    // Returns the amount credited.
+    pub fn redeem_voucher(env: Env, user_address: Address, code_preimage: Bytes) -> Result<i128, Error> {
+        user_address.require_auth();
+        Self::ensure_not_restricted(&env, &user_address, symbol_short!("voucher"))?;
+
+        let mut commitments: Map<Address, VoucherCommitment> = env.storage().instance().get(&RecordKey::VoucherCommitments).unwrap_or(Map::new(&env));
+        let committed = commitments.get(user_address.clone()).ok_or(Error::VoucherNotCommitted)?;
+        let commitment = BytesN::from(env.crypto().sha256(&(code_preimage.clone(), user_address.clone()).to_xdr(&env)));
+        if committed.commitment != commitment {
+            return Err(Error::VoucherNotCommitted);
+        }
+        if committed.ledger >= env.ledger().sequence() {
+            return Err(Error::VoucherCommitmentTooRecent);
+        }
+        commitments.remove(user_address.clone());
+        env.storage().instance().set(&RecordKey::VoucherCommitments, &commitments);
+
+        let code_hash = BytesN::from(env.crypto().sha256(&code_preimage));
+        let mut vouchers: Map<BytesN<32>, Voucher> = env.storage().instance().get(&DataKey::Vouchers).unwrap_or(Map::new(&env));
+        let mut voucher = vouchers.get(code_hash.clone()).ok_or(Error::VoucherNotFound)?;
+
+        if voucher.status != String::from_str(&env, "active") {
+            return Err(Error::VoucherNoLongerValid);
+        }
+        if env.ledger().timestamp() > voucher.expires_at {
+            return Err(Error::VoucherHasExpired);
+        }
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+        user.balance = Self::checked_credit(user.balance, voucher.amount)?;
+        Self::track_balance(&env, &user, voucher.amount, Bucket::Escrowed);
+        users.set(user_address.clone(), user);
+        users.save();
+
+        voucher.status = String::from_str(&env, "redeemed");
+        voucher.redeemed_by = Some(user_address.clone());
+        vouchers.set(code_hash.clone(), voucher.clone());
+        env.storage().instance().set(&DataKey::Vouchers, &vouchers);
+
+        env.events().publish((symbol_short!("vch_redm"), user_address), (code_hash, voucher.amount));
+
+        Ok(voucher.amount)
+    }
+
+    // Return the funds of an expired, unredeemed voucher to its funder
+    pub fn reclaim_expired_voucher(env: Env, funder: Address, code_hash: BytesN<32>) -> Result<i128, Error> {
+        funder.require_auth();
+
+        let mut vouchers: Map<BytesN<32>, Voucher> = env.storage().instance().get(&DataKey::Vouchers).unwrap_or(Map::new(&env));
+        let mut voucher = vouchers.get(code_hash.clone()).ok_or(Error::VoucherNotFound)?;
+
+        if voucher.funder != funder {
+            return Err(Error::Unauthorized);
+        }
+        if voucher.status != String::from_str(&env, "active") {
+            return Err(Error::VoucherNoLongerValid);
+        }
+        if env.ledger().timestamp() <= voucher.expires_at {
+            return Err(Self::fail_until(&env, &funder, symbol_short!("voucher"), symbol_short!("not_due"), voucher.expires_at + 1, Error::VoucherHasNotExpired));
+        }
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(funder.clone()).ok_or(Error::UserNotFound)?;
+        user.balance = Self::checked_credit(user.balance, voucher.amount)?;
+        Self::track_balance(&env, &user, voucher.amount, Bucket::Escrowed);
+        users.set(funder.clone(), user);
+        users.save();
+
+        voucher.status = String::from_str(&env, "reclaimed");
+        vouchers.set(code_hash.clone(), voucher.clone());
+        env.storage().instance().set(&DataKey::Vouchers, &vouchers);
+
+        env.events().publish((symbol_short!("vch_recl"), funder), (code_hash, voucher.amount));
+
+        Ok(voucher.amount)
+    }
+
+    // Get a voucher by its code hash
+    pub fn get_voucher(env: Env, code_hash: BytesN<32>) -> Result<Voucher, Error> {
+        let vouchers: Map<BytesN<32>, Voucher> = env.storage().instance().get(&DataKey::Vouchers).unwrap_or(Map::new(&env));
+
+        vouchers.get(code_hash).ok_or(Error::VoucherNotFound)
+    }
+
+    // Request a micro-loan. The amount is capped by the user's recent spending history.
+    pub fn request_loan(env: Env, user_address: Address, amount: i128, term_secs: u64) -> Result<u64, Error> {
+        user_address.require_auth();
+        Self::ensure_module_active(&env, &user_address, symbol_short!("loans"))?;
+        Self::ensure_not_restricted(&env, &user_address, symbol_short!("loan"))?;
+
+        if amount <= 0 || term_secs == 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        let users = Users::load(&env);
+        if !users.contains_key(user_address.clone()) {
+            return Err(Error::UserNotFound);
+        }
+
+        let active = String::from_str(&env, "active");
+        let requested = String::from_str(&env, "requested");
+        for loan in Self::loans_for(&env, &user_address).iter() {
+            if loan.status == active || loan.status == requested {
+                return Err(Error::UserAlreadyHasAnOpenLoan);
+            }
+        }
+
+        let now = env.ledger().timestamp();
+        let mut spent: i128 = 0;
+        for total in Self::get_spending_summary(env.clone(), user_address.clone(), now.saturating_sub(LOAN_HISTORY_SECS), now).iter() {
+            spent += total.amount;
+        }
+        let limit = spent * LOAN_LIMIT_BPS as i128 / BPS_DENOMINATOR;
+        if amount > limit {
+            return Err(Self::fail(&env, &user_address, symbol_short!("loan"), symbol_short!("limit"), amount, limit, Error::AmountAboveLoanLimit));
+        }
+
+        let fee_bps: u32 = env.storage().instance().get(&ConfigKey::LoanFeeBps).unwrap_or(DEFAULT_LOAN_FEE_BPS);
+        let mut loans: Map<u64, Loan> = env.storage().instance().get(&DataKey::Loans).unwrap_or(Map::new(&env));
+        let loan_id: u64 = env.storage().instance().get(&CounterKey::LoanId).unwrap_or(0) + 1;
+
+        loans.set(loan_id, Loan {
+            id: loan_id,
+            borrower: user_address.clone(),
+            amount,
+            owed: amount + amount * fee_bps as i128 / BPS_DENOMINATOR,
+            repaid: 0,
+            term_secs,
+            due_at: 0,
+            status: requested,
+        });
+        env.storage().instance().set(&DataKey::Loans, &loans);
+        env.storage().instance().set(&CounterKey::LoanId, &loan_id);
+        Self::index_record(&env, EntryKey::UserLoans(user_address.clone()), loan_id);
+
+        env.events().publish((symbol_short!("loan_req"), user_address), (loan_id, amount, term_secs));
+
+        Ok(loan_id)
+    }
+
+    // Approve or reject a requested loan; approved loans are paid out from the loan pool (underwriter only)
+    pub fn approve_loan(env: Env, caller: Address, loan_id: u64, approve: bool) -> Result<(), Error> {
+        Self::require_role(&env, &caller, Role::Underwriter)?;
+        Self::audit(&env, &caller, symbol_short!("loan_appr"), AuditTarget::Global, loan_id as i128);
+
+        let mut loans: Map<u64, Loan> = env.storage().instance().get(&DataKey::Loans).unwrap_or(Map::new(&env));
+        let mut loan = loans.get(loan_id).ok_or(Error::LoanNotFound)?;
+
+        if loan.status != String::from_str(&env, "requested") {
+            return Err(Error::LoanNotAwaitingApproval);
+        }
+
+        if !approve {
+            loan.status = String::from_str(&env, "rejected");
+            loans.set(loan_id, loan.clone());
+            env.storage().instance().set(&DataKey::Loans, &loans);
+            env.events().publish((symbol_short!("loan_rej"), loan.borrower), loan_id);
+            return Ok(());
+        }
+
+        let pool: i128 = env.storage().instance().get(&CounterKey::LoanPool).unwrap_or(0);
+        if pool < loan.amount {
+            return Err(Error::InsufficientLoanPool);
+        }
+        env.storage().instance().set(&CounterKey::LoanPool, &(pool - loan.amount));
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(loan.borrower.clone()).ok_or(Error::UserNotFound)?;
+        user.balance = Self::checked_credit(user.balance, loan.amount)?;
+        Self::track_balance(&env, &user, loan.amount, Bucket::Treasury);
+        users.set(loan.borrower.clone(), user);
+        users.save();
+
+        loan.status = String::from_str(&env, "active");
+        loan.due_at = env.ledger().timestamp() + loan.term_secs;
+        loans.set(loan_id, loan.clone());
+        env.storage().instance().set(&DataKey::Loans, &loans);
+
+        env.events().publish((symbol_short!("loan_ok"), loan.borrower), (loan_id, loan.amount, loan.due_at));
+
+        Ok(())
+    }
+
+    // Repay part or all of an active loan from the borrower's balance. Returns what is still owed.
+    pub fn repay_loan(env: Env, user_address: Address, loan_id: u64, amount: i128) -> Result<i128, Error> {
+        user_address.require_auth();
+
+        let mut loans: Map<u64, Loan> = env.storage().instance().get(&DataKey::Loans).unwrap_or(Map::new(&env));
+        let mut loan = loans.get(loan_id).ok_or(Error::LoanNotFound)?;
+
+        if loan.borrower != user_address {
+            return Err(Error::Unauthorized);
+        }
+        if loan.status != String::from_str(&env, "active") {
+            return Err(Error::LoanNotActive);
+        }
+        let amount = amount.min(loan.owed - loan.repaid);
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+        if user.balance < amount {
+            return Err(Self::fail(&env, &user_address, symbol_short!("repay"), symbol_short!("balance"), amount, user.balance, Error::InsufficientBalance));
+        }
+        user.balance = Self::checked_debit(user.balance, amount)?;
+        Self::track_balance(&env, &user, -amount, Bucket::Treasury);
+        users.set(user_address.clone(), user);
+        users.save();
+
+        Self::apply_loan_repayment(&env, &mut loan, amount);
+        loans.set(loan_id, loan.clone());
+        env.storage().instance().set(&DataKey::Loans, &loans);
+
+        Ok(loan.owed - loan.repaid)
+    }
+
+    // Add funds to the pool micro-loans are paid out from (treasurer only)
+    pub fn fund_loan_pool(env: Env, caller: Address, amount: i128) -> Result<(), Error> {
+        Self::require_role(&env, &caller, Role::Treasurer)?;
+        Self::audit(&env, &caller, symbol_short!("fund_loan"), AuditTarget::Global, amount);
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        let usdc: Address = env.storage().instance().get(&ConfigKey::UsdcToken).ok_or(Error::UsdcTokenNotConfigured)?;
+
+        let pool: i128 = env.storage().instance().get(&CounterKey::LoanPool).unwrap_or(0);
+        env.storage().instance().set(&CounterKey::LoanPool, &(pool + amount));
+        Self::post(&env, &caller, Bucket::External, Bucket::Treasury, amount);
+
+        // Loans are only paid out of USDC the treasurer actually sent
+        token::Client::new(&env, &usdc).transfer(&caller, &env.current_contract_address(), &amount);
+
+        Ok(())
+    }
+
+    // Set the fee charged on new loans (admin only)
+    pub fn set_loan_fee(env: Env, fee_bps: u32) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("loan_fee"), AuditTarget::Global, fee_bps as i128);
+
+        if fee_bps as i128 > BPS_DENOMINATOR {
+            return Err(Error::InvalidFee);
+        }
+
+        env.storage().instance().set(&ConfigKey::LoanFeeBps, &fee_bps);
+
+        Ok(())
+    }
+
+    // Get a user's loans
+    pub fn get_loans(env: Env, user_address: Address, cursor: u32, limit: u32) -> (Vec<Loan>, Option<u32>) {
+        Self::page(Self::loans_for(&env, &user_address), cursor, limit)
+    }
+
+    // Whether an active loan is past its due date
+    pub fn is_loan_overdue(env: Env, loan_id: u64) -> Result<bool, Error> {
+        let loans: Map<u64, Loan> = env.storage().instance().get(&DataKey::Loans).unwrap_or(Map::new(&env));
+        let loan = loans.get(loan_id).ok_or(Error::LoanNotFound)?;
+
+        Ok(loan.status == String::from_str(&env, "active") && env.ledger().timestamp() > loan.due_at)
+    }
+
+    // Configure collateralized credit lines (admin only)
+    pub fn set_credit_config(env: Env, config: CreditConfig) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("credit"), AuditTarget::Global, config.max_ltv_bps as i128);
+
+        if config.max_ltv_bps == 0 || config.max_ltv_bps >= config.liquidation_ltv_bps || config.liquidation_ltv_bps as i128 > BPS_DENOMINATOR {
+            return Err(Error::InvalidLtv);
+        }
+        if config.liquidation_penalty_bps as i128 > BPS_DENOMINATOR {
+            return Err(Error::InvalidFee);
+        }
+
+        env.storage().instance().set(&ConfigKey::CreditConfig, &config);
+
+        Ok(())
+    }
+
+    // Move USDC from the user's balance into collateral
+    pub fn lock_collateral(env: Env, user_address: Address, amount: i128) -> Result<(), Error> {
+        user_address.require_auth();
+        Self::ensure_module_active(&env, &user_address, symbol_short!("credit"))?;
+        Self::ensure_not_restricted(&env, &user_address, symbol_short!("credit"))?;
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+        if user.balance < amount {
+            return Err(Self::fail(&env, &user_address, symbol_short!("credit"), symbol_short!("balance"), amount, user.balance, Error::InsufficientBalance));
+        }
+        user.balance = Self::checked_debit(user.balance, amount)?;
+        Self::track_balance(&env, &user, -amount, Bucket::LockedSavings);
+        users.set(user_address.clone(), user);
+        users.save();
+
+        let mut line = Self::accrued_credit_line(&env, &user_address);
+        line.collateral += amount;
+        Self::save_credit_line(&env, &user_address, &line);
+
+        env.events().publish((symbol_short!("coll_lock"), user_address), amount);
+
+        Ok(())
+    }
+
+    // Return collateral to the user's balance, as long as the remaining debt stays within the max LTV
+    pub fn unlock_collateral(env: Env, user_address: Address, amount: i128) -> Result<(), Error> {
+        user_address.require_auth();
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        let config = Self::credit_config(&env)?;
+        let mut line = Self::accrued_credit_line(&env, &user_address);
+        if line.collateral < amount {
+            return Err(Error::InsufficientCollateral);
+        }
+        line.collateral -= amount;
+        if line.debt * BPS_DENOMINATOR > line.collateral * config.max_ltv_bps as i128 {
+            return Err(Self::fail(&env, &user_address, symbol_short!("credit"), symbol_short!("ltv"), amount, 0, Error::CollateralBacksDebt));
+        }
+        Self::save_credit_line(&env, &user_address, &line);
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+        user.balance = Self::checked_credit(user.balance, amount)?;
+        Self::track_balance(&env, &user, amount, Bucket::LockedSavings);
+        users.set(user_address.clone(), user);
+        users.save();
+
+        env.events().publish((symbol_short!("coll_free"), user_address), amount);
+
+        Ok(())
+    }
+
+    // Borrow against locked collateral, paid out from the loan pool into the user's balance
+    pub fn borrow(env: Env, user_address: Address, amount: i128) -> Result<(), Error> {
+        user_address.require_auth();
+        Self::ensure_module_active(&env, &user_address, symbol_short!("credit"))?;
+        Self::ensure_not_restricted(&env, &user_address, symbol_short!("credit"))?;
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        let config = Self::credit_config(&env)?;
+        let mut line = Self::accrued_credit_line(&env, &user_address);
+        let max_debt = line.collateral * config.max_ltv_bps as i128 / BPS_DENOMINATOR;
+        if line.debt + amount > max_debt {
+            return Err(Self::fail(&env, &user_address, symbol_short!("credit"), symbol_short!("ltv"), line.debt + amount, max_debt, Error::BorrowExceedsCreditLimit));
+        }
+
+        let pool: i128 = env.storage().instance().get(&CounterKey::LoanPool).unwrap_or(0);
+        if pool < amount {
+            return Err(Error::InsufficientLoanPool);
+        }
+        env.storage().instance().set(&CounterKey::LoanPool, &(pool - amount));
+
+        line.debt += amount;
+        Self::save_credit_line(&env, &user_address, &line);
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+        user.balance = Self::checked_credit(user.balance, amount)?;
+        Self::track_balance(&env, &user, amount, Bucket::Treasury);
+        users.set(user_address.clone(), user);
+        users.save();
+
+        env.events().publish((symbol_short!("borrow"), user_address), (amount, line.debt));
+
+        Ok(())
+    }
+
+    // Repay credit line debt from the user's balance. Returns the remaining debt.
+    pub fn repay(env: Env, user_address: Address, amount: i128) -> Result<i128, Error> {
+        user_address.require_auth();
+
+        let mut line = Self::accrued_credit_line(&env, &user_address);
+        let amount = amount.min(line.debt);
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+        if user.balance < amount {
+            return Err(Self::fail(&env, &user_address, symbol_short!("repay"), symbol_short!("balance"), amount, user.balance, Error::InsufficientBalance));
+        }
+        user.balance = Self::checked_debit(user.balance, amount)?;
+        Self::track_balance(&env, &user, -amount, Bucket::Treasury);
+        users.set(user_address.clone(), user);
+        users.save();
+
+        line.debt -= amount;
+        Self::save_credit_line(&env, &user_address, &line);
+
+        let pool: i128 = env.storage().instance().get(&CounterKey::LoanPool).unwrap_or(0);
+        env.storage().instance().set(&CounterKey::LoanPool, &(pool + amount));
+
+        env.events().publish((symbol_short!("repay"), user_address), (amount, line.debt));
+
+        Ok(line.debt)
+    }
+
+    // Close a position whose debt has grown past the liquidation LTV: the debt plus a penalty is
+    // taken from the collateral and the rest stays locked for the user. Callable by anyone.
+    pub fn liquidate(env: Env, user_address: Address) -> Result<i128, Error> {
+        let config = Self::credit_config(&env)?;
+        let mut line = Self::accrued_credit_line(&env, &user_address);
+
+        if line.debt == 0 || line.debt * BPS_DENOMINATOR <= line.collateral * config.liquidation_ltv_bps as i128 {
+            return Err(Error::PositionHealthy);
+        }
+
+        let penalty = line.debt * config.liquidation_penalty_bps as i128 / BPS_DENOMINATOR;
+        let seized = (line.debt + penalty).min(line.collateral);
+        let repaid = seized.min(line.debt);
+
+        let pool: i128 = env.storage().instance().get(&CounterKey::LoanPool).unwrap_or(0);
+        env.storage().instance().set(&CounterKey::LoanPool, &(pool + repaid));
+        Self::post(&env, &user_address, Bucket::LockedSavings, Bucket::Treasury, repaid);
+        if seized > repaid {
+            Self::collect_fee(&env, &user_address, Bucket::LockedSavings, seized - repaid);
+        }
+
+        line.collateral -= seized;
+        line.debt -= repaid;
+        Self::save_credit_line(&env, &user_address, &line);
+
+        env.events().publish((symbol_short!("liquidate"), user_address), (seized, line.debt));
+
+        Ok(seized)
+    }
+
+    // Get a user's credit line with interest accrued up to now
+    pub fn get_credit_line(env: Env, user_address: Address) -> CreditLine {
+        Self::accrued_credit_line(&env, &user_address)
+    }
+
+    // Add or replace a remittance corridor (admin only)
+    pub fn set_corridor(env: Env, corridor: Corridor) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("corridor"), AuditTarget::Key(corridor.country.clone()), corridor.flat_fee);
+
+        if !Self::is_supported_currency(&corridor.currency) {
+            return Err(Error::UnsupportedCurrency);
+        }
+        if corridor.fee_bps as i128 > BPS_DENOMINATOR || corridor.flat_fee < 0 {
+            return Err(Error::InvalidFee);
+        }
+
+        let mut corridors: Map<Symbol, Corridor> = env.storage().instance().get(&ConfigKey::Corridors).unwrap_or(Map::new(&env));
+        corridors.set(corridor.country.clone(), corridor);
+        env.storage().instance().set(&ConfigKey::Corridors, &corridors);
+
+        Ok(())
+    }
+
+    // Get all remittance corridors
+    pub fn get_corridors(env: Env, cursor: u32, limit: u32) -> (Vec<Corridor>, Option<u32>) {
+        let corridors: Map<Symbol, Corridor> = env.storage().instance().get(&ConfigKey::Corridors).unwrap_or(Map::new(&env));
+
+        Self::page(corridors.values(), cursor, limit)
+    }
+
+    // Save someone the user regularly sends money to
+    pub fn add_beneficiary(
+        env: Env,
+        user_address: Address,
+        name: String,
+        phone: String,
+        country: Symbol,
+        payout_method: String,
+    ) -> Result<u64, Error> {
+        user_address.require_auth();
+
+        let users = Users::load(&env);
+        if !users.contains_key(user_address.clone()) {
+            return Err(Error::UserNotFound);
+        }
+        let corridors: Map<Symbol, Corridor> = env.storage().instance().get(&ConfigKey::Corridors).unwrap_or(Map::new(&env));
+        if !corridors.contains_key(country.clone()) {
+            return Err(Error::UnsupportedCountry);
+        }
+
+        let mut beneficiaries: Map<u64, Beneficiary> = env.storage().instance().get(&DataKey::Beneficiaries).unwrap_or(Map::new(&env));
+        let beneficiary_id: u64 = env.storage().instance().get(&CounterKey::BeneficiaryId).unwrap_or(0) + 1;
+
+        beneficiaries.set(beneficiary_id, Beneficiary {
+            id: beneficiary_id,
+            owner: user_address.clone(),
+            name,
+            phone,
+            country,
+            payout_method,
+        });
+        env.storage().instance().set(&DataKey::Beneficiaries, &beneficiaries);
+        env.storage().instance().set(&CounterKey::BeneficiaryId, &beneficiary_id);
+
+        Ok(beneficiary_id)
+    }
+
+    // Remove a saved beneficiary
+    pub fn remove_beneficiary(env: Env, user_address: Address, beneficiary_id: u64) -> Result<(), Error> {
+        user_address.require_auth();
+
+        let mut beneficiaries: Map<u64, Beneficiary> = env.storage().instance().get(&DataKey::Beneficiaries).unwrap_or(Map::new(&env));
+        let beneficiary = beneficiaries.get(beneficiary_id).ok_or(Error::BeneficiaryNotFound)?;
+        if beneficiary.owner != user_address {
+            return Err(Error::Unauthorized);
+        }
+
+        beneficiaries.remove(beneficiary_id);
+        env.storage().instance().set(&DataKey::Beneficiaries, &beneficiaries);
+
+        Ok(())
+    }
+
+    // Get a user's saved beneficiaries
+    pub fn get_beneficiaries(env: Env, user_address: Address, cursor: u32, limit: u32) -> (Vec<Beneficiary>, Option<u32>) {
+        let beneficiaries: Map<u64, Beneficiary> = env.storage().instance().get(&DataKey::Beneficiaries).unwrap_or(Map::new(&env));
+        let mut user_beneficiaries = vec![&env];
+
+        for (_, beneficiary) in beneficiaries.iter() {
+            if beneficiary.owner == user_address {
+                user_beneficiaries.push_back(beneficiary);
+            }
+        }
+
+        Self::page(user_beneficiaries, cursor, limit)
+    }
+
+    // Send money home to a beneficiary at the corridor's rate. The corridor fee is charged on top
+    // and collected once the payout partner completes the remittance.
+    pub fn send_remittance(env: Env, user_address: Address, beneficiary_id: u64, usdc_amount: i128) -> Result<u64, Error> {
+        user_address.require_auth();
+        Self::ensure_module_active(&env, &user_address, symbol_short!("remit"))?;
+        Self::ensure_not_restricted(&env, &user_address, symbol_short!("remit"))?;
+        Self::check_subaccount(&env, &user_address, symbol_short!("remit"), usdc_amount)?;
+        Self::require_pin(&env, &user_address, usdc_amount, symbol_short!("remit"))?;
+        Self::check_country(&env, &user_address, usdc_amount, None, symbol_short!("remit"))?;
+
+        if usdc_amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        let beneficiaries: Map<u64, Beneficiary> = env.storage().instance().get(&DataKey::Beneficiaries).unwrap_or(Map::new(&env));
+        let beneficiary = beneficiaries.get(beneficiary_id).ok_or(Error::BeneficiaryNotFound)?;
+        if beneficiary.owner != user_address {
+            return Err(Error::Unauthorized);
+        }
+
+        let corridors: Map<Symbol, Corridor> = env.storage().instance().get(&ConfigKey::Corridors).unwrap_or(Map::new(&env));
+        let corridor = corridors.get(beneficiary.country.clone()).ok_or(Error::UnsupportedCountry)?;
+        if !corridor.active {
+            return Err(Self::fail(&env, &user_address, symbol_short!("remit"), symbol_short!("inactive"), usdc_amount, 0, Error::CorridorUnavailable));
+        }
+
+        let fee = corridor.flat_fee + usdc_amount * corridor.fee_bps as i128 / BPS_DENOMINATOR;
+        let mut users = Users::load(&env);
+        let mut user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+        if user.balance < usdc_amount + fee {
+            return Err(Self::fail(&env, &user_address, symbol_short!("remit"), symbol_short!("balance"), usdc_amount + fee, user.balance, Error::InsufficientBalance));
+        }
+        user.balance = Self::checked_debit(user.balance, usdc_amount + fee)?;
+        Self::track_balance(&env, &user, -(usdc_amount + fee), Bucket::Escrowed);
+        users.set(user_address.clone(), user);
+        users.save();
+
+        Self::record_spending(&env, &user_address, SpendCategory::Transfer, usdc_amount + fee);
+
+        let mut remittances: Map<u64, Remittance> = env.storage().instance().get(&DataKey::Remittances).unwrap_or(Map::new(&env));
+        let remittance_id: u64 = env.storage().instance().get(&CounterKey::RemittanceId).unwrap_or(0) + 1;
+        let (local_amount, _, spread) = Self::fx_quote(&env, usdc_amount, &corridor.currency)?;
+
+        remittances.set(remittance_id, Remittance {
+            id: remittance_id,
+            sender: user_address.clone(),
+            beneficiary_id,
+            usdc_amount,
+            fee,
+            local_amount,
+            currency: corridor.currency.clone(),
+            status: String::from_str(&env, "pending"),
+            timestamp: env.ledger().timestamp(),
+            spread,
+        });
+        env.storage().instance().set(&DataKey::Remittances, &remittances);
+        env.storage().instance().set(&CounterKey::RemittanceId, &remittance_id);
+        Self::index_record(&env, EntryKey::UserRemittances(user_address.clone()), remittance_id);
+
+        env.events().publish((symbol_short!("remit"), user_address, beneficiary.country), (remittance_id, usdc_amount, local_amount, corridor.currency));
+
+        Ok(remittance_id)
+    }
+
+    // Update a remittance as the payout partner processes it. Completing collects the fee and spread;
+    // failing refunds the amount and fee to the sender (status updater only).
+    pub fn set_remittance_status(env: Env, caller: Address, remittance_id: u64, status: PaymentStatus) -> Result<(), Error> {
+        Self::require_role(&env, &caller, Role::StatusUpdater)?;
+
+        let mut remittances: Map<u64, Remittance> = env.storage().instance().get(&DataKey::Remittances).unwrap_or(Map::new(&env));
+        let mut remittance = remittances.get(remittance_id).ok_or(Error::RemittanceNotFound)?;
+        Self::check_status_change(&env, &remittance.status)?;
+        remittance.status = Self::status_string(&env, status);
+
+        match status {
+            PaymentStatus::Completed => {
+                Self::post(&env, &remittance.sender, Bucket::Escrowed, Bucket::External, remittance.usdc_amount - remittance.spread);
+                Self::collect_fee(&env, &remittance.sender, Bucket::Escrowed, remittance.fee + remittance.spread);
+            }
+            PaymentStatus::Failed => {
+                let refund = remittance.usdc_amount + remittance.fee;
+                let mut users = Users::load(&env);
+                let mut user = users.get(remittance.sender.clone()).ok_or(Error::UserNotFound)?;
+                user.balance = Self::checked_credit(user.balance, refund)?;
+                Self::track_balance(&env, &user, refund, Bucket::Escrowed);
+                users.set(remittance.sender.clone(), user);
+                users.save();
+            }
+            PaymentStatus::Pending | PaymentStatus::Processing => {}
+        }
+
+        remittances.set(remittance_id, remittance.clone());
+        env.storage().instance().set(&DataKey::Remittances, &remittances);
+
+        Self::audit(&env, &caller, symbol_short!("remit_st"), AuditTarget::Global, remittance_id as i128);
+        match status {
+            PaymentStatus::Completed => Self::notify(&env, &remittance.sender, symbol_short!("rem_done"), NotificationRef::Id(remittance_id), remittance.usdc_amount),
+            PaymentStatus::Failed => Self::notify(&env, &remittance.sender, symbol_short!("rem_fail"), NotificationRef::Id(remittance_id), remittance.usdc_amount),
+            PaymentStatus::Pending | PaymentStatus::Processing => {}
+        }
+        env.events().publish((symbol_short!("remit_st"), remittance.sender), (remittance_id, remittance.status));
+
+        Ok(())
+    }
+
+    // Get remittances sent by a user
+    pub fn get_remittances(env: Env, user_address: Address, cursor: u32, limit: u32) -> (Vec<Remittance>, Option<u32>) {
+        Self::page(Self::remittances_for(&env, &user_address), cursor, limit)
+    }
+
+    // List unacknowledged operator tasks of a kind (`bill`, `withdraw`), oldest first
+    pub fn get_pending_tasks(env: Env, kind: Symbol, cursor: u32, limit: u32) -> (Vec<OperatorTask>, Option<u32>) {
+        let outbox: Map<u64, OperatorTask> = env.storage().instance().get(&DataKey::Outbox).unwrap_or(Map::new(&env));
+        let mut tasks = vec![&env];
+
+        for (_, task) in outbox.iter() {
+            if task.kind == kind {
+                tasks.push_back(task);
+            }
+        }
+
+        Self::page(tasks, cursor, limit)
+    }
+
+    // Remove a task from the outbox once it has been processed (status updater only)
+    pub fn ack_task(env: Env, caller: Address, task_id: u64) -> Result<(), Error> {
+        Self::require_role(&env, &caller, Role::StatusUpdater)?;
+
+        let mut outbox: Map<u64, OperatorTask> = env.storage().instance().get(&DataKey::Outbox).unwrap_or(Map::new(&env));
+        let task = outbox.get(task_id).ok_or(Error::TaskNotFound)?;
+        outbox.remove(task_id);
+        env.storage().instance().set(&DataKey::Outbox, &outbox);
+
+        env.events().publish((symbol_short!("task_ack"), caller), (task_id, task.kind, task.reference));
+
+        Ok(())
+    }
+
+    // Dispute a settled bill payment or a completed withdrawal
+    pub fn open_dispute(env: Env, user_address: Address, reference_id: String, reason: String) -> Result<u64, Error> {
+        user_address.require_auth();
+        Self::check_memo(&Some(reason.clone()))?;
+
+        let completed = String::from_str(&env, "completed");
+
+        // Failed withdrawals were already refunded, so only completed ones can be disputed
+        let amount = if let Some(payment) = Self::load_bill(&env, &reference_id) {
+            if payment.user_address != user_address {
+                return Err(Error::Unauthorized);
+            }
+            if payment.status != completed && payment.status != String::from_str(&env, "failed") {
+                return Err(Error::PaymentNotSettled);
+            }
+            payment.amount
+        } else if let Some(withdrawal) = Self::load_withdrawal(&env, &reference_id) {
+            if withdrawal.user_address != user_address {
+                return Err(Error::Unauthorized);
+            }
+            if withdrawal.status != completed {
+                return Err(Error::WithdrawalNotCompleted);
+            }
+            withdrawal.usdc_amount
+        } else {
+            return Err(Error::TransactionNotFound);
+        };
+
+        let mut disputes: Map<u64, Dispute> = env.storage().instance().get(&DataKey::Disputes).unwrap_or(Map::new(&env));
+        for (_, dispute) in disputes.iter() {
+            if dispute.reference_id == reference_id {
+                return Err(Error::TransactionAlreadyDisputed);
+            }
+        }
+
+        let dispute_id: u64 = env.storage().instance().get(&CounterKey::DisputeId).unwrap_or(0) + 1;
+        disputes.set(dispute_id, Dispute {
+            id: dispute_id,
+            user_address: user_address.clone(),
+            reference_id: reference_id.clone(),
+            amount,
+            reason,
+            status: String::from_str(&env, "open"),
+            opened_at: env.ledger().timestamp(),
+            resolved_at: 0,
+        });
+        env.storage().instance().set(&DataKey::Disputes, &disputes);
+        env.storage().instance().set(&CounterKey::DisputeId, &dispute_id);
+
+        env.events().publish((symbol_short!("disp_open"), user_address), (dispute_id, reference_id, amount));
+
+        Ok(dispute_id)
+    }
+
+    // Get disputes with a status (`open`, `refunded`, `upheld`)
+    pub fn get_disputes(env: Env, status: String, cursor: u32, limit: u32) -> (Vec<Dispute>, Option<u32>) {
+        let disputes: Map<u64, Dispute> = env.storage().instance().get(&DataKey::Disputes).unwrap_or(Map::new(&env));
+        let mut matching = vec![&env];
+
+        for (_, dispute) in disputes.iter() {
+            if dispute.status == status {
+                matching.push_back(dispute);
+            }
+        }
+
+        Self::page(matching, cursor, limit)
+    }
+
+    // Close an open dispute, refunding the disputed amount or upholding the transaction (compliance only)
+    pub fn resolve_dispute(env: Env, caller: Address, dispute_id: u64, outcome: DisputeOutcome) -> Result<(), Error> {
+        Self::require_role(&env, &caller, Role::Compliance)?;
+
+        let mut disputes: Map<u64, Dispute> = env.storage().instance().get(&DataKey::Disputes).unwrap_or(Map::new(&env));
+        let mut dispute = disputes.get(dispute_id).ok_or(Error::DisputeNotFound)?;
+        if dispute.status != String::from_str(&env, "open") {
+            return Err(Error::DisputeAlreadyResolved);
+        }
+
+        match outcome {
+            DisputeOutcome::Refund => {
+                Self::audit(&env, &caller, symbol_short!("disp_rfnd"), AuditTarget::Id(dispute.reference_id.clone()), dispute.amount);
+
+                let mut users = Users::load(&env);
+                let mut user = users.get(dispute.user_address.clone()).ok_or(Error::UserNotFound)?;
+                user.balance = Self::checked_credit(user.balance, dispute.amount)?;
+                Self::track_balance(&env, &user, dispute.amount, Bucket::Treasury);
+                users.set(dispute.user_address.clone(), user);
+                users.save();
+
+                dispute.status = String::from_str(&env, "refunded");
+            }
+            DisputeOutcome::Upheld => {
+                Self::audit(&env, &caller, symbol_short!("disp_uphd"), AuditTarget::Id(dispute.reference_id.clone()), dispute.amount);
+
+                dispute.status = String::from_str(&env, "upheld");
+            }
+        }
+        dispute.resolved_at = env.ledger().timestamp();
+
+        disputes.set(dispute_id, dispute.clone());
+        env.storage().instance().set(&DataKey::Disputes, &disputes);
+
+        Self::notify(&env, &dispute.user_address, symbol_short!("disp_done"), NotificationRef::Id(dispute_id), dispute.amount);
+        env.events().publish((symbol_short!("disp_done"), dispute.user_address), (dispute_id, dispute.status));
+
+        Ok(())
+    }
+
+    // Issue a new deposit reference so incoming deposits can be reconciled against it
+    pub fn create_deposit_reference(env: Env, user_address: Address) -> Result<String, Error> {
+        user_address.require_auth();
+
+        let users = Users::load(&env);
+        if !users.contains_key(user_address.clone()) {
+            return Err(Error::UserNotFound);
+        }
+
+        let ref_number: u32 = env.storage().instance().get(&CounterKey::DepositRefNumber).unwrap_or(0) + 1;
+        let ref_code = Self::demo_string(&env, b"ref_", ref_number);
+
+        let mut references: Map<String, Address> = env.storage().instance().get(&DataKey::DepositRefs).unwrap_or(Map::new(&env));
+        references.set(ref_code.clone(), user_address.clone());
+        env.storage().instance().set(&DataKey::DepositRefs, &references);
+        env.storage().instance().set(&CounterKey::DepositRefNumber, &ref_number);
+
+        env.events().publish((symbol_short!("dep_ref"), user_address), ref_code.clone());
+
+        Ok(ref_code)
+    }
+
+    // Deposit to a user and tag it with one of their deposit references
+    pub fn deposit_with_reference(env: Env, user_address: Address, amount: i128, ref_code: String) -> Result<u64, Error> {
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        let references: Map<String, Address> = env.storage().instance().get(&DataKey::DepositRefs).unwrap_or(Map::new(&env));
+        if references.get(ref_code.clone()) != Some(user_address.clone()) {
+            return Err(Error::UnknownDepositReference);
+        }
+
+        Self::deposit(env.clone(), user_address.clone(), amount)?;
+
+        let mut deposits: Map<u64, TaggedDeposit> = env.storage().instance().get(&DataKey::TaggedDeposits).unwrap_or(Map::new(&env));
+        let deposit_id: u64 = env.storage().instance().get(&CounterKey::TaggedDepositId).unwrap_or(0) + 1;
+
+        deposits.set(deposit_id, TaggedDeposit {
+            id: deposit_id,
+            user_address: user_address.clone(),
+            ref_code: ref_code.clone(),
+            amount,
+            timestamp: env.ledger().timestamp(),
+        });
+        env.storage().instance().set(&DataKey::TaggedDeposits, &deposits);
+        env.storage().instance().set(&CounterKey::TaggedDepositId, &deposit_id);
+
+        env.events().publish((symbol_short!("dep_tag"), user_address), (deposit_id, ref_code, amount));
+
+        Ok(deposit_id)
+    }
+
+    // Get the deposits tagged with a user's reference
+    pub fn get_deposits_by_reference(env: Env, user_address: Address, ref_code: String, cursor: u32, limit: u32) -> (Vec<TaggedDeposit>, Option<u32>) {
+        let deposits: Map<u64, TaggedDeposit> = env.storage().instance().get(&DataKey::TaggedDeposits).unwrap_or(Map::new(&env));
+        let mut matching = vec![&env];
+
+        for (_, deposit) in deposits.iter() {
+            if deposit.user_address == user_address && deposit.ref_code == ref_code {
+                matching.push_back(deposit);
+            }
+        }
+
+        Self::page(matching, cursor, limit)
+    }
+
+    // Set the minimum send amount and dust threshold; 0 disables either check (admin only)
+    pub fn set_dust_policy(env: Env, min_send: i128, dust_threshold: i128) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("dust"), AuditTarget::Global, dust_threshold);
+
+        if min_send < 0 || dust_threshold < 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        env.storage().instance().set(&ConfigKey::DustPolicy, &DustPolicy { min_send, dust_threshold });
+
+        Ok(())
+    }
+
+    // Get the current dust policy
+    pub fn get_dust_policy(env: Env) -> DustPolicy {
+        Self::dust_policy(&env)
+    }
+
+    // Clear a balance below the dust threshold, consolidating it into another account or,
+    // without one, donating it to the fee pool
+    pub fn sweep_dust(env: Env, user_address: Address, to_address: Option<Address>) -> Result<i128, Error> {
+        user_address.require_auth();
+        Self::ensure_not_restricted(&env, &user_address, symbol_short!("dust"))?;
+        if let Some(to_address) = &to_address {
+            Self::ensure_not_restricted(&env, to_address, symbol_short!("dust"))?;
+        }
+
+        let policy = Self::dust_policy(&env);
+        let mut users = Users::load(&env);
+        let mut user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+
+        let dust = user.balance;
+        if dust <= 0 || dust >= policy.dust_threshold {
+            return Err(Error::BalanceNotDust);
+        }
+
+        user.balance = 0;
+        Self::track_balance(&env, &user, -dust, Bucket::Clearing);
+        users.set(user_address.clone(), user);
+
+        match to_address.clone() {
+            Some(to_address) => {
+                if to_address == user_address {
+                    return Err(Error::CannotSweepToTheSameAccount);
+                }
+                let mut recipient = users.get(to_address.clone()).ok_or(Error::RecipientNotFound)?;
+                recipient.balance = Self::checked_credit(recipient.balance, dust)?;
+                Self::track_balance(&env, &recipient, dust, Bucket::Clearing);
+                users.set(to_address, recipient);
+            }
+            None => Self::collect_fee(&env, &user_address, Bucket::Clearing, dust),
+        }
+        users.save();
+
+        env.events().publish((symbol_short!("dust"), user_address), (dust, to_address));
+
+        Ok(dust)
+    }
+
+    // Set the hourly send and bill payment limits for a tier (admin only)
+    pub fn set_rate_limit(env: Env, tier: UserTier, limit: RateLimit) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("rate_lim"), AuditTarget::Global, limit.max_sends as i128);
+
+        let mut limits: Map<UserTier, RateLimit> = env.storage().instance().get(&ConfigKey::RateLimits).unwrap_or(Map::new(&env));
+        limits.set(tier, limit);
+        env.storage().instance().set(&ConfigKey::RateLimits, &limits);
+
+        Ok(())
+    }
+
+    // Get how much of their hourly limits a user has used
+    pub fn get_rate_limit_status(env: Env, user_address: Address) -> Result<RateLimitStatus, Error> {
+        let users = Users::load(&env);
+        let user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+
+        let tier = Self::user_tier(&user);
+        let limit = Self::rate_limit(&env, tier);
+        let sends = Self::recent_operations(&env, &user_address, symbol_short!("send"));
+        let bills = Self::recent_operations(&env, &user_address, symbol_short!("pay_bill"));
+
+        let mut resets_at = 0;
+        for oldest in [sends.first(), bills.first()].into_iter().flatten() {
+            if resets_at == 0 || oldest + RATE_LIMIT_WINDOW_SECS < resets_at {
+                resets_at = oldest + RATE_LIMIT_WINDOW_SECS;
+            }
+        }
+
+        Ok(RateLimitStatus {
+            tier,
+            sends_used: sends.len(),
+            max_sends: limit.max_sends,
+            bills_used: bills.len(),
+            max_bills: limit.max_bills,
+            resets_at,
+        })
+    }
+
+    // Save a contact, or rename one already saved
+    pub fn add_contact(env: Env, user_address: Address, contact_address: Address, nickname: String) -> Result<(), Error> {
+        user_address.require_auth();
+
+        if contact_address == user_address {
+            return Err(Error::CannotAddYourselfAsAContact);
+        }
+        if nickname.len() == 0 || nickname.len() > MAX_DISPLAY_NAME_LEN {
+            return Err(Error::InvalidNickname);
+        }
+
+        let users = Users::load(&env);
+        if !users.contains_key(user_address.clone()) {
+            return Err(Error::UserNotFound);
+        }
+        if !users.contains_key(contact_address.clone()) {
+            return Err(Error::ContactNotFound);
+        }
+
+        let mut contacts: Map<Address, Vec<Contact>> = env.storage().instance().get(&DataKey::Contacts).unwrap_or(Map::new(&env));
+        let mut user_contacts = contacts.get(user_address.clone()).unwrap_or(vec![&env]);
+
+        match user_contacts.iter().position(|contact| contact.address == contact_address) {
+            Some(index) => user_contacts.set(index as u32, Contact { address: contact_address, nickname }),
+            None => {
+                if user_contacts.len() >= MAX_CONTACTS {
+                    return Err(Error::ContactLimitReached);
+                }
+                user_contacts.push_back(Contact { address: contact_address, nickname });
+            }
+        }
+
+        contacts.set(user_address, user_contacts);
+        env.storage().instance().set(&DataKey::Contacts, &contacts);
+
+        Ok(())
+    }
+
+    // Remove a saved contact
+    pub fn remove_contact(env: Env, user_address: Address, contact_address: Address) -> Result<(), Error> {
+        user_address.require_auth();
+
+        let mut contacts: Map<Address, Vec<Contact>> = env.storage().instance().get(&DataKey::Contacts).unwrap_or(Map::new(&env));
+        let mut user_contacts = contacts.get(user_address.clone()).unwrap_or(vec![&env]);
+
+        let index = user_contacts.iter().position(|contact| contact.address == contact_address).ok_or(Error::ContactNotFound)?;
+        user_contacts.remove(index as u32);
+
+        if user_contacts.is_empty() {
+            contacts.remove(user_address);
+        } else {
+            contacts.set(user_address, user_contacts);
+        }
+        env.storage().instance().set(&DataKey::Contacts, &contacts);
+
+        Ok(())
+    }
+
+    // Get a user's saved contacts
+    pub fn get_contacts(env: Env, user_address: Address, cursor: u32, limit: u32) -> (Vec<Contact>, Option<u32>) {
+        let contacts: Map<Address, Vec<Contact>> = env.storage().instance().get(&DataKey::Contacts).unwrap_or(Map::new(&env));
+
+        Self::page(contacts.get(user_address).unwrap_or(vec![&env]), cursor, limit)
+    }
+
+    // Add a standing instruction that runs on every deposit
+    pub fn set_auto_rule(env: Env, user_address: Address, rule: AutoRule) -> Result<u64, Error> {
+        user_address.require_auth();
+
+        match rule.clone() {
+            AutoRule::SaveToGoal(goal_id, share_bps) => {
+                let goals: Map<u64, SavingsGoal> = env.storage().instance().get(&DataKey::Goals).unwrap_or(Map::new(&env));
+                if goals.get(goal_id).ok_or(Error::GoalNotFound)?.owner != user_address {
+                    return Err(Error::Unauthorized);
+                }
+                if share_bps == 0 || share_bps as i128 > BPS_DENOMINATOR {
+                    return Err(Error::InvalidShare);
+                }
+            }
+            AutoRule::PayBill(bill_type, account_number, threshold, amount) => {
+                if threshold < 0 {
+                    return Err(Error::InvalidAmount);
+                }
+                Self::validate_bill(&env, &user_address, &bill_type, &account_number, amount)?;
+            }
+        }
+
+        let mut rules: Map<u64, AutoRuleEntry> = env.storage().instance().get(&DataKey::AutoRules).unwrap_or(Map::new(&env));
+        if Self::rules_for(&env, &user_address).len() >= MAX_AUTO_RULES {
+            return Err(Error::RuleLimitReached);
+        }
+
+        let rule_id: u64 = env.storage().instance().get(&CounterKey::RuleId).unwrap_or(0) + 1;
+        rules.set(rule_id, AutoRuleEntry { id: rule_id, owner: user_address.clone(), rule });
+        env.storage().instance().set(&DataKey::AutoRules, &rules);
+        env.storage().instance().set(&CounterKey::RuleId, &rule_id);
+
+        Ok(rule_id)
+    }
+
+    // Get a user's standing instructions
+    pub fn get_rules(env: Env, user_address: Address, cursor: u32, limit: u32) -> (Vec<AutoRuleEntry>, Option<u32>) {
+        Self::page(Self::rules_for(&env, &user_address), cursor, limit)
+    }
+
+    // Remove a standing instruction
+    pub fn delete_rule(env: Env, user_address: Address, rule_id: u64) -> Result<(), Error> {
+        user_address.require_auth();
+
+        let mut rules: Map<u64, AutoRuleEntry> = env.storage().instance().get(&DataKey::AutoRules).unwrap_or(Map::new(&env));
+        let entry = rules.get(rule_id).ok_or(Error::RuleNotFound)?;
+        if entry.owner != user_address {
+            return Err(Error::Unauthorized);
+        }
+
+        rules.remove(rule_id);
+        env.storage().instance().set(&DataKey::AutoRules, &rules);
+
+        Ok(())
+    }
+
+    // Send to several recipients at once; the sender pays the fees. Legs are executed
+    // independently and each gets its own result. Repeated recipients are merged into one leg
+    // when `merge_duplicates` is set and rejected otherwise.
+    pub fn send_batch(env: Env, from_address: Address, legs: Vec<BatchLeg>, merge_duplicates: bool) -> Result<Vec<BatchLegResult>, Error> {
+        from_address.require_auth();
+
+        if legs.is_empty() || legs.len() > MAX_BATCH_LEGS {
+            return Err(Error::InvalidBatchSize);
+        }
+
+        // Combined amount per recipient and how many legs named it, plus first-seen order
+        let mut recipients: Map<Address, (i128, u32)> = Map::new(&env);
+        let mut order: Vec<Address> = vec![&env];
+        for leg in legs.iter() {
+            match recipients.get(leg.to_address.clone()) {
+                Some((total, count)) => recipients.set(leg.to_address.clone(), (total + leg.amount, count + 1)),
+                None => {
+                    recipients.set(leg.to_address.clone(), (leg.amount, 1));
+                    order.push_back(leg.to_address.clone());
+                }
+            }
+        }
+
+        // Step-up applies to the batch as a whole, so a large send cannot avoid it by being split
+        // into legs under the soft limit
+        let total = legs.iter().fold(0i128, |total, leg| total.saturating_add(leg.amount));
+        let stepped_up = Self::check_batch_step_up(&env, &from_address, total)?;
+
+        let mut results = vec![&env];
+        if merge_duplicates {
+            for to_address in order.iter() {
+                let (amount, _) = recipients.get(to_address.clone()).unwrap();
+                results.push_back(Self::send_batch_leg(&env, &from_address, to_address, amount));
+            }
+        } else {
+            for leg in legs.iter() {
+                let (_, count) = recipients.get(leg.to_address.clone()).unwrap();
+                if count > 1 {
+                    results.push_back(BatchLegResult {
+                        to_address: leg.to_address,
+                        amount: leg.amount,
+                        transfer_id: None,
+                        error: Some(Error::DuplicateRecipient),
+                    });
+                } else {
+                    results.push_back(Self::send_batch_leg(&env, &from_address, leg.to_address, leg.amount));
+                }
+            }
+        }
+        if stepped_up {
+            Self::clear_step_up(&env, &from_address);
+        }
+
+        Ok(results)
+    }
+
+    // Set the per-transaction cap and the velocity limit (admin only)
+    pub fn set_transaction_limits(env: Env, limits: TransactionLimits) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("tx_limits"), AuditTarget::Global, limits.max_transaction);
+
+        if limits.max_transaction < 0 || limits.velocity_limit < 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        env.storage().instance().set(&ConfigKey::TransactionLimits, &limits);
+
+        Ok(())
+    }
+
+    // Get the current transaction limits
+    pub fn get_transaction_limits(env: Env) -> TransactionLimits {
+        Self::transaction_limits(&env)
+    }
+
+    // Accounts flagged by the velocity rule or a high risk score, with when they were flagged
+    pub fn flagged_accounts(env: Env, cursor: u32, limit: u32) -> (Vec<(Address, u64)>, Option<u32>) {
+        let flagged: Map<Address, u64> = env.storage().instance().get(&ComplianceKey::Flagged).unwrap_or(Map::new(&env));
+        let mut accounts = vec![&env];
+
+        for entry in flagged.iter() {
+            accounts.push_back(entry);
+        }
+
+        // High-risk accounts are listed from when their score was set
+        let scores: Map<Address, RiskScore> = env.storage().instance().get(&ComplianceKey::RiskScores).unwrap_or(Map::new(&env));
+        for (user_address, risk) in scores.iter() {
+            if risk.score >= HIGH_RISK_SCORE && !flagged.contains_key(user_address.clone()) {
+                accounts.push_back((user_address, risk.updated_at));
+            }
+        }
+
+        Self::page(accounts, cursor, limit)
+    }
+
+    // Clear a velocity flag after review so the account can send again (compliance only)
+    pub fn clear_flag(env: Env, caller: Address, user_address: Address) -> Result<(), Error> {
+        Self::require_role(&env, &caller, Role::Compliance)?;
+        Self::audit(&env, &caller, symbol_short!("unflag"), AuditTarget::Address(user_address.clone()), 0);
+
+        let mut flagged: Map<Address, u64> = env.storage().instance().get(&ComplianceKey::Flagged).unwrap_or(Map::new(&env));
+        if !flagged.contains_key(user_address.clone()) {
+            return Err(Error::AccountNotFlagged);
+        }
+        flagged.remove(user_address.clone());
+        env.storage().instance().set(&ComplianceKey::Flagged, &flagged);
+
+        // Volume before the review no longer counts towards the next flag
+        let mut velocity: Map<Address, Vec<(u64, i128)>> = env.storage().instance().get(&ComplianceKey::Velocity).unwrap_or(Map::new(&env));
+        velocity.remove(user_address.clone());
+        env.storage().instance().set(&ComplianceKey::Velocity, &velocity);
+
+        env.events().publish((symbol_short!("unflag"), user_address), caller);
+
+        Ok(())
+    }
+
+    // Create a promo code that discounts transfer and withdrawal fees (admin only)
+    pub fn create_promo(env: Env, code_hash: BytesN<32>, discount_bps: u32, max_uses: u32, expiry: u64) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("promo"), AuditTarget::Global, discount_bps as i128);
+
+        if discount_bps == 0 || discount_bps as i128 > BPS_DENOMINATOR {
+            return Err(Error::InvalidDiscount);
+        }
+        if expiry <= env.ledger().timestamp() {
+            return Err(Error::InvalidExpiry);
+        }
+
+        let mut promos: Map<BytesN<32>, Promo> = env.storage().instance().get(&ConfigKey::Promos).unwrap_or(Map::new(&env));
+        if promos.contains_key(code_hash.clone()) {
+            return Err(Error::PromoAlreadyExists);
+        }
+        promos.set(code_hash.clone(), Promo { code_hash, discount_bps, max_uses, uses: 0, expiry, fees_waived: 0 });
+        env.storage().instance().set(&ConfigKey::Promos, &promos);
+
+        Ok(())
+    }
+
+    // Get how often a promo code has been used and the fees it waived
+    pub fn get_promo_stats(env: Env, code_hash: BytesN<32>) -> Result<Promo, Error> {
+        let promos: Map<BytesN<32>, Promo> = env.storage().instance().get(&ConfigKey::Promos).unwrap_or(Map::new(&env));
+
+        promos.get(code_hash).ok_or(Error::PromoNotFound)
+    }
+
+    // Whether an address has a registered account
+    pub fn user_exists(env: Env, user_address: Address) -> bool {
+        let users = Users::load(&env);
+
+        users.contains_key(user_address)
+    }
+
+    // Whether a phone number belongs to an open account
+    pub fn phone_registered(env: Env, phone: String) -> bool {
+        Self::get_user_by_phone(env, phone).is_some()
+    }
+
+    // Look up the account registered with a phone number
+    pub fn get_user_by_phone(env: Env, phone: String) -> Option<User> {
+        // Closed accounts have their phone number blanked
+        if phone.len() == 0 {
+            return None;
+        }
+
+        let address: Address = env.storage().persistent().get(&EntryKey::Phone(phone))?;
+        Users::load(&env).get(address)
+    }
+
+    // Pay several bills at once. Either every bill is paid or none is; the balance is checked
+    // once against the total. Returns the payment ids in request order.
+    pub fn pay_bills_batch(env: Env, user_address: Address, bills: Vec<BillRequest>) -> Result<Vec<String>, Error> {
+        Self::extend_instance(&env);
+        user_address.require_auth();
+
+        if bills.is_empty() || bills.len() > MAX_BILL_BATCH {
+            return Err(Error::InvalidBatchSize);
+        }
+
+        Self::ensure_module_active(&env, &user_address, symbol_short!("bills"))?;
+        Self::ensure_not_restricted(&env, &user_address, symbol_short!("pay_bill"))?;
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+
+        let mut total: i128 = 0;
+        for bill in bills.iter() {
+            Self::check_memo(&bill.memo)?;
+            Self::check_subaccount(&env, &user_address, symbol_short!("pay_bill"), bill.amount)?;
+            Self::check_transaction_cap(&env, &user_address, bill.amount, symbol_short!("pay_bill"))?;
+            Self::validate_bill(&env, &user_address, &bill.bill_type, &bill.account_number, bill.amount)?;
+            Self::check_rate_limit(&env, &user, symbol_short!("pay_bill"))?;
+            total = Self::checked_credit(total, bill.amount)?;
+        }
+        Self::require_pin(&env, &user_address, total, symbol_short!("pay_bill"))?;
+
+        if user.balance < total {
+            return Err(Self::fail(&env, &user_address, symbol_short!("pay_bill"), symbol_short!("balance"), total, user.balance, Error::InsufficientBalance));
+        }
+
+        user.balance = Self::checked_debit(user.balance, total)?;
+        Self::track_balance(&env, &user, -total, Bucket::Payable);
+        // Each bill rounds up on its own, as it would paid singly
+        for bill in bills.iter() {
+            Self::accrue_roundup(&env, &mut user, bill.amount)?;
+        }
+        users.set(user_address.clone(), user);
+        users.save();
+
+        Self::qualify_referral(&env, &user_address)?;
+
+        let mut payment_ids = vec![&env];
+        for bill in bills.iter() {
+            Self::accrue_cashback(&env, &user_address, &bill.bill_type, bill.amount);
+            Self::record_spending(&env, &user_address, SpendCategory::Bill(bill.bill_type.clone()), bill.amount);
+            payment_ids.push_back(Self::record_bill_payment(&env, &user_address, bill.bill_type, bill.account_number, bill.amount, bill.memo));
+        }
+
+        Ok(payment_ids)
+    }
+
+    // Reserve part of a user's balance for a card authorization (payment processor only)
+    pub fn place_hold(env: Env, caller: Address, user_address: Address, amount: i128, reference: String, expiry: u64) -> Result<u64, Error> {
+        Self::require_role(&env, &caller, Role::PaymentProcessor)?;
+        Self::ensure_not_restricted(&env, &user_address, symbol_short!("card"))?;
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        if expiry <= env.ledger().timestamp() {
+            return Err(Error::InvalidExpiry);
+        }
+        Self::check_transaction_cap(&env, &user_address, amount, symbol_short!("card"))?;
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+        if user.balance < amount {
+            return Err(Self::fail(&env, &user_address, symbol_short!("card"), symbol_short!("balance"), amount, user.balance, Error::InsufficientBalance));
+        }
+        user.balance = Self::checked_debit(user.balance, amount)?;
+        Self::track_balance(&env, &user, -amount, Bucket::Escrowed);
+        users.set(user_address.clone(), user);
+        users.save();
+
+        let mut holds: Map<u64, CardHold> = env.storage().instance().get(&DataKey::CardHolds).unwrap_or(Map::new(&env));
+        let hold_id: u64 = env.storage().instance().get(&CounterKey::CardHoldId).unwrap_or(0) + 1;
+        holds.set(hold_id, CardHold {
+            id: hold_id,
+            user_address: user_address.clone(),
+            processor: caller,
+            amount,
+            reference: reference.clone(),
+            expires_at: expiry,
+            status: String::from_str(&env, "active"),
+            captured: 0,
+        });
+        env.storage().instance().set(&DataKey::CardHolds, &holds);
+        env.storage().instance().set(&CounterKey::CardHoldId, &hold_id);
+        Self::index_record(&env, EntryKey::UserCardHolds(user_address.clone()), hold_id);
+
+        env.events().publish((symbol_short!("card_hold"), user_address), (hold_id, amount, reference));
+
+        Ok(hold_id)
+    }
+
+    // Settle a hold for up to its amount, paying the processor's account and returning the
+    // rest to the user (the processor that placed it only)
+    pub fn capture_hold(env: Env, caller: Address, hold_id: u64, amount: i128) -> Result<(), Error> {
+        Self::require_role(&env, &caller, Role::PaymentProcessor)?;
+
+        let mut holds: Map<u64, CardHold> = env.storage().instance().get(&DataKey::CardHolds).unwrap_or(Map::new(&env));
+        let mut hold = holds.get(hold_id).ok_or(Error::HoldNotFound)?;
+        if hold.processor != caller {
+            return Err(Error::Unauthorized);
+        }
+        if hold.status != String::from_str(&env, "active") {
+            return Err(Error::HoldNotActive);
+        }
+        if env.ledger().timestamp() > hold.expires_at {
+            return Err(Error::HoldHasExpired);
+        }
+        if amount <= 0 || amount > hold.amount {
+            return Err(Error::InvalidAmount);
+        }
+
+        let mut users = Users::load(&env);
+        let mut processor = users.get(caller.clone()).ok_or(Error::ProcessorAccountNotFound)?;
+        processor.balance = Self::checked_credit(processor.balance, amount)?;
+        Self::track_balance(&env, &processor, amount, Bucket::Escrowed);
+        users.set(caller.clone(), processor);
+
+        let remainder = hold.amount - amount;
+        if remainder > 0 {
+            let mut user = users.get(hold.user_address.clone()).ok_or(Error::UserNotFound)?;
+            user.balance = Self::checked_credit(user.balance, remainder)?;
+            Self::track_balance(&env, &user, remainder, Bucket::Escrowed);
+            users.set(hold.user_address.clone(), user);
+        }
+        users.save();
+
+        Self::record_spending(&env, &hold.user_address, SpendCategory::Transfer, amount);
+
+        hold.status = String::from_str(&env, "captured");
+        hold.captured = amount;
+        holds.set(hold_id, hold.clone());
+        env.storage().instance().set(&DataKey::CardHolds, &holds);
+
+        env.events().publish((symbol_short!("card_capt"), hold.user_address), (hold_id, amount, remainder));
+
+        Ok(())
+    }
+
+    // Return a hold's funds to the user. The processor that placed it can release it at any time;
+    // anyone can once it has expired.
+    pub fn release_hold(env: Env, caller: Address, hold_id: u64) -> Result<(), Error> {
+        let mut holds: Map<u64, CardHold> = env.storage().instance().get(&DataKey::CardHolds).unwrap_or(Map::new(&env));
+        let mut hold = holds.get(hold_id).ok_or(Error::HoldNotFound)?;
+        if hold.status != String::from_str(&env, "active") {
+            return Err(Error::HoldNotActive);
+        }
+        if env.ledger().timestamp() <= hold.expires_at {
+            if hold.processor != caller {
+                return Err(Error::Unauthorized);
+            }
+            Self::require_role(&env, &caller, Role::PaymentProcessor)?;
+        }
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(hold.user_address.clone()).ok_or(Error::UserNotFound)?;
+        user.balance = Self::checked_credit(user.balance, hold.amount)?;
+        Self::track_balance(&env, &user, hold.amount, Bucket::Escrowed);
+        users.set(hold.user_address.clone(), user);
+        users.save();
+
+        hold.status = String::from_str(&env, "released");
+        holds.set(hold_id, hold.clone());
+        env.storage().instance().set(&DataKey::CardHolds, &holds);
+
+        env.events().publish((symbol_short!("card_rel"), hold.user_address), (hold_id, hold.amount));
+
+        Ok(())
+    }
+
+    // Get a user's card holds
+    pub fn get_card_holds(env: Env, user_address: Address, cursor: u32, limit: u32) -> (Vec<CardHold>, Option<u32>) {
+        Self::page(Self::card_holds_for(&env, &user_address), cursor, limit)
+    }
+
+    // Link a dependent's account to a verified parent. Both have to sign; withdrawals are blocked
+    // until the parent allows them.
+    pub fn create_subaccount(env: Env, parent: Address, dependent: Address, label: String, limit: i128) -> Result<(), Error> {
+        parent.require_auth();
+        dependent.require_auth();
+
+        if parent == dependent {
+            return Err(Error::InvalidDependent);
+        }
+        if limit < 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        let users = Users::load(&env);
+        if !users.get(parent.clone()).ok_or(Error::UserNotFound)?.is_verified {
+            return Err(Error::ParentMustBeVerified);
+        }
+        if !users.contains_key(dependent.clone()) {
+            return Err(Error::DependentNotFound);
+        }
+
+        let mut subaccounts: Map<Address, SubAccount> = env.storage().instance().get(&DataKey::SubAccounts).unwrap_or(Map::new(&env));
+        if subaccounts.contains_key(dependent.clone()) || subaccounts.contains_key(parent.clone()) {
+            return Err(Error::AccountAlreadyASubAccount);
+        }
+
+        subaccounts.set(dependent.clone(), SubAccount {
+            dependent: dependent.clone(),
+            parent: parent.clone(),
+            label,
+            limit,
+            blocked: vec![&env, symbol_short!("withdraw"), symbol_short!("cash_out")],
+            created_at: env.ledger().timestamp(),
+        });
+        env.storage().instance().set(&DataKey::SubAccounts, &subaccounts);
+
+        env.events().publish((symbol_short!("sub_new"), parent), dependent);
+
+        Ok(())
+    }
+
+    // Change a sub-account's spending limit and blocked operations (parent only)
+    pub fn set_subaccount_controls(env: Env, parent: Address, dependent: Address, limit: i128, blocked: Vec<Symbol>) -> Result<(), Error> {
+        parent.require_auth();
+
+        if limit < 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        let mut subaccounts: Map<Address, SubAccount> = env.storage().instance().get(&DataKey::SubAccounts).unwrap_or(Map::new(&env));
+        let mut subaccount = Self::parent_subaccount(&subaccounts, &parent, &dependent)?;
+        subaccount.limit = limit;
+        subaccount.blocked = blocked;
+        subaccounts.set(dependent, subaccount);
+        env.storage().instance().set(&DataKey::SubAccounts, &subaccounts);
+
+        Ok(())
+    }
+
+    // Move part of the parent's balance to a sub-account
+    pub fn fund_subaccount(env: Env, parent: Address, dependent: Address, amount: i128) -> Result<(), Error> {
+        parent.require_auth();
+
+        let subaccounts: Map<Address, SubAccount> = env.storage().instance().get(&DataKey::SubAccounts).unwrap_or(Map::new(&env));
+        Self::parent_subaccount(&subaccounts, &parent, &dependent)?;
+
+        Self::move_balance(&env, &parent, &dependent, amount)?;
+        env.events().publish((symbol_short!("sub_fund"), parent), (dependent, amount));
+
+        Ok(())
+    }
+
+    // Pull funds back from a sub-account to the parent
+    pub fn claw_back(env: Env, parent: Address, dependent: Address, amount: i128) -> Result<(), Error> {
+        parent.require_auth();
+
+        let subaccounts: Map<Address, SubAccount> = env.storage().instance().get(&DataKey::SubAccounts).unwrap_or(Map::new(&env));
+        Self::parent_subaccount(&subaccounts, &parent, &dependent)?;
+
+        Self::move_balance(&env, &dependent, &parent, amount)?;
+        env.events().publish((symbol_short!("sub_claw"), parent), (dependent, amount));
+
+        Ok(())
+    }
+
+    // Get the sub-accounts a parent manages
+    pub fn get_subaccounts(env: Env, parent: Address, cursor: u32, limit: u32) -> (Vec<SubAccount>, Option<u32>) {
+        let subaccounts: Map<Address, SubAccount> = env.storage().instance().get(&DataKey::SubAccounts).unwrap_or(Map::new(&env));
+        let mut managed = vec![&env];
+
+        for (_, subaccount) in subaccounts.iter() {
+            if subaccount.parent == parent {
+                managed.push_back(subaccount);
+            }
+        }
+
+        Self::page(managed, cursor, limit)
+    }
+
+    // Get a sub-account's transfers, bill payments and withdrawals (parent only)
+    pub fn get_subaccount_history(env: Env, parent: Address, dependent: Address) -> Result<SubAccountHistory, Error> {
+        parent.require_auth();
+
+        let subaccounts: Map<Address, SubAccount> = env.storage().instance().get(&DataKey::SubAccounts).unwrap_or(Map::new(&env));
+        Self::parent_subaccount(&subaccounts, &parent, &dependent)?;
+
+        Ok(SubAccountHistory {
+            transfers: Self::transfers_for(&env, &dependent),
+            bill_payments: Self::bill_payments_for(&env, &dependent),
+            withdrawals: Self::withdrawals_for(&env, &dependent),
+        })
+    }
+
+    // Schedule salary payouts for `payday`; the total is escrowed from the employer's balance now
+    pub fn create_payroll(env: Env, employer: Address, payouts: Vec<(Address, i128)>, payday: u64) -> Result<u64, Error> {
+        employer.require_auth();
+        Self::ensure_module_active(&env, &employer, symbol_short!("payroll"))?;
+        Self::ensure_not_restricted(&env, &employer, symbol_short!("payroll"))?;
+
+        if payouts.is_empty() || payouts.len() > MAX_PAYROLL_ENTRIES {
+            return Err(Error::InvalidPayrollSize);
+        }
+        if payday <= env.ledger().timestamp() {
+            return Err(Error::PaydayMustBeInTheFuture);
+        }
+
+        let mut users = Users::load(&env);
+        let mut total: i128 = 0;
+        for (employee, amount) in payouts.iter() {
+            if amount <= 0 {
+                return Err(Error::InvalidAmount);
+            }
+            if employee == employer || !users.contains_key(employee.clone()) {
+                return Err(Error::InvalidEmployee);
+            }
+            total = Self::checked_credit(total, amount)?;
+        }
+
+        let mut user = users.get(employer.clone()).ok_or(Error::UserNotFound)?;
+        if user.balance < total {
+            return Err(Self::fail(&env, &employer, symbol_short!("payroll"), symbol_short!("balance"), total, user.balance, Error::InsufficientBalance));
+        }
+        user.balance = Self::checked_debit(user.balance, total)?;
+        Self::track_balance(&env, &user, -total, Bucket::Escrowed);
+        users.set(employer.clone(), user);
+        users.save();
+
+        let mut payrolls: Map<u64, Payroll> = env.storage().instance().get(&DataKey::Payrolls).unwrap_or(Map::new(&env));
+        let payroll_id: u64 = env.storage().instance().get(&CounterKey::PayrollId).unwrap_or(0) + 1;
+        payrolls.set(payroll_id, Payroll {
+            id: payroll_id,
+            employer: employer.clone(),
+            payouts,
+            total,
+            payday,
+            status: String::from_str(&env, "pending"),
+        });
+        env.storage().instance().set(&DataKey::Payrolls, &payrolls);
+        env.storage().instance().set(&CounterKey::PayrollId, &payroll_id);
+        Self::index_record(&env, EntryKey::UserPayrolls(employer.clone()), payroll_id);
+
+        env.events().publish((symbol_short!("payroll"), employer), (payroll_id, total, payday));
+
+        Ok(payroll_id)
+    }
+
+    // Pay out a payroll once payday has come; callable by anyone
+    pub fn execute_payroll(env: Env, payroll_id: u64) -> Result<(), Error> {
+        let mut payrolls: Map<u64, Payroll> = env.storage().instance().get(&DataKey::Payrolls).unwrap_or(Map::new(&env));
+        let mut payroll = payrolls.get(payroll_id).ok_or(Error::PayrollNotFound)?;
+        Self::ensure_module_active(&env, &payroll.employer, symbol_short!("payroll"))?;
+
+        if payroll.status != String::from_str(&env, "pending") {
+            return Err(Error::PayrollNotPending);
+        }
+        if env.ledger().timestamp() < payroll.payday {
+            return Err(Self::fail_until(&env, &payroll.employer, symbol_short!("payroll"), symbol_short!("not_due"), payroll.payday, Error::PaydayHasNotCome));
+        }
+
+        let now = env.ledger().timestamp();
+        let mut users = Users::load(&env);
+        let mut records: Vec<PayrollPayout> = env.storage().instance().get(&DataKey::PayrollPayouts).unwrap_or(vec![&env]);
+        let mut returned: i128 = 0;
+
+        for (employee, amount) in payroll.payouts.iter() {
+            // Salaries for accounts closed since the payroll was created go back to the employer
+            if Self::is_closed(&env, &employee) {
+                returned += amount;
+                continue;
+            }
+            let mut user = users.get(employee.clone()).ok_or(Error::UserNotFound)?;
+            user.balance = Self::checked_credit(user.balance, amount)?;
+            Self::track_balance(&env, &user, amount, Bucket::Escrowed);
+            users.set(employee.clone(), user);
+
+            records.push_back(PayrollPayout {
+                payroll_id,
+                employer: payroll.employer.clone(),
+                employee: employee.clone(),
+                amount,
+                paid_at: now,
+            });
+            Self::notify(&env, &employee, symbol_short!("salary"), NotificationRef::Id(payroll_id), amount);
+            env.events().publish((symbol_short!("salary"), employee), (payroll_id, amount));
+        }
+
+        if returned > 0 {
+            let mut employer = users.get(payroll.employer.clone()).ok_or(Error::UserNotFound)?;
+            employer.balance = Self::checked_credit(employer.balance, returned)?;
+            Self::track_balance(&env, &employer, returned, Bucket::Escrowed);
+            users.set(payroll.employer.clone(), employer);
+        }
+        users.save();
+        env.storage().instance().set(&DataKey::PayrollPayouts, &records);
+
+        payroll.status = String::from_str(&env, "executed");
+        payrolls.set(payroll_id, payroll);
+        env.storage().instance().set(&DataKey::Payrolls, &payrolls);
+
+        Ok(())
+    }
+
+    // Cancel a payroll before payday and refund the escrow to the employer
+    pub fn cancel_payroll(env: Env, employer: Address, payroll_id: u64) -> Result<(), Error> {
+        employer.require_auth();
+
+        let mut payrolls: Map<u64, Payroll> = env.storage().instance().get(&DataKey::Payrolls).unwrap_or(Map::new(&env));
+        let mut payroll = payrolls.get(payroll_id).ok_or(Error::PayrollNotFound)?;
+
+        if payroll.employer != employer {
+            return Err(Error::Unauthorized);
+        }
+        if payroll.status != String::from_str(&env, "pending") {
+            return Err(Error::PayrollNotPending);
+        }
+        if env.ledger().timestamp() >= payroll.payday {
+            return Err(Error::PaydayHasPassed);
+        }
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(employer.clone()).ok_or(Error::UserNotFound)?;
+        user.balance = Self::checked_credit(user.balance, payroll.total)?;
+        Self::track_balance(&env, &user, payroll.total, Bucket::Escrowed);
+        users.set(employer.clone(), user);
+        users.save();
+
+        payroll.status = String::from_str(&env, "cancelled");
+        payrolls.set(payroll_id, payroll.clone());
+        env.storage().instance().set(&DataKey::Payrolls, &payrolls);
+
+        env.events().publish((symbol_short!("payrl_cnl"), employer), (payroll_id, payroll.total));
+
+        Ok(())
+    }
+
+    // Get the payrolls an employer created
+    pub fn get_payrolls(env: Env, employer: Address, cursor: u32, limit: u32) -> (Vec<Payroll>, Option<u32>) {
+        Self::page(Self::payrolls_for(&env, &employer), cursor, limit)
+    }
+
+    // Get the salary payouts an employee received
+    pub fn get_payroll_payouts(env: Env, employee: Address, cursor: u32, limit: u32) -> (Vec<PayrollPayout>, Option<u32>) {
+        let records: Vec<PayrollPayout> = env.storage().instance().get(&DataKey::PayrollPayouts).unwrap_or(vec![&env]);
+        let mut employee_payouts = vec![&env];
+
+        for record in records.iter() {
+            if record.employee == employee {
+                employee_payouts.push_back(record);
+            }
+        }
+
+        Self::page(employee_payouts, cursor, limit)
+    }
+
+    // Get a page of the user's notifications, newest first
+    pub fn get_notifications(env: Env, user_address: Address, cursor: u32, limit: u32) -> (Vec<Notification>, Option<u32>) {
+        let mut newest_first = vec![&env];
+
+        for notification in Self::load_inbox(&env, &user_address).iter().rev() {
+            newest_first.push_back(notification);
+        }
+
+        Self::page(newest_first, cursor, limit)
+    }
+
+    // Mark one of the user's notifications as read
+    pub fn mark_read(env: Env, user_address: Address, notification_id: u64) -> Result<(), Error> {
+        user_address.require_auth();
+
+        let mut inbox = Self::load_inbox(&env, &user_address);
+        let index = inbox.iter().position(|notification| notification.id == notification_id).ok_or(Error::NotificationNotFound)?;
+
+        let mut notification = inbox.get(index as u32).unwrap();
+        notification.read = true;
+        inbox.set(index as u32, notification);
+        Self::save_inbox(&env, &user_address, &inbox);
+
+        Ok(())
+    }
+
+    // Set how long the off-ramp has to complete new withdrawals (admin only)
+    pub fn set_withdrawal_sla(env: Env, sla_secs: u64) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("wd_sla"), AuditTarget::Global, sla_secs as i128);
+
+        if sla_secs == 0 {
+            return Err(Error::InvalidSla);
+        }
+
+        env.storage().instance().set(&ConfigKey::WithdrawalSla, &sla_secs);
+
+        Ok(())
+    }
+
+    // Get open withdrawals that are past their processing deadline
+    pub fn get_overdue_withdrawals(env: Env, cursor: u32, limit: u32) -> (Vec<Withdrawal>, Option<u32>) {
+        // Every open withdrawal still has its funds on hold
+        let holds: Map<String, WithdrawalHold> = env.storage().instance().get(&ComplianceKey::WithdrawalHolds).unwrap_or(Map::new(&env));
+        let now = env.ledger().timestamp();
+        let mut overdue = vec![&env];
+
+        for withdrawal_id in holds.keys().iter() {
+            if let Some(withdrawal) = Self::load_withdrawal(&env, &withdrawal_id) {
+                if Self::is_open_withdrawal(&env, &withdrawal) && now > withdrawal.processing_deadline {
+                    overdue.push_back(withdrawal);
+                }
+            }
+        }
+
+        Self::page(overdue, cursor, limit)
+    }
+
+    // Cancel a withdrawal the off-ramp did not complete within the SLA and return the held funds
+    pub fn cancel_overdue_withdrawal(env: Env, user_address: Address, withdrawal_id: String) -> Result<(), Error> {
+        user_address.require_auth();
+
+        let mut withdrawal = Self::load_withdrawal(&env, &withdrawal_id).ok_or(Error::WithdrawalNotFound)?;
+
+        if withdrawal.user_address != user_address {
+            return Err(Error::Unauthorized);
+        }
+        if !Self::is_open_withdrawal(&env, &withdrawal) {
+            return Err(Error::StatusFinal);
+        }
+        if env.ledger().timestamp() <= withdrawal.processing_deadline {
+            return Err(Self::fail_until(&env, &user_address, symbol_short!("wd_cancel"), symbol_short!("not_due"), withdrawal.processing_deadline + 1, Error::WithdrawalWithinItsSla));
+        }
+
+        let mut holds: Map<String, WithdrawalHold> = env.storage().instance().get(&ComplianceKey::WithdrawalHolds).unwrap_or(Map::new(&env));
+        let hold = holds.get(withdrawal_id.clone()).ok_or(Error::WithdrawalHoldNotFound)?;
+
+        let previous = withdrawal.status.clone();
+        withdrawal.status = String::from_str(&env, "cancelled");
+
+        let mut stats = Self::load_stats(&env);
+        Self::count_status(&mut stats.withdrawal_statuses, Some(previous), withdrawal.status.clone());
+        Self::save_stats(&env, &stats);
+        Self::audit(&env, &user_address, symbol_short!("wd_cancel"), AuditTarget::Id(withdrawal_id.clone()), withdrawal.usdc_amount);
+        Self::notify(&env, &user_address, symbol_short!("wd_cancel"), NotificationRef::Key(withdrawal_id.clone()), withdrawal.usdc_amount);
+        Self::save_withdrawal(&env, &withdrawal);
+
+        holds.remove(withdrawal_id.clone());
+        env.storage().instance().set(&ComplianceKey::WithdrawalHolds, &holds);
+        Self::refund_withdrawal_hold(&env, &withdrawal_id, &hold)
+    }
+
+    // Send to someone who has no account yet, identified by the hash of their salted email.
+    // The amount is held until they claim it or it expires.
+    pub fn send_to_contact_hash(env: Env, from_address: Address, contact_hash: BytesN<32>, amount: i128, expiry: u64) -> Result<u64, Error> {
+        from_address.require_auth();
+        Self::ensure_module_active(&env, &from_address, symbol_short!("send"))?;
+        Self::ensure_not_restricted(&env, &from_address, symbol_short!("send"))?;
+        Self::check_subaccount(&env, &from_address, symbol_short!("send"), amount)?;
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        if expiry <= env.ledger().timestamp() {
+            return Err(Error::InvalidExpiry);
+        }
+        Self::check_transaction_cap(&env, &from_address, amount, symbol_short!("send"))?;
+        Self::check_step_up(&env, &from_address, amount)?;
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(from_address.clone()).ok_or(Error::UserNotFound)?;
+        if user.balance < amount {
+            return Err(Self::fail(&env, &from_address, symbol_short!("send"), symbol_short!("balance"), amount, user.balance, Error::InsufficientBalance));
+        }
+        user.balance = Self::checked_debit(user.balance, amount)?;
+        Self::track_balance(&env, &user, -amount, Bucket::Escrowed);
+        users.set(from_address.clone(), user);
+        users.save();
+
+        let claim_id: u64 = env.storage().instance().get(&CounterKey::ContactClaimId).unwrap_or(0) + 1;
+        let mut claims: Map<u64, ContactClaim> = env.storage().instance().get(&RecordKey::ContactClaims).unwrap_or(Map::new(&env));
+        claims.set(claim_id, ContactClaim {
+            id: claim_id,
+            sender: from_address.clone(),
+            contact_hash: contact_hash.clone(),
+            amount,
+            expires_at: expiry,
+            status: String::from_str(&env, "active"),
+            claimed_by: None,
+        });
+        env.storage().instance().set(&RecordKey::ContactClaims, &claims);
+        env.storage().instance().set(&CounterKey::ContactClaimId, &claim_id);
+        Self::index_record(&env, EntryKey::UserContactClaims(from_address.clone()), claim_id);
+
+        env.events().publish((symbol_short!("ct_send"), from_address), (claim_id, contact_hash, amount, expiry));
+
+        Ok(claim_id)
+    }
+
+    // Claim every unexpired send waiting for the contact whose salted email is `preimage`.
+    // Returns the amount credited.
+    pub fn claim_by_contact_proof(env: Env, new_user: Address, preimage: Bytes) -> Result<i128, Error> {
+        new_user.require_auth();
+        Self::ensure_not_restricted(&env, &new_user, symbol_short!("send"))?;
+
+        let contact_hash = BytesN::from(env.crypto().sha256(&preimage));
+        let now = env.ledger().timestamp();
+        let active = String::from_str(&env, "active");
+        let mut claims: Map<u64, ContactClaim> = env.storage().instance().get(&RecordKey::ContactClaims).unwrap_or(Map::new(&env));
+        let mut total: i128 = 0;
+
+        for (claim_id, mut claim) in claims.clone().iter() {
+            if claim.contact_hash == contact_hash && claim.status == active && now <= claim.expires_at {
+                total = Self::checked_credit(total, claim.amount)?;
+                claim.status = String::from_str(&env, "claimed");
+                claim.claimed_by = Some(new_user.clone());
+                claims.set(claim_id, claim);
+            }
+        }
+        if total == 0 {
+            return Err(Error::ClaimNotFound);
+        }
+        env.storage().instance().set(&RecordKey::ContactClaims, &claims);
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(new_user.clone()).ok_or(Error::UserNotFound)?;
+        user.balance = Self::checked_credit(user.balance, total)?;
+        Self::track_balance(&env, &user, total, Bucket::Escrowed);
+        users.set(new_user.clone(), user);
+        users.save();
+
+        env.events().publish((symbol_short!("ct_claim"), new_user), (contact_hash, total));
+
+        Ok(total)
+    }
+
+    // Return an expired, unclaimed contact send to its sender
+    pub fn reclaim_contact_send(env: Env, sender: Address, claim_id: u64) -> Result<i128, Error> {
+        sender.require_auth();
+
+        let mut claims: Map<u64, ContactClaim> = env.storage().instance().get(&RecordKey::ContactClaims).unwrap_or(Map::new(&env));
+        let mut claim = claims.get(claim_id).ok_or(Error::ClaimNotFound)?;
+
+        if claim.sender != sender {
+            return Err(Error::Unauthorized);
+        }
+        if claim.status != String::from_str(&env, "active") {
+            return Err(Error::ClaimNoLongerValid);
+        }
+        if env.ledger().timestamp() <= claim.expires_at {
+            return Err(Self::fail_until(&env, &sender, symbol_short!("ct_recl"), symbol_short!("not_due"), claim.expires_at + 1, Error::ClaimHasNotExpired));
+        }
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(sender.clone()).ok_or(Error::UserNotFound)?;
+        user.balance = Self::checked_credit(user.balance, claim.amount)?;
+        Self::track_balance(&env, &user, claim.amount, Bucket::Escrowed);
+        users.set(sender.clone(), user);
+        users.save();
+
+        claim.status = String::from_str(&env, "reclaimed");
+        claims.set(claim_id, claim.clone());
+        env.storage().instance().set(&RecordKey::ContactClaims, &claims);
+
+        env.events().publish((symbol_short!("ct_recl"), sender), (claim_id, claim.amount));
+
+        Ok(claim.amount)
+    }
+
+    // Get the sends waiting for a contact hash, so the app can show what a new user will receive
+    pub fn get_contact_claims(env: Env, contact_hash: BytesN<32>, cursor: u32, limit: u32) -> (Vec<ContactClaim>, Option<u32>) {
+        let claims: Map<u64, ContactClaim> = env.storage().instance().get(&RecordKey::ContactClaims).unwrap_or(Map::new(&env));
+        let mut matching = vec![&env];
+
+        for (_, claim) in claims.iter() {
+            if claim.contact_hash == contact_hash {
+                matching.push_back(claim);
+            }
+        }
+
+        Self::page(matching, cursor, limit)
+    }
+
+    // Set the fee charged on tips; it may not exceed the P2P transfer fee
+    pub fn set_tip_fee(env: Env, fee_bps: u32) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("tip_fee"), AuditTarget::Global, fee_bps as i128);
+
+        let transfer_fee_bps: u32 = env.storage().instance().get(&ConfigKey::TransferFeeBps).unwrap_or(0);
+        if fee_bps > transfer_fee_bps {
+            return Err(Error::TipFeeCannotExceedTransferFee);
+        }
+
+        env.storage().instance().set(&ConfigKey::TipFeeBps, &fee_bps);
+
+        Ok(())
+    }
+
+    // Send a small appreciation payment. The fee comes out of the tip and the recipient's public
+    // tip counters are updated; tips are not recorded as P2P transfers.
+    pub fn tip(env: Env, from_address: Address, to_address: Address, amount: i128, emoji_code: Symbol) -> Result<u64, Error> {
+        from_address.require_auth();
+        Self::extend_instance(&env);
+
+        if from_address == to_address {
+            return Err(Error::CannotSendToYourself);
+        }
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        Self::ensure_module_active(&env, &from_address, symbol_short!("transfers"))?;
+        Self::ensure_not_restricted(&env, &from_address, symbol_short!("send"))?;
+        Self::check_subaccount(&env, &from_address, symbol_short!("send"), amount)?;
+        Self::ensure_not_restricted(&env, &to_address, symbol_short!("send"))?;
+        Self::require_pin(&env, &from_address, amount, symbol_short!("send"))?;
+        Self::check_transaction_cap(&env, &from_address, amount, symbol_short!("send"))?;
+        Self::check_step_up(&env, &from_address, amount)?;
+        Self::check_velocity(&env, &from_address, amount)?;
+
+        let mut users = Users::load(&env);
+        let mut from_user = users.get(&from_address).ok_or(Error::SenderNotFound)?;
+        let mut to_user = users.get(&to_address).ok_or(Error::RecipientNotFound)?;
+        Self::check_rate_limit(&env, &from_user, symbol_short!("send"))?;
+
+        let transfer_fee_bps: u32 = env.storage().instance().get(&ConfigKey::TransferFeeBps).unwrap_or(0);
+        let fee_bps = transfer_fee_bps.min(env.storage().instance().get(&ConfigKey::TipFeeBps).unwrap_or(DEFAULT_TIP_FEE_BPS));
+        let quote = Self::split_fee(amount, amount * fee_bps as i128 / BPS_DENOMINATOR, &FeeBearer::Payee);
+
+        if from_user.balance < quote.payer_pays {
+            return Err(Self::fail(&env, &from_address, symbol_short!("tip"), symbol_short!("balance"), quote.payer_pays, from_user.balance, Error::InsufficientBalance));
+        }
+
+        from_user.balance = Self::checked_debit(from_user.balance, quote.payer_pays)?;
+        Self::track_balance(&env, &from_user, -quote.payer_pays, Bucket::Clearing);
+        Self::accrue_roundup(&env, &mut from_user, amount)?;
+        users.set(&from_address, &from_user);
+        to_user.balance = Self::checked_credit(to_user.balance, quote.payee_receives)?;
+        Self::track_balance(&env, &to_user, quote.payee_receives, Bucket::Clearing);
+        users.set(&to_address, &to_user);
+        users.save();
+
+        if quote.fee > 0 {
+            Self::collect_fee(&env, &from_address, Bucket::Clearing, quote.fee);
+        }
+        Self::record_spending(&env, &from_address, SpendCategory::Transfer, quote.payer_pays);
+
+        let mut all_stats: Map<Address, TipStats> = env.storage().instance().get(&RecordKey::TipStats).unwrap_or(Map::new(&env));
+        let mut stats = all_stats.get(to_address.clone()).unwrap_or(TipStats {
+            tip_count: 0,
+            total_received: 0,
+            last_tip_at: 0,
+        });
+        stats.tip_count += 1;
+        stats.total_received = Self::checked_credit(stats.total_received, quote.payee_receives)?;
+        stats.last_tip_at = env.ledger().timestamp();
+        all_stats.set(to_address.clone(), stats);
+        env.storage().instance().set(&RecordKey::TipStats, &all_stats);
+
+        let tip_id: u64 = env.storage().instance().get(&CounterKey::TipId).unwrap_or(0) + 1;
+        env.storage().instance().set(&CounterKey::TipId, &tip_id);
+
+        Self::notify(&env, &to_address, symbol_short!("tip"), NotificationRef::Id(tip_id), quote.payee_receives);
+        env.events().publish((symbol_short!("tip"), from_address, to_address), (tip_id, amount, emoji_code));
+
+        Ok(tip_id)
+    }
+
+    // Get the tip counters for a recipient
+    pub fn get_tip_stats(env: Env, user_address: Address) -> TipStats {
+        let all_stats: Map<Address, TipStats> = env.storage().instance().get(&RecordKey::TipStats).unwrap_or(Map::new(&env));
+
+        all_stats.get(user_address).unwrap_or(TipStats {
+            tip_count: 0,
+            total_received: 0,
+            last_tip_at: 0,
+        })
+    }
+
+    // Schedule downtime for a withdrawal rail or biller, keyed by rail or biller id (pauser only).
+    // Withdrawals and bill payments on it are rejected during the window.
+    pub fn set_rail_maintenance(env: Env, caller: Address, rail_id: String, start_ts: u64, end_ts: u64, message_code: Symbol) -> Result<(), Error> {
+        Self::require_role(&env, &caller, Role::Pauser)?;
+        Self::audit(&env, &caller, symbol_short!("maint"), AuditTarget::Id(rail_id.clone()), end_ts as i128);
+
+        if end_ts <= start_ts || end_ts <= env.ledger().timestamp() {
+            return Err(Error::InvalidMaintenanceWindow);
+        }
+
+        let mut windows: Map<String, MaintenanceWindow> = env.storage().instance().get(&ConfigKey::RailMaintenance).unwrap_or(Map::new(&env));
+        windows.set(rail_id.clone(), MaintenanceWindow {
+            start_ts,
+            end_ts,
+            message_code: message_code.clone(),
+        });
+        env.storage().instance().set(&ConfigKey::RailMaintenance, &windows);
+
+        env.events().publish((symbol_short!("maint"), rail_id), (start_ts, end_ts, message_code));
+
+        Ok(())
+    }
+
+    // Cancel a rail's scheduled or ongoing maintenance (pauser only)
+    pub fn clear_rail_maintenance(env: Env, caller: Address, rail_id: String) -> Result<(), Error> {
+        Self::require_role(&env, &caller, Role::Pauser)?;
+        Self::audit(&env, &caller, symbol_short!("maint_end"), AuditTarget::Id(rail_id.clone()), 0);
+
+        let mut windows: Map<String, MaintenanceWindow> = env.storage().instance().get(&ConfigKey::RailMaintenance).unwrap_or(Map::new(&env));
+        if windows.remove(rail_id.clone()).is_none() {
+            return Err(Error::MaintenanceWindowNotFound);
+        }
+        env.storage().instance().set(&ConfigKey::RailMaintenance, &windows);
+
+        env.events().publish((symbol_short!("maint_end"), rail_id), ());
+
+        Ok(())
+    }
+
+    // Get a rail's maintenance window, if one is scheduled and not yet over
+    pub fn get_rail_maintenance(env: Env, rail_id: String) -> Option<MaintenanceWindow> {
+        let windows: Map<String, MaintenanceWindow> = env.storage().instance().get(&ConfigKey::RailMaintenance).unwrap_or(Map::new(&env));
+
+        windows.get(rail_id).filter(|window| window.end_ts > env.ledger().timestamp())
+    }
+
+    // Pay a bill straight from the payer's own wallet. The USDC is pulled into the contract and
+    // the bill recorded in the same transaction; the payer needs no in-app balance or account.
+    pub fn pay_bill_external(env: Env, payer: Address, bill_type: String, account_number: String, amount: i128) -> Result<String, Error> {
+        payer.require_auth();
+        Self::extend_instance(&env);
+
+        Self::ensure_module_active(&env, &payer, symbol_short!("bills"))?;
+        Self::ensure_not_restricted(&env, &payer, symbol_short!("pay_bill"))?;
+        if Self::sunset_initiated(&env) {
+            return Err(Error::ContractWindingDown);
+        }
+        Self::validate_bill(&env, &payer, &bill_type, &account_number, amount)?;
+
+        let usdc: Address = env.storage().instance().get(&ConfigKey::UsdcToken).ok_or(Error::UsdcTokenNotConfigured)?;
+        token::Client::new(&env, &usdc).transfer(&payer, &env.current_contract_address(), &amount);
+
+        let payment_id = Self::record_bill_payment(&env, &payer, bill_type, account_number, amount, None);
+        Self::post(&env, &payer, Bucket::External, Bucket::Payable, amount);
+        env.events().publish((symbol_short!("bill_ext"), payer), (payment_id.clone(), amount));
+
+        Ok(payment_id)
+    }
+
+    // Anchor the sha256 of an off-chain receipt against a bill payment or withdrawal (status
+    // updater only). A receipt cannot be replaced once attached.
+    pub fn attach_receipt(env: Env, caller: Address, reference_id: String, sha256_hash: BytesN<32>) -> Result<(), Error> {
+        Self::require_role(&env, &caller, Role::StatusUpdater)?;
+        Self::audit(&env, &caller, symbol_short!("receipt"), AuditTarget::Id(reference_id.clone()), 0);
+
+        if Self::load_bill(&env, &reference_id).is_none() && Self::load_withdrawal(&env, &reference_id).is_none() {
+            return Err(Error::PaymentNotFound);
+        }
+
+        let mut receipts: Map<String, BytesN<32>> = env.storage().instance().get(&RecordKey::Receipts).unwrap_or(Map::new(&env));
+        if receipts.contains_key(reference_id.clone()) {
+            return Err(Error::ReceiptAlreadyAttached);
+        }
+        receipts.set(reference_id.clone(), sha256_hash.clone());
+        env.storage().instance().set(&RecordKey::Receipts, &receipts);
+
+        env.events().publish((symbol_short!("receipt"), reference_id), sha256_hash);
+
+        Ok(())
+    }
+
+    // Whether `hash` matches the receipt anchored for a bill payment or withdrawal
+    pub fn verify_receipt(env: Env, reference_id: String, hash: BytesN<32>) -> bool {
+        let receipts: Map<String, BytesN<32>> = env.storage().instance().get(&RecordKey::Receipts).unwrap_or(Map::new(&env));
+
+        receipts.get(reference_id) == Some(hash)
+    }
+
+    // Set the withdrawal cooling-off policy for new accounts and large deposits (admin only)
+    pub fn set_cooling_off_policy(env: Env, policy: CoolingOffPolicy) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("cool_off"), AuditTarget::Global, policy.withdraw_cap);
+
+        if policy.large_deposit < 0 || policy.withdraw_cap < 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        env.storage().instance().set(&ConfigKey::CoolingOffPolicy, &policy);
+
+        Ok(())
+    }
+
+    // Get the current cooling-off policy
+    pub fn get_cooling_off_policy(env: Env) -> CoolingOffPolicy {
+        Self::cooling_off_policy(&env)
+    }
+
+    // Get the holds currently limiting a user's withdrawals, so the app can explain them
+    pub fn get_restrictions(env: Env, user_address: Address) -> Vec<Restriction> {
+        let policy = Self::cooling_off_policy(&env);
+        let states: Map<Address, CoolingOff> = env.storage().instance().get(&ComplianceKey::CoolingOff).unwrap_or(Map::new(&env));
+        let now = env.ledger().timestamp();
+        let mut restrictions = vec![&env];
+
+        let state = match states.get(user_address.clone()) {
+            Some(state) => state,
+            None => return restrictions,
+        };
+
+        let mut holds = vec![&env];
+        if policy.new_account_secs > 0 {
+            holds.push_back((symbol_short!("new_acct"), state.registered_at, state.registered_at + policy.new_account_secs));
+        }
+        if let Some(deposited_at) = state.large_deposit_at {
+            if policy.large_deposit > 0 {
+                holds.push_back((symbol_short!("deposit"), deposited_at, deposited_at + policy.large_deposit_secs));
+            }
+        }
+
+        for (kind, since, until) in holds.iter() {
+            if now < until {
+                restrictions.push_back(Restriction {
+                    kind,
+                    until,
+                    withdraw_cap: policy.withdraw_cap,
+                    withdrawn: Self::withdrawn_since(&env, &user_address, since),
+                });
+            }
+        }
+
+        restrictions
+    }
+
+    // Record a user's counterparty risk score from the compliance backend (risk officer only)
+    pub fn set_risk_score(env: Env, caller: Address, user_address: Address, score: u32) -> Result<(), Error> {
+        Self::require_role(&env, &caller, Role::RiskOfficer)?;
+        Self::audit(&env, &caller, symbol_short!("risk"), AuditTarget::Address(user_address.clone()), score as i128);
+
+        if score > MAX_RISK_SCORE {
+            return Err(Error::InvalidRiskScore);
+        }
+
+        let mut scores: Map<Address, RiskScore> = env.storage().instance().get(&ComplianceKey::RiskScores).unwrap_or(Map::new(&env));
+        if score == 0 {
+            scores.remove(user_address.clone());
+        } else {
+            scores.set(user_address.clone(), RiskScore {
+                score,
+                updated_at: env.ledger().timestamp(),
+            });
+        }
+        env.storage().instance().set(&ComplianceKey::RiskScores, &scores);
+
+        env.events().publish((symbol_short!("risk"), user_address), score);
+
+        Ok(())
+    }
+
+    // Get a user's risk score, zero if none was set
+    pub fn get_risk_score(env: Env, user_address: Address) -> u32 {
+        let scores: Map<Address, RiskScore> = env.storage().instance().get(&ComplianceKey::RiskScores).unwrap_or(Map::new(&env));
+
+        scores.get(user_address).map(|risk| risk.score).unwrap_or(0)
+    }
+
+    // Snapshot of a user's full account state: profile, balances, limits, holds, goals, loans
+    // and recent history
+    pub fn export_account(env: Env, user_address: Address) -> Result<AccountExport, Error> {
+        let user = Self::get_user(env.clone(), user_address.clone())?;
+
+        let assets: Map<(Address, Address), i128> = env.storage().instance().get(&DataKey::Assets).unwrap_or(Map::new(&env));
+        let mut asset_balances = vec![&env];
+        for ((owner, token), balance) in assets.iter() {
+            if owner == user_address && balance != 0 {
+                asset_balances.push_back((token, balance));
+            }
+        }
+
+        Ok(AccountExport {
+            user,
+            preferences: Self::get_preferences(env.clone(), user_address.clone()),
+            asset_balances,
+            rewards_balance: Self::get_rewards_balance(env.clone(), user_address.clone()),
+            max_transaction: Self::max_transaction_for(&env, &user_address),
+            rate_limits: Self::get_rate_limit_status(env.clone(), user_address.clone())?,
+            restrictions: Self::get_restrictions(env.clone(), user_address.clone()),
+            risk_score: Self::get_risk_score(env.clone(), user_address.clone()),
+            pending: Self::get_pending_summary(env.clone(), user_address.clone()),
+            goals: Self::goals_for(&env, &user_address),
+            loans: Self::loans_for(&env, &user_address),
+            credit_line: Self::accrued_credit_line(&env, &user_address),
+            recent_transfers: Self::load_transfers(&env, Self::latest(Self::transfer_index(&env, &user_address), EXPORT_HISTORY_LEN)),
+            recent_bills: Self::load_each(&env, Self::latest(Self::user_index(&env, DataKey::UserBills(user_address.clone())), EXPORT_HISTORY_LEN), Self::load_bill),
+            recent_withdrawals: Self::load_each(
+                &env,
+                Self::latest(Self::user_index(&env, DataKey::UserWithdrawals(user_address.clone())), EXPORT_HISTORY_LEN),
+                Self::load_withdrawal,
+            ),
+        })
+    }
+
+    // Record another attempt at a bill payment the biller has not confirmed and queue it for the
+    // fulfillment service again. Retries back off exponentially up to `MAX_BILL_ATTEMPTS`
+    // (status updater only). Returns the attempt count.
+    pub fn retry_bill(env: Env, caller: Address, payment_id: String) -> Result<u32, Error> {
+        Self::require_role(&env, &caller, Role::StatusUpdater)?;
+        Self::audit(&env, &caller, symbol_short!("bill_rtry"), AuditTarget::Id(payment_id.clone()), 0);
+
+        let payment = Self::load_bill(&env, &payment_id).ok_or(Error::PaymentNotFound)?;
+        Self::check_status_change(&env, &payment.status)?;
+
+        let now = env.ledger().timestamp();
+        let mut all_attempts: Map<String, BillAttempts> = env.storage().instance().get(&RecordKey::BillAttempts).unwrap_or(Map::new(&env));
+        let mut record = all_attempts.get(payment_id.clone()).unwrap_or(BillAttempts {
+            attempts: 1,
+            next_retry_at: payment.timestamp + BILL_RETRY_BACKOFF_SECS,
+            history: vec![&env, payment.timestamp],
+        });
+
+        if record.attempts >= MAX_BILL_ATTEMPTS {
+            return Err(Error::RetryLimitReached);
+        }
+        if now < record.next_retry_at {
+            return Err(Self::fail_until(&env, &caller, symbol_short!("bill_rtry"), symbol_short!("backoff"), record.next_retry_at, Error::RetryBackoffHasNotElapsed));
+        }
+
+        record.attempts += 1;
+        record.history.push_back(now);
+        record.next_retry_at = now + (BILL_RETRY_BACKOFF_SECS << (record.attempts - 1));
+        all_attempts.set(payment_id.clone(), record.clone());
+        env.storage().instance().set(&RecordKey::BillAttempts, &all_attempts);
+
+        Self::enqueue_task(&env, symbol_short!("bill"), payment_id.clone(), &payment.user_address, payment.amount);
+        env.events().publish((symbol_short!("bill_rtry"), payment_id), (record.attempts, record.next_retry_at));
+
+        Ok(record.attempts)
+    }
+
+    // Get the retry history of a bill payment; `None` if it was never retried
+    pub fn get_bill_attempts(env: Env, payment_id: String) -> Option<BillAttempts> {
+        let all_attempts: Map<String, BillAttempts> = env.storage().instance().get(&RecordKey::BillAttempts).unwrap_or(Map::new(&env));
+
+        all_attempts.get(payment_id)
+    }
+
+    // Set the soft limit and the verifier key for step-up attestations (admin only)
+    pub fn set_step_up_policy(env: Env, policy: StepUpPolicy) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("step_up"), AuditTarget::Global, policy.soft_limit);
+
+        if policy.soft_limit <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        env.storage().instance().set(&ConfigKey::StepUpPolicy, &policy);
+
+        Ok(())
+    }
+
+    pub fn get_step_up_policy(env: Env) -> Option<StepUpPolicy> {
+        env.storage().instance().get(&ConfigKey::StepUpPolicy)
+    }
+
+    // Send USDC above the soft limit with a fresh attestation from the step-up verifier
+    #[allow(clippy::too_many_arguments)]
+    pub fn send_usdc_with_attestation(
+        env: Env,
+        from_address: Address,
+        to_address: Address,
+        amount: i128,
+        fee_bearer: FeeBearer,
+        memo: Option<String>,
+        idempotency_key: Option<BytesN<32>>,
+        attestation: StepUpAttestation,
+    ) -> Result<u64, Error> {
+        Self::approve_step_up(env.clone(), from_address.clone(), amount, attestation)?;
+
+        Self::send_usdc(env, from_address, to_address, amount, fee_bearer, memo, None, idempotency_key)
+    }
+
+    // Approve sends of up to `amount` above the soft limit with a fresh attestation from the
+    // step-up verifier, for a tip or batch that needs one. The approval is spent by the next
+    // sends above the limit.
+    pub fn approve_step_up(env: Env, user_address: Address, amount: i128, attestation: StepUpAttestation) -> Result<(), Error> {
+        user_address.require_auth();
+
+        let policy = Self::get_step_up_policy(env.clone()).ok_or(Error::StepUpNotConfigured)?;
+        let now = env.ledger().timestamp();
+        if attestation.issued_at > now || attestation.issued_at + policy.max_age_secs < now {
+            return Err(Error::AttestationHasExpired);
+        }
+        if attestation.amount < amount {
+            return Err(Error::AttestationDoesNotCoverTheAmount);
+        }
+
+        let mut step_ups: Map<Address, StepUp> = env.storage().instance().get(&ComplianceKey::StepUps).unwrap_or(Map::new(&env));
+        if let Some(last) = step_ups.get(user_address.clone()) {
+            if attestation.issued_at <= last.issued_at {
+                return Err(Error::AttestationAlreadyUsed);
+            }
+        }
+
+        let message = (symbol_short!("step_up"), env.current_contract_address(), user_address.clone(), attestation.amount, attestation.issued_at).to_xdr(&env);
+        Self::verify_signature(&env, &policy.verifier, &message, &attestation.signature);
+
+        step_ups.set(user_address, StepUp {
+            approved_amount: amount,
+            issued_at: attestation.issued_at,
+        });
+        env.storage().instance().set(&ComplianceKey::StepUps, &step_ups);
+
+        Ok(())
+    }
+
+    // Register a user in a country that has a policy, so its rules apply from the start
+    pub fn register_user_in_country(env: Env, user_address: Address, phone: String, country: Symbol) -> Result<(), Error> {
+        if Self::get_country_policy(env.clone(), country.clone()).is_none() {
+            return Err(Error::UnsupportedCountry);
+        }
+
+        Self::register_user(env.clone(), user_address.clone(), phone)?;
+
+        let mut users = Users::load(&env);
+        let mut user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+        user.country = Some(country);
+        users.set(user_address, user);
+        users.save();
+
+        Ok(())
+    }
+
+    // Add or replace the policy for a country (admin only)
+    pub fn set_country_policy(env: Env, policy: CountryPolicy) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("ctry_pol"), AuditTarget::Key(policy.country.clone()), policy.max_transaction);
+
+        if policy.max_transaction < 0 {
+            return Err(Error::InvalidAmount);
+        }
+        let rails: Map<String, WithdrawalRail> = env.storage().instance().get(&ConfigKey::Rails).unwrap_or(Map::new(&env));
+        for rail in policy.allowed_rails.iter() {
+            if !rails.contains_key(rail) {
+                return Err(Error::RailNotFound);
+            }
+        }
+
+        let mut policies: Map<Symbol, CountryPolicy> = env.storage().instance().get(&ConfigKey::CountryPolicies).unwrap_or(Map::new(&env));
+        policies.set(policy.country.clone(), policy);
+        env.storage().instance().set(&ConfigKey::CountryPolicies, &policies);
+
+        Ok(())
+    }
+
+    pub fn get_country_policy(env: Env, country: Symbol) -> Option<CountryPolicy> {
+        let policies: Map<Symbol, CountryPolicy> = env.storage().instance().get(&ConfigKey::CountryPolicies).unwrap_or(Map::new(&env));
+
+        policies.get(country)
+    }
+
+    // Get all country policies
+    pub fn get_country_policies(env: Env, cursor: u32, limit: u32) -> (Vec<CountryPolicy>, Option<u32>) {
+        let policies: Map<Symbol, CountryPolicy> = env.storage().instance().get(&ConfigKey::CountryPolicies).unwrap_or(Map::new(&env));
+
+        Self::page(policies.values(), cursor, limit)
+    }
+}
+
+impl Payvia {
+    // Pay the pot to the member whose turn it is and open the next round
+    fn payout_group_round(env: &Env, group: &mut SavingsGroup, users: &mut Users) -> Result<(), Error> {
+        let recipient = group.members.get(group.round).unwrap();
+
+        if let Some(mut user) = users.get(recipient.clone()) {
+            user.balance = Self::checked_credit(user.balance, group.pot)?;
+            Self::track_balance(env, &user, group.pot, Bucket::LockedSavings);
+            users.set(recipient.clone(), user);
+        }
+
+        env.events().publish((symbol_short!("grp_out"), recipient), (group.id, group.round, group.pot));
+
+        group.pot = 0;
+        group.paid = vec![env];
+        group.round += 1;
+        // A round closed late gives the next one a full cycle rather than one already overdue
+        group.round_deadline = group.round_deadline.max(env.ledger().timestamp()) + group.cycle_secs;
+
+        if group.round >= group.members.len() {
+            group.active = false;
+        }
+
+        Ok(())
+    }
+
+    // Called after every change to a user's balance. On the first change of a day the
+    // pre-change balance is recorded as that day's opening (= previous day's closing) snapshot,
+    // and a threshold event is emitted once per day when the balance moves too far from it.
+    // Also keeps the custodied total in `get_stats` current and posts the change to the ledger
+    // against the `counter` bucket the funds came from or went to.
+    fn track_balance(env: &Env, user: &User, delta: i128, counter: Bucket) {
+        let mut stats = Self::load_stats(env);
+        stats.custodied += delta;
+        Self::save_stats(env, &stats);
+        if delta < 0 {
+            Self::post(env, &user.address, Bucket::Spendable, counter, -delta);
+        } else {
+            Self::post(env, &user.address, counter, Bucket::Spendable, delta);
+        }
+
+        let now = env.ledger().timestamp();
+        let day_start = now - now % SECONDS_PER_DAY;
+
+        let key = EntryKey::BalanceHistory(user.address.clone());
+        let mut history: Vec<BalanceSnapshot> = env.storage().persistent().get(&key).unwrap_or(vec![env]);
+
+        let opening = match history.last() {
+            Some(last) if last.timestamp >= day_start => last.balance,
+            _ => {
+                let opening = user.balance - delta;
+                history.push_back(BalanceSnapshot { timestamp: day_start, balance: opening });
+                while history.len() > MAX_BALANCE_SNAPSHOTS {
+                    history.pop_front();
+                }
+
+                env.storage().persistent().set(&key, &history);
+                env.storage().persistent().extend_ttl(&key, INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+                opening
+            }
+        };
+
+        Self::check_balance_alert(env, user, opening, day_start);
+    }
+
+    // Emit a `bal_alert` event when the balance moved more than the configured share of the
+    // day's opening balance. Consumed by both user notifications and fraud monitoring.
+    fn check_balance_alert(env: &Env, user: &User, opening: i128, day_start: u64) {
+        let threshold_bps: u32 = env.storage().instance().get(&ConfigKey::AlertThresholdBps).unwrap_or(DEFAULT_BALANCE_ALERT_BPS);
+
+        // Without a baseline there is no meaningful percentage to compare against
+        if threshold_bps == 0 || opening <= 0 {
+            return;
+        }
+
+        let change = user.balance - opening;
+        let change_bps = change.abs() * BPS_DENOMINATOR / opening;
+        if change_bps <= threshold_bps as i128 {
+            return;
+        }
+
+        let mut alerted: Map<Address, u64> = env.storage().instance().get(&ComplianceKey::Alerted).unwrap_or(Map::new(env));
+        if alerted.get(user.address.clone()) == Some(day_start) {
+            return;
+        }
+        alerted.set(user.address.clone(), day_start);
+        env.storage().instance().set(&ComplianceKey::Alerted, &alerted);
+
+        env.events().publish((symbol_short!("bal_alert"), user.address.clone()), (opening, user.balance, change_bps));
+    }
+
+    // Amount of a stream vested at the current ledger time
+    fn stream_vested(env: &Env, stream: &PaymentStream) -> i128 {
+        let now = env.ledger().timestamp();
+
+        if now <= stream.start {
+            0
+        } else if now >= stream.end {
+            stream.total_amount
+        } else {
+            stream.total_amount * (now - stream.start) as i128 / (stream.end - stream.start) as i128
+        }
+    }
+
+    // Deterministic placeholder address for the i-th demo user
+    fn demo_address(env: &Env, i: u32) -> Address {
+        let mut salt = [0u8; 32];
+        salt[..4].copy_from_slice(b"demo");
+        salt[28..].copy_from_slice(&i.to_be_bytes());
+
+        env.deployer()
+            .with_current_contract(BytesN::from_array(env, &salt))
+            .deployed_address()
+    }
+
+    // `prefix` followed by the decimal digits of `n`
+    fn demo_string(env: &Env, prefix: &[u8], n: u32) -> String {
+        let mut buf = [0u8; 32];
+        let mut len = prefix.len();
+        buf[..len].copy_from_slice(prefix);
+
+        let mut digits = [0u8; 10];
+        let mut count = 0;
+        let mut rest = n;
+        loop {
+            digits[count] = b'0' + (rest % 10) as u8;
+            count += 1;
+            rest /= 10;
+            if rest == 0 {
+                break;
+            }
+        }
+        while count > 0 {
+            count -= 1;
+            buf[len] = digits[count];
+            len += 1;
+        }
+
+        String::from_bytes(env, &buf[..len])
+    }
+
+    // Pay the referrer's reward the first time a referred user completes a transaction.
+    // Must run after the caller has persisted its own changes to the users map.
+    fn qualify_referral(env: &Env, user_address: &Address) -> Result<(), Error> {
+        let mut pending: Map<Address, Address> = env.storage().instance().get(&DataKey::ReferralPending).unwrap_or(Map::new(env));
+        let referrer = match pending.get(user_address.clone()) {
+            Some(referrer) => referrer,
+            None => return Ok(()),
+        };
+
+        // The referral stays pending until the pool can pay it, so an unfunded pool only delays
+        // the reward
+        let reward: i128 = env.storage().instance().get(&ConfigKey::ReferralReward).unwrap_or(0);
+        let pool: i128 = env.storage().instance().get(&CounterKey::ReferralPool).unwrap_or(0);
+        let mut users = Users::load(env);
+        let mut paid = 0;
+        if let Some(mut user) = users.get(referrer.clone()) {
+            if reward <= 0 || pool < reward {
+                return Ok(());
+            }
+            paid = reward;
+            user.balance = Self::checked_credit(user.balance, paid)?;
+            Self::track_balance(env, &user, paid, Bucket::Treasury);
+            users.set(referrer.clone(), user);
+            users.save();
+            env.storage().instance().set(&CounterKey::ReferralPool, &(pool - paid));
+        }
+
+        pending.remove(user_address.clone());
+        env.storage().instance().set(&DataKey::ReferralPending, &pending);
+
+        let mut stats: Map<Address, ReferralStats> = env.storage().instance().get(&DataKey::ReferralStats).unwrap_or(Map::new(env));
+        if let Some(mut referrer_stats) = stats.get(referrer.clone()) {
+            referrer_stats.qualified_count += 1;
+            referrer_stats.rewards_earned += paid;
+            stats.set(referrer.clone(), referrer_stats);
+            env.storage().instance().set(&DataKey::ReferralStats, &stats);
+        }
+
+        env.events().publish((symbol_short!("ref_paid"), referrer), (user_address.clone(), paid));
+
+        Ok(())
+    }
+
+    // Accrue cashback points for a bill payment at the rate configured for its bill type
+    fn accrue_cashback(env: &Env, user_address: &Address, bill_type: &String, amount: i128) {
+        let rates: Map<String, u32> = env.storage().instance().get(&ConfigKey::CashbackRates).unwrap_or(Map::new(env));
+        let rate_bps = rates.get(bill_type.clone()).unwrap_or(0);
+
+        let points = amount * rate_bps as i128 / BPS_DENOMINATOR;
+        if points <= 0 {
+            return;
+        }
+
+        let mut rewards: Map<Address, i128> = env.storage().instance().get(&DataKey::Rewards).unwrap_or(Map::new(env));
+        let balance = rewards.get(user_address.clone()).unwrap_or(0);
+        rewards.set(user_address.clone(), balance + points);
+        env.storage().instance().set(&DataKey::Rewards, &rewards);
+        Self::post(env, user_address, Bucket::Treasury, Bucket::Rewards, points);
+
+        env.events().publish((symbol_short!("cashback"), user_address.clone()), (bill_type.clone(), points));
+    }
+
+    // Record why an operation was rejected for a user and return the error
+    fn fail(
+        env: &Env,
+        user_address: &Address,
+        operation: Symbol,
+        reason: Symbol,
+        requested: i128,
+        available: i128,
+        error: Error,
+    ) -> Error {
+        Self::record_failure(env, user_address, operation, reason, requested, available, 0, error)
+    }
+
+    // Record a rejection that can be retried once `retry_after` has passed
+    fn fail_until(
+        env: &Env,
+        user_address: &Address,
+        operation: Symbol,
+        reason: Symbol,
+        retry_after: u64,
+        error: Error,
+    ) -> Error {
+        Self::record_failure(env, user_address, operation, reason, 0, 0, retry_after, error)
+    }
+
+    // Balance arithmetic that reports an overflow instead of trapping. A negative amount would
+    // turn a credit into a debit, so it is refused.
+    fn checked_credit(balance: i128, amount: i128) -> Result<i128, Error> {
+        if amount < 0 {
+            return Err(Error::InvalidAmount);
+        }
+        balance.checked_add(amount).ok_or(Error::ArithmeticOverflow)
+    }
+
+    fn checked_debit(balance: i128, amount: i128) -> Result<i128, Error> {
+        if amount < 0 {
+            return Err(Error::InvalidAmount);
+        }
+        balance.checked_sub(amount).ok_or(Error::ArithmeticOverflow)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn record_failure(
+        env: &Env,
+        user_address: &Address,
+        operation: Symbol,
+        reason: Symbol,
+        requested: i128,
+        available: i128,
+        retry_after: u64,
+        error: Error,
+    ) -> Error {
+        env.storage().instance().set(&ComplianceKey::LastFailure, &FailureRecord {
+            user_address: user_address.clone(),
+            operation,
+            reason,
+            error,
+            requested,
+            available,
+            retry_after,
+            timestamp: env.ledger().timestamp(),
+        });
+
+        error
+    }
+
+    // Take the details recorded by the last check that failed within this invocation
+    pub(crate) fn take_failure(env: &Env) -> Option<FailureRecord> {
+        let failure = env.storage().instance().get(&ComplianceKey::LastFailure);
+        env.storage().instance().remove(&ComplianceKey::LastFailure);
+
+        failure
+    }
+
+    // Commission an agent earns on a cash-in or cash-out of `amount`
+    fn agent_commission(env: &Env, amount: i128) -> i128 {
+        let commission_bps: u32 = env.storage().instance().get(&ConfigKey::AgentCommissionBps).unwrap_or(0);
+
+        amount * commission_bps as i128 / BPS_DENOMINATOR
+    }
+
+    // Fee engine: apportion `fee` between payer and payee
+    fn split_fee(amount: i128, fee: i128, fee_bearer: &FeeBearer) -> FeeQuote {
+        let payee_share = match fee_bearer {
+            FeeBearer::Payer => 0,
+            FeeBearer::Payee => fee,
+            FeeBearer::Split => fee / 2,
+        };
+
+        FeeQuote {
+            amount,
+            fee,
+            payer_pays: amount + fee - payee_share,
+            payee_receives: amount - payee_share,
+        }
+    }
+
+    fn check_memo(memo: &Option<String>) -> Result<(), Error> {
+        if let Some(memo) = memo {
+            if memo.len() > MAX_MEMO_LEN {
+                return Err(Error::MemoTooLong);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Result of an earlier call made by `user_address` with the same idempotency key, if still live
+    fn idempotent_result(env: &Env, user_address: &Address, idempotency_key: &Option<BytesN<32>>) -> Option<IdempotentResult> {
+        let key = EntryKey::Processed(user_address.clone(), idempotency_key.clone()?);
+        let record: Option<IdempotencyRecord> = env.storage().temporary().get(&key);
+
+        match record {
+            Some(record) if record.expires_at > env.ledger().timestamp() => Some(record.result),
+            _ => None,
+        }
+    }
+
+    fn remember_result(env: &Env, user_address: &Address, idempotency_key: &Option<BytesN<32>>, result: IdempotentResult) {
+        let key = match idempotency_key {
+            Some(key) => key.clone(),
+            None => return,
+        };
+
+        let record = IdempotencyRecord { result, expires_at: env.ledger().timestamp() + IDEMPOTENCY_TTL_SECS };
+        Self::store_result(env, EntryKey::Processed(user_address.clone(), key), &record);
+    }
+
+    // Processed keys live in temporary storage, so they drop out on their own once the TTL passes
+    fn store_result(env: &Env, key: EntryKey, record: &IdempotencyRecord) {
+        env.storage().temporary().set(&key, record);
+        env.storage().temporary().extend_ttl(&key, IDEMPOTENCY_TTL_LEDGERS, IDEMPOTENCY_TTL_LEDGERS);
+    }
+
+    // Bounce payments below the recipient's configured minimum unless the sender is whitelisted
+    fn check_min_incoming(env: &Env, from_address: &Address, to_address: &Address, amount: i128, operation: Symbol) -> Result<(), Error> {
+        let minimums: Map<Address, i128> = env.storage().instance().get(&ComplianceKey::MinIncoming).unwrap_or(Map::new(env));
+        let min_amount = minimums.get(to_address.clone()).unwrap_or(0);
+        if amount >= min_amount {
+            return Ok(());
+        }
+
+        let whitelist: Map<(Address, Address), bool> = env.storage().instance().get(&ComplianceKey::MinIncomingWhitelist).unwrap_or(Map::new(env));
+        if whitelist.contains_key((to_address.clone(), from_address.clone())) {
+            return Ok(());
+        }
+
+        Err(Self::fail(env, from_address, operation, symbol_short!("below_min"), amount, min_amount, Error::AmountBelowRecipientMinimum))
+    }
+
+    // Set or clear a compliance flag (`frozen` or `blacklist`) and emit a compliance event
+    fn set_compliance_flag(env: &Env, caller: Address, address: Address, flag: Symbol, enabled: bool) -> Result<(), Error> {
+        Self::require_role(env, &caller, Role::Compliance)?;
+        Self::audit(env, &caller, flag.clone(), AuditTarget::Address(address.clone()), enabled as i128);
+
+        let key = if flag == symbol_short!("frozen") { ComplianceKey::Frozen } else { ComplianceKey::Blacklist };
+        let mut flagged: Map<Address, bool> = env.storage().instance().get(&key).unwrap_or(Map::new(env));
+        if enabled {
+            flagged.set(address.clone(), true);
+        } else {
+            flagged.remove(address.clone());
+        }
+        env.storage().instance().set(&key, &flagged);
+
+        env.events().publish((symbol_short!("comply"), flag, address), (caller, enabled));
+
+        Ok(())
+    }
+
+    // Reject operations by or direct payments to frozen and blacklisted accounts.
+    // Escrowed funds (scheduled payments, streams) stay claimable once the restriction is lifted.
+    fn ensure_not_restricted(env: &Env, address: &Address, operation: Symbol) -> Result<(), Error> {
+        let frozen: Map<Address, bool> = env.storage().instance().get(&ComplianceKey::Frozen).unwrap_or(Map::new(env));
+        if frozen.contains_key(address.clone()) {
+            return Err(Self::fail(env, address, operation, symbol_short!("frozen"), 0, 0, Error::AccountFrozen));
+        }
+
+        let blacklist: Map<Address, bool> = env.storage().instance().get(&ComplianceKey::Blacklist).unwrap_or(Map::new(env));
+        if blacklist.contains_key(address.clone()) {
+            return Err(Self::fail(env, address, operation, symbol_short!("blacklist"), 0, 0, Error::AddressBlacklisted));
+        }
+
+        if Self::is_closed(env, address) {
+            return Err(Self::fail(env, address, operation, symbol_short!("closed"), 0, 0, Error::AccountClosed));
+        }
+
+        Ok(())
+    }
+
+    // Apply the recipient's spam screening. Returns whether the transfer should be quarantined.
+    fn screen_incoming(env: &Env, from_address: &Address, to_address: &Address, amount: i128) -> Result<bool, Error> {
+        let policies: Map<Address, ScreeningPolicy> = env.storage().instance().get(&ComplianceKey::Screening).unwrap_or(Map::new(env));
+        let policy = match policies.get(to_address.clone()) {
+            Some(policy) => policy,
+            None => return Ok(false),
+        };
+
+        if amount >= policy.threshold {
+            return Ok(false);
+        }
+
+        let known: Map<(Address, Address), bool> = env.storage().instance().get(&ComplianceKey::KnownPayees).unwrap_or(Map::new(env));
+        if known.contains_key((to_address.clone(), from_address.clone())) {
+            return Ok(false);
+        }
+
+        match policy.mode {
+            ScreeningMode::Off => Ok(false),
+            ScreeningMode::Reject => Err(Self::fail(env, from_address, symbol_short!("send"), symbol_short!("screened"), amount, policy.threshold, Error::RejectedByScreening)),
+            ScreeningMode::Quarantine => Ok(true),
+        }
+    }
+
+    fn mark_known_sender(env: &Env, user_address: &Address, sender: &Address) {
+        let mut known: Map<(Address, Address), bool> = env.storage().instance().get(&ComplianceKey::KnownPayees).unwrap_or(Map::new(env));
+        if known.contains_key((user_address.clone(), sender.clone())) {
+            return;
+        }
+
+        known.set((user_address.clone(), sender.clone()), true);
+        env.storage().instance().set(&ComplianceKey::KnownPayees, &known);
+    }
+
+    fn review_quarantined(env: &Env, user_address: Address, transfer_id: u64, accept: bool) -> Result<(), Error> {
+        user_address.require_auth();
+
+        let mut quarantined: Map<u64, QuarantinedTransfer> = env.storage().instance().get(&ComplianceKey::Quarantined).unwrap_or(Map::new(env));
+        let transfer = quarantined.get(transfer_id).ok_or(Error::QuarantinedTransferNotFound)?;
+
+        if transfer.to_address != user_address {
+            return Err(Error::Unauthorized);
+        }
+
+        let beneficiary = if accept { transfer.to_address.clone() } else { transfer.from_address.clone() };
+
+        let mut users = Users::load(env);
+        let mut user = users.get(beneficiary.clone()).ok_or(Error::UserNotFound)?;
+        user.balance = Self::checked_credit(user.balance, transfer.amount)?;
+        Self::track_balance(env, &user, transfer.amount, Bucket::Escrowed);
+        users.set(beneficiary, user);
+        users.save();
+
+        quarantined.remove(transfer_id);
+        env.storage().instance().set(&ComplianceKey::Quarantined, &quarantined);
+
+        if accept {
+            Self::mark_known_sender(env, &transfer.to_address, &transfer.from_address);
+        }
+
+        env.events().publish((symbol_short!("quar_done"), transfer.from_address, transfer.to_address), (transfer_id, accept));
+
+        Ok(())
+    }
+
+    // v1 added `memo` to bill payments and withdrawals
+    fn migrate_v0_to_v1(env: &Env) {
+        let old_bills: Map<String, BillPaymentV0> = env.storage().instance().get(&DataKey::Bills).unwrap_or(Map::new(env));
+        let mut bill_payments: Map<String, BillPayment> = Map::new(env);
+        for (payment_id, old) in old_bills.iter() {
+            bill_payments.set(payment_id, BillPayment {
+                id: old.id,
+                user_address: old.user_address,
+                bill_type: old.bill_type,
+                account_number: old.account_number,
+                amount: old.amount,
+                status: old.status,
+                timestamp: old.timestamp,
+                memo: None,
+            });
+        }
+        env.storage().instance().set(&DataKey::Bills, &bill_payments);
+
+        let old_withdrawals: Map<String, WithdrawalV0> = env.storage().instance().get(&DataKey::Withdrawals).unwrap_or(Map::new(env));
+        let mut withdrawals: Map<String, WithdrawalV1> = Map::new(env);
+        for (withdrawal_id, old) in old_withdrawals.iter() {
+            withdrawals.set(withdrawal_id, WithdrawalV1 {
+                id: old.id,
+                user_address: old.user_address,
+                method: old.method,
+                account_number: old.account_number,
+                usdc_amount: old.usdc_amount,
+                ugx_amount: old.ugx_amount,
+                status: old.status,
+                timestamp: old.timestamp,
+                memo: None,
+            });
+        }
+        env.storage().instance().set(&DataKey::Withdrawals, &withdrawals);
+    }
+
+    // v2 records the payout currency on withdrawals; everything before it was paid out in UGX
+    fn migrate_v1_to_v2(env: &Env) {
+        let old_withdrawals: Map<String, WithdrawalV1> = env.storage().instance().get(&DataKey::Withdrawals).unwrap_or(Map::new(env));
+        let mut withdrawals: Map<String, WithdrawalV3> = Map::new(env);
+        for (withdrawal_id, old) in old_withdrawals.iter() {
+            withdrawals.set(withdrawal_id, WithdrawalV3 {
+                id: old.id,
+                user_address: old.user_address,
+                method: old.method,
+                account_number: old.account_number,
+                usdc_amount: old.usdc_amount,
+                local_amount: old.ugx_amount,
+                currency: symbol_short!("UGX"),
+                status: old.status,
+                timestamp: old.timestamp,
+                memo: old.memo,
+            });
+        }
+        env.storage().instance().set(&DataKey::Withdrawals, &withdrawals);
+    }
+
+    // v3 added optional profile fields to users
+    fn migrate_v2_to_v3(env: &Env) {
+        let old_users: Map<Address, UserV2> = env.storage().instance().get(&DataKey::Users).unwrap_or(Map::new(env));
+        let mut users: Map<Address, User> = Map::new(env);
+        for (address, old) in old_users.iter() {
+            users.set(address, User {
+                address: old.address,
+                phone: old.phone,
+                is_verified: old.is_verified,
+                balance: old.balance,
+                display_name: None,
+                country: None,
+                preferred_currency: None,
+            });
+        }
+        env.storage().instance().set(&DataKey::Users, &users);
+    }
+
+    // v19 indexes the records in the instance maps under each user who owns or takes part in them,
+    // rather than filtering every map by address on each read
+    fn migrate_v18_to_v19(env: &Env) {
+        let goals: Map<u64, SavingsGoal> = env.storage().instance().get(&DataKey::Goals).unwrap_or(Map::new(env));
+        for (goal_id, goal) in goals.iter() {
+            Self::index_record(env, EntryKey::UserGoals(goal.owner), goal_id);
+        }
+        let scheduled: Map<u64, ScheduledPayment> = env.storage().instance().get(&DataKey::Scheduled).unwrap_or(Map::new(env));
+        for (schedule_id, payment) in scheduled.iter() {
+            Self::index_record(env, EntryKey::UserScheduled(payment.from_address), schedule_id);
+        }
+        let streams: Map<u64, PaymentStream> = env.storage().instance().get(&DataKey::Streams).unwrap_or(Map::new(env));
+        for (stream_id, stream) in streams.iter() {
+            Self::index_record(env, EntryKey::UserStreams(stream.from_address), stream_id);
+            Self::index_record(env, EntryKey::UserStreams(stream.to_address), stream_id);
+        }
+        let cash_outs: Map<u64, CashOut> = env.storage().instance().get(&DataKey::CashOuts).unwrap_or(Map::new(env));
+        for (cash_out_id, cash_out) in cash_outs.iter() {
+            Self::index_record(env, EntryKey::UserCashOuts(cash_out.user_address), cash_out_id);
+        }
+        let splits: Map<u64, BillSplit> = env.storage().instance().get(&DataKey::Splits).unwrap_or(Map::new(env));
+        for (_, split) in splits.iter() {
+            Self::index_split(env, &split);
+        }
+        let vouchers: Map<BytesN<32>, Voucher> = env.storage().instance().get(&DataKey::Vouchers).unwrap_or(Map::new(env));
+        for (code_hash, voucher) in vouchers.iter() {
+            Self::index_record(env, EntryKey::UserVouchers(voucher.funder), code_hash);
+        }
+        let loans: Map<u64, Loan> = env.storage().instance().get(&DataKey::Loans).unwrap_or(Map::new(env));
+        for (loan_id, loan) in loans.iter() {
+            Self::index_record(env, EntryKey::UserLoans(loan.borrower), loan_id);
+        }
+        let remittances: Map<u64, Remittance> = env.storage().instance().get(&DataKey::Remittances).unwrap_or(Map::new(env));
+        for (remittance_id, remittance) in remittances.iter() {
+            Self::index_record(env, EntryKey::UserRemittances(remittance.sender), remittance_id);
+        }
+        let holds: Map<u64, CardHold> = env.storage().instance().get(&DataKey::CardHolds).unwrap_or(Map::new(env));
+        for (hold_id, hold) in holds.iter() {
+            Self::index_record(env, EntryKey::UserCardHolds(hold.user_address), hold_id);
+        }
+        let payrolls: Map<u64, Payroll> = env.storage().instance().get(&DataKey::Payrolls).unwrap_or(Map::new(env));
+        for (payroll_id, payroll) in payrolls.iter() {
+            Self::index_record(env, EntryKey::UserPayrolls(payroll.employer), payroll_id);
+        }
+        let claims: Map<u64, ContactClaim> = env.storage().instance().get(&RecordKey::ContactClaims).unwrap_or(Map::new(env));
+        for (claim_id, claim) in claims.iter() {
+            Self::index_record(env, EntryKey::UserContactClaims(claim.sender), claim_id);
+        }
+    }
+
+    // v18 keeps each transfer in its own persistent entry, indexed per party, rather than in one
+    // instance map that every send had to load whole
+    fn migrate_v17_to_v18(env: &Env) {
+        let transfers: Map<u64, Transfer> = env.storage().instance().get(&DataKey::Transfers).unwrap_or(Map::new(env));
+        for (_, transfer) in transfers.iter() {
+            Self::save_transfer(env, &transfer);
+        }
+        env.storage().instance().remove(&DataKey::Transfers);
+    }
+
+    // v17 keeps each user's balance snapshots in their own persistent entry rather than in one
+    // instance map that every balance change had to load whole
+    fn migrate_v16_to_v17(env: &Env) {
+        let snapshots: Map<Address, Vec<BalanceSnapshot>> = env.storage().instance().get(&DataKey::Snapshots).unwrap_or(Map::new(env));
+        for (user_address, history) in snapshots.iter() {
+            let key = EntryKey::BalanceHistory(user_address);
+            env.storage().persistent().set(&key, &history);
+            env.storage().persistent().extend_ttl(&key, INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+        }
+        env.storage().instance().remove(&DataKey::Snapshots);
+    }
+
+    // v16 keeps each idempotency key in its own temporary entry that expires with it, rather than
+    // in one map pruned on every keyed call
+    fn migrate_v15_to_v16(env: &Env) {
+        let records: Map<(Address, BytesN<32>), IdempotencyRecord> = env.storage().instance().get(&ComplianceKey::Idempotency).unwrap_or(Map::new(env));
+        for ((user_address, key), record) in records.iter() {
+            if record.expires_at > env.ledger().timestamp() {
+                Self::store_result(env, EntryKey::Processed(user_address, key), &record);
+            }
+        }
+        env.storage().instance().remove(&ComplianceKey::Idempotency);
+    }
+
+    // v15 carries what a member failed to pay in a savings group round into their next contribution
+    fn migrate_v14_to_v15(env: &Env) {
+        let old_groups: Map<u64, SavingsGroupV14> = env.storage().instance().get(&DataKey::Groups).unwrap_or(Map::new(env));
+        let mut groups: Map<u64, SavingsGroup> = Map::new(env);
+        for (group_id, old) in old_groups.iter() {
+            let mut arrears = vec![env];
+            for _ in old.members.iter() {
+                arrears.push_back(0i128);
+            }
+            groups.set(group_id, SavingsGroup {
+                id: old.id,
+                name: old.name,
+                members: old.members,
+                contribution: old.contribution,
+                cycle_secs: old.cycle_secs,
+                round: old.round,
+                round_deadline: old.round_deadline,
+                pot: old.pot,
+                paid: old.paid,
+                missed: old.missed,
+                arrears,
+                active: old.active,
+            });
+        }
+        env.storage().instance().set(&DataKey::Groups, &groups);
+    }
+
+    // v14 keeps each swap in its own entry, indexed per user so history pages read only their own swaps
+    fn migrate_v13_to_v14(env: &Env) {
+        let swaps: Map<u64, Swap> = env.storage().instance().get(&DataKey::Swaps).unwrap_or(Map::new(env));
+        for (_, swap) in swaps.iter() {
+            Self::save_swap(env, &swap);
+        }
+        env.storage().instance().remove(&DataKey::Swaps);
+    }
+
+    // v13 keeps each user, bill payment and withdrawal in its own persistent entry rather than in
+    // instance maps that every call had to load whole
+    fn migrate_v12_to_v13(env: &Env) {
+        let old_users: Map<Address, User> = env.storage().instance().get(&DataKey::Users).unwrap_or(Map::new(env));
+        let mut users = Users::load(env);
+        for (address, user) in old_users.iter() {
+            users.set(address, user);
+        }
+        users.save();
+        env.storage().instance().remove(&DataKey::Users);
+
+        let bill_payments: Map<String, BillPayment> = env.storage().instance().get(&DataKey::Bills).unwrap_or(Map::new(env));
+        for (_, bill_payment) in bill_payments.iter() {
+            Self::save_bill(env, &bill_payment);
+        }
+        env.storage().instance().remove(&DataKey::Bills);
+
+        let withdrawals: Map<String, Withdrawal> = env.storage().instance().get(&DataKey::Withdrawals).unwrap_or(Map::new(env));
+        for (_, withdrawal) in withdrawals.iter() {
+            Self::save_withdrawal(env, &withdrawal);
+        }
+        env.storage().instance().remove(&DataKey::Withdrawals);
+    }
+
+    // v12 salts PINs. Existing hashes cannot be re-salted without the PIN, so they stay bare
+    // until their owner sets a new one.
+    fn migrate_v11_to_v12(env: &Env) {
+        let old_pins: Map<Address, PinStateV11> = env.storage().instance().get(&ComplianceKey::Pins).unwrap_or(Map::new(env));
+        let mut pins: Map<Address, PinState> = Map::new(env);
+        for (address, old) in old_pins.iter() {
+            pins.set(address, PinState {
+                hash: old.hash,
+                salt: None,
+                failed_attempts: old.failed_attempts,
+                locked_until: old.locked_until,
+                approved_until: old.approved_until,
+            });
+        }
+        env.storage().instance().set(&ComplianceKey::Pins, &pins);
+    }
+
+    // v11 stopped keeping per-user failure records; a failed call rolled them back anyway
+    fn migrate_v10_to_v11(env: &Env) {
+        env.storage().instance().remove(&ComplianceKey::Failures);
+    }
+
+    // v10 records the FX spread on withdrawals and remittances. Earlier ones had none, so their
+    // effective rate is the rate their local amount was paid at.
+    fn migrate_v9_to_v10(env: &Env) {
+        let old_withdrawals: Map<String, WithdrawalV9> = env.storage().instance().get(&DataKey::Withdrawals).unwrap_or(Map::new(env));
+        let mut withdrawals: Map<String, Withdrawal> = Map::new(env);
+        for (withdrawal_id, old) in old_withdrawals.iter() {
+            let effective_rate = if old.usdc_amount > 0 { old.local_amount * RATE_SCALE / old.usdc_amount } else { 0 };
+            withdrawals.set(withdrawal_id, Withdrawal {
+                id: old.id,
+                user_address: old.user_address,
+                method: old.method,
+                account_number: old.account_number,
+                usdc_amount: old.usdc_amount,
+                local_amount: old.local_amount,
+                currency: old.currency,
+                status: old.status,
+                timestamp: old.timestamp,
+                memo: old.memo,
+                payout_reference: old.payout_reference,
+                processing_deadline: old.processing_deadline,
+                effective_rate,
+                spread: 0,
+            });
+        }
+        env.storage().instance().set(&DataKey::Withdrawals, &withdrawals);
+
+        let old_remittances: Map<u64, RemittanceV9> = env.storage().instance().get(&DataKey::Remittances).unwrap_or(Map::new(env));
+        let mut remittances: Map<u64, Remittance> = Map::new(env);
+        for (remittance_id, old) in old_remittances.iter() {
+            remittances.set(remittance_id, Remittance {
+                id: old.id,
+                sender: old.sender,
+                beneficiary_id: old.beneficiary_id,
+                usdc_amount: old.usdc_amount,
+                fee: old.fee,
+                local_amount: old.local_amount,
+                currency: old.currency,
+                status: old.status,
+                timestamp: old.timestamp,
+                spread: 0,
+            });
+        }
+        env.storage().instance().set(&DataKey::Remittances, &remittances);
+    }
+
+    // v9 keeps balances in ledger buckets; open them from the records so later postings reconcile
+    fn migrate_v8_to_v9(env: &Env) {
+        env.storage().instance().set(&CounterKey::Ledger, &Self::opening_ledger(env));
+    }
+
+    // v8 gave billers an amount policy; existing billers accept any amount within their limits
+    fn migrate_v7_to_v8(env: &Env) {
+        let old_providers: Map<String, BillProviderV7> = env.storage().instance().get(&ConfigKey::Providers).unwrap_or(Map::new(env));
+        let mut providers: Map<String, BillProvider> = Map::new(env);
+        for (provider_id, old) in old_providers.iter() {
+            providers.set(provider_id, BillProvider {
+                id: old.id,
+                name: old.name,
+                category: old.category,
+                min_amount: old.min_amount,
+                max_amount: old.max_amount,
+                account_min_len: old.account_min_len,
+                account_max_len: old.account_max_len,
+                numeric_account: old.numeric_account,
+                active: old.active,
+                amount_policy: AmountPolicy::Range,
+            });
+        }
+        env.storage().instance().set(&ConfigKey::Providers, &providers);
+    }
+
+    // v7 gave withdrawals a processing deadline, counted from when they were requested
+    fn migrate_v6_to_v7(env: &Env) {
+        let sla: u64 = env.storage().instance().get(&ConfigKey::WithdrawalSla).unwrap_or(DEFAULT_WITHDRAWAL_SLA_SECS);
+        let old_withdrawals: Map<String, WithdrawalV4> = env.storage().instance().get(&DataKey::Withdrawals).unwrap_or(Map::new(env));
+        let mut withdrawals: Map<String, WithdrawalV9> = Map::new(env);
+        for (withdrawal_id, old) in old_withdrawals.iter() {
+            withdrawals.set(withdrawal_id, WithdrawalV9 {
+                id: old.id,
+                user_address: old.user_address,
+                method: old.method,
+                account_number: old.account_number,
+                usdc_amount: old.usdc_amount,
+                local_amount: old.local_amount,
+                currency: old.currency,
+                status: old.status,
+                timestamp: old.timestamp,
+                memo: old.memo,
+                payout_reference: old.payout_reference,
+                processing_deadline: old.timestamp + sla,
+            });
+        }
+        env.storage().instance().set(&DataKey::Withdrawals, &withdrawals);
+    }
+
+    // v6 indexes bill payments and withdrawals by user
+    fn migrate_v5_to_v6(env: &Env) {
+        let bill_payments: Map<String, BillPayment> = env.storage().instance().get(&DataKey::Bills).unwrap_or(Map::new(env));
+        for (payment_id, payment) in bill_payments.iter() {
+            Self::add_to_index(env, DataKey::UserBills(payment.user_address), payment_id);
+        }
+
+        let withdrawals: Map<String, WithdrawalV4> = env.storage().instance().get(&DataKey::Withdrawals).unwrap_or(Map::new(env));
+        for (withdrawal_id, withdrawal) in withdrawals.iter() {
+            Self::add_to_index(env, DataKey::UserWithdrawals(withdrawal.user_address), withdrawal_id);
+        }
+    }
+
+    // v4 added the payout reference to withdrawals
+    fn migrate_v3_to_v4(env: &Env) {
+        let old_withdrawals: Map<String, WithdrawalV3> = env.storage().instance().get(&DataKey::Withdrawals).unwrap_or(Map::new(env));
+        let mut withdrawals: Map<String, WithdrawalV4> = Map::new(env);
+        for (withdrawal_id, old) in old_withdrawals.iter() {
+            withdrawals.set(withdrawal_id, WithdrawalV4 {
+                id: old.id,
+                user_address: old.user_address,
+                method: old.method,
+                account_number: old.account_number,
+                usdc_amount: old.usdc_amount,
+                local_amount: old.local_amount,
+                currency: old.currency,
+                status: old.status,
+                timestamp: old.timestamp,
+                memo: old.memo,
+                payout_reference: None,
+            });
+        }
+        env.storage().instance().set(&DataKey::Withdrawals, &withdrawals);
+    }
+
+    fn is_supported_currency(code: &Symbol) -> bool {
+        *code == symbol_short!("UGX") || *code == symbol_short!("KES") || *code == symbol_short!("TZS") || *code == symbol_short!("NGN")
+    }
+
+    fn require_council_member(env: &Env, signer: &Address) -> Result<(), Error> {
+        let council: Vec<Address> = env.storage().instance().get(&ConfigKey::Council).unwrap_or(vec![env]);
+
+        if !council.contains(signer.clone()) {
+            return Err(Error::Unauthorized);
+        }
+
+        Ok(())
+    }
+
+    // Once a council is configured, sensitive admin calls only run as part of an approved proposal
+    fn require_council_approval(env: &Env) -> Result<(), Error> {
+        let council: Vec<Address> = env.storage().instance().get(&ConfigKey::Council).unwrap_or(vec![env]);
+        if council.is_empty() {
+            return Ok(());
+        }
+
+        let executing: bool = env.storage().temporary().get(&ConfigKey::CouncilExecuting).unwrap_or(false);
+        if !executing {
+            return Err(Error::RequiresCouncilApproval);
+        }
+
+        Ok(())
+    }
+
+    fn execute_if_approved(env: &Env, proposal_id: u64) -> Result<(), Error> {
+        let mut proposals: Map<u64, AdminProposal> = env.storage().instance().get(&DataKey::Proposals).unwrap_or(Map::new(env));
+        let mut proposal = proposals.get(proposal_id).ok_or(Error::ProposalNotFound)?;
+
+        // Only approvals from the current council count; members removed since they approved do not
+        let council: Vec<Address> = env.storage().instance().get(&ConfigKey::Council).unwrap_or(vec![env]);
+        let approvals = proposal.approvals.iter().filter(|signer| council.contains(signer.clone())).count() as u32;
+
+        let threshold: u32 = env.storage().instance().get(&ConfigKey::CouncilThreshold).unwrap_or(0);
+        if proposal.executed || approvals < threshold {
+            return Ok(());
+        }
+
+        env.storage().temporary().set(&ConfigKey::CouncilExecuting, &true);
+        let result = match proposal.action.clone() {
+            AdminAction::UpdateBillStatus(payment_id, status) => Self::set_bill_status(env.clone(), env.current_contract_address(), payment_id, status),
+            AdminAction::UpdateWithdrawalStatus(withdrawal_id, status, payout_reference) => {
+                Self::set_withdrawal_status(env.clone(), env.current_contract_address(), withdrawal_id, status, payout_reference)
+            }
+            AdminAction::SetTransferFee(fee_bps) => Self::set_transfer_fee(env.clone(), fee_bps),
+            AdminAction::SetAgentCommission(commission_bps) => Self::set_agent_commission(env.clone(), commission_bps),
+            AdminAction::Upgrade(new_wasm_hash) => Self::upgrade(env.clone(), new_wasm_hash),
+            AdminAction::SweepFees(to_address, amount) => Self::sweep_fees(env.clone(), env.current_contract_address(), to_address, amount),
+            AdminAction::SetAdmins(admins, threshold) => Self::set_admins(env.clone(), admins, threshold),
+        };
+        env.storage().temporary().remove(&ConfigKey::CouncilExecuting);
+        result?;
+
+        proposal.executed = true;
+        proposals.set(proposal_id, proposal);
+        env.storage().instance().set(&DataKey::Proposals, &proposals);
+
+        env.events().publish((symbol_short!("executed"),), proposal_id);
+
+        Ok(())
+    }
+
+    // Validate a bill payment against the biller registry
+    fn validate_bill(env: &Env, user_address: &Address, bill_type: &String, account_number: &String, amount: i128) -> Result<BillProvider, Error> {
+        let providers: Map<String, BillProvider> = env.storage().instance().get(&ConfigKey::Providers).unwrap_or(Map::new(env));
+        let provider = providers.get(bill_type.clone()).ok_or(Error::UnknownBillProvider)?;
+
+        if !provider.active {
+            return Err(Self::fail(env, user_address, symbol_short!("pay_bill"), symbol_short!("inactive"), amount, 0, Error::BillProviderNotActive));
+        }
+        Self::check_maintenance(env, user_address, symbol_short!("pay_bill"), bill_type)?;
+        if amount < provider.min_amount {
+            return Err(Self::fail(env, user_address, symbol_short!("pay_bill"), symbol_short!("min_amt"), amount, provider.min_amount, Error::AmountBelowBillerMinimum));
+        }
+        if amount > provider.max_amount {
+            return Err(Self::fail(env, user_address, symbol_short!("pay_bill"), symbol_short!("max_amt"), amount, provider.max_amount, Error::AmountAboveBillerMaximum));
+        }
+        match &provider.amount_policy {
+            AmountPolicy::Range => {}
+            AmountPolicy::Exact(amounts) => {
+                if !amounts.contains(amount) {
+                    return Err(Self::fail(env, user_address, symbol_short!("pay_bill"), symbol_short!("exact_amt"), amount, 0, Error::AmountNotAcceptedByBiller));
+                }
+            }
+            AmountPolicy::Multiple(step) => {
+                if amount % step != 0 {
+                    return Err(Self::fail(env, user_address, symbol_short!("pay_bill"), symbol_short!("step_amt"), amount, *step, Error::AmountNotBillerMultiple));
+                }
+            }
+        }
+
+        let len = account_number.len();
+        if len < provider.account_min_len || len > provider.account_max_len {
+            return Err(Error::InvalidAccountNumber);
+        }
+        if provider.numeric_account {
+            let mut buf = [0u8; 64];
+            if len as usize > buf.len() {
+                return Err(Error::InvalidAccountNumber);
+            }
+            account_number.copy_into_slice(&mut buf[..len as usize]);
+            if !buf[..len as usize].iter().all(|c| c.is_ascii_digit()) {
+                return Err(Error::InvalidAccountNumber);
+            }
+        }
+
+        Ok(provider)
+    }
+
+    // Debit the user and store a pending top-up record
+    fn record_topup(env: &Env, user_address: &Address, telco: Symbol, phone: String, bundle_id: Option<u32>, amount: i128) -> Result<u64, Error> {
+        let mut users = Users::load(env);
+        let mut user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+
+        if user.balance < amount {
+            return Err(Self::fail(env, user_address, symbol_short!("topup"), symbol_short!("balance"), amount, user.balance, Error::InsufficientBalance));
+        }
+
+        user.balance = Self::checked_debit(user.balance, amount)?;
+        Self::track_balance(env, &user, -amount, Bucket::External);
+        users.set(user_address.clone(), user);
+        users.save();
+
+        let mut topups: Map<u64, TopUp> = env.storage().instance().get(&DataKey::TopUps).unwrap_or(Map::new(env));
+        let topup_id: u64 = env.storage().instance().get(&CounterKey::TopUpId).unwrap_or(0) + 1;
+
+        topups.set(topup_id, TopUp {
+            id: topup_id,
+            user_address: user_address.clone(),
+            telco,
+            phone,
+            bundle_id,
+            amount,
+            status: String::from_str(env, "pending"),
+            timestamp: env.ledger().timestamp(),
+        });
+        env.storage().instance().set(&DataKey::TopUps, &topups);
+        env.storage().instance().set(&CounterKey::TopUpId, &topup_id);
+
+        Ok(topup_id)
+    }
+
+    fn topups_for(env: &Env, user_address: &Address, data: bool) -> Vec<TopUp> {
+        let topups: Map<u64, TopUp> = env.storage().instance().get(&DataKey::TopUps).unwrap_or(Map::new(env));
+        let mut user_topups = vec![env];
+
+        for (_, topup) in topups.iter() {
+            if topup.user_address == *user_address && topup.bundle_id.is_some() == data {
+                user_topups.push_back(topup);
+            }
+        }
+
+        user_topups
+    }
+
+    // Reject the operation if its module or the whole contract is paused
+    fn ensure_module_active(env: &Env, user_address: &Address, module: Symbol) -> Result<(), Error> {
+        let paused: Map<Symbol, u64> = env.storage().instance().get(&ConfigKey::Paused).unwrap_or(Map::new(env));
+        let now = env.ledger().timestamp();
+
+        for key in [symbol_short!("all"), module.clone()] {
+            if let Some(until) = paused.get(key) {
+                if until == 0 || until > now {
+                    return Err(Self::fail_until(env, user_address, module, symbol_short!("paused"), until, Error::ModulePaused));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Local payout for `usdc_amount` at the oracle rate less the FX spread. Returns the local
+    // amount, the effective rate and the USDC kept as spread revenue.
+    fn fx_quote(env: &Env, usdc_amount: i128, currency: &Symbol) -> Result<(i128, i128, i128), Error> {
+        let local_amount = Self::convert(env.clone(), usdc_amount, currency.clone())?;
+        let rate = Self::get_rate(env.clone(), currency.clone())?;
+        let spread_bps = Self::get_fx_spread(env.clone()) as i128;
+
+        Ok((
+            local_amount * (BPS_DENOMINATOR - spread_bps) / BPS_DENOMINATOR,
+            rate.rate * (BPS_DENOMINATOR - spread_bps) / BPS_DENOMINATOR,
+            usdc_amount * spread_bps / BPS_DENOMINATOR,
+        ))
+    }
+
+    // Validate a withdrawal against its rail and return the rail fee
+    fn withdrawal_rail_fee(env: &Env, user_address: &Address, method: &String, usdc_amount: i128) -> Result<i128, Error> {
+        let rails: Map<String, WithdrawalRail> = env.storage().instance().get(&ConfigKey::Rails).unwrap_or(Map::new(env));
+        let rail = rails.get(method.clone()).ok_or(Error::UnknownWithdrawalMethod)?;
+
+        if !rail.enabled {
+            return Err(Self::fail(env, user_address, symbol_short!("withdraw"), symbol_short!("rail_off"), usdc_amount, 0, Error::WithdrawalMethodUnavailable));
+        }
+        Self::check_maintenance(env, user_address, symbol_short!("withdraw"), method)?;
+        if usdc_amount < rail.min_amount {
+            return Err(Self::fail(env, user_address, symbol_short!("withdraw"), symbol_short!("min_amt"), usdc_amount, rail.min_amount, Error::AmountBelowWithdrawalMinimum));
+        }
+        if usdc_amount > rail.max_amount {
+            return Err(Self::fail(env, user_address, symbol_short!("withdraw"), symbol_short!("max_amt"), usdc_amount, rail.max_amount, Error::AmountAboveWithdrawalMaximum));
+        }
+
+        Ok(rail.flat_fee + usdc_amount * rail.fee_bps as i128 / BPS_DENOMINATOR)
+    }
+
+    // Stored form of a payment status
+    fn status_string(env: &Env, status: PaymentStatus) -> String {
+        String::from_str(env, match status {
+            PaymentStatus::Pending => "pending",
+            PaymentStatus::Processing => "processing",
+            PaymentStatus::Completed => "completed",
+            PaymentStatus::Failed => "failed",
+        })
+    }
+
+    // Completed, failed and expired payments are final
+    fn check_status_change(env: &Env, current: &String) -> Result<(), Error> {
+        if *current == Self::status_string(env, PaymentStatus::Completed)
+            || *current == Self::status_string(env, PaymentStatus::Failed)
+            || *current == String::from_str(env, "expired")
+            || *current == String::from_str(env, "cancelled")
+        {
+            return Err(Error::StatusFinal);
+        }
+
+        Ok(())
+    }
+
+    // Move a withdrawal to `status` on behalf of `actor` once the caller has been authorized
+    fn update_withdrawal_status(env: &Env, actor: &Address, withdrawal_id: String, status: PaymentStatus, payout_reference: Option<String>) -> Result<(), Error> {
+        let mut withdrawal = Self::load_withdrawal(env, &withdrawal_id).ok_or(Error::WithdrawalNotFound)?;
+        Self::check_status_change(env, &withdrawal.status)?;
+        let previous = withdrawal.status.clone();
+        withdrawal.status = Self::status_string(env, status);
+
+        // A completed payout must be traceable to the off-ramp transaction that settled it
+        if status == PaymentStatus::Completed {
+            match payout_reference {
+                Some(reference) if reference.len() > 0 && reference.len() <= MAX_MEMO_LEN => withdrawal.payout_reference = Some(reference),
+                _ => return Err(Error::PayoutReferenceRequired),
+            }
+        }
+
+        let mut stats = Self::load_stats(env);
+        Self::count_status(&mut stats.withdrawal_statuses, Some(previous), withdrawal.status.clone());
+        Self::save_stats(env, &stats);
+
+        let action = match status {
+            PaymentStatus::Pending => symbol_short!("wd_pend"),
+            PaymentStatus::Processing => symbol_short!("wd_proc"),
+            PaymentStatus::Completed => symbol_short!("wd_done"),
+            PaymentStatus::Failed => symbol_short!("wd_fail"),
+        };
+        Self::audit(env, actor, action.clone(), AuditTarget::Id(withdrawal_id.clone()), withdrawal.usdc_amount);
+        if status == PaymentStatus::Completed || status == PaymentStatus::Failed {
+            Self::notify(env, &withdrawal.user_address, action, NotificationRef::Key(withdrawal_id.clone()), withdrawal.usdc_amount);
+        }
+
+        Self::save_withdrawal(env, &withdrawal);
+
+        Self::close_withdrawal_hold(env, &withdrawal_id, status)?;
+
+        Ok(())
+    }
+
+    // Settle or refund the hold behind a withdrawal once it reaches a final status
+    fn close_withdrawal_hold(env: &Env, withdrawal_id: &String, status: PaymentStatus) -> Result<(), Error> {
+        let mut holds: Map<String, WithdrawalHold> = env.storage().instance().get(&ComplianceKey::WithdrawalHolds).unwrap_or(Map::new(env));
+        // Withdrawals made before holds existed were debited outright
+        let hold = match holds.get(withdrawal_id.clone()) {
+            Some(hold) => hold,
+            None => return Ok(()),
+        };
+
+        match status {
+            PaymentStatus::Completed => {
+                // The payout left custody off-chain; only the fee stays behind
+                Self::post(env, &hold.user_address, Bucket::HeldForWithdrawal, Bucket::External, hold.amount);
+                Self::collect_fee(env, &hold.user_address, Bucket::HeldForWithdrawal, hold.fee);
+                env.events().publish((symbol_short!("wd_settle"), hold.user_address.clone()), (withdrawal_id.clone(), hold.amount, hold.fee));
+            }
+            PaymentStatus::Failed => Self::refund_withdrawal_hold(env, withdrawal_id, &hold)?,
+            PaymentStatus::Pending | PaymentStatus::Processing => return Ok(()),
+        }
+
+        holds.remove(withdrawal_id.clone());
+        env.storage().instance().set(&ComplianceKey::WithdrawalHolds, &holds);
+
+        Ok(())
+    }
+
+    // Return held withdrawal funds, fee included, to the user's spendable balance
+    fn refund_withdrawal_hold(env: &Env, withdrawal_id: &String, hold: &WithdrawalHold) -> Result<(), Error> {
+        let mut users = Users::load(env);
+        let mut user = users.get(hold.user_address.clone()).ok_or(Error::UserNotFound)?;
+        user.balance = Self::checked_credit(user.balance, hold.amount + hold.fee)?;
+        Self::track_balance(env, &user, hold.amount + hold.fee, Bucket::HeldForWithdrawal);
+        users.set(hold.user_address.clone(), user);
+        users.save();
+
+        env.events().publish((symbol_short!("wd_refund"), hold.user_address.clone()), (withdrawal_id.clone(), hold.amount + hold.fee));
+
+        Ok(())
+    }
+
+    // Check that the caller holds an operator role
+    // Require the admin's auth and return the admin
+    fn require_admin(env: &Env) -> Result<Address, Error> {
+        let admin: Address = env.storage().instance().get(&ConfigKey::Admin).unwrap();
+
+        // Actions executed through an approved council proposal were authorized by the council
+        let executing: bool = env.storage().temporary().get(&ConfigKey::CouncilExecuting).unwrap_or(false);
+        if !executing {
+            admin.require_auth();
+        }
+
+        Ok(admin)
+    }
+
+    fn require_role(env: &Env, caller: &Address, role: Role) -> Result<(), Error> {
+        // Actions executed through an approved council proposal were authorized by the council
+        let executing: bool = env.storage().temporary().get(&ConfigKey::CouncilExecuting).unwrap_or(false);
+        if executing {
+            return Ok(());
+        }
+
+        caller.require_auth();
+
+        let roles: Map<Address, Vec<Role>> = env.storage().instance().get(&ConfigKey::Roles).unwrap_or(Map::new(env));
+        match roles.get(caller.clone()) {
+            Some(granted) if granted.contains(role) => Ok(()),
+            _ => Err(Error::MissingRole),
+        }
+    }
+
+    // Consume the user's PIN approval for an operation above the PIN threshold.
+    // Users without a PIN are not affected.
+    fn require_pin(env: &Env, user_address: &Address, amount: i128, op: Symbol) -> Result<(), Error> {
+        let threshold: i128 = env.storage().instance().get(&ConfigKey::PinThreshold).unwrap_or(DEFAULT_PIN_THRESHOLD);
+        if amount <= threshold {
+            return Ok(());
+        }
+
+        let mut pins: Map<Address, PinState> = env.storage().instance().get(&ComplianceKey::Pins).unwrap_or(Map::new(env));
+        let mut pin = match pins.get(user_address.clone()) {
+            Some(pin) => pin,
+            None => return Ok(()),
+        };
+
+        if pin.approved_until < env.ledger().timestamp() {
+            return Err(Self::fail(env, user_address, op, symbol_short!("pin"), amount, threshold, Error::PinVerificationRequired));
+        }
+
+        pin.approved_until = 0;
+        pins.set(user_address.clone(), pin);
+        env.storage().instance().set(&ComplianceKey::Pins, &pins);
+
+        Ok(())
+    }
+
+    // sha256 of `salt || preimage`, or of the bare preimage for a PIN set before salts
+    fn pin_commitment(env: &Env, salt: &Option<BytesN<32>>, preimage: &Bytes) -> BytesN<32> {
+        let mut salted = Bytes::new(env);
+        if let Some(salt) = salt {
+            salted.append(&Bytes::from_array(env, &salt.to_array()));
+        }
+        salted.append(preimage);
+
+        BytesN::from(env.crypto().sha256(&salted))
+    }
+
+    // Sends above the soft limit go through only with a pending step-up from `approve_step_up`,
+    // which this spends
+    fn check_step_up(env: &Env, user_address: &Address, amount: i128) -> Result<(), Error> {
+        let policy = match Self::get_step_up_policy(env.clone()) {
+            Some(policy) => policy,
+            None => return Ok(()),
+        };
+        if amount <= policy.soft_limit {
+            return Ok(());
+        }
+
+        let mut step_ups: Map<Address, StepUp> = env.storage().instance().get(&ComplianceKey::StepUps).unwrap_or(Map::new(env));
+        let mut step_up = match step_ups.get(user_address.clone()) {
+            Some(step_up) if step_up.approved_amount >= amount => step_up,
+            _ => return Err(Self::fail(env, user_address, symbol_short!("send"), symbol_short!("step_up"), amount, policy.soft_limit, Error::StepUpVerificationRequired)),
+        };
+
+        step_up.approved_amount -= amount;
+        step_ups.set(user_address.clone(), step_up);
+        env.storage().instance().set(&ComplianceKey::StepUps, &step_ups);
+
+        Ok(())
+    }
+
+    // Whether a batch totalling `amount` is above the soft limit, failing unless a pending
+    // step-up covers it. Nothing is spent here; legs above the limit spend their own share.
+    fn check_batch_step_up(env: &Env, user_address: &Address, amount: i128) -> Result<bool, Error> {
+        let policy = match Self::get_step_up_policy(env.clone()) {
+            Some(policy) => policy,
+            None => return Ok(false),
+        };
+        if amount <= policy.soft_limit {
+            return Ok(false);
+        }
+        if Self::step_up_required(env, user_address, amount) {
+            return Err(Self::fail(env, user_address, symbol_short!("send"), symbol_short!("step_up"), amount, policy.soft_limit, Error::StepUpVerificationRequired));
+        }
+
+        Ok(true)
+    }
+
+    // Let whatever a batch left of a step-up lapse with it
+    fn clear_step_up(env: &Env, user_address: &Address) {
+        let mut step_ups: Map<Address, StepUp> = env.storage().instance().get(&ComplianceKey::StepUps).unwrap_or(Map::new(env));
+        if let Some(mut step_up) = step_ups.get(user_address.clone()) {
+            step_up.approved_amount = 0;
+            step_ups.set(user_address.clone(), step_up);
+            env.storage().instance().set(&ComplianceKey::StepUps, &step_ups);
+        }
+    }
+
+    // Whether a send of `amount` is above the soft limit and not covered by a pending step-up
+    fn step_up_required(env: &Env, user_address: &Address, amount: i128) -> bool {
+        let policy = match Self::get_step_up_policy(env.clone()) {
+            Some(policy) => policy,
+            None => return false,
+        };
+        let step_ups: Map<Address, StepUp> = env.storage().instance().get(&ComplianceKey::StepUps).unwrap_or(Map::new(env));
+        let approved = step_ups.get(user_address.clone()).map(|step_up| step_up.approved_amount).unwrap_or(0);
+
+        amount > policy.soft_limit && approved < amount
+    }
+
+    fn load_stats(env: &Env) -> Stats {
+        env.storage().instance().get(&CounterKey::Stats).unwrap_or(Stats {
+            user_count: 0,
+            custodied: 0,
+            bill_volume: 0,
+            withdrawal_volume: 0,
+            bill_statuses: Map::new(env),
+            withdrawal_statuses: Map::new(env),
+        })
+    }
+
+    fn save_stats(env: &Env, stats: &Stats) {
+        env.storage().instance().set(&CounterKey::Stats, stats);
+    }
+
+    // Move one item between status counters; `from` is None for newly created items
+    fn count_status(counts: &mut Map<String, u32>, from: Option<String>, to: String) {
+        if let Some(from) = from {
+            let count = counts.get(from.clone()).unwrap_or(0);
+            counts.set(from, count.saturating_sub(1));
+        }
+        let count = counts.get(to.clone()).unwrap_or(0);
+        counts.set(to, count + 1);
+    }
+
+    // Append an entry to the audit log. Each entry is its own persistent record so it can
+    // expire on its own once its retention runs out.
+    fn audit(env: &Env, actor: &Address, action: Symbol, target: AuditTarget, amount: i128) {
+        let seq: u64 = env.storage().instance().get(&CounterKey::AuditSeq).unwrap_or(0) + 1;
+        let ttl_ledgers: u32 = env.storage().instance().get(&ConfigKey::AuditTtl).unwrap_or(DEFAULT_AUDIT_TTL_LEDGERS);
+
+        let key = DataKey::Audit(seq);
+        env.storage().persistent().set(&key, &AuditEntry {
+            seq,
+            actor: actor.clone(),
+            action,
+            target,
+            amount,
+            timestamp: env.ledger().timestamp(),
+        });
+        env.storage().persistent().extend_ttl(&key, ttl_ledgers, ttl_ledgers);
+        env.storage().instance().set(&CounterKey::AuditSeq, &seq);
+    }
+
+    fn extend_instance(env: &Env) {
+        env.storage().instance().extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+    }
+
+    // Cut page `cursor..cursor + limit` out of a list, with `limit` capped at `MAX_PAGE_SIZE`.
+    // Also returns the cursor of the next page, or `None` on the last one.
+    fn page<T: IntoVal<Env, Val> + TryFromVal<Env, Val>>(items: Vec<T>, cursor: u32, limit: u32) -> (Vec<T>, Option<u32>) {
+        let start = cursor.min(items.len());
+        let end = start.saturating_add(limit.min(MAX_PAGE_SIZE)).min(items.len());
+        let next_cursor = if end < items.len() { Some(end) } else { None };
+
+        (items.slice(start..end), next_cursor)
+    }
+
+    // The last `count` items of an oldest-first list
+    fn latest<T: IntoVal<Env, Val> + TryFromVal<Env, Val>>(items: Vec<T>, count: u32) -> Vec<T> {
+        items.slice(items.len().saturating_sub(count)..items.len())
+    }
+
+    // Read the records with the given ids, so a page only loads the entries on it
+    fn load_each<T: IntoVal<Env, Val> + TryFromVal<Env, Val>>(env: &Env, ids: Vec<String>, load: fn(&Env, &String) -> Option<T>) -> Vec<T> {
+        let mut records = vec![env];
+
+        for id in ids.iter() {
+            if let Some(record) = load(env, &id) {
+                records.push_back(record);
+            }
+        }
+
+        records
+    }
+
+    // A user's bill payments, oldest first
+    fn bill_payments_for(env: &Env, user_address: &Address) -> Vec<BillPayment> {
+        Self::load_each(env, Self::user_index(env, DataKey::UserBills(user_address.clone())), Self::load_bill)
+    }
+
+    // A user's withdrawals, oldest first
+    fn withdrawals_for(env: &Env, user_address: &Address) -> Vec<Withdrawal> {
+        Self::load_each(env, Self::user_index(env, DataKey::UserWithdrawals(user_address.clone())), Self::load_withdrawal)
+    }
+
+    // All of a user's savings goals
+    fn goals_for(env: &Env, user_address: &Address) -> Vec<SavingsGoal> {
+        let goals: Map<u64, SavingsGoal> = env.storage().instance().get(&DataKey::Goals).unwrap_or(Map::new(env));
+        Self::indexed_records(env, goals, Self::record_index(env, &EntryKey::UserGoals(user_address.clone())))
+    }
+
+    // All scheduled payments a user created
+    fn scheduled_payments_for(env: &Env, user_address: &Address) -> Vec<ScheduledPayment> {
+        let scheduled: Map<u64, ScheduledPayment> = env.storage().instance().get(&DataKey::Scheduled).unwrap_or(Map::new(env));
+        Self::indexed_records(env, scheduled, Self::record_index(env, &EntryKey::UserScheduled(user_address.clone())))
+    }
+
+    // All streams a user sends or receives
+    fn streams_for(env: &Env, user_address: &Address) -> Vec<PaymentStream> {
+        let streams: Map<u64, PaymentStream> = env.storage().instance().get(&DataKey::Streams).unwrap_or(Map::new(env));
+        Self::indexed_records(env, streams, Self::record_index(env, &EntryKey::UserStreams(user_address.clone())))
+    }
+
+    // All cash-outs a user requested
+    fn cash_outs_for(env: &Env, user_address: &Address) -> Vec<CashOut> {
+        let cash_outs: Map<u64, CashOut> = env.storage().instance().get(&DataKey::CashOuts).unwrap_or(Map::new(env));
+        Self::indexed_records(env, cash_outs, Self::record_index(env, &EntryKey::UserCashOuts(user_address.clone())))
+    }
+
+    // All transfers a user sent or received
+    fn transfers_for(env: &Env, user_address: &Address) -> Vec<Transfer> {
+        Self::load_transfers(env, Self::transfer_index(env, user_address))
+    }
+
+    // All quarantined transfers to a user
+    fn quarantined_for(env: &Env, user_address: &Address) -> Vec<QuarantinedTransfer> {
+        let quarantined: Map<u64, QuarantinedTransfer> = env.storage().instance().get(&ComplianceKey::Quarantined).unwrap_or(Map::new(env));
+        let mut user_quarantined = vec![env];
+
+        for (_, transfer) in quarantined.iter() {
+            if transfer.to_address == *user_address {
+                user_quarantined.push_back(transfer);
+            }
+        }
+
+        user_quarantined
+    }
+
+    // All of a user's loans
+    fn loans_for(env: &Env, user_address: &Address) -> Vec<Loan> {
+        let loans: Map<u64, Loan> = env.storage().instance().get(&DataKey::Loans).unwrap_or(Map::new(env));
+        Self::indexed_records(env, loans, Self::record_index(env, &EntryKey::UserLoans(user_address.clone())))
+    }
+
+    // All remittances a user sent
+    fn remittances_for(env: &Env, user_address: &Address) -> Vec<Remittance> {
+        let remittances: Map<u64, Remittance> = env.storage().instance().get(&DataKey::Remittances).unwrap_or(Map::new(env));
+        Self::indexed_records(env, remittances, Self::record_index(env, &EntryKey::UserRemittances(user_address.clone())))
+    }
+
+    // All of a user's standing instructions
+    fn rules_for(env: &Env, user_address: &Address) -> Vec<AutoRuleEntry> {
+        let rules: Map<u64, AutoRuleEntry> = env.storage().instance().get(&DataKey::AutoRules).unwrap_or(Map::new(env));
+        let mut user_rules = vec![env];
+
+        for (_, entry) in rules.iter() {
+            if entry.owner == *user_address {
+                user_rules.push_back(entry);
+            }
+        }
+
+        user_rules
+    }
+
+    // All of a user's card holds
+    fn card_holds_for(env: &Env, user_address: &Address) -> Vec<CardHold> {
+        let holds: Map<u64, CardHold> = env.storage().instance().get(&DataKey::CardHolds).unwrap_or(Map::new(env));
+        Self::indexed_records(env, holds, Self::record_index(env, &EntryKey::UserCardHolds(user_address.clone())))
+    }
+
+    // All payrolls an employer created
+    fn payrolls_for(env: &Env, employer: &Address) -> Vec<Payroll> {
+        let payrolls: Map<u64, Payroll> = env.storage().instance().get(&DataKey::Payrolls).unwrap_or(Map::new(env));
+        Self::indexed_records(env, payrolls, Self::record_index(env, &EntryKey::UserPayrolls(employer.clone())))
+    }
+
+    // All split bills a user created or takes part in
+    fn splits_for(env: &Env, user_address: &Address) -> Vec<BillSplit> {
+        let splits: Map<u64, BillSplit> = env.storage().instance().get(&DataKey::Splits).unwrap_or(Map::new(env));
+        Self::indexed_records(env, splits, Self::record_index(env, &EntryKey::UserSplits(user_address.clone())))
+    }
+
+    // Index a split under its creator and each participant, once each
+    fn index_split(env: &Env, split: &BillSplit) {
+        Self::index_record(env, EntryKey::UserSplits(split.creator.clone()), split.id);
+        for (participant, _) in split.shares.iter() {
+            if participant != split.creator {
+                Self::index_record(env, EntryKey::UserSplits(participant), split.id);
+            }
+        }
+    }
+
+    // All vouchers a user funded
+    fn vouchers_for(env: &Env, funder: &Address) -> Vec<Voucher> {
+        let vouchers: Map<BytesN<32>, Voucher> = env.storage().instance().get(&DataKey::Vouchers).unwrap_or(Map::new(env));
+        Self::indexed_records(env, vouchers, Self::record_index(env, &EntryKey::UserVouchers(funder.clone())))
+    }
+
+    // All sends a user made to contacts who had not signed up
+    fn contact_claims_for(env: &Env, sender: &Address) -> Vec<ContactClaim> {
+        let claims: Map<u64, ContactClaim> = env.storage().instance().get(&RecordKey::ContactClaims).unwrap_or(Map::new(env));
+        Self::indexed_records(env, claims, Self::record_index(env, &EntryKey::UserContactClaims(sender.clone())))
+    }
+
+
+    // Ids of a user's records, oldest first
+    // Fail if the user still has scheduled payments, streams, group rounds, allowances, cash-outs,
+    // loans, credit, vouchers or sub-accounts open under their address
+    fn require_no_open_commitments(env: &Env, user_address: &Address) -> Result<(), Error> {
+        let now = env.ledger().timestamp();
+
+        for payment in Self::scheduled_payments_for(env, user_address).iter() {
+            if payment.status == String::from_str(env, "pending") {
+                return Err(Error::AccountHasPendingScheduledPayments);
+            }
+        }
+        for stream in Self::streams_for(env, user_address).iter() {
+            if stream.status == String::from_str(env, "active") {
+                return Err(Error::AccountHasActiveStreams);
+            }
+        }
+        let groups: Map<u64, SavingsGroup> = env.storage().instance().get(&DataKey::Groups).unwrap_or(Map::new(env));
+        for (_, group) in groups.iter() {
+            if group.active && group.members.contains(user_address.clone()) {
+                return Err(Error::AccountIsInAnActiveSavingsGroup);
+            }
+        }
+        let allowances: Map<(Address, Address), Allowance> = env.storage().instance().get(&DataKey::Allowances).unwrap_or(Map::new(env));
+        for ((owner, spender), allowance) in allowances.iter() {
+            if (owner == *user_address || spender == *user_address) && allowance.amount > 0 && allowance.expiry >= now {
+                return Err(Error::AccountHasOpenAllowances);
+            }
+        }
+        for cash_out in Self::cash_outs_for(env, user_address).iter() {
+            if cash_out.status == String::from_str(env, "pending") {
+                return Err(Error::AccountHasPendingCashOuts);
+            }
+        }
+        for loan in Self::loans_for(env, user_address).iter() {
+            if loan.status == String::from_str(env, "active") || loan.status == String::from_str(env, "requested") {
+                return Err(Error::AccountHasAnOpenLoan);
+            }
+        }
+        let line = Self::accrued_credit_line(env, user_address);
+        if line.collateral > 0 || line.debt > 0 {
+            return Err(Error::AccountHasAnOpenCreditLine);
+        }
+        for voucher in Self::vouchers_for(env, user_address).iter() {
+            if voucher.status == String::from_str(env, "active") {
+                return Err(Error::AccountHasActiveVouchers);
+            }
+        }
+        let subaccounts: Map<Address, SubAccount> = env.storage().instance().get(&DataKey::SubAccounts).unwrap_or(Map::new(env));
+        for (dependent, subaccount) in subaccounts.iter() {
+            if dependent == *user_address || subaccount.parent == *user_address {
+                return Err(Error::AccountHasLinkedSubAccounts);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn user_index(env: &Env, key: DataKey) -> Vec<String> {
+        env.storage().persistent().get(&key).unwrap_or(vec![env])
+    }
+
+    fn add_to_index(env: &Env, key: DataKey, id: String) {
+        let mut ids = Self::user_index(env, key.clone());
+        ids.push_back(id);
+        env.storage().persistent().set(&key, &ids);
+    }
+
+    // Append the records indexed under `from` to `to`
+    fn move_index(env: &Env, from: DataKey, to: DataKey) {
+        let mut ids = Self::user_index(env, to.clone());
+        ids.append(&Self::user_index(env, from.clone()));
+        env.storage().persistent().set(&to, &ids);
+        env.storage().persistent().remove(&from);
+    }
+
+    // Transfers are kept one persistent entry per id, indexed under both parties
+    fn save_transfer(env: &Env, transfer: &Transfer) {
+        let key = EntryKey::Transfer(transfer.id);
+        env.storage().persistent().set(&key, transfer);
+        env.storage().persistent().extend_ttl(&key, INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+
+        for address in [transfer.from_address.clone(), transfer.to_address.clone()] {
+            let index_key = EntryKey::UserTransfers(address);
+            let mut transfer_ids: Vec<u64> = env.storage().persistent().get(&index_key).unwrap_or(vec![env]);
+            transfer_ids.push_back(transfer.id);
+            env.storage().persistent().set(&index_key, &transfer_ids);
+        }
+    }
+
+    fn transfer_index(env: &Env, user_address: &Address) -> Vec<u64> {
+        env.storage().persistent().get(&EntryKey::UserTransfers(user_address.clone())).unwrap_or(vec![env])
+    }
+
+    fn load_transfers(env: &Env, transfer_ids: Vec<u64>) -> Vec<Transfer> {
+        let mut transfers = vec![env];
+        for transfer_id in transfer_ids.iter() {
+            if let Some(transfer) = env.storage().persistent().get(&EntryKey::Transfer(transfer_id)) {
+                transfers.push_back(transfer);
+            }
+        }
+
+        transfers
+    }
+
+    // Ids of the records a user has under `key`, oldest first
+    fn record_index<T: IntoVal<Env, Val> + TryFromVal<Env, Val>>(env: &Env, key: &EntryKey) -> Vec<T> {
+        env.storage().persistent().get(key).unwrap_or(vec![env])
+    }
+
+    fn index_record<T: IntoVal<Env, Val> + TryFromVal<Env, Val>>(env: &Env, key: EntryKey, id: T) {
+        let mut ids: Vec<T> = Self::record_index(env, &key);
+        ids.push_back(id);
+        env.storage().persistent().set(&key, &ids);
+        env.storage().persistent().extend_ttl(&key, INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+    }
+
+    // Look up the indexed records in an instance map, so a user's reads do not walk everyone else's
+    fn indexed_records<K, T>(env: &Env, records: Map<K, T>, ids: Vec<K>) -> Vec<T>
+    where
+        K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+        T: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+    {
+        let mut found = vec![env];
+        for id in ids.iter() {
+            if let Some(record) = records.get(id) {
+                found.push_back(record);
+            }
+        }
+
+        found
+    }
+
+    // Extend the TTL of a persistent entry, if it has not been removed
+    fn extend_entry<K: IntoVal<Env, Val>>(env: &Env, key: &K) {
+        if env.storage().persistent().has(key) {
+            env.storage().persistent().extend_ttl(key, INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+        }
+    }
+
+    // Bill payments and withdrawals are kept one persistent entry per id
+    fn load_bill(env: &Env, payment_id: &String) -> Option<BillPayment> {
+        env.storage().persistent().get(&EntryKey::Bill(payment_id.clone()))
+    }
+
+    fn save_bill(env: &Env, bill_payment: &BillPayment) {
+        let key = EntryKey::Bill(bill_payment.id.clone());
+        env.storage().persistent().set(&key, bill_payment);
+        env.storage().persistent().extend_ttl(&key, INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+    }
+
+    pub(crate) fn load_withdrawal(env: &Env, withdrawal_id: &String) -> Option<Withdrawal> {
+        env.storage().persistent().get(&EntryKey::Withdrawal(withdrawal_id.clone()))
+    }
+
+    fn save_withdrawal(env: &Env, withdrawal: &Withdrawal) {
+        let key = EntryKey::Withdrawal(withdrawal.id.clone());
+        env.storage().persistent().set(&key, withdrawal);
+        env.storage().persistent().extend_ttl(&key, INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+    }
+
+    // Audit entries written before v5 stay under `("audit", seq)` until their retention runs out
+    fn load_audit_entry(env: &Env, seq: u64) -> Option<AuditEntry> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Audit(seq))
+            .or_else(|| env.storage().persistent().get(&(symbol_short!("audit"), seq)))
+    }
+
+    fn is_closed(env: &Env, address: &Address) -> bool {
+        let closed: Map<Address, u64> = env.storage().instance().get(&ComplianceKey::Closed).unwrap_or(Map::new(env));
+
+        closed.contains_key(address.clone())
+    }
+
+    // Add to the user's spending counter for the current month
+    fn record_spending(env: &Env, user_address: &Address, category: SpendCategory, amount: i128) {
+        let month = Self::month_of(env.ledger().timestamp());
+        let mut spending: Map<(Address, u32), Map<SpendCategory, i128>> = env.storage().instance().get(&DataKey::Spending).unwrap_or(Map::new(env));
+        let mut categories = spending.get((user_address.clone(), month)).unwrap_or(Map::new(env));
+
+        let total = categories.get(category.clone()).unwrap_or(0);
+        categories.set(category, total + amount);
+        spending.set((user_address.clone(), month), categories);
+        env.storage().instance().set(&DataKey::Spending, &spending);
+    }
+
+    // Calendar month of a timestamp as `yyyymm` (UTC)
+    fn month_of(timestamp: u64) -> u32 {
+        // Civil-from-days conversion over 400-year eras, with years starting in March
+        let days = (timestamp / SECONDS_PER_DAY) as i64 + 719_468;
+        let era = days / 146_097;
+        let day_of_era = days - era * 146_097;
+        let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        (year * 100 + month) as u32
+    }
+
+    // Record an already funded bill payment for the operator to process
+    fn record_bill_payment(env: &Env, user_address: &Address, bill_type: String, account_number: String, amount: i128, memo: Option<String>) -> String {
+        // Sequential ids keep several payments in one ledger (batches, splits, deposit rules) apart
+        let bill_seq: u32 = env.storage().instance().get(&CounterKey::BillSeq).unwrap_or(0) + 1;
+        env.storage().instance().set(&CounterKey::BillSeq, &bill_seq);
+        let payment_id = Self::demo_string(env, b"bill_", bill_seq);
+        env.events().publish((symbol_short!("bill"), user_address.clone()), (payment_id.clone(), amount, memo.clone()));
+
+        let bill_payment = BillPayment {
+            id: payment_id.clone(),
+            user_address: user_address.clone(),
+            bill_type,
+            account_number,
+            amount,
+            status: "pending".into(),
+            timestamp: env.ledger().timestamp(),
+            memo,
+        };
+
+        let mut stats = Self::load_stats(env);
+        stats.bill_volume += bill_payment.amount;
+        Self::count_status(&mut stats.bill_statuses, None, bill_payment.status.clone());
+        Self::save_stats(env, &stats);
+
+        Self::save_bill(env, &bill_payment);
+        Self::add_to_index(env, DataKey::UserBills(user_address.clone()), payment_id.clone());
+        Self::enqueue_task(env, symbol_short!("bill"), payment_id.clone(), user_address, amount);
+
+        payment_id
+    }
+
+    // Record a repayment and return it to the loan pool
+    fn apply_loan_repayment(env: &Env, loan: &mut Loan, amount: i128) {
+        loan.repaid += amount;
+        if loan.repaid >= loan.owed {
+            loan.status = String::from_str(env, "repaid");
+        }
+
+        let pool: i128 = env.storage().instance().get(&CounterKey::LoanPool).unwrap_or(0);
+        env.storage().instance().set(&CounterKey::LoanPool, &(pool + amount));
+
+        env.events().publish((symbol_short!("loan_pay"), loan.borrower.clone()), (loan.id, amount, loan.owed - loan.repaid));
+    }
+
+    // Take repayments of overdue loans out of an incoming deposit, up to `incoming`. Called right
+    // after the deposit is credited; returns the amount collected.
+    fn collect_overdue_loans(env: &Env, user: &mut User, incoming: i128) -> Result<i128, Error> {
+        let now = env.ledger().timestamp();
+        let active = String::from_str(env, "active");
+        let mut loans: Map<u64, Loan> = env.storage().instance().get(&DataKey::Loans).unwrap_or(Map::new(env));
+        let mut collected: i128 = 0;
 
-#[contracttype]
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct User {
-    pub address: Address,
-    pub phone: String,
-    pub is_verified: bool,
-    pub balance: i128,
-}
+        for (loan_id, mut loan) in loans.clone().iter() {
+            if loan.borrower != user.address || loan.status != active || now <= loan.due_at {
+                continue;
+            }
+            let amount = (loan.owed - loan.repaid).min(incoming - collected).min(user.balance);
+            if amount <= 0 {
+                break;
+            }
+            user.balance = Self::checked_debit(user.balance, amount)?;
+            Self::track_balance(env, user, -amount, Bucket::Treasury);
+            Self::apply_loan_repayment(env, &mut loan, amount);
+            loans.set(loan_id, loan);
+            collected += amount;
+        }
 
-#[contracttype]
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct BillPayment {
-    pub id: String,
-    pub user_address: Address,
-    pub bill_type: String,
-    pub account_number: String,
-    pub amount: i128,
-    pub status: String,
-    pub timestamp: u64,
-}
+        if collected > 0 {
+            env.storage().instance().set(&DataKey::Loans, &loans);
+        }
 
-#[contracttype]
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct Withdrawal {
-    pub id: String,
-    pub user_address: Address,
-    pub method: String,
-    pub account_number: String,
-    pub usdc_amount: i128,
-    pub ugx_amount: i128,
-    pub status: String,
-    pub timestamp: u64,
-}
+        Ok(collected)
+    }
 
-#[contractimpl]
-impl Payvia {
-    // Initialize the contract
-    pub fn init(env: Env) {
-        // Set up initial data structures
-        env.storage().instance().set(&symbol_short!("admin"), &env.current_contract_address());
+    fn credit_config(env: &Env) -> Result<CreditConfig, Error> {
+        env.storage().instance().get(&ConfigKey::CreditConfig).ok_or(Error::CreditNotConfigured)
     }
 
-    // Register a new user
-    pub fn register_user(env: Env, user_address: Address, phone: String) -> Result<(), String> {
-        let users: Map<Address, User> = env.storage().instance().get(&symbol_short!("users")).unwrap_or(Map::new(&env));
-        
-        if users.contains_key(&user_address) {
-            return Err("User already exists".into());
-        }
+    // Load a credit line with simple interest added for the time since it was last accrued
+    fn accrued_credit_line(env: &Env, user_address: &Address) -> CreditLine {
+        let lines: Map<Address, CreditLine> = env.storage().instance().get(&DataKey::CreditLines).unwrap_or(Map::new(env));
+        let now = env.ledger().timestamp();
+        let mut line = lines.get(user_address.clone()).unwrap_or(CreditLine { collateral: 0, debt: 0, accrued_at: now });
 
-        let user = User {
-            address: user_address.clone(),
-            phone,
-            is_verified: false,
-            balance: 0,
+        let interest_bps: u32 = match env.storage().instance().get::<_, CreditConfig>(&ConfigKey::CreditConfig) {
+            Some(config) => config.interest_bps,
+            None => 0,
         };
+        let elapsed = (now - line.accrued_at) as i128;
+        line.debt += line.debt * interest_bps as i128 * elapsed / (BPS_DENOMINATOR * SECONDS_PER_YEAR as i128);
+        line.accrued_at = now;
+
+        line
+    }
+
+    fn save_credit_line(env: &Env, user_address: &Address, line: &CreditLine) {
+        let mut lines: Map<Address, CreditLine> = env.storage().instance().get(&DataKey::CreditLines).unwrap_or(Map::new(env));
+        if line.collateral == 0 && line.debt == 0 {
+            lines.remove(user_address.clone());
+        } else {
+            lines.set(user_address.clone(), line.clone());
+        }
+        env.storage().instance().set(&DataKey::CreditLines, &lines);
+    }
+
+    // Queue a new bill payment or withdrawal for the fulfillment service
+    fn enqueue_task(env: &Env, kind: Symbol, reference: String, user_address: &Address, amount: i128) {
+        let mut outbox: Map<u64, OperatorTask> = env.storage().instance().get(&DataKey::Outbox).unwrap_or(Map::new(env));
+        let task_id: u64 = env.storage().instance().get(&CounterKey::OutboxId).unwrap_or(0) + 1;
+
+        outbox.set(task_id, OperatorTask {
+            id: task_id,
+            kind: kind.clone(),
+            reference: reference.clone(),
+            user_address: user_address.clone(),
+            amount,
+            created_at: env.ledger().timestamp(),
+        });
+        env.storage().instance().set(&DataKey::Outbox, &outbox);
+        env.storage().instance().set(&CounterKey::OutboxId, &task_id);
+
+        env.events().publish((symbol_short!("task_new"), kind), (task_id, reference));
+    }
+
+    fn dust_policy(env: &Env) -> DustPolicy {
+        env.storage().instance().get(&ConfigKey::DustPolicy).unwrap_or(DustPolicy { min_send: 0, dust_threshold: 0 })
+    }
+
+    fn user_tier(user: &User) -> UserTier {
+        if user.is_verified {
+            UserTier::Verified
+        } else {
+            UserTier::Unverified
+        }
+    }
+
+    fn rate_limit(env: &Env, tier: UserTier) -> RateLimit {
+        let limits: Map<UserTier, RateLimit> = env.storage().instance().get(&ConfigKey::RateLimits).unwrap_or(Map::new(env));
+        limits.get(tier).unwrap_or(RateLimit { max_sends: 0, max_bills: 0 })
+    }
+
+    // Timestamps of the user's operations of a kind still inside the rate limit window
+    fn recent_operations(env: &Env, user_address: &Address, operation: Symbol) -> Vec<u64> {
+        let hits: Map<(Address, Symbol), Vec<u64>> = env.storage().instance().get(&ComplianceKey::RateLimitHits).unwrap_or(Map::new(env));
+        let now = env.ledger().timestamp();
+        let mut recent = vec![env];
+
+        for timestamp in hits.get((user_address.clone(), operation)).unwrap_or(vec![env]).iter() {
+            if timestamp + RATE_LIMIT_WINDOW_SECS > now {
+                recent.push_back(timestamp);
+            }
+        }
+
+        recent
+    }
+
+    // Count an operation against the user's tier limit, rejecting it once the limit is reached
+    fn check_rate_limit(env: &Env, user: &User, operation: Symbol) -> Result<(), Error> {
+        let limit = Self::rate_limit(env, Self::user_tier(user));
+        let max = if operation == symbol_short!("send") { limit.max_sends } else { limit.max_bills };
+
+        let mut recent = Self::recent_operations(env, &user.address, operation.clone());
+        if max > 0 && recent.len() >= max {
+            let retry_after = recent.first().unwrap_or(0) + RATE_LIMIT_WINDOW_SECS;
+            return Err(Self::fail_until(env, &user.address, operation, symbol_short!("rate_lim"), retry_after, Error::RateLimited));
+        }
+
+        recent.push_back(env.ledger().timestamp());
+        let mut hits: Map<(Address, Symbol), Vec<u64>> = env.storage().instance().get(&ComplianceKey::RateLimitHits).unwrap_or(Map::new(env));
+        hits.set((user.address.clone(), operation), recent);
+        env.storage().instance().set(&ComplianceKey::RateLimitHits, &hits);
 
-        let mut updated_users = users;
-        updated_users.set(&user_address, &user);
-        env.storage().instance().set(&symbol_short!("users"), &updated_users);
-        
         Ok(())
     }
 
-    // Get user profile
-    pub fn get_user(env: Env, user_address: Address) -> Result<User, String> {
-        let users: Map<Address, User> = env.storage().instance().get(&symbol_short!("users")).unwrap_or(Map::new(&env));
-        
-        users.get(&user_address).ok_or("User not found".into())
+    // Run the user's standing instructions against a deposit of `incoming`, in the order they were
+    // added. Rules that can no longer apply (closed goal, inactive biller, short balance) are skipped.
+    fn apply_auto_rules(env: &Env, user: &mut User, incoming: i128) -> Result<(), Error> {
+        let rules = Self::get_rules(env.clone(), user.address.clone());
+        if rules.is_empty() {
+            return Ok(());
+        }
+
+        let mut goals: Map<u64, SavingsGoal> = env.storage().instance().get(&DataKey::Goals).unwrap_or(Map::new(env));
+        for entry in rules.iter() {
+            match entry.rule {
+                AutoRule::SaveToGoal(goal_id, share_bps) => {
+                    let mut goal = match goals.get(goal_id) {
+                        Some(goal) => goal,
+                        None => continue,
+                    };
+                    let amount = (incoming * share_bps as i128 / BPS_DENOMINATOR).min(user.balance);
+                    if amount <= 0 {
+                        continue;
+                    }
+                    user.balance = Self::checked_debit(user.balance, amount)?;
+                    Self::track_balance(env, user, -amount, Bucket::LockedSavings);
+                    goal.saved += amount;
+                    goals.set(goal_id, goal);
+
+                    env.events().publish((symbol_short!("goal_add"), user.address.clone()), (goal_id, amount));
+                }
+                AutoRule::PayBill(bill_type, account_number, threshold, amount) => {
+                    if user.balance <= threshold || user.balance < amount {
+                        continue;
+                    }
+                    if Self::validate_bill(env, &user.address, &bill_type, &account_number, amount).is_err() {
+                        continue;
+                    }
+                    user.balance = Self::checked_debit(user.balance, amount)?;
+                    Self::track_balance(env, user, -amount, Bucket::Payable);
+                    Self::record_spending(env, &user.address, SpendCategory::Bill(bill_type.clone()), amount);
+                    Self::record_bill_payment(env, &user.address, bill_type, account_number, amount, None);
+                }
+            }
+        }
+        env.storage().instance().set(&DataKey::Goals, &goals);
+
+        Ok(())
     }
 
-    // Update user verification status
-    pub fn verify_user(env: Env, user_address: Address) -> Result<(), String> {
-        let mut users: Map<Address, User> = env.storage().instance().get(&symbol_short!("users")).unwrap_or(Map::new(&env));
-        
-        let mut user = users.get(&user_address).ok_or("User not found")?;
-        user.is_verified = true;
-        
-        users.set(&user_address, &user);
-        env.storage().instance().set(&symbol_short!("users"), &users);
-        
+    // Run one leg of a batch. A rejected leg leaves the sender's step-up approval, velocity and
+    // rate-limit counts as they were, since the send's checks record those before it can still fail.
+    fn send_batch_leg(env: &Env, from_address: &Address, to_address: Address, amount: i128) -> BatchLegResult {
+        let counters = [ComplianceKey::StepUps, ComplianceKey::Velocity, ComplianceKey::Flagged, ComplianceKey::RateLimitHits];
+        let before = counters.clone().map(|key| env.storage().instance().get::<_, Val>(&key));
+
+        match Self::send_usdc(env.clone(), from_address.clone(), to_address.clone(), amount, FeeBearer::Payer, None, None, None) {
+            Ok(transfer_id) => BatchLegResult { to_address, amount, transfer_id: Some(transfer_id), error: None },
+            Err(error) => {
+                Self::take_failure(env);
+                for (key, value) in counters.iter().zip(before.iter()) {
+                    match value {
+                        Some(value) => env.storage().instance().set(key, value),
+                        None => env.storage().instance().remove(key),
+                    }
+                }
+                BatchLegResult { to_address, amount, transfer_id: None, error: Some(error) }
+            }
+        }
+    }
+
+    fn transaction_limits(env: &Env) -> TransactionLimits {
+        env.storage().instance().get(&ConfigKey::TransactionLimits).unwrap_or(TransactionLimits { max_transaction: 0, velocity_limit: 0 })
+    }
+
+    fn check_transaction_cap(env: &Env, user_address: &Address, amount: i128, operation: Symbol) -> Result<(), Error> {
+        let limits = Self::transaction_limits(env);
+        let max_transaction = Self::max_transaction_for(env, user_address);
+        if limits.max_transaction > 0 && amount > max_transaction {
+            return Err(Self::fail(env, user_address, operation, symbol_short!("tx_max"), amount, max_transaction, Error::AmountAboveTransactionLimit));
+        }
+
         Ok(())
     }
 
-    // Deposit USDC to user account
-    pub fn deposit(env: Env, user_address: Address, amount: i128) -> Result<(), String> {
-        let mut users: Map<Address, User> = env.storage().instance().get(&symbol_short!("users")).unwrap_or(Map::new(&env));
-        
-        let mut user = users.get(&user_address).ok_or("User not found")?;
-        user.balance += amount;
-        
-        users.set(&user_address, &user);
-        env.storage().instance().set(&symbol_short!("users"), &users);
-        
+    // Refuse sends from flagged accounts, then count this send towards the sender's velocity.
+    // The send that crosses the limit still goes through; the account is flagged for later ones.
+    fn check_velocity(env: &Env, user_address: &Address, amount: i128) -> Result<(), Error> {
+        let mut flagged: Map<Address, u64> = env.storage().instance().get(&ComplianceKey::Flagged).unwrap_or(Map::new(env));
+        if flagged.contains_key(user_address.clone()) {
+            return Err(Self::fail(env, user_address, symbol_short!("send"), symbol_short!("flagged"), amount, 0, Error::AccountUnderReview));
+        }
+
+        let limits = Self::transaction_limits(env);
+        if limits.velocity_limit == 0 {
+            return Ok(());
+        }
+
+        let now = env.ledger().timestamp();
+        let mut velocity: Map<Address, Vec<(u64, i128)>> = env.storage().instance().get(&ComplianceKey::Velocity).unwrap_or(Map::new(env));
+        let mut recent = vec![env];
+        let mut volume = amount;
+        for (timestamp, sent) in velocity.get(user_address.clone()).unwrap_or(vec![env]).iter() {
+            if timestamp + VELOCITY_WINDOW_SECS > now {
+                recent.push_back((timestamp, sent));
+                volume += sent;
+            }
+        }
+        recent.push_back((now, amount));
+        velocity.set(user_address.clone(), recent);
+        env.storage().instance().set(&ComplianceKey::Velocity, &velocity);
+
+        if volume > limits.velocity_limit {
+            flagged.set(user_address.clone(), now);
+            env.storage().instance().set(&ComplianceKey::Flagged, &flagged);
+            env.events().publish((symbol_short!("flagged"), user_address.clone()), volume);
+        }
+
         Ok(())
     }
 
-    // Get user balance
-    pub fn get_balance(env: Env, user_address: Address) -> Result<i128, String> {
-        let users: Map<Address, User> = env.storage().instance().get(&symbol_short!("users")).unwrap_or(Map::new(&env));
-        
-        let user = users.get(&user_address).ok_or("User not found")?;
-        Ok(user.balance)
+    // Redeem a promo code against `fee` for the user and return the discounted fee.
+    // Each user can use a code once.
+    fn apply_promo(env: &Env, user_address: &Address, promo_code: &Option<Bytes>, fee: i128) -> Result<i128, Error> {
+        let code = match promo_code {
+            Some(code) => code,
+            None => return Ok(fee),
+        };
+
+        let code_hash = BytesN::from(env.crypto().sha256(code));
+        let mut promos: Map<BytesN<32>, Promo> = env.storage().instance().get(&ConfigKey::Promos).unwrap_or(Map::new(env));
+        let mut promo = promos.get(code_hash.clone()).ok_or(Error::PromoNotFound)?;
+
+        if env.ledger().timestamp() > promo.expiry {
+            return Err(Error::PromoHasExpired);
+        }
+        if promo.max_uses > 0 && promo.uses >= promo.max_uses {
+            return Err(Error::PromoUsedUp);
+        }
+
+        let mut used: Map<(BytesN<32>, Address), bool> = env.storage().instance().get(&DataKey::PromoUses).unwrap_or(Map::new(env));
+        let key = (code_hash.clone(), user_address.clone());
+        if used.contains_key(key.clone()) {
+            return Err(Error::PromoAlreadyUsed);
+        }
+        used.set(key, true);
+        env.storage().instance().set(&DataKey::PromoUses, &used);
+
+        let discount = fee * promo.discount_bps as i128 / BPS_DENOMINATOR;
+        promo.uses += 1;
+        promo.fees_waived += discount;
+        promos.set(code_hash.clone(), promo);
+        env.storage().instance().set(&ConfigKey::Promos, &promos);
+
+        env.events().publish((symbol_short!("promo"), user_address.clone()), (code_hash, discount));
+
+        Ok(fee - discount)
     }
 
-    // Send USDC to another user
-    pub fn send_usdc(env: Env, from_address: Address, to_address: Address, amount: i128) -> Result<(), String> {
-        let mut users: Map<Address, User> = env.storage().instance().get(&symbol_short!("users")).unwrap_or(Map::new(&env));
-        
-        let mut from_user = users.get(&from_address).ok_or("Sender not found")?;
-        let mut to_user = users.get(&to_address).ok_or("Recipient not found")?;
-        
+    fn parent_subaccount(subaccounts: &Map<Address, SubAccount>, parent: &Address, dependent: &Address) -> Result<SubAccount, Error> {
+        let subaccount = subaccounts.get(dependent.clone()).ok_or(Error::SubAccountNotFound)?;
+        if subaccount.parent != *parent {
+            return Err(Error::Unauthorized);
+        }
+
+        Ok(subaccount)
+    }
+
+    fn move_balance(env: &Env, from_address: &Address, to_address: &Address, amount: i128) -> Result<(), Error> {
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        let mut users = Users::load(env);
+        let mut from_user = users.get(from_address.clone()).ok_or(Error::UserNotFound)?;
+        let mut to_user = users.get(to_address.clone()).ok_or(Error::UserNotFound)?;
         if from_user.balance < amount {
-            return Err("Insufficient balance".into());
+            return Err(Self::fail(env, from_address, symbol_short!("sub_move"), symbol_short!("balance"), amount, from_user.balance, Error::InsufficientBalance));
         }
-        
-        from_user.balance -= amount;
-        to_user.balance += amount;
-        
-        users.set(&from_address, &from_user);
-        users.set(&to_address, &to_user);
-        env.storage().instance().set(&symbol_short!("users"), &users);
-        
+
+        from_user.balance = Self::checked_debit(from_user.balance, amount)?;
+        Self::track_balance(env, &from_user, -amount, Bucket::Clearing);
+        to_user.balance = Self::checked_credit(to_user.balance, amount)?;
+        Self::track_balance(env, &to_user, amount, Bucket::Clearing);
+        users.set(from_address.clone(), from_user);
+        users.set(to_address.clone(), to_user);
+        users.save();
+
         Ok(())
     }
 
-    // Pay bill with USDC
-    pub fn pay_bill(
-        env: Env,
-        user_address: Address,
-        bill_type: String,
-        account_number: String,
-        amount: i128,
-    ) -> Result<String, String> {
-        let mut users: Map<Address, User> = env.storage().instance().get(&symbol_short!("users")).unwrap_or(Map::new(&env));
-        let mut bill_payments: Map<String, BillPayment> = env.storage().instance().get(&symbol_short!("bills")).unwrap_or(Map::new(&env));
-        
-        let mut user = users.get(&user_address).ok_or("User not found")?;
-        
-        if user.balance < amount {
-            return Err("Insufficient balance".into());
+    // Apply a parent's controls to a dependent's spending; other accounts are unaffected
+    fn check_subaccount(env: &Env, user_address: &Address, operation: Symbol, amount: i128) -> Result<(), Error> {
+        let subaccounts: Map<Address, SubAccount> = env.storage().instance().get(&DataKey::SubAccounts).unwrap_or(Map::new(env));
+        let subaccount = match subaccounts.get(user_address.clone()) {
+            Some(subaccount) => subaccount,
+            None => return Ok(()),
+        };
+
+        if subaccount.blocked.contains(operation.clone()) {
+            return Err(Self::fail(env, user_address, operation, symbol_short!("parental"), amount, 0, Error::BlockedByParentalControls));
         }
-        
-        user.balance -= amount;
-        users.set(&user_address, &user);
-        env.storage().instance().set(&symbol_short!("users"), &users);
-        
-        let payment_id = format!("bill_{}", env.ledger().timestamp());
-        let bill_payment = BillPayment {
-            id: payment_id.clone(),
-            user_address,
-            bill_type,
-            account_number,
+        if subaccount.limit > 0 && amount > subaccount.limit {
+            return Err(Self::fail(env, user_address, operation, symbol_short!("parental"), amount, subaccount.limit, Error::AmountAboveSubAccountLimit));
+        }
+
+        Ok(())
+    }
+
+    // Append a notification to the user's inbox, dropping the oldest beyond `MAX_INBOX_LEN`
+    fn notify(env: &Env, user_address: &Address, kind: Symbol, reference: NotificationRef, amount: i128) {
+        let mut inbox = Self::load_inbox(env, user_address);
+        let notification_id: u64 = env.storage().instance().get(&CounterKey::NotificationId).unwrap_or(0) + 1;
+
+        inbox.push_back(Notification {
+            id: notification_id,
+            kind: kind.clone(),
+            reference,
             amount,
-            status: "pending".into(),
             timestamp: env.ledger().timestamp(),
-        };
-        
-        bill_payments.set(&payment_id, &bill_payment);
-        env.storage().instance().set(&symbol_short!("bills"), &bill_payments);
-        
-        Ok(payment_id)
+            read: false,
+        });
+        while inbox.len() > MAX_INBOX_LEN {
+            inbox.pop_front();
+        }
+
+        Self::save_inbox(env, user_address, &inbox);
+        env.storage().instance().set(&CounterKey::NotificationId, &notification_id);
+
+        let preferences = Self::get_preferences(env.clone(), user_address.clone());
+        env.events().publish((symbol_short!("notify"), user_address.clone()), (notification_id, kind, preferences));
     }
 
-    // Withdraw USDC to local currency
-    pub fn withdraw(
-        env: Env,
-        user_address: Address,
-        method: String,
-        account_number: String,
-        usdc_amount: i128,
-        ugx_amount: i128,
-    ) -> Result<String, String> {
-        let mut users: Map<Address, User> = env.storage().instance().get(&symbol_short!("users")).unwrap_or(Map::new(&env));
-        let mut withdrawals: Map<String, Withdrawal> = env.storage().instance().get(&symbol_short!("withdrawals")).unwrap_or(Map::new(&env));
-        
-        let mut user = users.get(&user_address).ok_or("User not found")?;
-        
-        if user.balance < usdc_amount {
-            return Err("Insufficient balance".into());
+    // Inboxes written before v5 sit under `("inbox", user)` until they are next saved
+    fn load_inbox(env: &Env, user_address: &Address) -> Vec<Notification> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Inbox(user_address.clone()))
+            .or_else(|| env.storage().persistent().get(&(symbol_short!("inbox"), user_address.clone())))
+            .unwrap_or(vec![env])
+    }
+
+    fn save_inbox(env: &Env, user_address: &Address, inbox: &Vec<Notification>) {
+        env.storage().persistent().set(&DataKey::Inbox(user_address.clone()), inbox);
+        env.storage().persistent().remove(&(symbol_short!("inbox"), user_address.clone()));
+    }
+
+    // Whether the off-ramp can still complete or fail a withdrawal
+    fn is_open_withdrawal(env: &Env, withdrawal: &Withdrawal) -> bool {
+        withdrawal.status == Self::status_string(env, PaymentStatus::Pending) || withdrawal.status == Self::status_string(env, PaymentStatus::Processing)
+    }
+
+    // Reject an operation on a rail or biller that is inside its maintenance window
+    fn check_maintenance(env: &Env, user_address: &Address, operation: Symbol, rail_id: &String) -> Result<(), Error> {
+        let windows: Map<String, MaintenanceWindow> = env.storage().instance().get(&ConfigKey::RailMaintenance).unwrap_or(Map::new(env));
+        let now = env.ledger().timestamp();
+
+        match windows.get(rail_id.clone()) {
+            Some(window) if window.start_ts <= now && now < window.end_ts => {
+                Err(Self::fail_until(env, user_address, operation, window.message_code, window.end_ts, Error::RailUnderMaintenance))
+            }
+            _ => Ok(()),
         }
-        
-        user.balance -= usdc_amount;
-        users.set(&user_address, &user);
-        env.storage().instance().set(&symbol_short!("users"), &users);
-        
-        let withdrawal_id = format!("withdraw_{}", env.ledger().timestamp());
-        let withdrawal = Withdrawal {
-            id: withdrawal_id.clone(),
-            user_address,
-            method,
-            account_number,
-            usdc_amount,
-            ugx_amount,
-            status: "pending".into(),
-            timestamp: env.ledger().timestamp(),
-        };
-        
-        withdrawals.set(&withdrawal_id, &withdrawal);
-        env.storage().instance().set(&symbol_short!("withdrawals"), &withdrawals);
-        
-        Ok(withdrawal_id)
     }
 
-    // Get bill payment history
-    pub fn get_bill_payments(env: Env, user_address: Address) -> Vec<BillPayment> {
-        let bill_payments: Map<String, BillPayment> = env.storage().instance().get(&symbol_short!("bills")).unwrap_or(Map::new(&env));
-        let mut user_bills = vec![&env];
-        
-        for (_, payment) in bill_payments.iter() {
-            if payment.user_address == user_address {
-                user_bills.push_back(payment);
+    fn cooling_off_policy(env: &Env) -> CoolingOffPolicy {
+        env.storage().instance().get(&ConfigKey::CoolingOffPolicy).unwrap_or(CoolingOffPolicy {
+            new_account_secs: 0,
+            large_deposit: 0,
+            large_deposit_secs: 0,
+            withdraw_cap: 0,
+        })
+    }
+
+    // Start the new-account or large-deposit cooling-off period
+    fn start_cooling_off(env: &Env, user_address: &Address, large_deposit: bool) {
+        let mut states: Map<Address, CoolingOff> = env.storage().instance().get(&ComplianceKey::CoolingOff).unwrap_or(Map::new(env));
+        let now = env.ledger().timestamp();
+
+        let state = match states.get(user_address.clone()) {
+            Some(mut state) if large_deposit => {
+                state.large_deposit_at = Some(now);
+                state
             }
+            Some(state) => state,
+            None => CoolingOff {
+                registered_at: now,
+                large_deposit_at: if large_deposit { Some(now) } else { None },
+            },
+        };
+        states.set(user_address.clone(), state);
+        env.storage().instance().set(&ComplianceKey::CoolingOff, &states);
+    }
+
+    // Start a cooling-off period if a deposit or cash-in is large enough
+    fn note_deposit(env: &Env, user_address: &Address, amount: i128) {
+        let policy = Self::cooling_off_policy(env);
+        if policy.large_deposit > 0 && amount >= policy.large_deposit {
+            Self::start_cooling_off(env, user_address, true);
         }
-        
-        user_bills
     }
 
-    // Get withdrawal history
-    pub fn get_withdrawals(env: Env, user_address: Address) -> Vec<Withdrawal> {
-        let withdrawals: Map<String, Withdrawal> = env.storage().instance().get(&symbol_short!("withdrawals")).unwrap_or(Map::new(&env));
-        let mut user_withdrawals = vec![&env];
-        
-        for (_, withdrawal) in withdrawals.iter() {
-            if withdrawal.user_address == user_address {
-                user_withdrawals.push_back(withdrawal);
+    // USDC a user has withdrawn, or has on its way out, since `since`
+    fn withdrawn_since(env: &Env, user_address: &Address, since: u64) -> i128 {
+        let failed = String::from_str(env, "failed");
+        let cancelled = String::from_str(env, "cancelled");
+        let mut total: i128 = 0;
+
+        for withdrawal in Self::withdrawals_for(env, user_address).iter() {
+            if withdrawal.timestamp >= since && withdrawal.status != failed && withdrawal.status != cancelled {
+                total += withdrawal.usdc_amount;
             }
         }
-        
-        user_withdrawals
+
+        total
     }
 
-    // Update bill payment status (admin only)
-    pub fn update_bill_status(env: Env, payment_id: String, status: String) -> Result<(), String> {
-        let admin: Address = env.storage().instance().get(&symbol_short!("admin")).unwrap();
-        
-        if env.current_contract_address() != admin {
-            return Err("Unauthorized".into());
+    // Block or cap a withdrawal while any cooling-off hold is active
+    fn check_cooling_off(env: &Env, user_address: &Address, usdc_amount: i128) -> Result<(), Error> {
+        for restriction in Self::get_restrictions(env.clone(), user_address.clone()).iter() {
+            if restriction.withdraw_cap == 0 {
+                return Err(Self::fail_until(env, user_address, symbol_short!("withdraw"), restriction.kind, restriction.until, Error::WithdrawalsOnHold));
+            }
+            if restriction.withdrawn + usdc_amount > restriction.withdraw_cap {
+                return Err(Self::fail(
+                    env,
+                    user_address,
+                    symbol_short!("withdraw"),
+                    restriction.kind,
+                    usdc_amount,
+                    restriction.withdraw_cap - restriction.withdrawn,
+                    Error::AmountAboveCoolingOffWithdrawalCap,
+                ));
+            }
         }
-        
-        let mut bill_payments: Map<String, BillPayment> = env.storage().instance().get(&symbol_short!("bills")).unwrap_or(Map::new(&env));
-        
-        let mut payment = bill_payments.get(&payment_id).ok_or("Payment not found")?;
-        payment.status = status;
-        
-        bill_payments.set(&payment_id, &payment);
-        env.storage().instance().set(&symbol_short!("bills"), &bill_payments);
-        
+
         Ok(())
     }
 
-    // Update withdrawal status (admin only)
-    pub fn update_withdrawal_status(env: Env, withdrawal_id: String, status: String) -> Result<(), String> {
-        let admin: Address = env.storage().instance().get(&symbol_short!("admin")).unwrap();
-        
-        if env.current_contract_address() != admin {
-            return Err("Unauthorized".into());
+    fn is_regulated(env: &Env, country: &Option<Symbol>) -> bool {
+        match country {
+            Some(country) => Self::get_country_policy(env.clone(), country.clone()).is_some(),
+            None => false,
         }
-        
-        let mut withdrawals: Map<String, Withdrawal> = env.storage().instance().get(&symbol_short!("withdrawals")).unwrap_or(Map::new(&env));
-        
-        let mut withdrawal = withdrawals.get(&withdrawal_id).ok_or("Withdrawal not found")?;
-        withdrawal.status = status;
-        
-        withdrawals.set(&withdrawal_id, &withdrawal);
-        env.storage().instance().set(&symbol_short!("withdrawals"), &withdrawals);
-        
+    }
+
+    // Apply the policy of the user's country, if any, to a withdrawal or remittance
+    fn check_country(env: &Env, user_address: &Address, usdc_amount: i128, rail: Option<&String>, op: Symbol) -> Result<(), Error> {
+        let users = Users::load(env);
+        let user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+        let policy = match user.country.and_then(|country| Self::get_country_policy(env.clone(), country)) {
+            Some(policy) => policy,
+            None => return Ok(()),
+        };
+
+        if policy.require_kyc && !user.is_verified {
+            return Err(Self::fail(env, user_address, op, symbol_short!("kyc"), usdc_amount, 0, Error::VerificationRequiredInThisCountry));
+        }
+        if policy.max_transaction > 0 && usdc_amount > policy.max_transaction {
+            return Err(Self::fail(env, user_address, op, symbol_short!("ctry_lim"), usdc_amount, policy.max_transaction, Error::AmountAboveCountryLimit));
+        }
+        if let Some(rail) = rail {
+            if !policy.allowed_rails.is_empty() && !policy.allowed_rails.contains(rail) {
+                return Err(Self::fail(env, user_address, op, symbol_short!("ctry_rail"), usdc_amount, 0, Error::RailNotAvailableInThisCountry));
+            }
+        }
+
         Ok(())
     }
+
+    fn is_high_risk(env: &Env, user_address: &Address) -> bool {
+        Self::get_risk_score(env.clone(), user_address.clone()) >= HIGH_RISK_SCORE
+    }
+
+    // Largest single transaction a user may make; high-risk accounts get a share of the normal limit
+    fn max_transaction_for(env: &Env, user_address: &Address) -> i128 {
+        if let Some(max_transaction) = Self::limit_override(env, user_address) {
+            return max_transaction;
+        }
+
+        let max_transaction = Self::transaction_limits(env).max_transaction;
+        if Self::is_high_risk(env, user_address) {
+            return max_transaction * HIGH_RISK_LIMIT_BPS as i128 / BPS_DENOMINATOR;
+        }
+
+        max_transaction
+    }
 }
 
+mod approvals;
+mod errors;
+mod invariants;
+mod keeper;
+mod ledger;
+#[cfg(feature = "legacy-api")]
+mod legacy;
+mod lending;
+mod overdraft;
+mod reserves;
+mod roundup;
+mod settlement;
+mod simulate;
+mod storage;
+mod sunset;
+mod swap;
 mod test;
+pub mod testutils;
+
+pub use approvals::{Approval, ApprovalKind, LimitOverride};
+pub use errors::Error;
+pub use keeper::{KeeperConfig, KeeperTask, TaskLease, TASK_LEASE_SECS};
+pub use ledger::Bucket;
+pub use lending::{LendingPool, LendingPoolClient, YieldPosition};
+pub use overdraft::OverdraftStatus;
+pub use reserves::ReserveProof;
+pub use roundup::{Charity, RoundUp};
+pub use settlement::Settlement;
+pub use simulate::Simulation;
+pub use storage::{ComplianceKey, ConfigKey, CounterKey, DataKey, EntryKey, RecordKey};
+use storage::Users;
+pub use sunset::SUNSET_NOTICE_SECS;
+pub use swap::{Swap, SwapPool, SwapPoolClient};
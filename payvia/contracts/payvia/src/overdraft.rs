@@ -0,0 +1,169 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, Env, Map};
+
+use crate::{AuditTarget, ComplianceKey, ConfigKey, Error, Payvia, Role, User, Users};
+
+// A user's interest-free overdraft on bill payments
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OverdraftStatus {
+    pub enabled: bool,
+    // Opted in, verified and tier 2, so bill payments may overdraw the balance
+    pub eligible: bool,
+    pub buffer: i128,
+    // Negative balance still to be repaid; spends are blocked until it is zero
+    pub owed: i128,
+    // How far the next bill payment may overdraw
+    pub available: i128,
+    // When the current overdraft was drawn; zero when nothing is owed
+    pub drawn_at: u64,
+}
+
+#[contractimpl]
+impl Payvia {
+    // Set how far below zero verified tier-2 users who opted in may go on bill payments; zero disables
+    // new overdrafts (admin only)
+    pub fn set_overdraft_buffer(env: Env, buffer: i128) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("od_buffer"), AuditTarget::Global, buffer);
+
+        if buffer < 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        env.storage().instance().set(&ConfigKey::OverdraftBuffer, &buffer);
+
+        Ok(())
+    }
+
+    // Clear a verified user for tier 2, or take it away (KYC officer only)
+    pub fn set_trusted(env: Env, caller: Address, user_address: Address, trusted: bool) -> Result<(), Error> {
+        Self::require_role(&env, &caller, Role::KycOfficer)?;
+        Self::audit(&env, &caller, symbol_short!("trusted"), AuditTarget::Address(user_address.clone()), trusted as i128);
+
+        let users = Users::load(&env);
+        let user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+        if trusted && !user.is_verified {
+            return Err(Error::VerificationRequired);
+        }
+
+        let mut trusted_users: Map<Address, u64> = env.storage().instance().get(&ComplianceKey::TrustedUsers).unwrap_or(Map::new(&env));
+        if trusted {
+            trusted_users.set(user_address.clone(), env.ledger().timestamp());
+        } else {
+            trusted_users.remove(user_address.clone());
+        }
+        env.storage().instance().set(&ComplianceKey::TrustedUsers, &trusted_users);
+
+        env.events().publish((symbol_short!("trusted"), user_address), trusted);
+
+        Ok(())
+    }
+
+    // Opt in to the overdraft buffer; only verified tier-2 users qualify
+    pub fn enable_overdraft(env: Env, user_address: Address) -> Result<(), Error> {
+        user_address.require_auth();
+
+        let users = Users::load(&env);
+        let user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+        if !user.is_verified {
+            return Err(Error::VerificationRequired);
+        }
+        if !Self::is_trusted(&env, &user_address) {
+            return Err(Error::TrustedTierRequired);
+        }
+
+        let mut overdrafts: Map<Address, u64> = env.storage().instance().get(&ComplianceKey::Overdrafts).unwrap_or(Map::new(&env));
+        if !overdrafts.contains_key(user_address.clone()) {
+            overdrafts.set(user_address.clone(), 0);
+            env.storage().instance().set(&ComplianceKey::Overdrafts, &overdrafts);
+        }
+
+        env.events().publish((symbol_short!("od_on"), user_address), ());
+
+        Ok(())
+    }
+
+    // Opt out of the overdraft buffer once nothing is owed
+    pub fn disable_overdraft(env: Env, user_address: Address) -> Result<(), Error> {
+        user_address.require_auth();
+
+        let users = Users::load(&env);
+        let user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+        if user.balance < 0 {
+            return Err(Error::OverdraftNotRepaid);
+        }
+
+        let mut overdrafts: Map<Address, u64> = env.storage().instance().get(&ComplianceKey::Overdrafts).unwrap_or(Map::new(&env));
+        overdrafts.remove(user_address.clone());
+        env.storage().instance().set(&ComplianceKey::Overdrafts, &overdrafts);
+
+        env.events().publish((symbol_short!("od_off"), user_address), ());
+
+        Ok(())
+    }
+
+    pub fn get_overdraft_status(env: Env, user_address: Address) -> Result<OverdraftStatus, Error> {
+        let users = Users::load(&env);
+        let user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+        let overdrafts: Map<Address, u64> = env.storage().instance().get(&ComplianceKey::Overdrafts).unwrap_or(Map::new(&env));
+        let owed = (-user.balance).max(0);
+
+        Ok(OverdraftStatus {
+            enabled: overdrafts.contains_key(user_address.clone()),
+            eligible: overdrafts.contains_key(user_address.clone()) && user.is_verified && Self::is_trusted(&env, &user_address),
+            buffer: Self::overdraft_buffer(&env),
+            owed,
+            available: Self::overdraft_available(&env, &user),
+            drawn_at: if owed > 0 { overdrafts.get(user.address).unwrap_or(0) } else { 0 },
+        })
+    }
+}
+
+impl Payvia {
+    fn overdraft_buffer(env: &Env) -> i128 {
+        env.storage().instance().get(&ConfigKey::OverdraftBuffer).unwrap_or(0)
+    }
+
+    // Whether a KYC officer cleared the user for tier 2
+    pub(crate) fn is_trusted(env: &Env, user_address: &Address) -> bool {
+        let trusted_users: Map<Address, u64> = env.storage().instance().get(&ComplianceKey::TrustedUsers).unwrap_or(Map::new(env));
+
+        trusted_users.contains_key(user_address.clone())
+    }
+
+    // How far a bill payment may take the user below zero; nothing while an overdraft is outstanding
+    pub(crate) fn overdraft_available(env: &Env, user: &User) -> i128 {
+        if !user.is_verified || user.balance < 0 || !Self::is_trusted(env, &user.address) {
+            return 0;
+        }
+        let overdrafts: Map<Address, u64> = env.storage().instance().get(&ComplianceKey::Overdrafts).unwrap_or(Map::new(env));
+        if !overdrafts.contains_key(user.address.clone()) {
+            return 0;
+        }
+
+        Self::overdraft_buffer(env)
+    }
+
+    // Record that a bill payment took the user below zero
+    pub(crate) fn draw_overdraft(env: &Env, user: &User) {
+        if user.balance >= 0 {
+            return;
+        }
+
+        let mut overdrafts: Map<Address, u64> = env.storage().instance().get(&ComplianceKey::Overdrafts).unwrap_or(Map::new(env));
+        overdrafts.set(user.address.clone(), env.ledger().timestamp());
+        env.storage().instance().set(&ComplianceKey::Overdrafts, &overdrafts);
+
+        env.events().publish((symbol_short!("od_drawn"), user.address.clone()), -user.balance);
+    }
+
+    // Report the part of a deposit that went to repaying an overdraft, given the balance before it
+    pub(crate) fn claw_back_overdraft(env: &Env, user: &User, balance_before: i128) {
+        if balance_before >= 0 {
+            return;
+        }
+
+        let repaid = user.balance.min(0) - balance_before;
+        env.events().publish((symbol_short!("od_repaid"), user.address.clone()), (repaid, (-user.balance).max(0)));
+    }
+}
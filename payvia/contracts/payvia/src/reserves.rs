@@ -0,0 +1,44 @@
+use soroban_sdk::{contractimpl, contracttype, token, Address, Env};
+
+use crate::{ConfigKey, CounterKey, Error, LendingPoolClient, Payvia};
+
+// USDC backing user funds compared with what the contract owes
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReserveProof {
+    // USDC held by the contract plus the current value of savings supplied to the lending pool
+    pub reserves: i128,
+    // Ledger totals of the buckets owed to users and billers
+    pub liabilities: i128,
+    pub timestamp: u64,
+}
+
+#[contractimpl]
+impl Payvia {
+    // Compare the custodied USDC with the contract's liabilities; callable by anyone
+    pub fn proof_of_reserves(env: Env) -> Result<ReserveProof, Error> {
+        let usdc: Address = env.storage().instance().get(&ConfigKey::UsdcToken).ok_or(Error::UsdcTokenNotConfigured)?;
+        let mut reserves = token::Client::new(&env, &usdc).balance(&env.current_contract_address());
+
+        let total_shares: i128 = env.storage().instance().get(&CounterKey::YieldShares).unwrap_or(0);
+        if total_shares > 0 {
+            if let Some(pool) = env.storage().instance().get::<_, Address>(&ConfigKey::YieldPool) {
+                reserves += LendingPoolClient::new(&env, &pool).preview_redeem(&total_shares);
+            }
+        }
+
+        Ok(ReserveProof {
+            reserves,
+            liabilities: Self::ledger_liabilities(&env),
+            timestamp: env.ledger().timestamp(),
+        })
+    }
+
+    // Whether reserves cover liabilities
+    pub fn is_solvent(env: Env) -> bool {
+        match Self::proof_of_reserves(env) {
+            Ok(proof) => proof.reserves >= proof.liabilities,
+            Err(_) => false,
+        }
+    }
+}
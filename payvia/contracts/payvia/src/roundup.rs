@@ -0,0 +1,164 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, Env, Map, String, Symbol};
+
+use crate::{AuditTarget, Bucket, ConfigKey, Error, Payvia, RecordKey, User, Users};
+
+// A charity round-ups can be donated to
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Charity {
+    pub id: Symbol,
+    pub name: String,
+    // Registered user the donations are credited to
+    pub wallet: Address,
+    pub active: bool,
+}
+
+// A user's round-up setting and what has accrued since the last donation
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RoundUp {
+    pub charity_id: Symbol,
+    // Sends and bill payments are rounded up to a multiple of this
+    pub round_to: i128,
+    pub accrued: i128,
+}
+
+#[contractimpl]
+impl Payvia {
+    // Add or update a charity (admin only)
+    pub fn set_charity(env: Env, charity: Charity) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("charity"), AuditTarget::Key(charity.id.clone()), 0);
+
+        let users = Users::load(&env);
+        if !users.contains_key(charity.wallet.clone()) {
+            return Err(Error::UserNotFound);
+        }
+
+        let mut charities: Map<Symbol, Charity> = env.storage().instance().get(&ConfigKey::Charities).unwrap_or(Map::new(&env));
+        charities.set(charity.id.clone(), charity);
+        env.storage().instance().set(&ConfigKey::Charities, &charities);
+
+        Ok(())
+    }
+
+    pub fn get_charity(env: Env, charity_id: Symbol) -> Option<Charity> {
+        let charities: Map<Symbol, Charity> = env.storage().instance().get(&ConfigKey::Charities).unwrap_or(Map::new(&env));
+
+        charities.get(charity_id)
+    }
+
+    // Round each send and bill payment up to a multiple of `round_to` and set the difference aside
+    // for the charity. Switching charity needs the accrued amount donated first.
+    pub fn enable_roundup(env: Env, user_address: Address, charity_id: Symbol, round_to: i128) -> Result<(), Error> {
+        user_address.require_auth();
+
+        if round_to <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        match Self::get_charity(env.clone(), charity_id.clone()) {
+            Some(charity) if charity.active => {}
+            _ => return Err(Error::CharityNotFound),
+        }
+
+        let mut round_ups: Map<Address, RoundUp> = env.storage().instance().get(&RecordKey::RoundUps).unwrap_or(Map::new(&env));
+        let accrued = match round_ups.get(user_address.clone()) {
+            Some(round_up) if round_up.accrued > 0 && round_up.charity_id != charity_id => {
+                return Err(Error::DonateAccruedRoundUpsFirst);
+            }
+            Some(round_up) => round_up.accrued,
+            None => 0,
+        };
+        round_ups.set(user_address.clone(), RoundUp { charity_id: charity_id.clone(), round_to, accrued });
+        env.storage().instance().set(&RecordKey::RoundUps, &round_ups);
+
+        env.events().publish((symbol_short!("roundup"), user_address), (charity_id, round_to));
+
+        Ok(())
+    }
+
+    // Stop rounding up once the accrued amount has been donated
+    pub fn disable_roundup(env: Env, user_address: Address) -> Result<(), Error> {
+        user_address.require_auth();
+
+        let mut round_ups: Map<Address, RoundUp> = env.storage().instance().get(&RecordKey::RoundUps).unwrap_or(Map::new(&env));
+        let round_up = round_ups.get(user_address.clone()).ok_or(Error::RoundUpNotEnabled)?;
+        if round_up.accrued > 0 {
+            return Err(Error::DonateAccruedRoundUpsFirst);
+        }
+        round_ups.remove(user_address.clone());
+        env.storage().instance().set(&RecordKey::RoundUps, &round_ups);
+
+        env.events().publish((symbol_short!("rndup_off"), user_address), ());
+
+        Ok(())
+    }
+
+    pub fn get_roundup(env: Env, user_address: Address) -> Option<RoundUp> {
+        let round_ups: Map<Address, RoundUp> = env.storage().instance().get(&RecordKey::RoundUps).unwrap_or(Map::new(&env));
+
+        round_ups.get(user_address)
+    }
+
+    // Pay the user's accrued round-ups to their charity; callable by anyone so it can run on a
+    // schedule. Returns the amount donated.
+    pub fn donate_accrued(env: Env, user_address: Address) -> Result<i128, Error> {
+        let mut round_ups: Map<Address, RoundUp> = env.storage().instance().get(&RecordKey::RoundUps).unwrap_or(Map::new(&env));
+        let mut round_up = round_ups.get(user_address.clone()).ok_or(Error::RoundUpNotEnabled)?;
+        if round_up.accrued == 0 {
+            return Ok(0);
+        }
+        let charity = Self::get_charity(env.clone(), round_up.charity_id.clone()).ok_or(Error::CharityNotFound)?;
+
+        let mut users = Users::load(&env);
+        let mut wallet = users.get(charity.wallet.clone()).ok_or(Error::UserNotFound)?;
+        wallet.balance = Self::checked_credit(wallet.balance, round_up.accrued)?;
+        Self::track_balance(&env, &wallet, round_up.accrued, Bucket::Escrowed);
+        users.set(charity.wallet.clone(), wallet);
+        users.save();
+
+        let mut totals: Map<Symbol, i128> = env.storage().instance().get(&RecordKey::CharityTotals).unwrap_or(Map::new(&env));
+        totals.set(charity.id.clone(), totals.get(charity.id.clone()).unwrap_or(0) + round_up.accrued);
+        env.storage().instance().set(&RecordKey::CharityTotals, &totals);
+
+        let donated = round_up.accrued;
+        round_up.accrued = 0;
+        round_ups.set(user_address.clone(), round_up);
+        env.storage().instance().set(&RecordKey::RoundUps, &round_ups);
+
+        env.events().publish((symbol_short!("donated"), user_address), (charity.id, donated));
+
+        Ok(donated)
+    }
+
+    // Get the total donated to a charity
+    pub fn get_charity_total(env: Env, charity_id: Symbol) -> i128 {
+        let totals: Map<Symbol, i128> = env.storage().instance().get(&RecordKey::CharityTotals).unwrap_or(Map::new(&env));
+
+        totals.get(charity_id).unwrap_or(0)
+    }
+}
+
+impl Payvia {
+    // Set aside the round-up on a send or bill payment of `amount`; skipped when the balance
+    // cannot cover it
+    pub(crate) fn accrue_roundup(env: &Env, user: &mut User, amount: i128) -> Result<(), Error> {
+        let mut round_ups: Map<Address, RoundUp> = env.storage().instance().get(&RecordKey::RoundUps).unwrap_or(Map::new(env));
+        let mut round_up = match round_ups.get(user.address.clone()) {
+            Some(round_up) => round_up,
+            None => return Ok(()),
+        };
+
+        let difference = (round_up.round_to - amount % round_up.round_to) % round_up.round_to;
+        if difference <= 0 || user.balance < difference {
+            return Ok(());
+        }
+        user.balance = Self::checked_debit(user.balance, difference)?;
+        Self::track_balance(env, user, -difference, Bucket::Escrowed);
+        round_up.accrued += difference;
+        round_ups.set(user.address.clone(), round_up);
+        env.storage().instance().set(&RecordKey::RoundUps, &round_ups);
+
+        Ok(())
+    }
+}
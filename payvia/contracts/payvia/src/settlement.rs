@@ -0,0 +1,108 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, token, vec, Address, Env, Map, String, Vec};
+
+use crate::{AuditTarget, BillProvider, Bucket, ConfigKey, CounterKey, DataKey, Error, Payvia, Role};
+
+// Payout of accrued bill payments to a provider's settlement account
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Settlement {
+    pub id: u64,
+    pub provider: String,
+    pub amount: i128,
+    // Treasury reference for the batch, e.g. the provider's invoice number
+    pub reference: String,
+    pub settled_at: u64,
+}
+
+#[contractimpl]
+impl Payvia {
+    // Set the address a bill provider's settlements are paid to (admin only)
+    pub fn set_settlement_account(env: Env, provider: String, account: Address) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("stl_acct"), AuditTarget::Id(provider.clone()), 0);
+
+        let providers: Map<String, BillProvider> = env.storage().instance().get(&ConfigKey::Providers).unwrap_or(Map::new(&env));
+        if !providers.contains_key(provider.clone()) {
+            return Err(Error::UnknownBillProvider);
+        }
+
+        let mut accounts: Map<String, Address> = env.storage().instance().get(&ConfigKey::SettlementAccounts).unwrap_or(Map::new(&env));
+        accounts.set(provider, account);
+        env.storage().instance().set(&ConfigKey::SettlementAccounts, &accounts);
+
+        Ok(())
+    }
+
+    // Completed bill payments owed to a provider and not yet settled
+    pub fn get_settlement_due(env: Env, provider: String) -> i128 {
+        let due: Map<String, i128> = env.storage().instance().get(&DataKey::SettlementDue).unwrap_or(Map::new(&env));
+
+        due.get(provider).unwrap_or(0)
+    }
+
+    // Pay part or all of a provider's due balance to its settlement account as USDC
+    // and record the batch (treasurer only)
+    pub fn settle_provider(env: Env, caller: Address, provider: String, amount: i128, reference: String) -> Result<u64, Error> {
+        Self::require_role(&env, &caller, Role::Treasurer)?;
+        Self::audit(&env, &caller, symbol_short!("settle"), AuditTarget::Id(provider.clone()), amount);
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        let mut due: Map<String, i128> = env.storage().instance().get(&DataKey::SettlementDue).unwrap_or(Map::new(&env));
+        let owed = due.get(provider.clone()).unwrap_or(0);
+        if amount > owed {
+            return Err(Error::AmountExceedsSettlementDue);
+        }
+
+        let accounts: Map<String, Address> = env.storage().instance().get(&ConfigKey::SettlementAccounts).unwrap_or(Map::new(&env));
+        let account = accounts.get(provider.clone()).ok_or(Error::SettlementAccountNotSet)?;
+        let usdc: Address = env.storage().instance().get(&ConfigKey::UsdcToken).ok_or(Error::UsdcTokenNotConfigured)?;
+
+        due.set(provider.clone(), owed - amount);
+        env.storage().instance().set(&DataKey::SettlementDue, &due);
+        Self::post(&env, &account, Bucket::Payable, Bucket::External, amount);
+
+        let mut settlements: Map<u64, Settlement> = env.storage().instance().get(&DataKey::Settlements).unwrap_or(Map::new(&env));
+        let settlement_id: u64 = env.storage().instance().get(&CounterKey::SettlementId).unwrap_or(0) + 1;
+        settlements.set(settlement_id, Settlement {
+            id: settlement_id,
+            provider: provider.clone(),
+            amount,
+            reference: reference.clone(),
+            settled_at: env.ledger().timestamp(),
+        });
+        env.storage().instance().set(&DataKey::Settlements, &settlements);
+        env.storage().instance().set(&CounterKey::SettlementId, &settlement_id);
+
+        token::Client::new(&env, &usdc).transfer(&env.current_contract_address(), &account, &amount);
+
+        env.events().publish((symbol_short!("settle"), provider), (settlement_id, amount, reference));
+
+        Ok(settlement_id)
+    }
+
+    // Get the settlements paid to a provider
+    pub fn get_settlements(env: Env, provider: String, cursor: u32, limit: u32) -> (Vec<Settlement>, Option<u32>) {
+        let settlements: Map<u64, Settlement> = env.storage().instance().get(&DataKey::Settlements).unwrap_or(Map::new(&env));
+        let mut provider_settlements = vec![&env];
+
+        for (_, settlement) in settlements.iter() {
+            if settlement.provider == provider {
+                provider_settlements.push_back(settlement);
+            }
+        }
+
+        Self::page(provider_settlements, cursor, limit)
+    }
+}
+
+impl Payvia {
+    // Add a completed bill payment to what is owed to its provider
+    pub(crate) fn accrue_settlement(env: &Env, provider: &String, amount: i128) {
+        let mut due: Map<String, i128> = env.storage().instance().get(&DataKey::SettlementDue).unwrap_or(Map::new(env));
+        due.set(provider.clone(), due.get(provider.clone()).unwrap_or(0) + amount);
+        env.storage().instance().set(&DataKey::SettlementDue, &due);
+    }
+}
@@ -0,0 +1,222 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, Env, Map, String, Symbol};
+
+use crate::{ComplianceKey, ConfigKey, Error, FailureRecord, FeeBearer, Payvia, PinState, User, Users, DEFAULT_PIN_THRESHOLD};
+
+// What a send, withdrawal or bill payment would do if submitted now, for the confirmation screen
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Simulation {
+    pub would_succeed: bool,
+    // The rejection the call would fail with
+    pub error: Option<Error>,
+    // Reason, amounts and earliest retry time of the rejection, when the failing check reports them
+    pub failure: Option<FailureRecord>,
+    pub amount: i128,
+    pub fee: i128,
+    pub payer_pays: i128,
+    pub payee_receives: i128,
+    // Oracle rate less the FX spread and local currency payout, for withdrawals
+    pub fx_rate: Option<i128>,
+    pub local_amount: Option<i128>,
+    // Per-transaction limit that applies to the user; zero if none
+    pub max_transaction: i128,
+    // Operations in the current rate-limit window including this one, against the tier maximum
+    // (zero if unlimited)
+    pub rate_limit_used: u32,
+    pub rate_limit_max: u32,
+    // The amount is above the PIN threshold and no `verify_pin` approval is pending
+    pub pin_required: bool,
+    // The send is above the step-up soft limit and needs `approve_step_up` or
+    // `send_usdc_with_attestation`
+    pub step_up_required: bool,
+    pub balance_after: i128,
+}
+
+#[contractimpl]
+impl Payvia {
+    // Preview a P2P send without changing any state
+    pub fn simulate_send(env: Env, from_address: Address, to_address: Address, amount: i128, fee_bearer: FeeBearer) -> Simulation {
+        let quote = Self::quote_transfer(env.clone(), amount, fee_bearer);
+        let mut simulation = Self::simulation(&env, &from_address, amount, symbol_short!("send"));
+        simulation.fee = quote.fee;
+        simulation.payer_pays = quote.payer_pays;
+        simulation.payee_receives = quote.payee_receives;
+        simulation.step_up_required = Self::step_up_required(&env, &from_address, amount);
+
+        let result = Self::dry_run(&env, || {
+            if from_address == to_address {
+                return Err(Error::CannotSendToYourself);
+            }
+            Self::ensure_module_active(&env, &from_address, symbol_short!("transfers"))?;
+            Self::ensure_not_restricted(&env, &from_address, symbol_short!("send"))?;
+            Self::check_subaccount(&env, &from_address, symbol_short!("send"), amount)?;
+            Self::ensure_not_restricted(&env, &to_address, symbol_short!("send"))?;
+            Self::check_transaction_cap(&env, &from_address, amount, symbol_short!("send"))?;
+
+            let flagged: Map<Address, u64> = env.storage().instance().get(&ComplianceKey::Flagged).unwrap_or(Map::new(&env));
+            if flagged.contains_key(from_address.clone()) {
+                return Err(Error::AccountUnderReview);
+            }
+
+            let user = Self::simulated_user(&env, &from_address)?;
+            Self::simulated_user(&env, &to_address).map_err(|_| Error::RecipientNotFound)?;
+            Self::check_min_incoming(&env, &from_address, &to_address, quote.payee_receives, symbol_short!("send"))?;
+            Self::check_simulated_rate_limit(&simulation)?;
+            if user.balance < quote.payer_pays {
+                return Err(Error::InsufficientBalance);
+            }
+
+            let dust = Self::dust_policy(&env);
+            if amount < dust.min_send {
+                return Err(Error::AmountBelowMinimumSend);
+            }
+            let remaining = user.balance - quote.payer_pays;
+            if remaining > 0 && remaining < dust.dust_threshold {
+                return Err(Error::SendWouldLeaveADustBalance);
+            }
+
+            Ok(remaining)
+        });
+
+        Self::finish_simulation(&env, simulation, result)
+    }
+
+    // Preview a withdrawal without changing any state
+    pub fn simulate_withdraw(env: Env, user_address: Address, method: String, usdc_amount: i128, currency: Symbol) -> Simulation {
+        let mut simulation = Self::simulation(&env, &user_address, usdc_amount, symbol_short!("withdraw"));
+
+        let result = Self::dry_run(&env, || {
+            Self::ensure_module_active(&env, &user_address, symbol_short!("withdraw"))?;
+            Self::ensure_not_restricted(&env, &user_address, symbol_short!("withdraw"))?;
+            Self::check_subaccount(&env, &user_address, symbol_short!("withdraw"), usdc_amount)?;
+            Self::check_transaction_cap(&env, &user_address, usdc_amount, symbol_short!("withdraw"))?;
+            Self::check_cooling_off(&env, &user_address, usdc_amount)?;
+            Self::check_country(&env, &user_address, usdc_amount, Some(&method), symbol_short!("withdraw"))?;
+
+            let fee = Self::withdrawal_rail_fee(&env, &user_address, &method, usdc_amount)?;
+            simulation.fee = fee;
+            simulation.payer_pays = usdc_amount + fee;
+            simulation.payee_receives = usdc_amount;
+
+            let (local_amount, effective_rate, _) = Self::fx_quote(&env, usdc_amount, &currency)?;
+            simulation.fx_rate = Some(effective_rate);
+            simulation.local_amount = Some(local_amount);
+
+            let user = Self::simulated_user(&env, &user_address)?;
+            if user.balance < usdc_amount + fee {
+                return Err(Error::InsufficientBalance);
+            }
+
+            Ok(user.balance - usdc_amount - fee)
+        });
+
+        Self::finish_simulation(&env, simulation, result)
+    }
+
+    // Preview a bill payment without changing any state
+    pub fn simulate_pay_bill(env: Env, user_address: Address, bill_type: String, account_number: String, amount: i128) -> Simulation {
+        let mut simulation = Self::simulation(&env, &user_address, amount, symbol_short!("pay_bill"));
+        simulation.payer_pays = amount;
+        simulation.payee_receives = amount;
+
+        let result = Self::dry_run(&env, || {
+            Self::ensure_module_active(&env, &user_address, symbol_short!("bills"))?;
+            Self::ensure_not_restricted(&env, &user_address, symbol_short!("pay_bill"))?;
+            Self::check_subaccount(&env, &user_address, symbol_short!("pay_bill"), amount)?;
+            Self::check_transaction_cap(&env, &user_address, amount, symbol_short!("pay_bill"))?;
+            Self::validate_bill(&env, &user_address, &bill_type, &account_number, amount)?;
+
+            let user = Self::simulated_user(&env, &user_address)?;
+            Self::check_simulated_rate_limit(&simulation)?;
+            if user.balance + Self::overdraft_available(&env, &user) < amount {
+                return Err(Error::InsufficientBalance);
+            }
+
+            Ok(user.balance - amount)
+        });
+
+        Self::finish_simulation(&env, simulation, result)
+    }
+}
+
+impl Payvia {
+    // A simulation with the user's limits filled in and nothing charged yet
+    fn simulation(env: &Env, user_address: &Address, amount: i128, operation: Symbol) -> Simulation {
+        let users = Users::load(env);
+        let (rate_limit_used, rate_limit_max) = match users.get(user_address.clone()) {
+            Some(user) if operation != symbol_short!("withdraw") => {
+                let limit = Self::rate_limit(env, Self::user_tier(&user));
+                let max = if operation == symbol_short!("send") { limit.max_sends } else { limit.max_bills };
+                (Self::recent_operations(env, user_address, operation).len() + 1, max)
+            }
+            _ => (0, 0),
+        };
+
+        Simulation {
+            would_succeed: false,
+            error: None,
+            failure: None,
+            amount,
+            fee: 0,
+            payer_pays: amount,
+            payee_receives: amount,
+            fx_rate: None,
+            local_amount: None,
+            max_transaction: Self::max_transaction_for(env, user_address),
+            rate_limit_used,
+            rate_limit_max,
+            pin_required: Self::pin_required(env, user_address, amount),
+            step_up_required: false,
+            balance_after: 0,
+        }
+    }
+
+    fn simulated_user(env: &Env, user_address: &Address) -> Result<User, Error> {
+        let users = Users::load(env);
+        users.get(user_address.clone()).ok_or(Error::UserNotFound)
+    }
+
+    fn check_simulated_rate_limit(simulation: &Simulation) -> Result<(), Error> {
+        if simulation.rate_limit_max > 0 && simulation.rate_limit_used > simulation.rate_limit_max {
+            return Err(Error::RateLimited);
+        }
+
+        Ok(())
+    }
+
+    // Whether `require_pin` would reject the amount
+    fn pin_required(env: &Env, user_address: &Address, amount: i128) -> bool {
+        let threshold: i128 = env.storage().instance().get(&ConfigKey::PinThreshold).unwrap_or(DEFAULT_PIN_THRESHOLD);
+        let pins: Map<Address, PinState> = env.storage().instance().get(&ComplianceKey::Pins).unwrap_or(Map::new(env));
+
+        match pins.get(user_address.clone()) {
+            Some(pin) => amount > threshold && pin.approved_until < env.ledger().timestamp(),
+            None => false,
+        }
+    }
+
+    // Run validation without a stale failure from earlier in the invocation
+    fn dry_run<F: FnOnce() -> Result<i128, Error>>(env: &Env, checks: F) -> Result<i128, Error> {
+        Self::take_failure(env);
+
+        checks()
+    }
+
+    // Fill in the outcome, taking the failure details the checks recorded so the simulation
+    // leaves no trace
+    fn finish_simulation(env: &Env, mut simulation: Simulation, result: Result<i128, Error>) -> Simulation {
+        let failure = Self::take_failure(env);
+        match result {
+            Ok(balance_after) => {
+                simulation.would_succeed = true;
+                simulation.balance_after = balance_after;
+            }
+            Err(error) => {
+                simulation.error = Some(error);
+                simulation.failure = failure;
+            }
+        }
+
+        simulation
+    }
+}
@@ -0,0 +1,478 @@
+use core::borrow::Borrow;
+
+use soroban_sdk::{contracttype, symbol_short, Address, BytesN, Env, IntoVal, Map, String, Symbol, Val};
+
+use crate::{Payvia, User, INSTANCE_BUMP_AMOUNT, INSTANCE_LIFETIME_THRESHOLD};
+
+// Storage keys. Split across several enums because a contract type can have at most 50 variants;
+// variant names are unique across all of them so no two keys encode to the same value.
+
+// Admin-set configuration and contract-wide state
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ConfigKey {
+    Admin,
+    UsdcToken,
+    Version,
+    SystemStatus,
+    Sandbox,
+    Sunset,
+    Paused,
+    Roles,
+    Council,
+    CouncilThreshold,
+    CouncilExecuting,
+    TransferFeeBps,
+    TipFeeBps,
+    FxSpreadBps,
+    AgentCommissionBps,
+    LoanFeeBps,
+    GoalPenaltyBps,
+    GroupPenaltyBps,
+    AlertThresholdBps,
+    CashbackRates,
+    ReferralReward,
+    PinThreshold,
+    WithdrawalHoldWindow,
+    WithdrawalSla,
+    CashOutWindow,
+    AuditTtl,
+    RateLimits,
+    TransactionLimits,
+    DustPolicy,
+    OverdraftBuffer,
+    Charities,
+    CoolingOffPolicy,
+    StepUpPolicy,
+    ApprovalKeys,
+    KeeperConfigs,
+    Rails,
+    RailMaintenance,
+    Providers,
+    Telcos,
+    Bundles,
+    Corridors,
+    CountryPolicies,
+    FxRates,
+    AssetRates,
+    SwapPool,
+    SwapTokens,
+    YieldPool,
+    SettlementAccounts,
+    Promos,
+    CreditConfig,
+}
+
+// Id sequences and running totals
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CounterKey {
+    AuditSeq,
+    BillSeq,
+    WithdrawalSeq,
+    BeneficiaryId,
+    CardHoldId,
+    CashOutId,
+    DisputeId,
+    DepositRefNumber,
+    GoalId,
+    GroupId,
+    LoanId,
+    NotificationId,
+    ContactClaimId,
+    OutboxId,
+    PayrollId,
+    ProposalId,
+    RemittanceId,
+    RuleId,
+    ScheduleId,
+    SplitId,
+    SettlementId,
+    StreamId,
+    SwapId,
+    TipId,
+    TaggedDepositId,
+    TopUpId,
+    TransferId,
+    Fees,
+    KeeperPool,
+    LoanPool,
+    ReferralPool,
+    RewardPool,
+    SandboxMinted,
+    YieldShares,
+    Stats,
+    Ledger,
+}
+
+// Collections of user-facing records, plus per-entry persistent records
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DataKey {
+    // Maps of every user, bill payment and withdrawal before v13
+    Users,
+    Bills,
+    Withdrawals,
+    // Map of every transfer before v18
+    Transfers,
+    Agents,
+    Merchants,
+    CashOuts,
+    TopUps,
+    Goals,
+    Groups,
+    Loans,
+    CreditLines,
+    Scheduled,
+    Streams,
+    Splits,
+    Vouchers,
+    Allowances,
+    Assets,
+    Swaps,
+    YieldPositions,
+    YieldEscrow,
+    // Balance snapshots of every user before v17
+    Snapshots,
+    Spending,
+    Rewards,
+    ReferralPending,
+    ReferralStats,
+    FeeStatements,
+    Proposals,
+    KeeperLastPaid,
+    KeeperLeases,
+    Beneficiaries,
+    Remittances,
+    Outbox,
+    Disputes,
+    DepositRefs,
+    TaggedDeposits,
+    Contacts,
+    AutoRules,
+    SettlementDue,
+    Settlements,
+    PromoUses,
+    CardHolds,
+    SubAccounts,
+    Payrolls,
+    PayrollPayouts,
+    Preferences,
+    // Persistent, one record per entry
+    Audit(u64),
+    Inbox(Address),
+    UserBills(Address),
+    UserWithdrawals(Address),
+}
+
+// Security, risk and compliance state
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ComplianceKey {
+    Pins,
+    Recoveries,
+    Guardians,
+    Frozen,
+    Blacklist,
+    Flagged,
+    Velocity,
+    RateLimitHits,
+    // Idempotency records of every user before v16
+    Idempotency,
+    // Per-user failure records before v11
+    Failures,
+    LastFailure,
+    WithdrawalHolds,
+    Quarantined,
+    KnownPayees,
+    MinIncoming,
+    MinIncomingWhitelist,
+    Screening,
+    Alerted,
+    Closed,
+    CoolingOff,
+    RiskScores,
+    StepUps,
+    LimitOverrides,
+    UsedApprovals,
+    Overdrafts,
+    // Verified users a KYC officer cleared for tier 2, with when
+    TrustedUsers,
+}
+
+// Record collections added once `DataKey` was full
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RecordKey {
+    ContactClaims,
+    TipStats,
+    Receipts,
+    BillAttempts,
+    RoundUps,
+    CharityTotals,
+    VoucherCommitments,
+    // Members of each savings group who have not joined it yet
+    GroupInvites,
+}
+
+// Persistent, one record per entry, added once `DataKey` was full
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum EntryKey {
+    User(Address),
+    Bill(String),
+    Withdrawal(String),
+    // Address of the open account registered with a phone number
+    Phone(String),
+    Swap(u64),
+    // Ids of a user's swaps, oldest first
+    UserSwaps(Address),
+    Transfer(u64),
+    // Ids of the transfers a user sent or received, oldest first
+    UserTransfers(Address),
+    // Temporary; the result of a call made with this user's idempotency key
+    Processed(Address, BytesN<32>),
+    // A user's daily balance snapshots, oldest first
+    BalanceHistory(Address),
+    // Ids of the records in each instance map that a user owns or takes part in, oldest first
+    UserGoals(Address),
+    UserScheduled(Address),
+    UserStreams(Address),
+    UserCashOuts(Address),
+    UserSplits(Address),
+    UserVouchers(Address),
+    UserLoans(Address),
+    UserRemittances(Address),
+    UserCardHolds(Address),
+    UserPayrolls(Address),
+    UserContactClaims(Address),
+}
+
+// Write-back view of the per-user records. Reads fall through to storage and writes are held
+// until `save`, so an operation rejected after updating a user leaves that user untouched.
+pub(crate) struct Users {
+    env: Env,
+    // `None` marks a user removed since the last save
+    pending: Map<Address, Option<User>>,
+}
+
+impl Users {
+    pub(crate) fn load(env: &Env) -> Self {
+        Users { env: env.clone(), pending: Map::new(env) }
+    }
+
+    pub(crate) fn get(&self, address: impl Borrow<Address>) -> Option<User> {
+        let address = address.borrow();
+        match self.pending.get(address.clone()) {
+            Some(pending) => pending,
+            None => Self::stored(&self.env, address),
+        }
+    }
+
+    pub(crate) fn contains_key(&self, address: impl Borrow<Address>) -> bool {
+        self.get(address).is_some()
+    }
+
+    pub(crate) fn set(&mut self, address: impl Borrow<Address>, user: impl Borrow<User>) {
+        self.pending.set(address.borrow().clone(), Some(user.borrow().clone()));
+    }
+
+    pub(crate) fn remove(&mut self, address: impl Borrow<Address>) {
+        self.pending.set(address.borrow().clone(), None);
+    }
+
+    // Write the held changes, keeping the phone index in step
+    pub(crate) fn save(&self) {
+        let storage = self.env.storage().persistent();
+        for (address, pending) in self.pending.iter() {
+            let key = EntryKey::User(address.clone());
+            let old_phone = Self::stored(&self.env, &address).map(|user| user.phone);
+            let new_phone = pending.as_ref().map(|user| user.phone.clone());
+            if old_phone != new_phone {
+                if let Some(phone) = old_phone {
+                    storage.remove(&EntryKey::Phone(phone));
+                }
+                if let Some(phone) = new_phone.filter(|phone| phone.len() > 0) {
+                    storage.set(&EntryKey::Phone(phone), &address);
+                }
+            }
+
+            match pending {
+                Some(user) => {
+                    storage.set(&key, &user);
+                    storage.extend_ttl(&key, INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+                }
+                None => storage.remove(&key),
+            }
+        }
+    }
+
+    fn stored(env: &Env, address: &Address) -> Option<User> {
+        env.storage().persistent().get(&EntryKey::User(address.clone()))
+    }
+}
+
+impl Payvia {
+    // Move values written under the pre-v5 `symbol_short!` keys to their typed keys. Per-entry
+    // persistent records (audit log, inboxes) are read through a fallback instead and expire on their own.
+    pub(crate) fn migrate_storage_keys(env: &Env) {
+        for (legacy, key) in [
+            (symbol_short!("admin"), ConfigKey::Admin),
+            (symbol_short!("usdc_tkn"), ConfigKey::UsdcToken),
+            (symbol_short!("version"), ConfigKey::Version),
+            (symbol_short!("status"), ConfigKey::SystemStatus),
+            (symbol_short!("sandbox"), ConfigKey::Sandbox),
+            (symbol_short!("sunset"), ConfigKey::Sunset),
+            (symbol_short!("paused"), ConfigKey::Paused),
+            (symbol_short!("roles"), ConfigKey::Roles),
+            (symbol_short!("council"), ConfigKey::Council),
+            (symbol_short!("cncl_thr"), ConfigKey::CouncilThreshold),
+            (symbol_short!("cncl_exec"), ConfigKey::CouncilExecuting),
+            (symbol_short!("xfer_fee"), ConfigKey::TransferFeeBps),
+            (symbol_short!("agt_comm"), ConfigKey::AgentCommissionBps),
+            (symbol_short!("loan_fee"), ConfigKey::LoanFeeBps),
+            (symbol_short!("goal_pen"), ConfigKey::GoalPenaltyBps),
+            (symbol_short!("grp_pen"), ConfigKey::GroupPenaltyBps),
+            (symbol_short!("alert_bps"), ConfigKey::AlertThresholdBps),
+            (symbol_short!("cb_rates"), ConfigKey::CashbackRates),
+            (symbol_short!("ref_rew"), ConfigKey::ReferralReward),
+            (symbol_short!("pin_min"), ConfigKey::PinThreshold),
+            (symbol_short!("wd_win"), ConfigKey::WithdrawalHoldWindow),
+            (symbol_short!("cash_win"), ConfigKey::CashOutWindow),
+            (symbol_short!("audit_ttl"), ConfigKey::AuditTtl),
+            (symbol_short!("rl_cfg"), ConfigKey::RateLimits),
+            (symbol_short!("tx_limits"), ConfigKey::TransactionLimits),
+            (symbol_short!("dust"), ConfigKey::DustPolicy),
+            (symbol_short!("kpr_cfg"), ConfigKey::KeeperConfigs),
+            (symbol_short!("rails"), ConfigKey::Rails),
+            (symbol_short!("providers"), ConfigKey::Providers),
+            (symbol_short!("telcos"), ConfigKey::Telcos),
+            (symbol_short!("bundles"), ConfigKey::Bundles),
+            (symbol_short!("corridors"), ConfigKey::Corridors),
+            (symbol_short!("fx_rates"), ConfigKey::FxRates),
+            (symbol_short!("rates"), ConfigKey::AssetRates),
+            (symbol_short!("swap_pool"), ConfigKey::SwapPool),
+            (symbol_short!("swap_tkns"), ConfigKey::SwapTokens),
+            (symbol_short!("yield_pl"), ConfigKey::YieldPool),
+            (symbol_short!("stl_accts"), ConfigKey::SettlementAccounts),
+            (symbol_short!("promos"), ConfigKey::Promos),
+            (symbol_short!("credit"), ConfigKey::CreditConfig),
+        ] {
+            Self::relocate_key(env, legacy, key);
+        }
+        for (legacy, key) in [
+            (symbol_short!("audit_seq"), CounterKey::AuditSeq),
+            (symbol_short!("bill_seq"), CounterKey::BillSeq),
+            (symbol_short!("benef_id"), CounterKey::BeneficiaryId),
+            (symbol_short!("card_id"), CounterKey::CardHoldId),
+            (Symbol::new(env, "cashout_id"), CounterKey::CashOutId),
+            (symbol_short!("disp_id"), CounterKey::DisputeId),
+            (symbol_short!("dep_refno"), CounterKey::DepositRefNumber),
+            (symbol_short!("goal_id"), CounterKey::GoalId),
+            (symbol_short!("group_id"), CounterKey::GroupId),
+            (symbol_short!("loan_id"), CounterKey::LoanId),
+            (symbol_short!("notif_id"), CounterKey::NotificationId),
+            (symbol_short!("outbox_id"), CounterKey::OutboxId),
+            (symbol_short!("payrl_id"), CounterKey::PayrollId),
+            (symbol_short!("prop_id"), CounterKey::ProposalId),
+            (symbol_short!("remit_id"), CounterKey::RemittanceId),
+            (symbol_short!("rule_id"), CounterKey::RuleId),
+            (symbol_short!("sched_id"), CounterKey::ScheduleId),
+            (symbol_short!("split_id"), CounterKey::SplitId),
+            (symbol_short!("stl_id"), CounterKey::SettlementId),
+            (symbol_short!("stream_id"), CounterKey::StreamId),
+            (symbol_short!("swap_id"), CounterKey::SwapId),
+            (Symbol::new(env, "tag_dep_id"), CounterKey::TaggedDepositId),
+            (symbol_short!("topup_id"), CounterKey::TopUpId),
+            (symbol_short!("xfer_id"), CounterKey::TransferId),
+            (symbol_short!("fees"), CounterKey::Fees),
+            (symbol_short!("kpr_pool"), CounterKey::KeeperPool),
+            (symbol_short!("loan_pool"), CounterKey::LoanPool),
+            (symbol_short!("ref_pool"), CounterKey::ReferralPool),
+            (symbol_short!("rw_pool"), CounterKey::RewardPool),
+            (symbol_short!("sbx_mint"), CounterKey::SandboxMinted),
+            (symbol_short!("yield_shr"), CounterKey::YieldShares),
+            (symbol_short!("stats"), CounterKey::Stats),
+        ] {
+            Self::relocate_key(env, legacy, key);
+        }
+        for (legacy, key) in [
+            (symbol_short!("users"), DataKey::Users),
+            (symbol_short!("bills"), DataKey::Bills),
+            (Symbol::new(env, "withdrawals"), DataKey::Withdrawals),
+            (symbol_short!("transfers"), DataKey::Transfers),
+            (symbol_short!("agents"), DataKey::Agents),
+            (symbol_short!("merchants"), DataKey::Merchants),
+            (symbol_short!("cashouts"), DataKey::CashOuts),
+            (symbol_short!("topups"), DataKey::TopUps),
+            (symbol_short!("goals"), DataKey::Goals),
+            (symbol_short!("groups"), DataKey::Groups),
+            (symbol_short!("loans"), DataKey::Loans),
+            (symbol_short!("credits"), DataKey::CreditLines),
+            (symbol_short!("sched"), DataKey::Scheduled),
+            (symbol_short!("streams"), DataKey::Streams),
+            (symbol_short!("splits"), DataKey::Splits),
+            (symbol_short!("vouchers"), DataKey::Vouchers),
+            (symbol_short!("allows"), DataKey::Allowances),
+            (symbol_short!("assets"), DataKey::Assets),
+            (symbol_short!("swaps"), DataKey::Swaps),
+            (symbol_short!("yield_pos"), DataKey::YieldPositions),
+            (symbol_short!("yield_esc"), DataKey::YieldEscrow),
+            (symbol_short!("snaps"), DataKey::Snapshots),
+            (symbol_short!("spend"), DataKey::Spending),
+            (symbol_short!("rewards"), DataKey::Rewards),
+            (symbol_short!("ref_pend"), DataKey::ReferralPending),
+            (symbol_short!("ref_stats"), DataKey::ReferralStats),
+            (symbol_short!("fee_stmts"), DataKey::FeeStatements),
+            (symbol_short!("proposals"), DataKey::Proposals),
+            (symbol_short!("kpr_last"), DataKey::KeeperLastPaid),
+            (symbol_short!("kpr_lease"), DataKey::KeeperLeases),
+            (symbol_short!("benefs"), DataKey::Beneficiaries),
+            (symbol_short!("remits"), DataKey::Remittances),
+            (symbol_short!("outbox"), DataKey::Outbox),
+            (symbol_short!("disputes"), DataKey::Disputes),
+            (symbol_short!("dep_refs"), DataKey::DepositRefs),
+            (symbol_short!("tag_deps"), DataKey::TaggedDeposits),
+            (symbol_short!("contacts"), DataKey::Contacts),
+            (symbol_short!("rules"), DataKey::AutoRules),
+            (symbol_short!("stl_due"), DataKey::SettlementDue),
+            (symbol_short!("stlmnts"), DataKey::Settlements),
+            (symbol_short!("promo_use"), DataKey::PromoUses),
+            (symbol_short!("card_hlds"), DataKey::CardHolds),
+            (symbol_short!("subaccts"), DataKey::SubAccounts),
+            (symbol_short!("payrolls"), DataKey::Payrolls),
+            (symbol_short!("payrl_out"), DataKey::PayrollPayouts),
+        ] {
+            Self::relocate_key(env, legacy, key);
+        }
+        for (legacy, key) in [
+            (symbol_short!("pins"), ComplianceKey::Pins),
+            (symbol_short!("recovery"), ComplianceKey::Recoveries),
+            (symbol_short!("guardians"), ComplianceKey::Guardians),
+            (symbol_short!("frozen"), ComplianceKey::Frozen),
+            (symbol_short!("blacklist"), ComplianceKey::Blacklist),
+            (symbol_short!("flagged"), ComplianceKey::Flagged),
+            (symbol_short!("velocity"), ComplianceKey::Velocity),
+            (symbol_short!("rl_hits"), ComplianceKey::RateLimitHits),
+            (symbol_short!("idem"), ComplianceKey::Idempotency),
+            (symbol_short!("failures"), ComplianceKey::Failures),
+            (symbol_short!("wd_holds"), ComplianceKey::WithdrawalHolds),
+            (symbol_short!("quarant"), ComplianceKey::Quarantined),
+            (symbol_short!("known"), ComplianceKey::KnownPayees),
+            (symbol_short!("min_in"), ComplianceKey::MinIncoming),
+            (symbol_short!("min_wl"), ComplianceKey::MinIncomingWhitelist),
+            (symbol_short!("screen"), ComplianceKey::Screening),
+            (symbol_short!("alerted"), ComplianceKey::Alerted),
+            (symbol_short!("closed"), ComplianceKey::Closed),
+        ] {
+            Self::relocate_key(env, legacy, key);
+        }
+    }
+
+    fn relocate_key<K: IntoVal<Env, Val>>(env: &Env, legacy: Symbol, key: K) {
+        if let Some(value) = env.storage().instance().get::<Symbol, Val>(&legacy) {
+            env.storage().instance().set(&key, &value);
+            env.storage().instance().remove(&legacy);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use soroban_sdk::{contractimpl, symbol_short, token, Address, Env};
+
+use crate::{AuditTarget, Bucket, ConfigKey, Error, Payvia, Users, SECONDS_PER_DAY};
+
+// Time between announcing a wind-down and users being able to exit
+pub const SUNSET_NOTICE_SECS: u64 = 30 * SECONDS_PER_DAY;
+
+#[contractimpl]
+impl Payvia {
+    // Announce that the contract is winding down. New deposits are refused from now on and
+    // balances can be exited as USDC once the notice period has passed. Returns when exits open
+    // (admin only).
+    pub fn initiate_sunset(env: Env) -> Result<u64, Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("sunset"), AuditTarget::Global, 0);
+
+        if Self::sunset_initiated(&env) {
+            return Err(Error::SunsetAlreadyInitiated);
+        }
+        env.storage().instance().get::<_, Address>(&ConfigKey::UsdcToken).ok_or(Error::UsdcTokenNotConfigured)?;
+
+        let exits_open_at = env.ledger().timestamp() + SUNSET_NOTICE_SECS;
+        env.storage().instance().set(&ConfigKey::Sunset, &exits_open_at);
+
+        env.events().publish((symbol_short!("sunset"),), exits_open_at);
+
+        Ok(exits_open_at)
+    }
+
+    // When exits open, or None while no sunset is in progress
+    pub fn get_sunset(env: Env) -> Option<u64> {
+        env.storage().instance().get(&ConfigKey::Sunset)
+    }
+
+    // Withdraw the user's full balance as USDC once the sunset notice period has passed
+    pub fn exit(env: Env, user_address: Address) -> Result<i128, Error> {
+        user_address.require_auth();
+
+        Self::pay_out_balance(&env, &user_address)
+    }
+
+    // Send an unclaimed balance back to its owner after the notice period (admin only)
+    pub fn force_return(env: Env, user_address: Address) -> Result<i128, Error> {
+        let admin = Self::require_admin(&env)?;
+
+        let amount = Self::pay_out_balance(&env, &user_address)?;
+        Self::audit(&env, &admin, symbol_short!("force_ret"), AuditTarget::Address(user_address), amount);
+
+        Ok(amount)
+    }
+}
+
+impl Payvia {
+    pub(crate) fn sunset_initiated(env: &Env) -> bool {
+        env.storage().instance().has(&ConfigKey::Sunset)
+    }
+
+    fn pay_out_balance(env: &Env, user_address: &Address) -> Result<i128, Error> {
+        let exits_open_at: u64 = env.storage().instance().get(&ConfigKey::Sunset).ok_or(Error::SunsetNotInitiated)?;
+        if env.ledger().timestamp() < exits_open_at {
+            return Err(Error::SunsetNoticePeriodHasNotPassed);
+        }
+
+        let mut users = Users::load(env);
+        let mut user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+        let amount = user.balance;
+        if amount <= 0 {
+            return Err(Error::NothingToReturn);
+        }
+        // Sandbox balances are play money minted by the faucet, not USDC the contract holds
+        if env.storage().instance().get(&ConfigKey::Sandbox).unwrap_or(false) {
+            return Err(Error::NotAvailableInSandboxMode);
+        }
+
+        user.balance = 0;
+        Self::track_balance(env, &user, -amount, Bucket::External);
+        users.set(user_address.clone(), user);
+        users.save();
+
+        let usdc: Address = env.storage().instance().get(&ConfigKey::UsdcToken).ok_or(Error::UsdcTokenNotConfigured)?;
+        token::Client::new(env, &usdc).transfer(&env.current_contract_address(), user_address, &amount);
+
+        env.events().publish((symbol_short!("exit"), user_address.clone()), amount);
+
+        Ok(amount)
+    }
+}
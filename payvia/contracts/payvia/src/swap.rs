@@ -0,0 +1,218 @@
+use soroban_sdk::{contractclient, contractimpl, contracttype, symbol_short, token, vec, Address, Env, Map, Symbol, Vec};
+
+use crate::{AuditTarget, Bucket, ConfigKey, CounterKey, DataKey, EntryKey, Error, Payvia, Users, INSTANCE_BUMP_AMOUNT, INSTANCE_LIFETIME_THRESHOLD};
+
+// Interface of the AMM pool swaps are routed through
+#[contractclient(name = "SwapPoolClient")]
+pub trait SwapPool {
+    // Swap `amount_in` of `token_in`, already transferred to the pool, and send the output to `to`
+    fn swap(env: Env, to: Address, token_in: Address, token_out: Address, amount_in: i128, min_out: i128) -> i128;
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Swap {
+    pub id: u64,
+    pub user_address: Address,
+    pub from_token: Address,
+    pub to_token: Address,
+    pub amount_in: i128,
+    pub amount_out: i128,
+    pub timestamp: u64,
+}
+
+#[contractimpl]
+impl Payvia {
+    // Set the AMM pool contract swaps are routed through (admin only)
+    pub fn set_swap_pool(env: Env, pool: Address) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("swap_pool"), AuditTarget::Address(pool.clone()), 0);
+
+        env.storage().instance().set(&ConfigKey::SwapPool, &pool);
+
+        Ok(())
+    }
+
+    // List a token for swaps under its oracle asset code. Listing it as `USDC` makes it the
+    // token backing user balances (admin only).
+    pub fn set_swap_token(env: Env, token: Address, asset: Symbol) -> Result<(), Error> {
+        let admin = Self::require_admin(&env)?;
+        Self::audit(&env, &admin, symbol_short!("swap_tkn"), AuditTarget::Address(token.clone()), 0);
+
+        let mut tokens: Map<Address, Symbol> = env.storage().instance().get(&ConfigKey::SwapTokens).unwrap_or(Map::new(&env));
+        tokens.set(token.clone(), asset.clone());
+        env.storage().instance().set(&ConfigKey::SwapTokens, &tokens);
+
+        if asset == symbol_short!("USDC") {
+            env.storage().instance().set(&ConfigKey::UsdcToken, &token);
+        }
+
+        Ok(())
+    }
+
+    // Move a listed non-USDC token from the user's wallet into their in-app asset balance
+    pub fn deposit_asset(env: Env, user_address: Address, token: Address, amount: i128) -> Result<(), Error> {
+        user_address.require_auth();
+        Self::ensure_not_restricted(&env, &user_address, symbol_short!("deposit"))?;
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        let tokens: Map<Address, Symbol> = env.storage().instance().get(&ConfigKey::SwapTokens).unwrap_or(Map::new(&env));
+        let asset = tokens.get(token.clone()).ok_or(Error::TokenNotSupported)?;
+        if asset == symbol_short!("USDC") {
+            return Err(Error::UseDepositForUsdc);
+        }
+
+        token::Client::new(&env, &token).transfer(&user_address, &env.current_contract_address(), &amount);
+        Self::credit_asset(&env, &user_address, &token, amount)?;
+
+        env.events().publish((symbol_short!("dep_asset"), user_address, token), amount);
+
+        Ok(())
+    }
+
+    // Swap between listed tokens through the configured pool, crediting at least `min_out`
+    // to the user's balance. Returns the amount received.
+    pub fn swap(env: Env, user_address: Address, from_token: Address, to_token: Address, amount: i128, min_out: i128) -> Result<i128, Error> {
+        user_address.require_auth();
+        Self::ensure_module_active(&env, &user_address, symbol_short!("swaps"))?;
+        Self::ensure_not_restricted(&env, &user_address, symbol_short!("swap"))?;
+
+        if amount <= 0 || min_out < 0 {
+            return Err(Error::InvalidAmount);
+        }
+        if from_token == to_token {
+            return Err(Error::TokensMustDiffer);
+        }
+        let tokens: Map<Address, Symbol> = env.storage().instance().get(&ConfigKey::SwapTokens).unwrap_or(Map::new(&env));
+        if !tokens.contains_key(from_token.clone()) || !tokens.contains_key(to_token.clone()) {
+            return Err(Error::TokenNotSupported);
+        }
+        let pool: Address = env.storage().instance().get(&ConfigKey::SwapPool).ok_or(Error::SwapPoolNotConfigured)?;
+
+        Self::debit_asset(&env, &user_address, &from_token, amount)?;
+
+        // Measure what actually arrived rather than trusting the pool's return value
+        let contract = env.current_contract_address();
+        let out_token = token::Client::new(&env, &to_token);
+        let before = out_token.balance(&contract);
+        token::Client::new(&env, &from_token).transfer(&contract, &pool, &amount);
+        SwapPoolClient::new(&env, &pool).swap(&contract, &from_token, &to_token, &amount, &min_out);
+        let amount_out = out_token.balance(&contract) - before;
+
+        if amount_out < min_out {
+            return Err(Self::fail(&env, &user_address, symbol_short!("swap"), symbol_short!("slippage"), min_out, amount_out, Error::SwapOutputBelowMinimum));
+        }
+
+        Self::credit_asset(&env, &user_address, &to_token, amount_out)?;
+
+        let swap_id: u64 = env.storage().instance().get(&CounterKey::SwapId).unwrap_or(0) + 1;
+        Self::save_swap(&env, &Swap {
+            id: swap_id,
+            user_address: user_address.clone(),
+            from_token: from_token.clone(),
+            to_token: to_token.clone(),
+            amount_in: amount,
+            amount_out,
+            timestamp: env.ledger().timestamp(),
+        });
+        env.storage().instance().set(&CounterKey::SwapId, &swap_id);
+
+        env.events().publish((symbol_short!("swap"), user_address, from_token, to_token), (swap_id, amount, amount_out));
+
+        Ok(amount_out)
+    }
+
+    // Get a user's balance of a non-USDC token
+    pub fn get_asset_balance(env: Env, user_address: Address, token: Address) -> i128 {
+        let assets: Map<(Address, Address), i128> = env.storage().instance().get(&DataKey::Assets).unwrap_or(Map::new(&env));
+
+        assets.get((user_address, token)).unwrap_or(0)
+    }
+
+    // Get swap history
+    pub fn get_swaps(env: Env, user_address: Address, cursor: u32, limit: u32) -> (Vec<Swap>, Option<u32>) {
+        let swap_ids: Vec<u64> = env.storage().persistent().get(&EntryKey::UserSwaps(user_address)).unwrap_or(vec![&env]);
+        let (swap_ids, next_cursor) = Self::page(swap_ids, cursor, limit);
+        let mut user_swaps = vec![&env];
+
+        for swap_id in swap_ids.iter() {
+            if let Some(swap) = env.storage().persistent().get(&EntryKey::Swap(swap_id)) {
+                user_swaps.push_back(swap);
+            }
+        }
+
+        (user_swaps, next_cursor)
+    }
+}
+
+impl Payvia {
+    // Store a swap under its own entry and add it to its user's history
+    pub(crate) fn save_swap(env: &Env, swap: &Swap) {
+        let key = EntryKey::Swap(swap.id);
+        env.storage().persistent().set(&key, swap);
+        env.storage().persistent().extend_ttl(&key, INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+
+        let index_key = EntryKey::UserSwaps(swap.user_address.clone());
+        let mut swap_ids: Vec<u64> = env.storage().persistent().get(&index_key).unwrap_or(vec![env]);
+        swap_ids.push_back(swap.id);
+        env.storage().persistent().set(&index_key, &swap_ids);
+    }
+
+    // USDC lives in the user's main balance; other tokens in the per-token asset balances
+    fn debit_asset(env: &Env, user_address: &Address, token: &Address, amount: i128) -> Result<(), Error> {
+        let usdc: Option<Address> = env.storage().instance().get(&ConfigKey::UsdcToken);
+        if usdc.as_ref() == Some(token) {
+            // Sandbox balances are faucet play money, so they cannot be swapped out for real tokens
+            if env.storage().instance().get(&ConfigKey::Sandbox).unwrap_or(false) {
+                return Err(Error::NotAvailableInSandboxMode);
+            }
+            let mut users = Users::load(env);
+            let mut user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+            if user.balance < amount {
+                return Err(Self::fail(env, user_address, symbol_short!("swap"), symbol_short!("balance"), amount, user.balance, Error::InsufficientBalance));
+            }
+            user.balance = Self::checked_debit(user.balance, amount)?;
+            Self::track_balance(env, &user, -amount, Bucket::External);
+            users.set(user_address.clone(), user);
+            users.save();
+            return Ok(());
+        }
+
+        let mut assets: Map<(Address, Address), i128> = env.storage().instance().get(&DataKey::Assets).unwrap_or(Map::new(env));
+        let balance = assets.get((user_address.clone(), token.clone())).unwrap_or(0);
+        if balance < amount {
+            return Err(Self::fail(env, user_address, symbol_short!("swap"), symbol_short!("balance"), amount, balance, Error::InsufficientBalance));
+        }
+        assets.set((user_address.clone(), token.clone()), balance - amount);
+        env.storage().instance().set(&DataKey::Assets, &assets);
+
+        Ok(())
+    }
+
+    fn credit_asset(env: &Env, user_address: &Address, token: &Address, amount: i128) -> Result<(), Error> {
+        let usdc: Option<Address> = env.storage().instance().get(&ConfigKey::UsdcToken);
+        if usdc.as_ref() == Some(token) {
+            let mut users = Users::load(env);
+            let mut user = users.get(user_address.clone()).ok_or(Error::UserNotFound)?;
+            user.balance = Self::checked_credit(user.balance, amount)?;
+            Self::track_balance(env, &user, amount, Bucket::External);
+            users.set(user_address.clone(), user);
+            users.save();
+            return Ok(());
+        }
+
+        let users = Users::load(env);
+        if !users.contains_key(user_address.clone()) {
+            return Err(Error::UserNotFound);
+        }
+
+        let mut assets: Map<(Address, Address), i128> = env.storage().instance().get(&DataKey::Assets).unwrap_or(Map::new(env));
+        let balance = assets.get((user_address.clone(), token.clone())).unwrap_or(0);
+        assets.set((user_address.clone(), token.clone()), balance + amount);
+        env.storage().instance().set(&DataKey::Assets, &assets);
+
+        Ok(())
+    }
+}
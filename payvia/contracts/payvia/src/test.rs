@@ -1,21 +1,1447 @@
 #![cfg(test)]
 
 use super::*;
-use soroban_sdk::{vec, Env, String};
+use crate::testutils::{Fixture, TEST_BILLER, TEST_CURRENCY};
+use ed25519_dalek::{Signer, SigningKey};
+use soroban_sdk::testutils::storage::Persistent as _;
+use soroban_sdk::testutils::{Address as _, Ledger};
+
+// Balances and amounts at and around the i128 limits
+const EXTREMES: [i128; 11] = [
+    i128::MIN,
+    i128::MIN + 1,
+    -RATE_SCALE,
+    -1,
+    0,
+    1,
+    RATE_SCALE,
+    i128::MAX / 2,
+    i128::MAX / 2 + 1,
+    i128::MAX - 1,
+    i128::MAX,
+];
+
+#[test]
+fn credit_then_debit_restores_balance() {
+    for balance in EXTREMES {
+        for amount in EXTREMES {
+            if let Ok(credited) = Payvia::checked_credit(balance, amount) {
+                assert_eq!(Payvia::checked_debit(credited, amount), Ok(balance));
+            }
+        }
+    }
+}
+
+#[test]
+fn credit_overflows_only_past_the_limits() {
+    for balance in EXTREMES {
+        for amount in EXTREMES {
+            match Payvia::checked_credit(balance, amount) {
+                Ok(credited) => {
+                    assert!(amount >= 0 && balance <= i128::MAX - amount);
+                    assert_eq!(credited - amount, balance);
+                }
+                Err(Error::InvalidAmount) => assert!(amount < 0),
+                Err(error) => {
+                    assert_eq!(error, Error::ArithmeticOverflow);
+                    assert!(amount >= 0 && balance > i128::MAX - amount);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn debit_overflows_only_past_the_limits() {
+    for balance in EXTREMES {
+        for amount in EXTREMES {
+            match Payvia::checked_debit(balance, amount) {
+                Ok(debited) => {
+                    assert!(amount >= 0 && balance >= i128::MIN + amount);
+                    assert_eq!(debited + amount, balance);
+                }
+                Err(Error::InvalidAmount) => assert!(amount < 0),
+                Err(error) => {
+                    assert_eq!(error, Error::ArithmeticOverflow);
+                    assert!(amount >= 0 && balance < i128::MIN + amount);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn deposit_at_max_balance_reports_overflow() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let user = fixture.funded_user(i128::MAX);
+
+    assert_eq!(client.try_deposit(&user, &1), Err(Ok(Error::ArithmeticOverflow)));
+    assert_eq!(client.get_balance(&user), i128::MAX);
+}
+
+#[test]
+fn fixtures_build_pending_payments() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let user = fixture.funded_user(1_000);
+    fixture.pending_bill(&user, 300);
+    fixture.advance_time(SECONDS_PER_DAY);
+    fixture.pending_withdrawal(&user, 200);
+
+    assert_eq!(client.get_balance(&user), 500);
+    let summary = client.get_pending_summary(&user);
+    assert_eq!(summary.bill_total, 300);
+    assert_eq!(summary.withdrawal_total, 200);
+}
+
+#[test]
+fn granting_a_role_requires_the_admin() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let treasurer = Address::generate(&fixture.env);
+    client.grant_role(&treasurer, &Role::Treasurer);
+    assert_eq!(fixture.env.auths()[0].0, fixture.admin);
+    assert_eq!(client.get_roles(&treasurer), vec![&fixture.env, Role::Treasurer]);
+
+    client.revoke_role(&treasurer, &Role::Treasurer);
+    assert_eq!(fixture.env.auths()[0].0, fixture.admin);
+    assert!(client.get_roles(&treasurer).is_empty());
+}
+
+#[test]
+fn roles_cannot_be_granted_without_the_admin() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let attacker = Address::generate(&fixture.env);
+    fixture.revoke_auths();
+
+    assert!(client.try_grant_role(&attacker, &Role::Treasurer).is_err());
+    assert!(client.try_set_transfer_fee(&10_000).is_err());
+    assert!(client.get_roles(&attacker).is_empty());
+}
+
+#[test]
+fn role_entrypoints_reject_callers_without_the_role() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let user = fixture.user();
+    let attacker = Address::generate(&fixture.env);
+
+    assert_eq!(client.try_verify_user(&attacker, &user), Err(Ok(Error::MissingRole)));
+    assert_eq!(client.try_fund_loan_pool(&attacker, &1_000), Err(Ok(Error::MissingRole)));
+}
+
+#[test]
+fn init_only_runs_once() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let attacker = Address::generate(&fixture.env);
+    assert_eq!(client.try_init(&attacker), Err(Ok(Error::AlreadyInitialized)));
+
+    assert_eq!(client.version(), CONTRACT_VERSION);
+    assert_eq!(client.try_migrate(), Err(Ok(Error::AlreadyMigrated)));
+}
+
+#[test]
+fn upgrade_and_migrate_require_the_admin() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    fixture.revoke_auths();
+
+    assert!(client.try_upgrade(&BytesN::from_array(&fixture.env, &[0; 32])).is_err());
+    assert!(client.try_migrate().is_err());
+}
+
+#[test]
+fn first_council_is_set_by_the_admin_only() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let attacker = Address::generate(&fixture.env);
+    fixture.revoke_auths();
+
+    assert!(client.try_set_admins(&vec![&fixture.env, attacker], &1).is_err());
+    assert_eq!(client.get_admins(), (vec![&fixture.env], 0));
+}
+
+#[test]
+fn council_changes_need_a_council_proposal() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let first = Address::generate(&fixture.env);
+    let second = Address::generate(&fixture.env);
+    client.set_admins(&vec![&fixture.env, first.clone(), second.clone()], &2);
+    assert_eq!(fixture.env.auths()[0].0, fixture.admin);
+
+    let attacker = Address::generate(&fixture.env);
+    assert_eq!(client.try_set_admins(&vec![&fixture.env, attacker], &1), Err(Ok(Error::RequiresCouncilApproval)));
+
+    let proposal_id = client.propose_admin_action(&first, &AdminAction::SetAdmins(vec![&fixture.env, first.clone()], 1));
+    assert_eq!(client.get_admins().1, 2);
+    client.approve_admin_action(&second, &proposal_id);
+
+    assert_eq!(client.get_admins(), (vec![&fixture.env, first], 1));
+}
+
+// A fresh address holding `role`
+fn role_holder(fixture: &Fixture, role: Role) -> Address {
+    let holder = Address::generate(&fixture.env);
+    fixture.client().grant_role(&holder, &role);
+    holder
+}
+
+fn bill(fixture: &Fixture, bill_type: &str, amount: i128) -> BillRequest {
+    BillRequest {
+        bill_type: String::from_str(&fixture.env, bill_type),
+        account_number: String::from_str(&fixture.env, "0123456789"),
+        amount,
+        memo: None,
+    }
+}
+
+#[test]
+fn disputes_are_resolved_only_by_compliance() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+    let updater = role_holder(&fixture, Role::StatusUpdater);
+    let compliance = role_holder(&fixture, Role::Compliance);
+
+    let user = fixture.funded_user(1_000);
+    let payment_id = fixture.pending_bill(&user, 400);
+    client.set_bill_status(&updater, &payment_id, &PaymentStatus::Completed);
+    let dispute_id = client.open_dispute(&user, &payment_id, &String::from_str(&fixture.env, "Not received"));
+
+    assert_eq!(client.try_resolve_dispute(&user, &dispute_id, &DisputeOutcome::Refund), Err(Ok(Error::MissingRole)));
+    assert_eq!(client.get_balance(&user), 600);
+
+    client.resolve_dispute(&compliance, &dispute_id, &DisputeOutcome::Refund);
+    assert_eq!(client.get_balance(&user), 1_000);
+    assert_eq!(
+        client.try_resolve_dispute(&compliance, &dispute_id, &DisputeOutcome::Refund),
+        Err(Ok(Error::DisputeAlreadyResolved))
+    );
+}
+
+#[test]
+fn balance_history_opens_each_day_with_the_previous_balance() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let user = fixture.funded_user(1_000);
+    fixture.advance_time(SECONDS_PER_DAY);
+    fixture.pending_bill(&user, 300);
+    fixture.pending_bill(&user, 200);
+
+    let (history, next) = client.get_balance_history(&user, &0, &u64::MAX, &0, &10);
+    assert_eq!(next, None);
+    assert_eq!(history.len(), 2);
+    assert_eq!(history.get(0).unwrap().balance, 0);
+    assert_eq!(history.get(1).unwrap().balance, 1_000);
+}
+
+#[test]
+fn transfers_are_listed_for_both_parties() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let from = fixture.funded_user(1_000);
+    let to = fixture.user();
+    client.send_usdc(&from, &to, &100, &FeeBearer::Payer, &None, &None, &None);
+    client.send_usdc(&from, &to, &100, &FeeBearer::Payer, &None, &None, &None);
+
+    assert_eq!(client.get_transfers(&from, &0, &10).0.len(), 2);
+    assert_eq!(client.get_transfers(&to, &0, &10).0.len(), 2);
+    let (page, next) = client.get_transfers(&to, &0, &1);
+    assert_eq!(page.len(), 1);
+    assert_eq!(next, Some(1));
+}
+
+#[test]
+fn bump_user_extends_the_users_records() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let from = fixture.funded_user(1_000);
+    let to = fixture.user();
+    let transfer_id = client.send_usdc(&from, &to, &100, &FeeBearer::Payer, &None, &None, &None);
+    fixture.env.ledger().with_mut(|ledger| ledger.sequence_number += 2 * DAY_IN_LEDGERS);
+
+    client.bump_user(&from);
+
+    fixture.env.as_contract(&fixture.contract_id, || {
+        let storage = fixture.env.storage().persistent();
+        assert_eq!(storage.get_ttl(&EntryKey::User(from.clone())), INSTANCE_BUMP_AMOUNT);
+        assert_eq!(storage.get_ttl(&EntryKey::UserTransfers(from.clone())), INSTANCE_BUMP_AMOUNT);
+        assert_eq!(storage.get_ttl(&EntryKey::Transfer(transfer_id)), INSTANCE_BUMP_AMOUNT);
+    });
+}
+
+#[test]
+fn frozen_accounts_cannot_be_closed_or_swept_into() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+    let compliance = role_holder(&fixture, Role::Compliance);
+
+    let user = fixture.funded_user(1_000);
+    let other = fixture.user();
+    client.freeze_account(&compliance, &user);
+    assert_eq!(client.try_close_account(&user, &Some(other.clone())), Err(Ok(Error::AccountFrozen)));
+    assert_eq!(client.get_balance(&user), 1_000);
+
+    client.unfreeze_account(&compliance, &user);
+    client.blacklist_address(&compliance, &other);
+    assert_eq!(client.try_close_account(&user, &Some(other.clone())), Err(Ok(Error::AddressBlacklisted)));
+
+    client.unblacklist_address(&compliance, &other);
+    client.close_account(&user, &Some(other.clone()));
+    assert_eq!(client.get_balance(&other), 1_000);
+}
+
+#[test]
+fn only_compliance_freezes_and_blacklists() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+    let compliance = role_holder(&fixture, Role::Compliance);
+    let user = fixture.user();
+
+    assert_eq!(client.try_freeze_account(&fixture.admin, &user), Err(Ok(Error::MissingRole)));
+    assert_eq!(client.try_blacklist_address(&fixture.admin, &user), Err(Ok(Error::MissingRole)));
+
+    client.freeze_account(&compliance, &user);
+    client.blacklist_address(&compliance, &user);
+    assert!(client.is_frozen(&user));
+    assert!(client.is_blacklisted(&user));
+
+    fixture.revoke_auths();
+    assert!(client.try_unfreeze_account(&compliance, &user).is_err());
+    assert!(client.try_unblacklist_address(&compliance, &user).is_err());
+}
+
+#[test]
+fn frozen_accounts_cannot_sweep_dust() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+    let compliance = role_holder(&fixture, Role::Compliance);
+    client.set_dust_policy(&0, &100);
+
+    let user = fixture.funded_user(50);
+    client.freeze_account(&compliance, &user);
+    assert_eq!(client.try_sweep_dust(&user, &None), Err(Ok(Error::AccountFrozen)));
+    assert_eq!(client.get_balance(&user), 50);
+
+    client.unfreeze_account(&compliance, &user);
+    assert_eq!(client.sweep_dust(&user, &None), 50);
+    assert_eq!(client.get_balance(&user), 0);
+}
+
+#[test]
+fn completed_withdrawal_settles_its_hold() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+    let updater = role_holder(&fixture, Role::StatusUpdater);
+
+    let user = fixture.funded_user(1_000);
+    let withdrawal_id = fixture.pending_withdrawal(&user, 400);
+    assert_eq!(client.get_balance(&user), 600);
+    assert_eq!(client.get_withdrawal_hold(&withdrawal_id).unwrap().amount, 400);
+
+    client.set_withdrawal_status(&updater, &withdrawal_id, &PaymentStatus::Completed, &Some(String::from_str(&fixture.env, "MP123")));
+
+    assert_eq!(client.get_withdrawal_hold(&withdrawal_id), None);
+    assert_eq!(client.get_balance(&user), 600);
+    assert_eq!(client.get_ledger().get(Bucket::HeldForWithdrawal), Some(0));
+    assert!(client.is_ledger_balanced());
+}
 
 #[test]
-fn test() {
-    let env = Env::default();
-    let contract_id = env.register(Contract, ());
-    let client = ContractClient::new(&env, &contract_id);
+fn failed_withdrawal_refunds_its_hold() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+    let updater = role_holder(&fixture, Role::StatusUpdater);
+
+    let user = fixture.funded_user(1_000);
+    let withdrawal_id = fixture.pending_withdrawal(&user, 400);
+
+    client.set_withdrawal_status(&updater, &withdrawal_id, &PaymentStatus::Failed, &None);
 
-    let words = client.hello(&String::from_str(&env, "Dev"));
+    assert_eq!(client.get_withdrawal_hold(&withdrawal_id), None);
+    assert_eq!(client.get_balance(&user), 1_000);
     assert_eq!(
-        words,
-        vec![
-            &env,
-            String::from_str(&env, "Hello"),
-            String::from_str(&env, "Dev"),
-        ]
+        client.try_set_withdrawal_status(&updater, &withdrawal_id, &PaymentStatus::Completed, &Some(String::from_str(&fixture.env, "MP123"))),
+        Err(Ok(Error::StatusFinal))
     );
+    assert!(client.is_ledger_balanced());
+}
+
+#[test]
+fn expired_withdrawal_hold_is_refunded_once() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let user = fixture.funded_user(1_000);
+    let withdrawal_id = fixture.pending_withdrawal(&user, 400);
+
+    assert_eq!(client.try_refund_expired_withdrawal(&withdrawal_id), Err(Ok(Error::WithdrawalHoldHasNotExpired)));
+    fixture.advance_time(DEFAULT_WITHDRAWAL_HOLD_SECS + 1);
+    client.refund_expired_withdrawal(&withdrawal_id);
+
+    assert_eq!(client.get_balance(&user), 1_000);
+    assert_eq!(client.try_refund_expired_withdrawal(&withdrawal_id), Err(Ok(Error::WithdrawalHoldNotFound)));
+    assert!(client.is_ledger_balanced());
+}
+
+#[test]
+fn bill_batch_is_all_or_nothing() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let user = fixture.funded_user(1_000);
+
+    let unknown = vec![&fixture.env, bill(&fixture, TEST_BILLER, 100), bill(&fixture, "nobody", 100)];
+    assert_eq!(client.try_pay_bills_batch(&user, &unknown), Err(Ok(Error::UnknownBillProvider)));
+    let too_much = vec![&fixture.env, bill(&fixture, TEST_BILLER, 600), bill(&fixture, TEST_BILLER, 600)];
+    assert_eq!(client.try_pay_bills_batch(&user, &too_much), Err(Ok(Error::InsufficientBalance)));
+    assert_eq!(client.get_balance(&user), 1_000);
+
+    let bills = vec![&fixture.env, bill(&fixture, TEST_BILLER, 300), bill(&fixture, TEST_BILLER, 200)];
+    assert_eq!(client.pay_bills_batch(&user, &bills).len(), 2);
+
+    assert_eq!(client.get_balance(&user), 500);
+    assert_eq!(client.get_pending_summary(&user).bill_total, 500);
+    assert_eq!(client.get_ledger().get(Bucket::Payable), Some(500));
+    assert!(client.is_ledger_balanced());
+}
+
+#[test]
+fn ledger_tracks_deposits_against_external() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    fixture.funded_user(700);
+    fixture.funded_user(300);
+
+    let ledger = client.get_ledger();
+    assert_eq!(ledger.get(Bucket::Spendable), Some(1_000));
+    assert_eq!(ledger.get(Bucket::External), Some(-1_000));
+    assert!(client.is_ledger_balanced());
+}
+
+#[test]
+fn liquidation_repays_debt_and_penalty_from_collateral() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+    let treasurer = role_holder(&fixture, Role::Treasurer);
+
+    client.set_credit_config(&CreditConfig {
+        max_ltv_bps: 5_000,
+        liquidation_ltv_bps: 8_000,
+        interest_bps: 10_000,
+        liquidation_penalty_bps: 1_000,
+    });
+    fixture.mint(&treasurer, 500);
+    client.fund_loan_pool(&treasurer, &500);
+
+    let user = fixture.funded_user(1_000);
+    client.lock_collateral(&user, &1_000);
+    assert_eq!(client.try_borrow(&user, &501), Err(Ok(Error::BorrowExceedsCreditLimit)));
+    client.borrow(&user, &500);
+    assert_eq!(client.get_balance(&user), 500);
+    assert_eq!(client.try_liquidate(&user), Err(Ok(Error::PositionHealthy)));
+
+    // 70% interest over the year takes the debt to 850, past the 80% liquidation LTV
+    fixture.advance_time(SECONDS_PER_YEAR * 7 / 10);
+    assert_eq!(client.get_credit_line(&user).debt, 850);
+    assert_eq!(client.liquidate(&user), 935);
+
+    let line = client.get_credit_line(&user);
+    assert_eq!(line.collateral, 65);
+    assert_eq!(line.debt, 0);
+    assert_eq!(client.get_balance(&user), 500);
+    assert!(client.is_ledger_balanced());
+}
+
+#[test]
+fn funding_the_loan_pool_moves_usdc_in() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+    let treasurer = role_holder(&fixture, Role::Treasurer);
+    let usdc = token::Client::new(&fixture.env, &fixture.usdc);
+
+    fixture.mint(&treasurer, 500);
+    client.fund_loan_pool(&treasurer, &500);
+    assert_eq!(usdc.balance(&treasurer), 0);
+    assert_eq!(usdc.balance(&fixture.contract_id), 500);
+
+    // Nothing is credited to the pool without the USDC behind it
+    assert!(client.try_fund_loan_pool(&treasurer, &100).is_err());
+}
+
+#[test]
+fn funding_the_keeper_pool_moves_usdc_in() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+    let treasurer = role_holder(&fixture, Role::Treasurer);
+    let usdc = token::Client::new(&fixture.env, &fixture.usdc);
+
+    fixture.mint(&treasurer, 300);
+    client.fund_keeper_pool(&treasurer, &300);
+    assert_eq!(usdc.balance(&treasurer), 0);
+    assert_eq!(usdc.balance(&fixture.contract_id), 300);
+    assert!(client.try_fund_keeper_pool(&treasurer, &100).is_err());
+}
+
+#[test]
+fn funding_an_agent_float_moves_usdc_in() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+    let treasurer = role_holder(&fixture, Role::Treasurer);
+    let usdc = token::Client::new(&fixture.env, &fixture.usdc);
+    let agent = Address::generate(&fixture.env);
+    client.register_agent(&agent, &String::from_str(&fixture.env, "Kampala Central"));
+
+    fixture.mint(&treasurer, 400);
+    client.fund_agent_float(&treasurer, &agent, &400);
+    assert_eq!(client.get_agent(&agent).float_balance, 400);
+    assert_eq!(usdc.balance(&fixture.contract_id), 400);
+
+    assert!(client.try_fund_agent_float(&treasurer, &agent, &100).is_err());
+    assert_eq!(client.get_agent(&agent).float_balance, 400);
+}
+
+#[test]
+fn missed_group_contribution_is_carried_into_the_next_round() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let alice = fixture.funded_user(1_000);
+    let bob = fixture.user();
+    let members = vec![&fixture.env, alice.clone(), bob.clone()];
+    let group_id = client.create_group(&alice, &String::from_str(&fixture.env, "Chama"), &members, &100, &SECONDS_PER_DAY);
+    client.join_group(&group_id, &bob);
+
+    client.contribute(&group_id, &alice);
+    fixture.advance_time(SECONDS_PER_DAY + 1);
+    client.close_group_round(&group_id);
+
+    // Bob could not pay the contribution plus penalty, so it is owed next round
+    let group = client.get_group_status(&group_id);
+    assert_eq!(group.missed.get(1), Some(1));
+    assert_eq!(group.arrears.get(1), Some(110));
+    assert_eq!(group.round_deadline, fixture.now() + SECONDS_PER_DAY);
+    assert_eq!(client.get_balance(&alice), 1_000);
+
+    fixture.mint(&bob, 1_000);
+    client.deposit(&bob, &1_000);
+    client.contribute(&group_id, &bob);
+    client.contribute(&group_id, &alice);
+
+    let group = client.get_group_status(&group_id);
+    assert!(!group.active);
+    assert_eq!(group.arrears.get(1), Some(0));
+    assert_eq!(client.get_balance(&alice), 900);
+    assert_eq!(client.get_balance(&bob), 1_100);
+    assert!(client.is_ledger_balanced());
+}
+
+#[test]
+fn group_takes_nothing_from_members_who_have_not_joined() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let attacker = fixture.user();
+    let victim = fixture.funded_user(1_000);
+    let members = vec![&fixture.env, attacker.clone(), victim.clone()];
+    let group_id = client.create_group(&attacker, &String::from_str(&fixture.env, "Chama"), &members, &1_000, &1);
+    assert_eq!(client.get_group_invites(&group_id), vec![&fixture.env, victim.clone()]);
+
+    fixture.advance_time(2);
+    assert_eq!(client.try_close_group_round(&group_id), Err(Ok(Error::GroupNotActive)));
+    assert_eq!(client.try_contribute(&group_id, &victim), Err(Ok(Error::GroupNotActive)));
+    assert_eq!(client.try_join_group(&group_id, &attacker), Err(Ok(Error::NotInvitedToGroup)));
+    assert_eq!(client.get_balance(&victim), 1_000);
+
+    client.join_group(&group_id, &victim);
+    assert_eq!(fixture.env.auths()[0].0, victim);
+    let group = client.get_group_status(&group_id);
+    assert!(group.active);
+    assert_eq!(group.round_deadline, fixture.now() + 1);
+    assert!(client.get_group_invites(&group_id).is_empty());
+}
+
+#[test]
+fn scheduled_payment_is_escrowed_until_due() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let sender = fixture.funded_user(1_000);
+    let recipient = fixture.user();
+    let schedule_id = client.schedule_payment(&sender, &recipient, &400, &(fixture.now() + SECONDS_PER_DAY));
+    assert_eq!(client.get_balance(&sender), 600);
+
+    assert_eq!(client.try_execute_scheduled(&schedule_id), Err(Ok(Error::ScheduledPaymentNotDueYet)));
+    fixture.advance_time(SECONDS_PER_DAY);
+    client.execute_scheduled(&schedule_id);
+
+    assert_eq!(client.get_balance(&recipient), 400);
+    assert_eq!(client.try_cancel_scheduled(&schedule_id), Err(Ok(Error::ScheduledPaymentNotPending)));
+    assert!(client.is_ledger_balanced());
+}
+
+#[test]
+fn cancelled_scheduled_payment_is_refunded() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let sender = fixture.funded_user(1_000);
+    let recipient = fixture.user();
+    let schedule_id = client.schedule_payment(&sender, &recipient, &400, &(fixture.now() + SECONDS_PER_DAY));
+
+    client.cancel_scheduled(&schedule_id);
+
+    assert_eq!(client.get_balance(&sender), 1_000);
+    assert_eq!(client.get_balance(&recipient), 0);
+    assert!(client.is_ledger_balanced());
+}
+
+#[test]
+fn early_goal_withdrawal_pays_the_penalty() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let user = fixture.funded_user(1_000);
+    let goal_id = client.create_goal(&user, &String::from_str(&fixture.env, "School fees"), &1_000, &(fixture.now() + SECONDS_PER_DAY));
+    client.contribute_to_goal(&user, &goal_id, &1_000);
+    assert_eq!(client.get_balance(&user), 0);
+
+    assert_eq!(client.try_withdraw_from_goal(&user, &goal_id, &400, &false), Err(Ok(Error::GoalLocked)));
+    assert_eq!(client.withdraw_from_goal(&user, &goal_id, &400, &true), 380);
+    fixture.advance_time(SECONDS_PER_DAY);
+    assert_eq!(client.withdraw_from_goal(&user, &goal_id, &600, &false), 600);
+
+    assert_eq!(client.get_balance(&user), 980);
+    assert!(client.is_ledger_balanced());
+}
+
+#[test]
+fn transfer_from_spends_down_the_allowance() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let owner = fixture.funded_user(1_000);
+    let recipient = fixture.user();
+    let spender = Address::generate(&fixture.env);
+    client.approve(&owner, &spender, &300, &(fixture.now() + SECONDS_PER_DAY));
+
+    client.transfer_from(&spender, &owner, &recipient, &200);
+    assert_eq!(client.allowance(&owner, &spender), 100);
+    assert_eq!(client.try_transfer_from(&spender, &owner, &recipient, &200), Err(Ok(Error::InsufficientAllowance)));
+
+    assert_eq!(client.get_balance(&owner), 800);
+    assert_eq!(client.get_balance(&recipient), 200);
+    assert!(client.is_ledger_balanced());
+}
+
+#[test]
+fn referral_stays_pending_until_the_pool_can_pay() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+    let treasurer = role_holder(&fixture, Role::Treasurer);
+
+    client.set_referral_reward(&50);
+    let referrer = fixture.user();
+    let referred = Address::generate(&fixture.env);
+    client.register_user_with_referrer(&referred, &String::from_str(&fixture.env, "+256700000001"), &referrer);
+    fixture.mint(&referred, 1_000);
+    client.deposit(&referred, &1_000);
+
+    fixture.pending_bill(&referred, 100);
+    assert_eq!(client.get_referral_stats(&referrer).qualified_count, 0);
+
+    fixture.mint(&treasurer, 50);
+    client.fund_referral_pool(&treasurer, &50);
+    fixture.pending_bill(&referred, 100);
+
+    let stats = client.get_referral_stats(&referrer);
+    assert_eq!(stats.qualified_count, 1);
+    assert_eq!(stats.rewards_earned, 50);
+    assert_eq!(client.get_balance(&referrer), 50);
+    assert!(client.is_ledger_balanced());
+}
+
+#[test]
+fn cashback_is_redeemed_from_the_funded_pool() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+    let treasurer = role_holder(&fixture, Role::Treasurer);
+
+    client.set_cashback_rate(&String::from_str(&fixture.env, TEST_BILLER), &100);
+    let user = fixture.funded_user(1_000);
+    fixture.pending_bill(&user, 1_000);
+    assert_eq!(client.get_rewards_balance(&user), 10);
+
+    assert_eq!(client.try_redeem_rewards(&user), Err(Ok(Error::RewardsPoolUnderfunded)));
+    fixture.mint(&treasurer, 10);
+    client.fund_rewards_pool(&treasurer, &10);
+    assert_eq!(client.redeem_rewards(&user), 10);
+
+    assert_eq!(client.get_balance(&user), 10);
+    assert_eq!(client.get_rewards_balance(&user), 0);
+    assert!(client.is_ledger_balanced());
+}
+
+// sha256 of `salt || pin`, as the app commits to a PIN
+fn pin_hash(fixture: &Fixture, salt: &BytesN<32>, pin: &[u8]) -> BytesN<32> {
+    let mut salted = Bytes::from_array(&fixture.env, &salt.to_array());
+    salted.append(&Bytes::from_slice(&fixture.env, pin));
+    BytesN::from(fixture.env.crypto().sha256(&salted))
+}
+
+#[test]
+fn sends_above_the_threshold_need_a_fresh_pin() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+    client.set_pin_threshold(&100);
+    fixture.set_time(SECONDS_PER_DAY);
+
+    let from = fixture.funded_user(1_000);
+    let to = fixture.user();
+    let salt = BytesN::from_array(&fixture.env, &[7; 32]);
+    client.set_pin_hash(&from, &salt, &pin_hash(&fixture, &salt, b"1234"));
+
+    let send = || client.try_send_usdc(&from, &to, &200, &FeeBearer::Payer, &None, &None, &None);
+    assert_eq!(send(), Err(Ok(Error::PinVerificationRequired)));
+    assert!(!client.verify_pin(&from, &Bytes::from_slice(&fixture.env, b"0000"), &None));
+    assert_eq!(send(), Err(Ok(Error::PinVerificationRequired)));
+
+    assert!(client.verify_pin(&from, &Bytes::from_slice(&fixture.env, b"1234"), &None));
+    assert!(send().is_ok());
+    // Each verification authorizes a single operation
+    assert_eq!(send(), Err(Ok(Error::PinVerificationRequired)));
+
+    // Small sends never need the PIN
+    client.send_usdc(&from, &to, &100, &FeeBearer::Payer, &None, &None, &None);
+}
+
+#[test]
+fn repeated_wrong_pins_lock_verification() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+    fixture.set_time(SECONDS_PER_DAY);
+
+    let user = fixture.user();
+    let salt = BytesN::from_array(&fixture.env, &[7; 32]);
+    client.set_pin_hash(&user, &salt, &pin_hash(&fixture, &salt, b"1234"));
+
+    for _ in 0..MAX_PIN_ATTEMPTS {
+        assert!(!client.verify_pin(&user, &Bytes::from_slice(&fixture.env, b"0000"), &None));
+    }
+    assert_eq!(client.try_verify_pin(&user, &Bytes::from_slice(&fixture.env, b"1234"), &None), Err(Ok(Error::PinLocked)));
+
+    fixture.advance_time(PIN_LOCKOUT_SECS);
+    assert!(client.verify_pin(&user, &Bytes::from_slice(&fixture.env, b"1234"), &None));
+}
+
+#[test]
+fn replacing_a_pin_needs_the_old_one() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+    fixture.set_time(SECONDS_PER_DAY);
+
+    let user = fixture.user();
+    let salt = BytesN::from_array(&fixture.env, &[7; 32]);
+    let next_salt = BytesN::from_array(&fixture.env, &[9; 32]);
+    client.set_pin_hash(&user, &salt, &pin_hash(&fixture, &salt, b"1234"));
+    assert_eq!(client.try_set_pin_hash(&user, &next_salt, &pin_hash(&fixture, &next_salt, b"9999")), Err(Ok(Error::PinVerificationRequired)));
+
+    client.verify_pin(&user, &Bytes::from_slice(&fixture.env, b"1234"), &None);
+    client.set_pin_hash(&user, &next_salt, &pin_hash(&fixture, &next_salt, b"9999"));
+    assert!(client.verify_pin(&user, &Bytes::from_slice(&fixture.env, b"9999"), &None));
+
+    fixture.revoke_auths();
+    assert!(client.try_verify_pin(&user, &Bytes::from_slice(&fixture.env, b"9999"), &None).is_err());
+}
+
+#[test]
+fn guardians_recover_an_account_after_the_delay() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let user = fixture.funded_user(1_000);
+    let new_address = Address::generate(&fixture.env);
+    let first = Address::generate(&fixture.env);
+    let second = Address::generate(&fixture.env);
+    client.set_guardians(&user, &vec![&fixture.env, first.clone(), second.clone()], &2);
+
+    let stranger = Address::generate(&fixture.env);
+    assert_eq!(client.try_recover_account(&stranger, &user, &new_address), Err(Ok(Error::NotAGuardian)));
+
+    client.recover_account(&first, &user, &new_address);
+    assert_eq!(client.try_finalize_recovery(&user), Err(Ok(Error::NotEnoughGuardianApprovals)));
+    client.recover_account(&second, &user, &new_address);
+    assert_eq!(client.try_finalize_recovery(&user), Err(Ok(Error::RecoveryDelayHasNotPassed)));
+
+    fixture.advance_time(RECOVERY_DELAY_SECS);
+    client.finalize_recovery(&user);
+    assert_eq!(client.get_balance(&new_address), 1_000);
+    assert_eq!(client.try_get_balance(&user), Err(Ok(Error::UserNotFound)));
+}
+
+#[test]
+fn owner_can_cancel_a_recovery() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let user = fixture.funded_user(1_000);
+    let new_address = Address::generate(&fixture.env);
+    let guardian = Address::generate(&fixture.env);
+    client.set_guardians(&user, &vec![&fixture.env, guardian.clone()], &1);
+    client.recover_account(&guardian, &user, &new_address);
+
+    client.cancel_recovery(&user);
+    assert_eq!(client.get_recovery(&user), None);
+    fixture.advance_time(RECOVERY_DELAY_SECS);
+    assert_eq!(client.try_finalize_recovery(&user), Err(Ok(Error::RecoveryNotFound)));
+
+    fixture.revoke_auths();
+    assert!(client.try_recover_account(&guardian, &user, &new_address).is_err());
+}
+
+// A registered agent whose float holds `float` USDC sent in by a treasurer
+fn funded_agent(fixture: &Fixture, float: i128) -> Address {
+    let client = fixture.client();
+    let treasurer = role_holder(fixture, Role::Treasurer);
+    let agent = Address::generate(&fixture.env);
+    client.register_agent(&agent, &String::from_str(&fixture.env, "Kampala Central"));
+    if float > 0 {
+        fixture.mint(&treasurer, float);
+        client.fund_agent_float(&treasurer, &agent, &float);
+    }
+    agent
+}
+
+#[test]
+fn agent_cash_in_is_paid_from_the_float() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let agent = funded_agent(&fixture, 500);
+    let user = fixture.user();
+    client.agent_cash_in(&agent, &user, &300);
+    assert_eq!(client.get_balance(&user), 300);
+    assert_eq!(client.get_agent(&agent).float_balance, 200);
+
+    assert_eq!(client.try_agent_cash_in(&agent, &user, &300), Err(Ok(Error::InsufficientAgentFloat)));
+    client.set_agent_active(&agent, &false);
+    assert_eq!(client.try_agent_cash_in(&agent, &user, &100), Err(Ok(Error::AgentNotActive)));
+
+    let stranger = Address::generate(&fixture.env);
+    assert_eq!(client.try_agent_cash_in(&stranger, &user, &100), Err(Ok(Error::AgentNotFound)));
+}
+
+#[test]
+fn agent_cash_in_needs_the_agents_auth() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let agent = funded_agent(&fixture, 500);
+    let user = fixture.user();
+    fixture.revoke_auths();
+    assert!(client.try_agent_cash_in(&agent, &user, &100).is_err());
+    assert_eq!(client.get_balance(&user), 0);
+}
+
+#[test]
+fn cash_out_completes_only_with_the_users_code() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let agent = funded_agent(&fixture, 0);
+    let user = fixture.funded_user(1_000);
+    let code = Bytes::from_slice(&fixture.env, b"483920");
+    let code_hash = fixture.env.crypto().sha256(&code).to_bytes();
+    let cash_out_id = client.agent_cash_out(&user, &agent, &400, &code_hash);
+    assert_eq!(client.get_balance(&user), 600);
+
+    let wrong = Bytes::from_slice(&fixture.env, b"000000");
+    assert_eq!(client.try_confirm_cash_out(&agent, &cash_out_id, &wrong), Err(Ok(Error::InvalidConfirmationCode)));
+    let other_agent = funded_agent(&fixture, 0);
+    assert_eq!(client.try_confirm_cash_out(&other_agent, &cash_out_id, &code), Err(Ok(Error::Unauthorized)));
+
+    client.confirm_cash_out(&agent, &cash_out_id, &code);
+    assert_eq!(client.get_agent(&agent).float_balance, 400);
+    assert_eq!(client.try_cancel_cash_out(&user, &cash_out_id), Err(Ok(Error::CashOutNotPending)));
+}
+
+#[test]
+fn unconfirmed_cash_out_is_released_after_the_window() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let agent = funded_agent(&fixture, 0);
+    let user = fixture.funded_user(1_000);
+    let code = Bytes::from_slice(&fixture.env, b"483920");
+    let code_hash = fixture.env.crypto().sha256(&code).to_bytes();
+    let cash_out_id = client.agent_cash_out(&user, &agent, &400, &code_hash);
+
+    assert_eq!(client.try_release_expired_cash_out(&cash_out_id), Err(Ok(Error::CashOutHasNotExpired)));
+    fixture.advance_time(DEFAULT_CASH_OUT_WINDOW_SECS + 1);
+    assert_eq!(client.try_confirm_cash_out(&agent, &cash_out_id, &code), Err(Ok(Error::CashOutHasExpired)));
+
+    client.release_expired_cash_out(&cash_out_id);
+    assert_eq!(client.get_balance(&user), 1_000);
+    assert_eq!(client.get_agent(&agent).float_balance, 0);
+}
+
+// A corridor to Kenya paying out in the test currency at par, with a 1% + 5 fee
+fn corridor(fixture: &Fixture) -> Symbol {
+    let client = fixture.client();
+    client.set_rate(&TEST_CURRENCY, &RATE_SCALE, &fixture.now());
+    client.set_corridor(&Corridor {
+        country: symbol_short!("KE"),
+        currency: TEST_CURRENCY,
+        fee_bps: 100,
+        flat_fee: 5,
+        active: true,
+    });
+    symbol_short!("KE")
+}
+
+#[test]
+fn failed_remittance_refunds_the_amount_and_fee() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+    let updater = role_holder(&fixture, Role::StatusUpdater);
+    let country = corridor(&fixture);
+
+    let user = fixture.funded_user(1_000);
+    let beneficiary_id = client.add_beneficiary(
+        &user,
+        &String::from_str(&fixture.env, "Amani"),
+        &String::from_str(&fixture.env, "+254700000000"),
+        &country,
+        &String::from_str(&fixture.env, "mpesa"),
+    );
+    let remittance_id = client.send_remittance(&user, &beneficiary_id, &500);
+    assert_eq!(client.get_balance(&user), 490);
+
+    assert_eq!(client.try_set_remittance_status(&fixture.admin, &remittance_id, &PaymentStatus::Failed), Err(Ok(Error::MissingRole)));
+    client.set_remittance_status(&updater, &remittance_id, &PaymentStatus::Failed);
+    assert_eq!(client.get_balance(&user), 1_000);
+    assert_eq!(
+        client.try_set_remittance_status(&updater, &remittance_id, &PaymentStatus::Completed),
+        Err(Ok(Error::StatusFinal))
+    );
+    assert!(client.is_ledger_balanced());
+}
+
+#[test]
+fn remittances_go_only_to_the_senders_own_beneficiaries() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+    let country = corridor(&fixture);
+
+    let owner = fixture.funded_user(1_000);
+    let other = fixture.funded_user(1_000);
+    let beneficiary_id = client.add_beneficiary(
+        &owner,
+        &String::from_str(&fixture.env, "Amani"),
+        &String::from_str(&fixture.env, "+254700000000"),
+        &country,
+        &String::from_str(&fixture.env, "mpesa"),
+    );
+    assert_eq!(client.try_send_remittance(&other, &beneficiary_id, &100), Err(Ok(Error::Unauthorized)));
+
+    client.set_corridor(&Corridor {
+        country: country.clone(),
+        currency: TEST_CURRENCY,
+        fee_bps: 100,
+        flat_fee: 5,
+        active: false,
+    });
+    assert_eq!(client.try_send_remittance(&owner, &beneficiary_id, &100), Err(Ok(Error::CorridorUnavailable)));
+    assert_eq!(client.get_balance(&owner), 1_000);
+}
+
+// AMM pool paying out two units of the output token per unit in, from balances minted to it
+#[contract]
+struct DoublingPool;
+
+#[contractimpl]
+impl DoublingPool {
+    pub fn swap(env: Env, to: Address, _token_in: Address, token_out: Address, amount_in: i128, _min_out: i128) -> i128 {
+        let amount_out = amount_in * 2;
+        token::Client::new(&env, &token_out).transfer(&env.current_contract_address(), &to, &amount_out);
+        amount_out
+    }
+}
+
+// A second listed token and a funded pool to swap USDC into it through
+fn swap_pool(fixture: &Fixture) -> Address {
+    let client = fixture.client();
+    let token = fixture.env.register_stellar_asset_contract_v2(Address::generate(&fixture.env)).address();
+    let pool = fixture.env.register(DoublingPool, ());
+    client.set_swap_token(&token, &symbol_short!("XLM"));
+    client.set_swap_pool(&pool);
+    token::StellarAssetClient::new(&fixture.env, &token).mint(&pool, &10_000);
+    token
+}
+
+#[test]
+fn swap_credits_what_the_pool_paid_out() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+    let xlm = swap_pool(&fixture);
+
+    let user = fixture.funded_user(1_000);
+    assert_eq!(client.swap(&user, &fixture.usdc, &xlm, &100, &200), 200);
+    assert_eq!(client.get_balance(&user), 900);
+    assert_eq!(client.get_asset_balance(&user, &xlm), 200);
+    assert_eq!(client.get_swaps(&user, &0, &10).0.len(), 1);
+
+    assert_eq!(client.try_swap(&user, &fixture.usdc, &xlm, &100, &201), Err(Ok(Error::SwapOutputBelowMinimum)));
+    assert_eq!(client.try_swap(&user, &fixture.usdc, &fixture.usdc, &100, &0), Err(Ok(Error::TokensMustDiffer)));
+    let unlisted = Address::generate(&fixture.env);
+    assert_eq!(client.try_swap(&user, &fixture.usdc, &unlisted, &100, &0), Err(Ok(Error::TokenNotSupported)));
+    assert_eq!(client.get_balance(&user), 900);
+}
+
+#[test]
+fn sandbox_balances_cannot_be_swapped_out() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+    client.enable_sandbox();
+    let xlm = swap_pool(&fixture);
+
+    let user = fixture.user();
+    client.faucet(&user, &1_000);
+    assert_eq!(client.try_swap(&user, &fixture.usdc, &xlm, &100, &0), Err(Ok(Error::NotAvailableInSandboxMode)));
+    assert_eq!(client.get_balance(&user), 1_000);
+}
+
+#[test]
+fn swaps_need_the_users_auth() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+    let xlm = swap_pool(&fixture);
+
+    let user = fixture.funded_user(1_000);
+    fixture.revoke_auths();
+    assert!(client.try_swap(&user, &fixture.usdc, &xlm, &100, &0).is_err());
+}
+
+// Lending pool minting one share per unit supplied and redeeming at a set value per share
+#[contract]
+struct MockLendingPool;
+
+#[contractimpl]
+impl MockLendingPool {
+    pub fn init(env: Env, usdc: Address, value_bps: i128) {
+        env.storage().instance().set(&symbol_short!("usdc"), &usdc);
+        env.storage().instance().set(&symbol_short!("value"), &value_bps);
+    }
+
+    pub fn supply(_env: Env, _on_behalf_of: Address, amount: i128) -> i128 {
+        amount
+    }
+
+    pub fn redeem(env: Env, to: Address, shares: i128) -> i128 {
+        let amount = Self::preview_redeem(env.clone(), shares);
+        let usdc: Address = env.storage().instance().get(&symbol_short!("usdc")).unwrap();
+        token::Client::new(&env, &usdc).transfer(&env.current_contract_address(), &to, &amount);
+        amount
+    }
+
+    pub fn preview_redeem(env: Env, shares: i128) -> i128 {
+        let value_bps: i128 = env.storage().instance().get(&symbol_short!("value")).unwrap();
+        shares * value_bps / BPS_DENOMINATOR
+    }
+}
+
+// A user with `saved` in a savings goal supplied to a pool redeeming at `value_bps` per share
+fn user_earning_yield(fixture: &Fixture, saved: i128, value_bps: i128) -> Address {
+    let client = fixture.client();
+    let pool = fixture.env.register(MockLendingPool, ());
+    MockLendingPoolClient::new(&fixture.env, &pool).init(&fixture.usdc, &value_bps);
+    // Interest the pool has earned from its borrowers
+    fixture.mint(&pool, saved);
+    client.set_yield_pool(&pool);
+
+    let user = fixture.funded_user(saved);
+    let goal_id = client.create_goal(&user, &String::from_str(&fixture.env, "School fees"), &saved, &(fixture.now() + SECONDS_PER_DAY));
+    client.contribute_to_goal(&user, &goal_id, &saved);
+    assert_eq!(client.enable_yield(&user), saved);
+    user
+}
+
+#[test]
+fn yield_interest_is_credited_to_the_balance() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let user = user_earning_yield(&fixture, 1_000, 11_000);
+    assert_eq!(client.accrued_interest(&user), 100);
+    assert_eq!(client.try_enable_yield(&user), Err(Ok(Error::NoIdleSavings)));
+
+    assert_eq!(client.disable_yield(&user), 100);
+    assert_eq!(client.get_balance(&user), 100);
+    assert_eq!(client.get_goals(&user, &0, &1).0.get(0).unwrap().saved, 1_000);
+    assert!(client.is_ledger_balanced());
+}
+
+#[test]
+fn yield_losses_come_out_of_the_goals() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let user = user_earning_yield(&fixture, 1_000, 9_000);
+    let goal_id = client.get_goals(&user, &0, &1).0.get(0).unwrap().id;
+    fixture.advance_time(SECONDS_PER_DAY);
+    assert_eq!(client.try_withdraw_from_goal(&user, &goal_id, &100, &false), Err(Ok(Error::SavingsInYield)));
+
+    assert_eq!(client.disable_yield(&user), -100);
+    assert_eq!(client.get_balance(&user), 0);
+    assert_eq!(client.get_goals(&user, &0, &1).0.get(0).unwrap().saved, 900);
+    assert!(client.is_ledger_balanced());
+}
+
+// What a redeemer commits to before revealing a voucher code
+fn voucher_commitment(fixture: &Fixture, code: &Bytes, redeemer: &Address) -> BytesN<32> {
+    BytesN::from(fixture.env.crypto().sha256(&(code.clone(), redeemer.clone()).to_xdr(&fixture.env)))
+}
+
+fn next_ledger(fixture: &Fixture) {
+    fixture.env.ledger().with_mut(|ledger| ledger.sequence_number += 1);
+}
+
+#[test]
+fn voucher_is_redeemed_only_against_the_redeemers_commitment() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let funder = fixture.funded_user(1_000);
+    let code = Bytes::from_slice(&fixture.env, b"GIFT-2026");
+    let code_hash = fixture.env.crypto().sha256(&code).to_bytes();
+    client.create_voucher(&funder, &300, &code_hash, &(fixture.now() + SECONDS_PER_DAY));
+    assert_eq!(client.get_balance(&funder), 700);
+
+    let user = fixture.user();
+    assert_eq!(client.try_redeem_voucher(&user, &code), Err(Ok(Error::VoucherNotCommitted)));
+    client.commit_voucher(&user, &voucher_commitment(&fixture, &code, &user));
+    assert_eq!(client.try_redeem_voucher(&user, &code), Err(Ok(Error::VoucherCommitmentTooRecent)));
+
+    // Copying the user's commitment does not let another account redeem the code
+    let thief = fixture.user();
+    client.commit_voucher(&thief, &voucher_commitment(&fixture, &code, &user));
+    next_ledger(&fixture);
+    assert_eq!(client.try_redeem_voucher(&thief, &code), Err(Ok(Error::VoucherNotCommitted)));
+
+    assert_eq!(client.redeem_voucher(&user, &code), 300);
+    assert_eq!(client.get_balance(&user), 300);
+    assert_eq!(client.get_voucher(&code_hash).redeemed_by, Some(user.clone()));
+
+    client.commit_voucher(&user, &voucher_commitment(&fixture, &code, &user));
+    next_ledger(&fixture);
+    assert_eq!(client.try_redeem_voucher(&user, &code), Err(Ok(Error::VoucherNoLongerValid)));
+}
+
+#[test]
+fn expired_voucher_is_reclaimed_by_its_funder() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let funder = fixture.funded_user(1_000);
+    let code_hash = fixture.env.crypto().sha256(&Bytes::from_slice(&fixture.env, b"GIFT-2026")).to_bytes();
+    client.create_voucher(&funder, &300, &code_hash, &(fixture.now() + SECONDS_PER_DAY));
+
+    assert_eq!(client.try_reclaim_expired_voucher(&funder, &code_hash), Err(Ok(Error::VoucherHasNotExpired)));
+    fixture.advance_time(SECONDS_PER_DAY + 1);
+    let other = fixture.user();
+    assert_eq!(client.try_reclaim_expired_voucher(&other, &code_hash), Err(Ok(Error::Unauthorized)));
+
+    assert_eq!(client.reclaim_expired_voucher(&funder, &code_hash), 300);
+    assert_eq!(client.get_balance(&funder), 1_000);
+    assert!(client.is_ledger_balanced());
+}
+
+#[test]
+fn payroll_is_escrowed_until_payday() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let employer = fixture.funded_user(1_000);
+    let first = fixture.user();
+    let second = fixture.user();
+    let payday = fixture.now() + SECONDS_PER_DAY;
+    let payouts = vec![&fixture.env, (first.clone(), 300), (second.clone(), 200)];
+    let payroll_id = client.create_payroll(&employer, &payouts, &payday);
+    assert_eq!(client.get_balance(&employer), 500);
+
+    assert_eq!(client.try_execute_payroll(&payroll_id), Err(Ok(Error::PaydayHasNotCome)));
+    fixture.set_time(payday);
+    client.execute_payroll(&payroll_id);
+    assert_eq!(client.get_balance(&first), 300);
+    assert_eq!(client.get_balance(&second), 200);
+    assert_eq!(client.get_payroll_payouts(&first, &0, &10).0.len(), 1);
+
+    assert_eq!(client.try_execute_payroll(&payroll_id), Err(Ok(Error::PayrollNotPending)));
+    assert!(client.is_ledger_balanced());
+}
+
+#[test]
+fn pending_summary_counts_vouchers_and_payrolls() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let user = fixture.funded_user(1_000);
+    let code_hash = fixture.env.crypto().sha256(&Bytes::from_slice(&fixture.env, b"GIFT-2026")).to_bytes();
+    client.create_voucher(&user, &300, &code_hash, &(fixture.now() + SECONDS_PER_DAY));
+    let employee = fixture.user();
+    client.create_payroll(&user, &vec![&fixture.env, (employee, 200)], &(fixture.now() + SECONDS_PER_DAY));
+
+    let summary = client.get_pending_summary(&user);
+    assert_eq!((summary.voucher_count, summary.voucher_total), (1, 300));
+    assert_eq!((summary.payroll_count, summary.payroll_total), (1, 200));
+    assert_eq!((summary.count, summary.total), (2, 500));
+    assert_eq!(client.get_balance(&user) + summary.total, 1_000);
+}
+
+#[test]
+fn portfolio_value_counts_only_the_users_own_records() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+    let usdc = symbol_short!("USDC");
+
+    let user = fixture.funded_user(1_000);
+    let goal_id = client.create_goal(&user, &String::from_str(&fixture.env, "School fees"), &1_000, &(fixture.now() + SECONDS_PER_DAY));
+    client.contribute_to_goal(&user, &goal_id, &200);
+    let code_hash = fixture.env.crypto().sha256(&Bytes::from_slice(&fixture.env, b"GIFT-2026")).to_bytes();
+    client.create_voucher(&user, &300, &code_hash, &(fixture.now() + SECONDS_PER_DAY));
+
+    // A split the creator also takes part in is indexed under them once
+    let friend = fixture.funded_user(1_000);
+    let shares = vec![&fixture.env, (user.clone(), 100), (friend.clone(), 100)];
+    let split_id = client.create_split(&user, &String::from_str(&fixture.env, TEST_BILLER), &String::from_str(&fixture.env, "0123456789"), &200, &shares);
+    client.accept_split(&user, &split_id);
+    assert_eq!(client.get_splits(&user, &0, &10).0.len(), 1);
+
+    let other = fixture.funded_user(1_000);
+    client.create_payroll(&other, &vec![&fixture.env, (friend.clone(), 400)], &(fixture.now() + SECONDS_PER_DAY));
+
+    assert_eq!(client.get_balance(&user), 400);
+    assert_eq!(client.get_portfolio_value(&user, &usdc), 1_000);
+    assert_eq!(client.get_portfolio_value(&other, &usdc), 1_000);
+    assert_eq!(client.get_portfolio_value(&friend, &usdc), 1_000);
+}
+
+#[test]
+fn cancelled_payroll_refunds_the_employer() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let employer = fixture.funded_user(1_000);
+    let employee = fixture.user();
+    let payday = fixture.now() + SECONDS_PER_DAY;
+    let payroll_id = client.create_payroll(&employer, &vec![&fixture.env, (employee.clone(), 400)], &payday);
+
+    assert_eq!(client.try_cancel_payroll(&employee, &payroll_id), Err(Ok(Error::Unauthorized)));
+    client.cancel_payroll(&employer, &payroll_id);
+    assert_eq!(client.get_balance(&employer), 1_000);
+
+    fixture.set_time(payday);
+    assert_eq!(client.try_execute_payroll(&payroll_id), Err(Ok(Error::PayrollNotPending)));
+    assert_eq!(client.get_balance(&employee), 0);
+}
+
+#[test]
+fn payroll_pays_only_registered_employees() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let employer = fixture.funded_user(1_000);
+    let stranger = Address::generate(&fixture.env);
+    let payday = fixture.now() + SECONDS_PER_DAY;
+    assert_eq!(
+        client.try_create_payroll(&employer, &vec![&fixture.env, (stranger, 100)], &payday),
+        Err(Ok(Error::InvalidEmployee))
+    );
+
+    let employee = fixture.user();
+    fixture.revoke_auths();
+    assert!(client.try_create_payroll(&employer, &vec![&fixture.env, (employee, 100)], &payday).is_err());
+}
+
+// Operator signing key for step-up attestations and signed approvals
+fn operator_key() -> SigningKey {
+    SigningKey::from_bytes(&[42; 32])
+}
+
+fn public_key(fixture: &Fixture, key: &SigningKey) -> BytesN<32> {
+    BytesN::from_array(&fixture.env, &key.verifying_key().to_bytes())
+}
+
+fn sign(fixture: &Fixture, key: &SigningKey, message: &Bytes) -> BytesN<64> {
+    let message: std::vec::Vec<u8> = message.iter().collect();
+    BytesN::from_array(&fixture.env, &key.sign(&message).to_bytes())
+}
+
+fn step_up_attestation(fixture: &Fixture, key: &SigningKey, user: &Address, amount: i128, issued_at: u64) -> StepUpAttestation {
+    let message = (symbol_short!("step_up"), fixture.contract_id.clone(), user.clone(), amount, issued_at).to_xdr(&fixture.env);
+    StepUpAttestation { amount, issued_at, signature: sign(fixture, key, &message) }
+}
+
+#[test]
+fn sends_above_the_soft_limit_need_a_signed_step_up() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+    let key = operator_key();
+    client.set_step_up_policy(&StepUpPolicy { soft_limit: 100, verifier: public_key(&fixture, &key), max_age_secs: 300 });
+    fixture.set_time(SECONDS_PER_DAY);
+
+    let from = fixture.funded_user(1_000);
+    let to = fixture.user();
+    assert_eq!(
+        client.try_send_usdc(&from, &to, &200, &FeeBearer::Payer, &None, &None, &None),
+        Err(Ok(Error::StepUpVerificationRequired))
+    );
+
+    let attestation = step_up_attestation(&fixture, &key, &from, 200, fixture.now());
+    client.send_usdc_with_attestation(&from, &to, &200, &FeeBearer::Payer, &None, &None, &attestation);
+    assert_eq!(client.get_balance(&to), 200);
+
+    // An attestation is good for one approval only
+    assert_eq!(
+        client.try_send_usdc_with_attestation(&from, &to, &200, &FeeBearer::Payer, &None, &None, &attestation),
+        Err(Ok(Error::AttestationAlreadyUsed))
+    );
+    client.send_usdc(&from, &to, &100, &FeeBearer::Payer, &None, &None, &None);
+}
+
+#[test]
+fn step_up_attestations_must_be_fresh_and_signed_by_the_verifier() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+    let key = operator_key();
+    client.set_step_up_policy(&StepUpPolicy { soft_limit: 100, verifier: public_key(&fixture, &key), max_age_secs: 300 });
+    fixture.set_time(SECONDS_PER_DAY);
+
+    let user = fixture.funded_user(1_000);
+    let stale = step_up_attestation(&fixture, &key, &user, 200, fixture.now() - 301);
+    assert_eq!(client.try_approve_step_up(&user, &200, &stale), Err(Ok(Error::AttestationHasExpired)));
+
+    let small = step_up_attestation(&fixture, &key, &user, 150, fixture.now());
+    assert_eq!(client.try_approve_step_up(&user, &200, &small), Err(Ok(Error::AttestationDoesNotCoverTheAmount)));
+
+    let forged = step_up_attestation(&fixture, &SigningKey::from_bytes(&[7; 32]), &user, 200, fixture.now());
+    assert!(client.try_approve_step_up(&user, &200, &forged).is_err());
+
+    fixture.revoke_auths();
+    let attestation = step_up_attestation(&fixture, &key, &user, 200, fixture.now());
+    assert!(client.try_approve_step_up(&user, &200, &attestation).is_err());
+}
+
+fn approval(fixture: &Fixture, key: &SigningKey, kind: ApprovalKind, subject: &Address, nonce: u64) -> Approval {
+    let expires_at = fixture.now() + SECONDS_PER_DAY;
+    let message = (symbol_short!("approval"), fixture.contract_id.clone(), kind.clone(), subject.clone(), expires_at, nonce).to_xdr(&fixture.env);
+    Approval { kind, subject: subject.clone(), expires_at, nonce, signature: sign(fixture, key, &message) }
+}
+
+#[test]
+fn signed_kyc_upgrade_verifies_the_user_once() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+    let key = operator_key();
+    client.set_approval_key(&Role::KycOfficer, &public_key(&fixture, &key));
+
+    let user = fixture.user();
+    let upgrade = approval(&fixture, &key, ApprovalKind::KycUpgrade, &user, 1);
+    client.submit_approval(&upgrade);
+    assert!(client.get_user(&user).is_verified);
+    assert_eq!(client.try_submit_approval(&upgrade), Err(Ok(Error::ApprovalAlreadyUsed)));
+
+    // Only the key registered for the role that would make the change can sign it
+    let override_approval = approval(&fixture, &key, ApprovalKind::LimitOverride(5_000), &user, 2);
+    assert_eq!(client.try_submit_approval(&override_approval), Err(Ok(Error::ApprovalKeyNotSet)));
+    client.set_approval_key(&Role::RiskOfficer, &public_key(&fixture, &SigningKey::from_bytes(&[7; 32])));
+    assert!(client.try_submit_approval(&override_approval).is_err());
+
+    let other = fixture.user();
+    let expired = approval(&fixture, &key, ApprovalKind::KycUpgrade, &other, 3);
+    fixture.advance_time(SECONDS_PER_DAY + 1);
+    assert_eq!(client.try_submit_approval(&expired), Err(Ok(Error::ApprovalHasExpired)));
+}
+
+#[test]
+fn signed_payout_confirmation_completes_only_the_subjects_withdrawal() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+    let key = operator_key();
+    client.set_approval_key(&Role::StatusUpdater, &public_key(&fixture, &key));
+
+    let user = fixture.funded_user(1_000);
+    let withdrawal_id = fixture.pending_withdrawal(&user, 400);
+    let reference = String::from_str(&fixture.env, "MP123");
+    let kind = ApprovalKind::PayoutConfirmation(withdrawal_id.clone(), reference.clone());
+
+    let other = fixture.user();
+    assert_eq!(client.try_submit_approval(&approval(&fixture, &key, kind.clone(), &other, 1)), Err(Ok(Error::Unauthorized)));
+
+    client.submit_approval(&approval(&fixture, &key, kind, &user, 2));
+    let withdrawal = client.get_withdrawals(&user, &0, &1).0.get(0).unwrap();
+    assert_eq!(withdrawal.status, String::from_str(&fixture.env, "completed"));
+    assert_eq!(withdrawal.payout_reference, Some(reference));
+    assert_eq!(client.get_withdrawal_hold(&withdrawal_id), None);
+}
+
+#[test]
+fn sunset_pays_balances_out_after_the_notice() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+    let usdc = token::Client::new(&fixture.env, &fixture.usdc);
+
+    let user = fixture.funded_user(1_000);
+    let idle = fixture.funded_user(500);
+    assert_eq!(client.try_exit(&user), Err(Ok(Error::SunsetNotInitiated)));
+
+    let exits_open_at = client.initiate_sunset();
+    assert_eq!(exits_open_at, fixture.now() + SUNSET_NOTICE_SECS);
+    fixture.mint(&user, 100);
+    assert_eq!(client.try_deposit(&user, &100), Err(Ok(Error::ContractWindingDown)));
+    assert_eq!(client.try_exit(&user), Err(Ok(Error::SunsetNoticePeriodHasNotPassed)));
+
+    fixture.set_time(exits_open_at);
+    assert_eq!(client.exit(&user), 1_000);
+    assert_eq!(usdc.balance(&user), 1_100);
+    assert_eq!(client.try_exit(&user), Err(Ok(Error::NothingToReturn)));
+
+    assert_eq!(client.force_return(&idle), 500);
+    assert_eq!(usdc.balance(&idle), 500);
+    assert_eq!(usdc.balance(&fixture.contract_id), 0);
+    assert!(client.is_ledger_balanced());
+}
+
+#[test]
+fn sunset_and_forced_returns_need_the_admin() {
+    let fixture = Fixture::new();
+    let client = fixture.client();
+
+    let user = fixture.funded_user(1_000);
+    fixture.revoke_auths();
+    assert!(client.try_initiate_sunset().is_err());
+    assert_eq!(client.get_sunset(), None);
+
+    fixture.env.mock_all_auths();
+    let exits_open_at = client.initiate_sunset();
+    fixture.set_time(exits_open_at);
+    fixture.revoke_auths();
+    assert!(client.try_force_return(&user).is_err());
+    assert_eq!(client.get_balance(&user), 1_000);
 }
@@ -0,0 +1,154 @@
+#![cfg(any(test, feature = "testutils"))]
+
+use soroban_sdk::{
+    symbol_short,
+    testutils::{Address as _, Ledger},
+    token, Address, Env, String, Symbol,
+};
+
+use crate::{AmountPolicy, BillProvider, Payvia, PayviaClient, Role, WithdrawalRail, RATE_SCALE};
+
+// Biller registered by `Fixture::new`, accepting any positive amount
+pub const TEST_BILLER: &str = "umeme";
+
+// Withdrawal rail registered by `Fixture::new`, with no fee and no limits
+pub const TEST_RAIL: &str = "mtn_momo";
+
+// Currency `Fixture::pending_withdrawal` pays out in, at one unit per USDC
+pub const TEST_CURRENCY: Symbol = symbol_short!("UGX");
+
+// An initialized contract with all auths mocked, plus builders for common scenarios. Every
+// builder goes through the public entrypoints, so fixtures stay valid as the contract evolves.
+pub struct Fixture {
+    pub env: Env,
+    pub contract_id: Address,
+    // Address the contract was initialized with as admin
+    pub admin: Address,
+    // Test USDC token backing user balances
+    pub usdc: Address,
+}
+
+impl Default for Fixture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Fixture {
+    // Register the contract and a test USDC token, initialize it and add a test biller and withdrawal rail
+    pub fn new() -> Self {
+        let env = Env::default();
+        env.mock_all_auths();
+        let contract_id = env.register(Payvia, ());
+        let admin = Address::generate(&env);
+        let usdc = env.register_stellar_asset_contract_v2(Address::generate(&env)).address();
+        let fixture = Fixture { env, contract_id, admin, usdc };
+
+        let client = fixture.client();
+        client.init(&fixture.admin);
+        client.set_swap_token(&fixture.usdc, &symbol_short!("USDC"));
+        client.set_bill_provider(&BillProvider {
+            id: String::from_str(&fixture.env, TEST_BILLER),
+            name: String::from_str(&fixture.env, "Test biller"),
+            category: symbol_short!("utility"),
+            min_amount: 1,
+            max_amount: i128::MAX,
+            account_min_len: 1,
+            account_max_len: 32,
+            numeric_account: false,
+            active: true,
+            amount_policy: AmountPolicy::Range,
+        });
+        client.set_withdrawal_rail(&WithdrawalRail {
+            id: String::from_str(&fixture.env, TEST_RAIL),
+            name: String::from_str(&fixture.env, "Test rail"),
+            min_amount: 1,
+            max_amount: i128::MAX,
+            fee_bps: 0,
+            flat_fee: 0,
+            enabled: true,
+        });
+
+        fixture
+    }
+
+    pub fn client(&self) -> PayviaClient<'_> {
+        PayviaClient::new(&self.env, &self.contract_id)
+    }
+
+    // A registered, unverified user with no balance
+    pub fn user(&self) -> Address {
+        let user = Address::generate(&self.env);
+        self.client().register_user(&user, &String::from_str(&self.env, "+256700000000"));
+        user
+    }
+
+    // A registered user verified by a freshly granted KYC officer
+    pub fn verified_user(&self) -> Address {
+        let user = self.user();
+        let officer = Address::generate(&self.env);
+        self.client().grant_role(&officer, &Role::KycOfficer);
+        self.client().verify_user(&officer, &user);
+        user
+    }
+
+    // Mint test USDC to a wallet, e.g. ahead of a deposit
+    pub fn mint(&self, to: &Address, amount: i128) {
+        token::StellarAssetClient::new(&self.env, &self.usdc).mint(to, &amount);
+    }
+
+    // A registered user holding `balance` USDC
+    pub fn funded_user(&self, balance: i128) -> Address {
+        let user = self.user();
+        self.mint(&user, balance);
+        self.client().deposit(&user, &balance);
+        user
+    }
+
+    // A pending bill payment of `amount` to the test biller; the user must hold enough USDC
+    pub fn pending_bill(&self, user: &Address, amount: i128) -> String {
+        self.client().pay_bill(
+            user,
+            &String::from_str(&self.env, TEST_BILLER),
+            &String::from_str(&self.env, "0123456789"),
+            &amount,
+            &None,
+            &None,
+        )
+    }
+
+    // A pending withdrawal of `amount` over the test rail. Publishes a fresh rate first so the
+    // conversion is never stale after time travel.
+    pub fn pending_withdrawal(&self, user: &Address, amount: i128) -> String {
+        self.client().set_rate(&TEST_CURRENCY, &RATE_SCALE, &self.now());
+        self.client().withdraw(
+            user,
+            &String::from_str(&self.env, TEST_RAIL),
+            &String::from_str(&self.env, "256700000000"),
+            &amount,
+            &TEST_CURRENCY,
+            &None,
+            &None,
+            &None,
+        )
+    }
+
+    // Stop mocking auths, so every `require_auth` in later calls fails
+    pub fn revoke_auths(&self) {
+        self.env.set_auths(&[]);
+    }
+
+    pub fn now(&self) -> u64 {
+        self.env.ledger().timestamp()
+    }
+
+    // Move the ledger clock forward
+    pub fn advance_time(&self, secs: u64) {
+        self.env.ledger().with_mut(|ledger| ledger.timestamp += secs);
+    }
+
+    // Jump the ledger clock to `timestamp`
+    pub fn set_time(&self, timestamp: u64) {
+        self.env.ledger().set_timestamp(timestamp);
+    }
+}